@@ -1,7 +1,7 @@
 use std::sync::Arc;
 
 use crate::https_helper::{https, HttpsRequestError, Method, Response};
-use crate::jose::{key_authorization_sha256, sign, sign_eab, JoseError};
+use crate::jose::{key_authorization, key_authorization_sha256, sign, sign_eab, JoseError};
 use base64::engine::general_purpose::URL_SAFE_NO_PAD;
 use base64::Engine;
 use rcgen::{CustomExtension, Error as RcgenError, PKCS_ECDSA_P256_SHA256};
@@ -125,9 +125,14 @@ impl Account {
         &self,
         client_config: &Arc<ClientConfig>,
         domains: Vec<String>,
+        replaces: Option<String>,
     ) -> Result<(String, Order), AcmeError> {
         let domains: Vec<Identifier> = domains.into_iter().map(Identifier::Dns).collect();
-        let payload = format!("{{\"identifiers\":{}}}", serde_json::to_string(&domains)?);
+        let payload = match replaces {
+            // Tells an ARI capable CA which certificate this order renews.
+            Some(cert_id) => json!({ "identifiers": domains, "replaces": cert_id }).to_string(),
+            None => format!("{{\"identifiers\":{}}}", serde_json::to_string(&domains)?),
+        };
         let response = self
             .request(client_config, &self.directory.new_order, &payload)
             .await?;
@@ -204,6 +209,30 @@ impl Account {
         let certified_key = CertifiedKey::new(vec![cert.der().clone()], pk);
         Ok((challenge, certified_key))
     }
+    /// Returns the http-01 challenge and the key authorization to serve for its token.
+    pub fn http_01<'a>(
+        &self,
+        challenges: &'a [Challenge],
+    ) -> Result<(&'a Challenge, String), AcmeError> {
+        let challenge = challenges
+            .iter()
+            .find(|c| c.typ == ChallengeType::Http01)
+            .ok_or(AcmeError::NoHttp01Challenge)?;
+        let key_auth = key_authorization(&self.key_pair, &challenge.token)?;
+        Ok((challenge, key_auth))
+    }
+    /// Returns the dns-01 challenge and the value of the TXT record to publish for it.
+    pub fn dns_01<'a>(
+        &self,
+        challenges: &'a [Challenge],
+    ) -> Result<(&'a Challenge, String), AcmeError> {
+        let challenge = challenges
+            .iter()
+            .find(|c| c.typ == ChallengeType::Dns01)
+            .ok_or(AcmeError::NoDns01Challenge)?;
+        let key_auth = key_authorization_sha256(&self.key_pair, &challenge.token)?;
+        Ok((challenge, URL_SAFE_NO_PAD.encode(key_auth.as_ref())))
+    }
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -212,6 +241,9 @@ pub struct Directory {
     pub new_nonce: String,
     pub new_account: String,
     pub new_order: String,
+    /// Only set by CAs that support ACME Renewal Information.
+    #[serde(default)]
+    pub renewal_info: Option<String>,
 }
 
 impl Directory {
@@ -337,6 +369,10 @@ pub enum AcmeError {
     MissingHeader(&'static str),
     #[error("no tls-alpn-01 challenge found")]
     NoTlsAlpn01Challenge,
+    #[error("no http-01 challenge found")]
+    NoHttp01Challenge,
+    #[error("no dns-01 challenge found")]
+    NoDns01Challenge,
 }
 
 fn get_header(response: &Response, header: &'static str) -> Result<String, AcmeError> {
@@ -0,0 +1,90 @@
+//! ACME Renewal Information (RFC 9773), which lets the CA tell us when to renew a certificate.
+//!
+//! CAs use it to spread renewals out, and to ask for early renewal ahead of a mass revocation.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use ring::rand::{SecureRandom, SystemRandom};
+use rustls::ClientConfig;
+use serde::Deserialize;
+use x509_parser::extensions::ParsedExtension;
+use x509_parser::parse_x509_certificate;
+
+use crate::acme::{AcmeError, Directory};
+use crate::https_helper::{https, HttpsRequestError, Method};
+
+/// How long to wait before checking again when the CA doesn't send Retry-After.
+const DEFAULT_RETRY_AFTER: Duration = Duration::from_secs(6 * 3600);
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RenewalInfo {
+    pub suggested_window: SuggestedWindow,
+    pub explanation_url: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct SuggestedWindow {
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+}
+
+impl SuggestedWindow {
+    /// Picks a random time within the window, so that hosts sharing a CA don't all renew at once.
+    pub fn pick(&self) -> DateTime<Utc> {
+        let mut bytes = [0u8; 8];
+        if SystemRandom::new().fill(&mut bytes).is_err() || self.end <= self.start {
+            return self.start;
+        }
+        let fraction = u64::from_be_bytes(bytes) as f64 / u64::MAX as f64;
+        let span = (self.end - self.start).num_seconds() as f64;
+        self.start + chrono::Duration::seconds((span * fraction) as i64)
+    }
+}
+
+/// The ARI identifier of a certificate: `base64url(authority key id) "." base64url(serial)`.
+///
+/// Returns `None` if the certificate has no authority key identifier.
+pub fn cert_id(cert_der: &[u8]) -> Option<String> {
+    let (_, cert) = parse_x509_certificate(cert_der).ok()?;
+    let key_identifier =
+        cert.extensions()
+            .iter()
+            .find_map(|extension| match extension.parsed_extension() {
+                ParsedExtension::AuthorityKeyIdentifier(aki) => aki.key_identifier.as_ref(),
+                _ => None,
+            })?;
+    Some(format!(
+        "{}.{}",
+        URL_SAFE_NO_PAD.encode(key_identifier.0),
+        URL_SAFE_NO_PAD.encode(cert.raw_serial())
+    ))
+}
+
+/// Fetches the renewal information for `cert_id`.
+///
+/// Returns `None` if the directory doesn't support ARI, otherwise the renewal information
+/// and how long to wait before asking again.
+pub async fn fetch(
+    client_config: &Arc<ClientConfig>,
+    directory: &Directory,
+    cert_id: &str,
+) -> Result<Option<(RenewalInfo, Duration)>, AcmeError> {
+    let Some(renewal_info_url) = &directory.renewal_info else {
+        return Ok(None);
+    };
+    let url = format!("{}/{}", renewal_info_url.trim_end_matches('/'), cert_id);
+    let response = https(client_config, url, Method::Get, None).await?;
+    let retry_after = response
+        .headers()
+        .get("retry-after")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.trim().parse::<u64>().ok())
+        .map_or(DEFAULT_RETRY_AFTER, Duration::from_secs);
+    let body = response.bytes().await.map_err(HttpsRequestError::from)?;
+    Ok(Some((serde_json::from_slice(&body)?, retry_after)))
+}
@@ -20,6 +20,28 @@ pub trait CertCache: Send + Sync {
         directory_url: &str,
         cert: &[u8],
     ) -> Result<(), Self::EC>;
+    /// Called before a renewal is ordered, with the certificate that is currently deployed.
+    ///
+    /// Caches shared by several servers can return a certificate that another server renewed
+    /// in the meantime, which is then deployed instead of ordering a new one.
+    async fn load_renewed_cert(
+        &self,
+        _domains: &[String],
+        _directory_url: &str,
+        _current: &[u8],
+    ) -> Result<Option<Vec<u8>>, Self::EC> {
+        Ok(None)
+    }
+    /// Called when ordering a certificate fails.
+    ///
+    /// Caches shared by several servers can release any lock they took for the order here.
+    async fn order_failed(
+        &self,
+        _domains: &[String],
+        _directory_url: &str,
+    ) -> Result<(), Self::EC> {
+        Ok(())
+    }
 }
 
 #[async_trait]
@@ -47,6 +47,25 @@ where
             .await
             .map_err(box_err)
     }
+
+    async fn load_renewed_cert(
+        &self,
+        domains: &[String],
+        directory_url: &str,
+        current: &[u8],
+    ) -> Result<Option<Vec<u8>>, Self::EC> {
+        self.inner
+            .load_renewed_cert(domains, directory_url, current)
+            .await
+            .map_err(box_err)
+    }
+
+    async fn order_failed(&self, domains: &[String], directory_url: &str) -> Result<(), Self::EC> {
+        self.inner
+            .order_failed(domains, directory_url)
+            .await
+            .map_err(box_err)
+    }
 }
 
 #[async_trait]
@@ -39,6 +39,21 @@ impl<C: CertCache + Send + Sync, A: AccountCache + Send + Sync> CertCache for Co
             .store_cert(domains, directory_url, cert)
             .await
     }
+
+    async fn load_renewed_cert(
+        &self,
+        domains: &[String],
+        directory_url: &str,
+        current: &[u8],
+    ) -> Result<Option<Vec<u8>>, Self::EC> {
+        self.cert_cache
+            .load_renewed_cert(domains, directory_url, current)
+            .await
+    }
+
+    async fn order_failed(&self, domains: &[String], directory_url: &str) -> Result<(), Self::EC> {
+        self.cert_cache.order_failed(domains, directory_url).await
+    }
 }
 
 #[async_trait]
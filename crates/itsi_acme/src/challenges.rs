@@ -0,0 +1,151 @@
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// The challenge type used to prove control over the domains of an order.
+#[derive(Clone)]
+pub enum AcmeChallenge {
+    /// tls-alpn-01, answered by [crate::ResolvesServerCertAcme] on the TLS port.
+    TlsAlpn01,
+    /// http-01, answered by whoever serves plain HTTP on port 80 using the shared [Http01Challenges].
+    Http01(Arc<Http01Challenges>),
+    /// dns-01, answered by publishing a TXT record through a [Dns01Provider].
+    /// This is the only challenge type that can validate wildcard domains.
+    Dns01(Arc<dyn Dns01Provider>),
+}
+
+impl Debug for AcmeChallenge {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AcmeChallenge::TlsAlpn01 => write!(f, "tls-alpn-01"),
+            AcmeChallenge::Http01(_) => write!(f, "http-01"),
+            AcmeChallenge::Dns01(_) => write!(f, "dns-01"),
+        }
+    }
+}
+
+/// Key authorizations for pending http-01 challenges, by token.
+///
+/// A store created with [Http01Challenges::with_dir] also writes each key authorization to a file
+/// named after its token, so that other processes sharing the directory can answer the challenge.
+#[derive(Debug, Default)]
+pub struct Http01Challenges {
+    responses: Mutex<HashMap<String, String>>,
+    dir: Option<PathBuf>,
+}
+
+pub const HTTP_01_CHALLENGE_PATH: &str = "/.well-known/acme-challenge/";
+
+impl Http01Challenges {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_dir(dir: impl AsRef<Path>) -> Self {
+        Self {
+            responses: Default::default(),
+            dir: Some(dir.as_ref().to_path_buf()),
+        }
+    }
+
+    pub(crate) async fn insert(&self, token: &str, key_authorization: String) -> io::Result<()> {
+        if let Some(dir) = &self.dir {
+            tokio::fs::create_dir_all(dir).await?;
+            tokio::fs::write(dir.join(token), &key_authorization).await?;
+        }
+        self.responses
+            .lock()
+            .unwrap()
+            .insert(token.to_string(), key_authorization);
+        Ok(())
+    }
+
+    pub(crate) async fn remove(&self, token: &str) {
+        if let Some(dir) = &self.dir {
+            tokio::fs::remove_file(dir.join(token)).await.ok();
+        }
+        self.responses.lock().unwrap().remove(token);
+    }
+
+    /// Returns the key authorization to serve for a token.
+    pub async fn key_authorization(&self, token: &str) -> Option<String> {
+        // Tokens are base64url, anything else could escape the challenge directory.
+        if token.is_empty()
+            || !token
+                .bytes()
+                .all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_')
+        {
+            return None;
+        }
+        if let Some(key_authorization) = self.responses.lock().unwrap().get(token) {
+            return Some(key_authorization.clone());
+        }
+        let dir = self.dir.as_ref()?;
+        tokio::fs::read_to_string(dir.join(token)).await.ok()
+    }
+
+    /// Returns the key authorization to serve for a request path,
+    /// if it is a challenge path (`/.well-known/acme-challenge/<token>`) for a known token.
+    pub async fn respond(&self, path: &str) -> Option<String> {
+        self.key_authorization(path.strip_prefix(HTTP_01_CHALLENGE_PATH)?)
+            .await
+    }
+}
+
+/// Publishes the TXT records for dns-01 challenges.
+///
+/// `name` is the full record name (e.g. `_acme-challenge.example.com`) and `value` the record content.
+/// [Dns01Provider::present] must only return once the record is visible to the ACME server.
+#[async_trait]
+pub trait Dns01Provider: Send + Sync {
+    async fn present(&self, name: &str, value: &str) -> io::Result<()>;
+    async fn cleanup(&self, name: &str, value: &str) -> io::Result<()>;
+}
+
+/// A [Dns01Provider] that delegates to a hook script.
+///
+/// The script is invoked as `<command> present <name> <value>` and `<command> cleanup <name> <value>`,
+/// and must exit with status 0 on success.
+#[derive(Debug, Clone)]
+pub struct ExecDns01Provider {
+    command: PathBuf,
+}
+
+impl ExecDns01Provider {
+    pub fn new(command: impl AsRef<Path>) -> Self {
+        Self {
+            command: command.as_ref().to_path_buf(),
+        }
+    }
+
+    async fn run(&self, action: &str, name: &str, value: &str) -> io::Result<()> {
+        let output = tokio::process::Command::new(&self.command)
+            .args([action, name, value])
+            .output()
+            .await?;
+        if !output.status.success() {
+            return Err(io::Error::other(format!(
+                "dns-01 hook {:?} {} failed with {}: {}",
+                self.command,
+                action,
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Dns01Provider for ExecDns01Provider {
+    async fn present(&self, name: &str, value: &str) -> io::Result<()> {
+        self.run("present", name, value).await
+    }
+
+    async fn cleanup(&self, name: &str, value: &str) -> io::Result<()> {
+        self.run("cleanup", name, value).await
+    }
+}
@@ -3,12 +3,14 @@ use crate::acme::{
 };
 use crate::caches::{BoxedErrCache, CompositeCache, NoCache};
 use crate::{AccountCache, Cache, CertCache};
+use crate::{AcmeChallenge, KeyType};
 use crate::{AcmeState, Incoming};
 use futures::Stream;
 use rustls::{ClientConfig, RootCertStore};
 use std::convert::Infallible;
 use std::fmt::Debug;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::io::{AsyncRead, AsyncWrite};
 use webpki_roots::TLS_SERVER_ROOTS;
 
@@ -22,6 +24,9 @@ pub struct AcmeConfig<EC: Debug, EA: Debug = EC> {
     pub(crate) contact: Vec<String>,
     pub(crate) cache: Box<dyn Cache<EC = EC, EA = EA>>,
     pub(crate) eab: Option<ExternalAccountKey>,
+    pub(crate) challenge: AcmeChallenge,
+    pub(crate) key_type: KeyType,
+    pub(crate) renew_before: Option<Duration>,
 }
 
 impl AcmeConfig<Infallible, Infallible> {
@@ -74,6 +79,9 @@ impl AcmeConfig<Infallible, Infallible> {
             contact: vec![],
             cache: Box::new(NoCache::new()),
             eab: None,
+            challenge: AcmeChallenge::TlsAlpn01,
+            key_type: KeyType::default(),
+            renew_before: None,
         }
     }
 }
@@ -110,6 +118,27 @@ impl<EC: 'static + Debug, EA: 'static + Debug> AcmeConfig<EC, EA> {
         self
     }
 
+    /// Select the challenge type used to validate domains. Defaults to [AcmeChallenge::TlsAlpn01].
+    pub fn challenge(mut self, challenge: AcmeChallenge) -> Self {
+        self.challenge = challenge;
+        self
+    }
+
+    /// Select the key algorithm of ordered certificates. Defaults to [KeyType::EcdsaP256].
+    pub fn key_type(mut self, key_type: KeyType) -> Self {
+        self.key_type = key_type;
+        self
+    }
+
+    /// Renew certificates this long before they expire.
+    ///
+    /// Defaults to a third of the certificate's lifetime, and is capped at half of it.
+    /// If the CA supports ACME Renewal Information, its suggested window takes precedence when it is earlier.
+    pub fn renew_before(mut self, renew_before: Duration) -> Self {
+        self.renew_before = Some(renew_before);
+        self
+    }
+
     /// Provide a list of contacts for the account.
     ///
     /// Note that email addresses must include a `mailto:` prefix.
@@ -134,6 +163,9 @@ impl<EC: 'static + Debug, EA: 'static + Debug> AcmeConfig<EC, EA> {
             contact: self.contact,
             cache: Box::new(cache),
             eab: self.eab,
+            challenge: self.challenge,
+            key_type: self.key_type,
+            renew_before: self.renew_before,
         }
     }
     pub fn cache_compose<CC: 'static + CertCache, CA: 'static + AccountCache>(
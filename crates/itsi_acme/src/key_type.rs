@@ -0,0 +1,64 @@
+use rcgen::{
+    KeyPair, SignatureAlgorithm, PKCS_ECDSA_P256_SHA256, PKCS_ECDSA_P384_SHA384, PKCS_RSA_SHA256,
+};
+use rsa::pkcs8::EncodePrivateKey;
+use rustls::pki_types::PrivatePkcs8KeyDer;
+use std::str::FromStr;
+
+/// The key algorithm of ordered certificates. Accounts always use ECDSA P-256 keys.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum KeyType {
+    #[default]
+    EcdsaP256,
+    EcdsaP384,
+    /// An RSA key with the given modulus size in bits.
+    Rsa(usize),
+}
+
+impl KeyType {
+    pub(crate) fn generate(&self) -> Result<KeyPair, rcgen::Error> {
+        match self {
+            KeyType::EcdsaP256 => KeyPair::generate_for(&PKCS_ECDSA_P256_SHA256),
+            KeyType::EcdsaP384 => KeyPair::generate_for(&PKCS_ECDSA_P384_SHA384),
+            // ring can sign with RSA keys, but not generate them.
+            KeyType::Rsa(bits) => {
+                let key = rsa::RsaPrivateKey::new(&mut rand_core::OsRng, *bits)
+                    .map_err(|_| rcgen::Error::KeyGenerationUnavailable)?;
+                let der = key
+                    .to_pkcs8_der()
+                    .map_err(|_| rcgen::Error::KeyGenerationUnavailable)?;
+                KeyPair::from_pkcs8_der_and_sign_algo(
+                    &PrivatePkcs8KeyDer::from(der.as_bytes()),
+                    self.signature_algorithm(),
+                )
+            }
+        }
+    }
+
+    fn signature_algorithm(&self) -> &'static SignatureAlgorithm {
+        match self {
+            KeyType::EcdsaP256 => &PKCS_ECDSA_P256_SHA256,
+            KeyType::EcdsaP384 => &PKCS_ECDSA_P384_SHA384,
+            KeyType::Rsa(_) => &PKCS_RSA_SHA256,
+        }
+    }
+}
+
+impl FromStr for KeyType {
+    type Err = String;
+
+    /// Parses `p256`, `p384`, `rsa2048`, `rsa3072` or `rsa4096`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "p256" | "ecdsa-p256" => Ok(KeyType::EcdsaP256),
+            "p384" | "ecdsa-p384" => Ok(KeyType::EcdsaP384),
+            "rsa" | "rsa2048" => Ok(KeyType::Rsa(2048)),
+            "rsa3072" => Ok(KeyType::Rsa(3072)),
+            "rsa4096" => Ok(KeyType::Rsa(4096)),
+            other => Err(format!(
+                "Invalid key type {}. Expected one of p256, p384, rsa2048, rsa3072 or rsa4096",
+                other
+            )),
+        }
+    }
+}
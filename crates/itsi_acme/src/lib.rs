@@ -1,6 +1,6 @@
 //! An easy-to-use, async compatible [ACME] client library using [rustls] with [ring].
-//! The validation mechanism used is tls-alpn-01, which allows serving acme challenge responses and
-//! regular TLS traffic on the same port.
+//! The default validation mechanism is tls-alpn-01, which allows serving acme challenge responses and
+//! regular TLS traffic on the same port. http-01 and dns-01 are available through [AcmeConfig::challenge].
 //!
 //! Is designed to use the tokio runtime, if you need support for other runtimes take a look
 //! at the original implementation [rustls-acme](https://github.com/FlorianUekermann/rustls-acme).
@@ -121,14 +121,18 @@
 
 mod acceptor;
 pub mod acme;
+pub mod ari;
 #[cfg(feature = "axum")]
 pub mod axum;
 mod cache;
 pub mod caches;
+pub mod challenges;
 mod config;
 mod https_helper;
 mod incoming;
 mod jose;
+mod key_type;
+pub mod ocsp;
 mod resolver;
 mod state;
 
@@ -136,7 +140,9 @@ pub use tokio_rustls;
 
 pub use acceptor::*;
 pub use cache::*;
+pub use challenges::AcmeChallenge;
 pub use config::*;
 pub use incoming::*;
+pub use key_type::KeyType;
 pub use resolver::*;
 pub use state::*;
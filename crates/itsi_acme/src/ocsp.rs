@@ -0,0 +1,419 @@
+//! Minimal OCSP client, used to fetch responses for stapling.
+//!
+//! Responses are fetched over plain HTTP, so before a response is cached or stapled we check
+//! it's signed by the issuer (or a responder the issuer delegated to), that it answers for our
+//! certificate (by its CertID), and that the status is good.
+
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use ring::digest::{digest, SHA1_FOR_LEGACY_USE_ONLY};
+use ring::signature::{self, UnparsedPublicKey, VerificationAlgorithm};
+use rustls::pki_types::CertificateDer;
+use thiserror::Error;
+use x509_parser::extensions::{GeneralName, ParsedExtension};
+use x509_parser::prelude::*;
+
+const OID_AD_OCSP: &str = "1.3.6.1.5.5.7.48.1";
+/// DER encoding of `AlgorithmIdentifier { sha1, NULL }`.
+const SHA1_ALGORITHM_IDENTIFIER: &[u8] = &[
+    0x30, 0x09, 0x06, 0x05, 0x2b, 0x0e, 0x03, 0x02, 0x1a, 0x05, 0x00,
+];
+/// Contents of the sha1 OID.
+const SHA1_OID: &[u8] = &[0x2b, 0x0e, 0x03, 0x02, 0x1a];
+/// Contents of the signature algorithm OIDs we can verify.
+const SHA1_WITH_RSA: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x05];
+const SHA256_WITH_RSA: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x0b];
+const SHA384_WITH_RSA: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x0c];
+const SHA512_WITH_RSA: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x0d];
+const ECDSA_WITH_SHA256: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x04, 0x03, 0x02];
+const ECDSA_WITH_SHA384: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x04, 0x03, 0x03];
+const ED25519: &[u8] = &[0x2b, 0x65, 0x70];
+/// Contents of the named curve OIDs.
+const SECP256R1: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x03, 0x01, 0x07];
+const SECP384R1: &[u8] = &[0x2b, 0x81, 0x04, 0x00, 0x22];
+/// Responses without a nextUpdate are refreshed this often.
+const DEFAULT_VALIDITY: i64 = 12 * 3600;
+const REQUEST_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+
+#[derive(Error, Debug)]
+pub enum OcspError {
+    #[error("certificate chain has no issuer certificate")]
+    MissingIssuer,
+    #[error("certificate has no OCSP responder")]
+    NoResponder,
+    #[error("X509 parsing error: {0}")]
+    X509(#[from] x509_parser::nom::Err<x509_parser::error::X509Error>),
+    #[error("http request error: {0}")]
+    Http(#[from] reqwest::Error),
+    #[error("OCSP responder returned status {0}")]
+    ResponderStatus(u8),
+    #[error("certificate status is {0}")]
+    CertificateStatus(&'static str),
+    #[error("OCSP response is not for the requested certificate")]
+    CertIdMismatch,
+    #[error("OCSP response is not signed by the issuer or a delegated responder")]
+    InvalidSignature,
+    #[error("malformed OCSP response")]
+    Malformed,
+}
+
+/// An OCSP response, as stapled to the TLS handshake.
+#[derive(Debug, Clone)]
+pub struct OcspResponse {
+    pub der: Vec<u8>,
+    pub this_update: DateTime<Utc>,
+    pub next_update: Option<DateTime<Utc>>,
+}
+
+impl OcspResponse {
+    /// Parses a DER encoded response, and checks it's signed for the issuer of `chain`
+    /// and reports its leaf as good.
+    pub fn from_der(der: Vec<u8>, chain: &[CertificateDer]) -> Result<Self, OcspError> {
+        let issuer = chain.get(1).ok_or(OcspError::MissingIssuer)?;
+        let (this_update, next_update) =
+            parse_response(&der, &CertId::new(chain)?, issuer.as_ref())?;
+        Ok(Self {
+            der,
+            this_update,
+            next_update,
+        })
+    }
+
+    /// Responses are refreshed half way through their validity period.
+    pub fn refresh_at(&self) -> DateTime<Utc> {
+        let next_update = self
+            .next_update
+            .unwrap_or(self.this_update + Duration::seconds(DEFAULT_VALIDITY));
+        self.this_update + (next_update - self.this_update) / 2
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.next_update
+            .is_some_and(|next_update| next_update <= Utc::now())
+    }
+}
+
+/// Returns the OCSP responder URL of the leaf certificate, if it has one.
+pub fn responder_url(chain: &[CertificateDer]) -> Option<String> {
+    let (_, leaf) = parse_x509_certificate(chain.first()?.as_ref()).ok()?;
+    leaf.extensions()
+        .iter()
+        .find_map(|extension| match extension.parsed_extension() {
+            ParsedExtension::AuthorityInfoAccess(aia) => {
+                aia.accessdescs.iter().find_map(|description| {
+                    match (&description.access_method, &description.access_location) {
+                        (method, GeneralName::URI(uri)) if method.to_id_string() == OID_AD_OCSP => {
+                            Some(uri.to_string())
+                        }
+                        _ => None,
+                    }
+                })
+            }
+            _ => None,
+        })
+}
+
+/// Fetches a fresh OCSP response for the leaf of `chain`, which must include its issuer.
+pub async fn fetch(chain: &[CertificateDer<'_>]) -> Result<OcspResponse, OcspError> {
+    let url = responder_url(chain).ok_or(OcspError::NoResponder)?;
+    let request = request_der(chain)?;
+    let response = reqwest::Client::builder()
+        .timeout(REQUEST_TIMEOUT)
+        .build()?
+        .post(url)
+        .header("Content-Type", "application/ocsp-request")
+        .body(request)
+        .send()
+        .await?
+        .error_for_status()?;
+    OcspResponse::from_der(response.bytes().await?.to_vec(), chain)
+}
+
+/// Identifies the leaf certificate of a chain to the OCSP responder, using SHA-1 hashes.
+struct CertId {
+    issuer_name_hash: Vec<u8>,
+    issuer_key_hash: Vec<u8>,
+    serial: Vec<u8>,
+}
+
+impl CertId {
+    fn new(chain: &[CertificateDer]) -> Result<Self, OcspError> {
+        let (_, leaf) =
+            parse_x509_certificate(chain.first().ok_or(OcspError::MissingIssuer)?.as_ref())?;
+        let (_, issuer) =
+            parse_x509_certificate(chain.get(1).ok_or(OcspError::MissingIssuer)?.as_ref())?;
+        Ok(Self {
+            issuer_name_hash: digest(&SHA1_FOR_LEGACY_USE_ONLY, issuer.subject().as_raw())
+                .as_ref()
+                .to_vec(),
+            issuer_key_hash: digest(
+                &SHA1_FOR_LEGACY_USE_ONLY,
+                issuer.public_key().subject_public_key.data.as_ref(),
+            )
+            .as_ref()
+            .to_vec(),
+            serial: leaf.raw_serial().to_vec(),
+        })
+    }
+
+    fn to_der(&self) -> Vec<u8> {
+        tlv(
+            0x30,
+            &[
+                SHA1_ALGORITHM_IDENTIFIER.to_vec(),
+                tlv(0x04, &[self.issuer_name_hash.clone()]),
+                tlv(0x04, &[self.issuer_key_hash.clone()]),
+                tlv(0x02, &[self.serial.clone()]),
+            ],
+        )
+    }
+
+    /// Checks the contents of a response's `CertID` name the same certificate.
+    /// The hash algorithm parameters may be NULL or absent.
+    fn matches(&self, cert_id: &[u8]) -> Result<bool, OcspError> {
+        let (algorithm, rest) = expect_tlv(cert_id, 0x30)?;
+        let (oid, _) = expect_tlv(algorithm, 0x06)?;
+        let (issuer_name_hash, rest) = expect_tlv(rest, 0x04)?;
+        let (issuer_key_hash, rest) = expect_tlv(rest, 0x04)?;
+        let (serial, _) = expect_tlv(rest, 0x02)?;
+        Ok(oid == SHA1_OID
+            && issuer_name_hash == self.issuer_name_hash.as_slice()
+            && issuer_key_hash == self.issuer_key_hash.as_slice()
+            && serial == self.serial.as_slice())
+    }
+}
+
+/// Encodes an `OCSPRequest` for the leaf certificate of `chain`.
+pub fn request_der(chain: &[CertificateDer]) -> Result<Vec<u8>, OcspError> {
+    let cert_id = CertId::new(chain)?.to_der();
+    // OCSPRequest { TBSRequest { requestList { Request { CertID } } } }
+    let request = tlv(0x30, &[cert_id]);
+    let request_list = tlv(0x30, &[request]);
+    let tbs_request = tlv(0x30, &[request_list]);
+    Ok(tlv(0x30, &[tbs_request]))
+}
+
+fn tlv(tag: u8, contents: &[Vec<u8>]) -> Vec<u8> {
+    let length: usize = contents.iter().map(Vec::len).sum();
+    let mut out = vec![tag];
+    if length < 0x80 {
+        out.push(length as u8);
+    } else {
+        let bytes = length.to_be_bytes();
+        let skip = bytes.iter().take_while(|b| **b == 0).count();
+        out.push(0x80 | (bytes.len() - skip) as u8);
+        out.extend_from_slice(&bytes[skip..]);
+    }
+    for content in contents {
+        out.extend_from_slice(content);
+    }
+    out
+}
+
+/// Splits the next TLV off `input`, returning its tag, contents and the remaining input.
+fn read_tlv(input: &[u8]) -> Result<(u8, &[u8], &[u8]), OcspError> {
+    let (&tag, rest) = input.split_first().ok_or(OcspError::Malformed)?;
+    let (&first, rest) = rest.split_first().ok_or(OcspError::Malformed)?;
+    let (length, rest) = if first < 0x80 {
+        (first as usize, rest)
+    } else {
+        let count = (first & 0x7f) as usize;
+        if count == 0 || count > 4 || rest.len() < count {
+            return Err(OcspError::Malformed);
+        }
+        let length = rest[..count]
+            .iter()
+            .fold(0usize, |length, b| (length << 8) | *b as usize);
+        (length, &rest[count..])
+    };
+    if rest.len() < length {
+        return Err(OcspError::Malformed);
+    }
+    Ok((tag, &rest[..length], &rest[length..]))
+}
+
+fn expect_tlv(input: &[u8], expected: u8) -> Result<(&[u8], &[u8]), OcspError> {
+    match read_tlv(input)? {
+        (tag, contents, rest) if tag == expected => Ok((contents, rest)),
+        _ => Err(OcspError::Malformed),
+    }
+}
+
+/// Splits the next TLV off `input`, returning its whole encoding (tag and length included)
+/// and the remaining input.
+fn split_tlv(input: &[u8]) -> Result<(&[u8], &[u8]), OcspError> {
+    let (_, _, rest) = read_tlv(input)?;
+    Ok((&input[..input.len() - rest.len()], rest))
+}
+
+/// Checks `signature` over `message`, made with the key of `signer` using `algorithm`
+/// (the contents of its OID).
+fn verify_signature(
+    algorithm: &[u8],
+    signer: &SubjectPublicKeyInfo,
+    message: &[u8],
+    signature: &[u8],
+) -> Result<(), OcspError> {
+    let curve = signer
+        .algorithm
+        .parameters
+        .as_ref()
+        .and_then(|parameters| parameters.as_oid().ok());
+    let curve = curve.as_ref().map(|oid| oid.as_bytes());
+    let algorithm: &'static dyn VerificationAlgorithm = match (algorithm, curve) {
+        (SHA1_WITH_RSA, _) => &signature::RSA_PKCS1_2048_8192_SHA1_FOR_LEGACY_USE_ONLY,
+        (SHA256_WITH_RSA, _) => &signature::RSA_PKCS1_2048_8192_SHA256,
+        (SHA384_WITH_RSA, _) => &signature::RSA_PKCS1_2048_8192_SHA384,
+        (SHA512_WITH_RSA, _) => &signature::RSA_PKCS1_2048_8192_SHA512,
+        (ECDSA_WITH_SHA256, Some(SECP256R1)) => &signature::ECDSA_P256_SHA256_ASN1,
+        (ECDSA_WITH_SHA256, Some(SECP384R1)) => &signature::ECDSA_P384_SHA256_ASN1,
+        (ECDSA_WITH_SHA384, Some(SECP256R1)) => &signature::ECDSA_P256_SHA384_ASN1,
+        (ECDSA_WITH_SHA384, Some(SECP384R1)) => &signature::ECDSA_P384_SHA384_ASN1,
+        (ED25519, _) => &signature::ED25519,
+        _ => return Err(OcspError::InvalidSignature),
+    };
+    UnparsedPublicKey::new(algorithm, signer.subject_public_key.data.as_ref())
+        .verify(message, signature)
+        .map_err(|_| OcspError::InvalidSignature)
+}
+
+/// Whether `responder` may sign OCSP responses on behalf of `issuer`: it must be issued
+/// and signed by `issuer`, currently valid, and have the id-kp-OCSPSigning extended key usage.
+fn is_delegated_responder(responder: &X509Certificate, issuer: &X509Certificate) -> bool {
+    responder.issuer().as_raw() == issuer.subject().as_raw()
+        && responder.validity().is_valid()
+        && responder
+            .extended_key_usage()
+            .ok()
+            .flatten()
+            .is_some_and(|usage| usage.value.ocsp_signing)
+        && verify_signature(
+            responder.signature_algorithm.algorithm.as_bytes(),
+            issuer.public_key(),
+            responder.tbs_certificate.as_ref(),
+            responder.signature_value.data.as_ref(),
+        )
+        .is_ok()
+}
+
+/// Checks the signature of a `BasicOCSPResponse`, given its `tbsResponseData` and the fields
+/// following it (`signatureAlgorithm`, `signature` and optional `certs`).
+fn verify_response_signature(
+    tbs_response_data: &[u8],
+    rest: &[u8],
+    issuer_der: &[u8],
+) -> Result<(), OcspError> {
+    let (_, issuer) = parse_x509_certificate(issuer_der)?;
+    let (signature_algorithm, rest) = expect_tlv(rest, 0x30)?;
+    let (algorithm, _) = expect_tlv(signature_algorithm, 0x06)?;
+    let (signature, rest) = expect_tlv(rest, 0x03)?;
+    let signature = match signature.split_first() {
+        Some((0, signature)) => signature,
+        _ => return Err(OcspError::Malformed),
+    };
+
+    if verify_signature(algorithm, issuer.public_key(), tbs_response_data, signature).is_ok() {
+        return Ok(());
+    }
+    // Otherwise the response must be signed by a delegated responder, included in `certs`.
+    if let Ok((0xa0, certs, _)) = read_tlv(rest) {
+        let (mut certs, _) = expect_tlv(certs, 0x30)?;
+        while !certs.is_empty() {
+            let (cert, remaining) = split_tlv(certs)?;
+            certs = remaining;
+            let (_, responder) = parse_x509_certificate(cert)?;
+            if is_delegated_responder(&responder, &issuer)
+                && verify_signature(
+                    algorithm,
+                    responder.public_key(),
+                    tbs_response_data,
+                    signature,
+                )
+                .is_ok()
+            {
+                return Ok(());
+            }
+        }
+    }
+    Err(OcspError::InvalidSignature)
+}
+
+/// Walks an `OCSPResponse` down to the `SingleResponse` for `cert_id`, checking the response
+/// is signed for the issuer, and returns its thisUpdate and nextUpdate times.
+fn parse_response(
+    der: &[u8],
+    cert_id: &CertId,
+    issuer_der: &[u8],
+) -> Result<(DateTime<Utc>, Option<DateTime<Utc>>), OcspError> {
+    let (response, _) = expect_tlv(der, 0x30)?;
+    let (status, rest) = expect_tlv(response, 0x0a)?;
+    match status {
+        [0] => {}
+        [status] => return Err(OcspError::ResponderStatus(*status)),
+        _ => return Err(OcspError::Malformed),
+    }
+    let (response_bytes, _) = expect_tlv(rest, 0xa0)?;
+    let (response_bytes, _) = expect_tlv(response_bytes, 0x30)?;
+    let (_response_type, rest) = expect_tlv(response_bytes, 0x06)?;
+    let (basic_response, _) = expect_tlv(rest, 0x04)?;
+    let (basic_response, _) = expect_tlv(basic_response, 0x30)?;
+    let (tbs_response_data, rest) = split_tlv(basic_response)?;
+    verify_response_signature(tbs_response_data, rest, issuer_der)?;
+    let (response_data, _) = expect_tlv(tbs_response_data, 0x30)?;
+
+    let mut rest = response_data;
+    if rest.first() == Some(&0xa0) {
+        rest = read_tlv(rest)?.2; // version
+    }
+    rest = read_tlv(rest)?.2; // responderID
+    rest = expect_tlv(rest, 0x18)?.1; // producedAt
+    let (mut responses, _) = expect_tlv(rest, 0x30)?;
+    // Responders may answer for several certificates, ours isn't necessarily first.
+    let rest = loop {
+        if responses.is_empty() {
+            return Err(OcspError::CertIdMismatch);
+        }
+        let (single_response, remaining) = expect_tlv(responses, 0x30)?;
+        responses = remaining;
+        let (response_cert_id, rest) = expect_tlv(single_response, 0x30)?;
+        if cert_id.matches(response_cert_id)? {
+            break rest;
+        }
+    };
+    let (status_tag, _, rest) = read_tlv(rest)?;
+    match status_tag {
+        0x80 => {}
+        0xa1 => return Err(OcspError::CertificateStatus("revoked")),
+        _ => return Err(OcspError::CertificateStatus("unknown")),
+    }
+    let (this_update, rest) = expect_tlv(rest, 0x18)?;
+    let next_update = match read_tlv(rest) {
+        Ok((0xa0, next_update, _)) => {
+            Some(parse_generalized_time(expect_tlv(next_update, 0x18)?.0)?)
+        }
+        _ => None,
+    };
+    Ok((parse_generalized_time(this_update)?, next_update))
+}
+
+/// Parses a `YYYYMMDDHHMMSS[.fff]Z` GeneralizedTime.
+fn parse_generalized_time(time: &[u8]) -> Result<DateTime<Utc>, OcspError> {
+    let digits = time.get(..14).ok_or(OcspError::Malformed)?;
+    if !digits.iter().all(u8::is_ascii_digit) {
+        return Err(OcspError::Malformed);
+    }
+    let number = |range: std::ops::Range<usize>| {
+        digits[range]
+            .iter()
+            .fold(0u32, |n, d| n * 10 + (d - b'0') as u32)
+    };
+    Utc.with_ymd_and_hms(
+        number(0..4) as i32,
+        number(4..6),
+        number(6..8),
+        number(8..10),
+        number(10..12),
+        number(12..14),
+    )
+    .single()
+    .ok_or(OcspError::Malformed)
+}
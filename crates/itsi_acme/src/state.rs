@@ -9,8 +9,8 @@ use std::time::Duration;
 use chrono::{DateTime, TimeZone, Utc};
 use futures::future::try_join_all;
 use futures::{ready, FutureExt, Stream};
-use rcgen::{CertificateParams, DistinguishedName, Error as RcgenError, PKCS_ECDSA_P256_SHA256};
-use rustls::crypto::ring::sign::any_ecdsa_type;
+use rcgen::{CertificateParams, DistinguishedName, Error as RcgenError};
+use rustls::crypto::ring::sign::any_supported_type;
 use rustls::pki_types::{CertificateDer as RustlsCertificate, PrivateKeyDer, PrivatePkcs8KeyDer};
 use rustls::sign::CertifiedKey;
 use thiserror::Error;
@@ -20,17 +20,28 @@ use x509_parser::parse_x509_certificate;
 
 use crate::acceptor::AcmeAcceptor;
 use crate::acme::{
-    Account, AcmeError, Auth, AuthStatus, Directory, Identifier, Order, OrderStatus,
+    Account, AcmeError, Auth, AuthStatus, Challenge, Directory, Identifier, Order, OrderStatus,
 };
-use crate::{AcmeConfig, Incoming, ResolvesServerCertAcme};
+use crate::ari::{self, RenewalInfo, SuggestedWindow};
+use crate::{AcmeChallenge, AcmeConfig, Incoming, ResolvesServerCertAcme};
 
 type Timer = std::pin::Pin<Box<Sleep>>;
 type BoxFuture<T> = Pin<Box<dyn Future<Output = T> + Send>>;
 
+/// How long to wait before asking for renewal information again after a failed request.
+const RENEWAL_INFO_RETRY: Duration = Duration::from_secs(3600);
+
 pub fn after(d: std::time::Duration) -> Timer {
     Box::pin(tokio::time::sleep(d))
 }
 
+fn until(time: DateTime<Utc>) -> Duration {
+    (time - Utc::now())
+        .max(chrono::Duration::zero())
+        .to_std()
+        .unwrap_or_default()
+}
+
 #[allow(clippy::type_complexity)]
 pub struct AcmeState<EC: Debug = Infallible, EA: Debug = EC> {
     config: Arc<AcmeConfig<EC, EA>>,
@@ -41,12 +52,30 @@ pub struct AcmeState<EC: Debug = Infallible, EA: Debug = EC> {
     load_cert: Option<BoxFuture<Result<Option<Vec<u8>>, EC>>>,
     load_account: Option<BoxFuture<Result<Option<Vec<u8>>, EA>>>,
     order: Option<BoxFuture<Result<Vec<u8>, OrderError>>>,
+    load_renewed_cert: Option<BoxFuture<Result<Option<Vec<u8>>, EC>>>,
+    /// The deployed certificate, until the cache has been checked for a renewal made elsewhere.
+    renewal_pending_check: Option<Vec<u8>>,
+    /// The deployed certificate, if any.
+    deployed_cert: Option<Vec<u8>>,
+    /// Polls the CA's renewal information for the deployed certificate.
+    renewal_info: Option<BoxFuture<Result<Option<(RenewalInfo, Duration)>, AcmeError>>>,
+    /// The ARI identifier of the deployed certificate, if it has one.
+    ari_cert_id: Option<String>,
+    renewal_window: Option<SuggestedWindow>,
+    renew_at: DateTime<Utc>,
     backoff_cnt: usize,
     wait: Option<Timer>,
 }
 
 pub type Event<EC, EA> = Result<EventOk, EventError<EC, EA>>;
 
+/// What has to be withdrawn once an authorization completes.
+enum PresentedChallenge {
+    TlsAlpn01,
+    Http01(String),
+    Dns01(String, String),
+}
+
 #[derive(Debug)]
 pub enum EventOk {
     DeployedCachedCert,
@@ -154,6 +183,13 @@ impl<EC: 'static + Debug, EA: 'static + Debug> AcmeState<EC, EA> {
                 }
             })),
             order: None,
+            load_renewed_cert: None,
+            renewal_pending_check: None,
+            deployed_cert: None,
+            renewal_info: None,
+            ari_cert_id: None,
+            renewal_window: None,
+            renew_at: Utc::now(),
             backoff_cnt: 0,
             wait: None,
         }
@@ -166,7 +202,7 @@ impl<EC: 'static + Debug, EA: 'static + Debug> AcmeState<EC, EA> {
         let pk_bytes = pems.remove(0).into_contents();
         let pk_der: PrivatePkcs8KeyDer = pk_bytes.into();
         let pk: PrivateKeyDer = pk_der.into();
-        let pk = match any_ecdsa_type(&pk) {
+        let pk = match any_supported_type(&pk) {
             Ok(pk) => pk,
             Err(_) => return Err(CertParseError::InvalidPrivateKey),
         };
@@ -195,12 +231,34 @@ impl<EC: 'static + Debug, EA: 'static + Debug> AcmeState<EC, EA> {
                 }
             }
         };
+        self.ari_cert_id = ari::cert_id(cert.cert[0].as_ref());
         self.resolver.set_cert(Arc::new(cert));
-        let wait_duration = (validity[1] - (validity[1] - validity[0]) / 3 - Utc::now())
-            .max(chrono::Duration::zero())
-            .to_std()
-            .unwrap_or_default();
-        self.wait = Some(after(wait_duration));
+        self.renewal_pending_check = Some(pem.clone());
+        self.deployed_cert = Some(pem.clone());
+        let lifetime = validity[1] - validity[0];
+        self.renew_at = match self.config.renew_before {
+            Some(renew_before) => {
+                // Renewing any earlier would leave us re-ordering short-lived certificates in a loop.
+                let max_renew_before = lifetime / 2;
+                let renew_before = chrono::Duration::from_std(renew_before)
+                    .unwrap_or(chrono::Duration::MAX)
+                    .min(max_renew_before);
+                if renew_before == max_renew_before {
+                    log::warn!(
+                        "renew_before is at least half the certificate's {} day lifetime, renewing at the halfway point",
+                        lifetime.num_days()
+                    );
+                }
+                validity[1] - renew_before
+            }
+            None => validity[1] - lifetime / 3,
+        };
+        self.wait = Some(after(until(self.renew_at)));
+        self.renewal_window = None;
+        self.renewal_info = None;
+        if let Some(cert_id) = self.ari_cert_id.clone() {
+            self.check_renewal_info(cert_id, Duration::ZERO);
+        }
         if cached {
             return Ok(EventOk::DeployedCachedCert);
         }
@@ -217,12 +275,53 @@ impl<EC: 'static + Debug, EA: 'static + Debug> AcmeState<EC, EA> {
         }));
         Event::Ok(EventOk::DeployedNewCert)
     }
+    fn check_renewal_info(&mut self, cert_id: String, delay: Duration) {
+        let config = self.config.clone();
+        self.renewal_info = Some(Box::pin(async move {
+            after(delay).await;
+            let directory =
+                Directory::discover(&config.client_config, &config.directory_url).await?;
+            ari::fetch(&config.client_config, &directory, &cert_id).await
+        }));
+    }
+    /// Moves the renewal forward if the CA suggests renewing earlier than we would on our own.
+    fn process_renewal_info(&mut self, result: Result<Option<(RenewalInfo, Duration)>, AcmeError>) {
+        let Some(cert_id) = self.ari_cert_id.clone() else {
+            return;
+        };
+        let retry_after = match result {
+            Ok(None) => return,
+            Ok(Some((info, retry_after))) => {
+                // Only pick a new time when the window changes, so repeated checks don't drift earlier.
+                if self.renewal_window.as_ref() != Some(&info.suggested_window) {
+                    let renew_at = info.suggested_window.pick();
+                    if renew_at < self.renew_at {
+                        log::info!("renewing at {} as suggested by the CA", renew_at);
+                        if let Some(explanation_url) = &info.explanation_url {
+                            log::info!("renewal explanation: {}", explanation_url);
+                        }
+                        self.renew_at = renew_at;
+                        self.wait = Some(after(until(renew_at)));
+                    }
+                    self.renewal_window = Some(info.suggested_window);
+                }
+                retry_after
+            }
+            Err(err) => {
+                log::warn!("failed to fetch renewal info: {}", err);
+                RENEWAL_INFO_RETRY
+            }
+        };
+        self.check_renewal_info(cert_id, retry_after);
+    }
     async fn order(
         config: Arc<AcmeConfig<EC, EA>>,
         resolver: Arc<ResolvesServerCertAcme>,
         key_pair: Vec<u8>,
+        replaces: Option<String>,
     ) -> Result<Vec<u8>, OrderError> {
         let directory = Directory::discover(&config.client_config, &config.directory_url).await?;
+        let replaces = directory.renewal_info.as_ref().and(replaces);
         let account = Account::create_with_keypair(
             &config.client_config,
             directory,
@@ -234,10 +333,10 @@ impl<EC: 'static + Debug, EA: 'static + Debug> AcmeState<EC, EA> {
 
         let mut params = CertificateParams::new(config.domains.clone())?;
         params.distinguished_name = DistinguishedName::new();
-        let key_pair = rcgen::KeyPair::generate_for(&PKCS_ECDSA_P256_SHA256)?;
+        let key_pair = config.key_type.generate()?;
 
         let (order_url, mut order) = account
-            .new_order(&config.client_config, config.domains.clone())
+            .new_order(&config.client_config, config.domains.clone(), replaces)
             .await?;
         loop {
             match order.status {
@@ -293,39 +392,109 @@ impl<EC: 'static + Debug, EA: 'static + Debug> AcmeState<EC, EA> {
         url: &String,
     ) -> Result<(), OrderError> {
         let auth = account.auth(&config.client_config, url).await?;
-        let (domain, challenge_url) = match auth.status {
+        let (domain, challenge_url, presented) = match auth.status {
             AuthStatus::Pending => {
                 let Identifier::Dns(domain) = auth.identifier;
-                log::info!("trigger challenge for {}", &domain);
-                let (challenge, auth_key) =
-                    account.tls_alpn_01(&auth.challenges, domain.clone())?;
-                resolver.set_auth_key(domain.clone(), Arc::new(auth_key));
-                account
-                    .challenge(&config.client_config, &challenge.url)
-                    .await?;
-                (domain, challenge.url.clone())
+                log::info!("trigger {:?} challenge for {}", config.challenge, &domain);
+                let (challenge_url, presented) =
+                    Self::present_challenge(config, resolver, account, &auth.challenges, &domain)
+                        .await?;
+                (domain, challenge_url, presented)
             }
             AuthStatus::Valid => return Ok(()),
             _ => return Err(OrderError::BadAuth(auth)),
         };
+        let result = Self::validate(config, account, url, &domain, &challenge_url).await;
+        Self::cleanup_challenge(config, presented).await;
+        result
+    }
+    async fn present_challenge(
+        config: &AcmeConfig<EC, EA>,
+        resolver: &ResolvesServerCertAcme,
+        account: &Account,
+        challenges: &[Challenge],
+        domain: &str,
+    ) -> Result<(String, PresentedChallenge), OrderError> {
+        let (challenge, presented) = match &config.challenge {
+            AcmeChallenge::TlsAlpn01 => {
+                let (challenge, auth_key) = account.tls_alpn_01(challenges, domain.to_string())?;
+                resolver.set_auth_key(domain.to_string(), Arc::new(auth_key));
+                (challenge, PresentedChallenge::TlsAlpn01)
+            }
+            AcmeChallenge::Http01(responses) => {
+                let (challenge, key_authorization) = account.http_01(challenges)?;
+                responses
+                    .insert(&challenge.token, key_authorization)
+                    .await
+                    .map_err(AcmeError::from)?;
+                (
+                    challenge,
+                    PresentedChallenge::Http01(challenge.token.clone()),
+                )
+            }
+            AcmeChallenge::Dns01(provider) => {
+                let (challenge, value) = account.dns_01(challenges)?;
+                let name = format!("_acme-challenge.{}", domain);
+                provider
+                    .present(&name, &value)
+                    .await
+                    .map_err(AcmeError::from)?;
+                (challenge, PresentedChallenge::Dns01(name, value))
+            }
+        };
+        account
+            .challenge(&config.client_config, &challenge.url)
+            .await?;
+        Ok((challenge.url.clone(), presented))
+    }
+    async fn validate(
+        config: &AcmeConfig<EC, EA>,
+        account: &Account,
+        url: &String,
+        domain: &str,
+        challenge_url: &str,
+    ) -> Result<(), OrderError> {
         for i in 0u64..5 {
             after(Duration::from_secs(1u64 << i)).await;
             let auth = account.auth(&config.client_config, url).await?;
             match auth.status {
                 AuthStatus::Pending => {
-                    log::info!("authorization for {} still pending", &domain);
+                    log::info!("authorization for {} still pending", domain);
                     account
-                        .challenge(&config.client_config, &challenge_url)
+                        .challenge(&config.client_config, challenge_url)
                         .await?
                 }
                 AuthStatus::Valid => return Ok(()),
                 _ => return Err(OrderError::BadAuth(auth)),
             }
         }
-        Err(OrderError::TooManyAttemptsAuth(domain))
+        Err(OrderError::TooManyAttemptsAuth(domain.to_string()))
+    }
+    async fn cleanup_challenge(config: &AcmeConfig<EC, EA>, presented: PresentedChallenge) {
+        match (&config.challenge, presented) {
+            (AcmeChallenge::Http01(responses), PresentedChallenge::Http01(token)) => {
+                responses.remove(&token).await
+            }
+            (AcmeChallenge::Dns01(provider), PresentedChallenge::Dns01(name, value)) => {
+                if let Err(err) = provider.cleanup(&name, &value).await {
+                    log::warn!("failed to clean up dns-01 record {}: {}", name, err);
+                }
+            }
+            _ => {}
+        }
     }
     fn poll_next_infinite(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Event<EC, EA>> {
         loop {
+            // follow the CA's renewal information, without blocking on it
+            if let Some(renewal_info) = &mut self.renewal_info {
+                if let Poll::Ready(result) = renewal_info.poll_unpin(cx) {
+                    self.renewal_info.take();
+                    self.process_renewal_info(result);
+                    // Poll the next check (and any new timer) so it registers a wakeup.
+                    continue;
+                }
+            }
+
             // queued early action
             if let Some(early_action) = &mut self.early_action {
                 let result = ready!(early_action.poll_unpin(cx));
@@ -376,11 +545,48 @@ impl<EC: 'static + Debug, EA: 'static + Debug> AcmeState<EC, EA> {
                         // TODO: replace key on some errors or high backoff_cnt?
                         self.wait = Some(after(Duration::from_secs(1 << self.backoff_cnt)));
                         self.backoff_cnt = (self.backoff_cnt + 1).min(16);
+                        // Check the cache again before retrying: another server sharing it may have
+                        // ordered the certificate meanwhile, and shared caches take their lock again.
+                        match self.deployed_cert.clone() {
+                            Some(current) => self.renewal_pending_check = Some(current),
+                            None => {
+                                let config = self.config.clone();
+                                self.load_cert = Some(Box::pin(async move {
+                                    config
+                                        .cache
+                                        .load_cert(&config.domains, &config.directory_url)
+                                        .await
+                                }));
+                            }
+                        }
                         return Poll::Ready(Err(EventError::Order(err)));
                     }
                 }
             }
 
+            // check whether the certificate was renewed elsewhere
+            if let Some(load_renewed_cert) = &mut self.load_renewed_cert {
+                let result = ready!(load_renewed_cert.poll_unpin(cx));
+                self.load_renewed_cert.take();
+                match result {
+                    Ok(Some(pem)) => {
+                        return Poll::Ready(Self::process_cert(self.get_mut(), pem, true));
+                    }
+                    Ok(None) => {}
+                    Err(err) => return Poll::Ready(Err(EventError::CertCacheLoad(err))),
+                }
+            }
+            if let Some(current) = self.renewal_pending_check.take() {
+                let config = self.config.clone();
+                self.load_renewed_cert = Some(Box::pin(async move {
+                    config
+                        .cache
+                        .load_renewed_cert(&config.domains, &config.directory_url, &current)
+                        .await
+                }));
+                continue;
+            }
+
             // schedule order
             let account_key = match &self.account_key {
                 None => {
@@ -408,8 +614,21 @@ impl<EC: 'static + Debug, EA: 'static + Debug> AcmeState<EC, EA> {
             };
             let config = self.config.clone();
             let resolver = self.resolver.clone();
-            self.order = Some(Box::pin({
-                Self::order(config.clone(), resolver.clone(), account_key)
+            self.renewal_info = None;
+            let replaces = self.ari_cert_id.clone();
+            self.order = Some(Box::pin(async move {
+                let result = Self::order(config.clone(), resolver, account_key, replaces).await;
+                if result.is_err() {
+                    // Let other servers sharing the cache order instead, rather than wait on us.
+                    if let Err(err) = config
+                        .cache
+                        .order_failed(&config.domains, &config.directory_url)
+                        .await
+                    {
+                        log::warn!("failed to report the failed order to the cache: {:?}", err);
+                    }
+                }
+                result
             }));
         }
     }
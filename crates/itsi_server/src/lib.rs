@@ -15,8 +15,8 @@ use ruby_types::{
     itsi_http_response::ItsiHttpResponse, itsi_server::ItsiServer, ITSI_BODY_PROXY, ITSI_GRPC_CALL,
     ITSI_GRPC_RESPONSE_STREAM, ITSI_MODULE, ITSI_REQUEST, ITSI_RESPONSE, ITSI_SERVER,
 };
-use server::signal::reset_signal_handlers;
-use services::password_hasher;
+use server::{binds::tls::dev_ca, signal::reset_signal_handlers};
+use services::{password_hasher, response_cache};
 
 #[magnus::init]
 fn init(ruby: &Ruby) -> Result<()> {
@@ -38,6 +38,16 @@ fn init(ruby: &Ruby) -> Result<()> {
     let server = ruby.get_inner(&ITSI_SERVER);
     server.define_singleton_method("new", function!(ItsiServer::new, 3))?;
     server.define_singleton_method("reset_signal_handlers", function!(reset_signal_handlers, 0))?;
+    server.define_singleton_method(
+        "purge_response_cache_for",
+        function!(response_cache::purge_response_cache, 1),
+    )?;
+    server.define_singleton_method("dev_ca_path", function!(dev_ca::dev_ca_path, 0))?;
+    server.define_singleton_method("install_dev_ca", function!(dev_ca::install_dev_ca, 0))?;
+    server.define_singleton_method(
+        "dev_ca_certificates",
+        function!(dev_ca::dev_ca_certificates, 0),
+    )?;
     server.define_method("start", method!(ItsiServer::start, 0))?;
     server.define_method("stop", method!(ItsiServer::stop, 0))?;
 
@@ -64,6 +74,18 @@ fn init(ruby: &Ruby) -> Result<()> {
     request.define_method("[]", method!(ItsiHttpRequest::header, 1))?;
     request.define_method("scheme", method!(ItsiHttpRequest::scheme, 0))?;
     request.define_method("remote_addr", method!(ItsiHttpRequest::remote_addr, 0))?;
+    request.define_method(
+        "client_cert_subject",
+        method!(ItsiHttpRequest::client_cert_subject, 0),
+    )?;
+    request.define_method(
+        "client_cert_sans",
+        method!(ItsiHttpRequest::client_cert_sans, 0),
+    )?;
+    request.define_method(
+        "client_cert_fingerprint",
+        method!(ItsiHttpRequest::client_cert_fingerprint, 0),
+    )?;
     request.define_method("port", method!(ItsiHttpRequest::port, 0))?;
     request.define_method("body_parts", method!(ItsiHttpRequest::body, 0))?;
     request.define_method("response", method!(ItsiHttpRequest::response, 0))?;
@@ -183,47 +183,70 @@ impl ItsiHttpRequest {
     ) -> itsi_error::Result<HttpResponse> {
         match ItsiHttpRequest::new(hyper_request, context, script_name).await {
             Ok((request, receiver)) => {
-                let sender = if nonblocking {
-                    &context.nonblocking_sender
-                } else {
-                    &context.job_sender
-                };
-                match sender.try_send(RequestJob::ProcessHttpRequest(request, app)) {
-                    Err(err) => match err {
-                        async_channel::TrySendError::Full(_) => Ok(SERVICE_UNAVAILABLE_RESPONSE
-                            .to_http_response(context.accept)
-                            .await),
-                        async_channel::TrySendError::Closed(_) => {
-                            error!("Channel closed while sending request job");
-                            Ok(INTERNAL_SERVER_ERROR_RESPONSE
-                                .to_http_response(context.accept)
-                                .await)
-                        }
-                    },
-                    Ok(_) => match receiver.await {
-                        Ok(ResponseFrame::HttpResponse(response)) => Ok(response),
-                        Ok(ResponseFrame::HijackedResponse(response)) => {
-                            match response.process_hijacked_response().await {
-                                Ok(result) => Ok(result),
-                                Err(e) => {
-                                    error!("Error processing hijacked response: {}", e);
-                                    Ok(Response::new(HttpBody::empty()))
-                                }
-                            }
-                        }
-                        Err(_) => {
-                            error!("Failed to receive response from receiver");
-                            Ok(INTERNAL_SERVER_ERROR_RESPONSE
-                                .to_http_response(context.accept)
-                                .await)
-                        }
-                    },
-                }
+                Self::dispatch(app, request, receiver, context, nonblocking).await
             }
             Err(err_resp) => Ok(err_resp),
         }
     }
 
+    /// Calls the app with a request that has no body, such as an `auth_request` subrequest.
+    pub(crate) async fn process_bodyless_request(
+        app: Arc<HeapValue<Proc>>,
+        parts: Parts,
+        context: &HttpRequestContext,
+        script_name: String,
+        nonblocking: bool,
+    ) -> itsi_error::Result<HttpResponse> {
+        let (request, receiver) =
+            Self::from_parts(Arc::new(parts), ItsiBody::Empty, context, script_name);
+        Self::dispatch(app, request, receiver, context, nonblocking).await
+    }
+
+    async fn dispatch(
+        app: Arc<HeapValue<Proc>>,
+        request: ItsiHttpRequest,
+        receiver: tokio::sync::oneshot::Receiver<ResponseFrame>,
+        context: &HttpRequestContext,
+        nonblocking: bool,
+    ) -> itsi_error::Result<HttpResponse> {
+        let sender = if nonblocking {
+            &context.nonblocking_sender
+        } else {
+            &context.job_sender
+        };
+        match sender.try_send(RequestJob::ProcessHttpRequest(request, app)) {
+            Err(err) => match err {
+                async_channel::TrySendError::Full(_) => Ok(SERVICE_UNAVAILABLE_RESPONSE
+                    .to_http_response(context.accept)
+                    .await),
+                async_channel::TrySendError::Closed(_) => {
+                    error!("Channel closed while sending request job");
+                    Ok(INTERNAL_SERVER_ERROR_RESPONSE
+                        .to_http_response(context.accept)
+                        .await)
+                }
+            },
+            Ok(_) => match receiver.await {
+                Ok(ResponseFrame::HttpResponse(response)) => Ok(response),
+                Ok(ResponseFrame::HijackedResponse(response)) => {
+                    match response.process_hijacked_response().await {
+                        Ok(result) => Ok(result),
+                        Err(e) => {
+                            error!("Error processing hijacked response: {}", e);
+                            Ok(Response::new(HttpBody::empty()))
+                        }
+                    }
+                }
+                Err(_) => {
+                    error!("Failed to receive response from receiver");
+                    Ok(INTERNAL_SERVER_ERROR_RESPONSE
+                        .to_http_response(context.accept)
+                        .await)
+                }
+            },
+        }
+    }
+
     pub(crate) async fn new(
         request: HttpRequest,
         context: &HttpRequestContext,
@@ -258,8 +281,20 @@ impl ItsiHttpRequest {
             }
             ItsiBody::Buffered(body_bytes)
         };
+        Ok(Self::from_parts(parts, body, context, script_name))
+    }
+
+    fn from_parts(
+        parts: Arc<Parts>,
+        body: ItsiBody,
+        context: &HttpRequestContext,
+        script_name: String,
+    ) -> (
+        ItsiHttpRequest,
+        tokio::sync::oneshot::Receiver<ResponseFrame>,
+    ) {
         let (sender, receiver) = tokio::sync::oneshot::channel::<ResponseFrame>();
-        Ok((
+        (
             Self {
                 context: context.clone(),
                 version: parts.version,
@@ -274,7 +309,7 @@ impl ItsiHttpRequest {
                 parts,
             },
             receiver,
-        ))
+        )
     }
 
     pub(crate) fn path(&self) -> MagnusResult<&str> {
@@ -378,6 +413,30 @@ impl ItsiHttpRequest {
         Ok(&self.context.addr)
     }
 
+    pub(crate) fn client_cert_subject(&self) -> MagnusResult<Option<String>> {
+        Ok(self
+            .context
+            .client_cert
+            .as_ref()
+            .map(|cert| cert.subject.clone()))
+    }
+
+    pub(crate) fn client_cert_sans(&self) -> MagnusResult<Option<Vec<String>>> {
+        Ok(self
+            .context
+            .client_cert
+            .as_ref()
+            .map(|cert| cert.sans.clone()))
+    }
+
+    pub(crate) fn client_cert_fingerprint(&self) -> MagnusResult<Option<String>> {
+        Ok(self
+            .context
+            .client_cert
+            .as_ref()
+            .map(|cert| cert.fingerprint.clone()))
+    }
+
     pub(crate) fn port(&self) -> MagnusResult<u16> {
         Ok(self
             .parts
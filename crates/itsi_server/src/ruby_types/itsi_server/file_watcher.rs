@@ -5,8 +5,9 @@ use nix::unistd::{close, fork, pipe, read};
 use notify::event::ModifyKind;
 use notify::{Event, RecursiveMode, Watcher};
 use notify::{EventKind, RecommendedWatcher};
+use std::hash::Hash;
 use std::path::Path;
-use std::sync::mpsc::Sender;
+use std::sync::mpsc::RecvTimeoutError;
 use std::time::{Duration, Instant};
 use std::{collections::HashSet, fs};
 use std::{
@@ -16,7 +17,7 @@ use std::{
     sync::mpsc,
     thread::{self},
 };
-use tracing::debug;
+use tracing::{debug, warn};
 
 /// Represents a set of patterns and commands.
 #[derive(Debug, Clone)]
@@ -25,7 +26,115 @@ struct PatternGroup {
     glob_set: GlobSet,
     pattern: String,
     commands: Vec<Vec<String>>,
-    last_triggered: Option<Instant>,
+}
+
+impl PatternGroup {
+    fn matches(&self, path: &Path) -> bool {
+        path.strip_prefix(&self.base_dir).is_ok_and(|rel_path| {
+            self.glob_set.is_match(rel_path) || rel_path.to_str().is_some_and(|s| s == self.pattern)
+        })
+    }
+
+    fn run_commands(&self) {
+        debug!("Matched pattern: {:?}", self.pattern);
+        for command in &self.commands {
+            if command.is_empty() {
+                continue;
+            }
+            let mut cmd = Command::new(&command[0]);
+            if command.len() > 1 {
+                cmd.args(&command[1..]);
+            }
+            debug!(
+                "Executing command: {:?} due to change in {:?}",
+                command, self.pattern
+            );
+            match cmd.spawn() {
+                Ok(mut child) => {
+                    if let Err(e) = child.wait() {
+                        eprintln!("Command {:?} failed: {:?}", command, e);
+                    }
+                }
+                Err(e) => {
+                    eprintln!("Failed to execute command {:?}: {:?}", command, e);
+                }
+            }
+        }
+    }
+}
+
+/// Watches each of `dirs`, passing filesystem events to `on_event`.
+/// Directories that can't be watched are logged and skipped.
+pub(crate) fn watch_dirs<'a>(
+    dirs: impl IntoIterator<Item = &'a PathBuf>,
+    mode: RecursiveMode,
+    on_event: impl Fn(Event) + Send + 'static,
+) -> notify::Result<RecommendedWatcher> {
+    let mut watcher =
+        notify::recommended_watcher(move |result: notify::Result<Event>| match result {
+            Ok(event) => on_event(event),
+            Err(e) => warn!("File watch error: {:?}", e),
+        })?;
+    for dir in dirs {
+        if let Err(e) = watcher.watch(dir, mode) {
+            warn!("Failed to watch {:?}: {}", dir, e);
+        }
+    }
+    Ok(watcher)
+}
+
+/// Whether an event changes a file's contents, or creates, removes or renames a file.
+/// Files replaced by a rename (as many editors and cert-manager do) show up as creates or renames.
+pub(crate) fn is_change(event: &Event) -> bool {
+    matches!(
+        event.kind,
+        EventKind::Create(_)
+            | EventKind::Remove(_)
+            | EventKind::Modify(ModifyKind::Data(_) | ModifyKind::Name(_) | ModifyKind::Any)
+    )
+}
+
+/// Collects changes (to files, or groups of files) until they settle,
+/// so writers touching several files in quick succession trigger a single reload.
+pub(crate) struct Debouncer<K> {
+    quiet_period: Duration,
+    pending: HashSet<K>,
+    last_change: Option<Instant>,
+}
+
+impl<K: Eq + Hash> Debouncer<K> {
+    pub(crate) fn new(quiet_period: Duration) -> Self {
+        Self {
+            quiet_period,
+            pending: HashSet::new(),
+            last_change: None,
+        }
+    }
+
+    pub(crate) fn record(&mut self, changed: impl IntoIterator<Item = K>) {
+        let mut changed = changed.into_iter().peekable();
+        if changed.peek().is_some() {
+            self.pending.extend(changed);
+            self.last_change = Some(Instant::now());
+        }
+    }
+
+    /// How long until pending changes settle, or `None` if there are none.
+    pub(crate) fn remaining(&self) -> Option<Duration> {
+        self.last_change
+            .map(|last_change| self.quiet_period.saturating_sub(last_change.elapsed()))
+    }
+
+    /// Returns the pending changes, once no further changes have been recorded for the quiet period.
+    pub(crate) fn take_settled(&mut self) -> HashSet<K> {
+        match self.remaining() {
+            Some(remaining) if remaining.is_zero() => {
+                self.last_change = None;
+                std::mem::take(&mut self.pending)
+            }
+            _ => HashSet::new(),
+        }
+    }
 }
 
 /// Extracts the base directory from a wildcard pattern by taking the portion up to the first
@@ -56,7 +165,7 @@ fn extract_and_canonicalize_base_dir(pattern: &str) -> PathBuf {
     fs::canonicalize(&base).unwrap_or(base)
 }
 
-/// Minimum time between triggering the same pattern group (debounce time)
+/// How long a pattern group's files must go unchanged before its commands run (debounce time)
 const DEBOUNCE_DURATION: Duration = Duration::from_millis(2000);
 
 pub fn watch_groups(pattern_groups: Vec<(String, Vec<Vec<String>>)>) -> Result<Option<OwnedFd>> {
@@ -113,108 +222,49 @@ pub fn watch_groups(pattern_groups: Vec<(String, Vec<Vec<String>>)>) -> Result<O
                 glob_set,
                 pattern,
                 commands,
-                last_triggered: None,
             });
         }
 
-        // Create a channel and a watcher.
-        let (tx, rx) = mpsc::channel::<notify::Result<Event>>();
-        let sender = tx.clone();
-        fn event_fn(sender: Sender<notify::Result<Event>>) -> impl Fn(notify::Result<Event>) {
-            move |res| match res {
-                Ok(event) => {
-                    sender.send(Ok(event)).unwrap();
-                }
-                Err(e) => println!("watch error: {:?}", e),
-            }
-        }
-
-        let mut watched_dirs = HashSet::new();
-        let mut watcher: RecommendedWatcher =
-            notify::recommended_watcher(event_fn(sender)).expect("Failed to create watcher");
-        for group in &groups {
-            if watched_dirs.insert(group.base_dir.clone()) {
-                debug!("Watching {}/{}", group.base_dir.display(), group.pattern);
-                watcher
-                    .watch(&group.base_dir, RecursiveMode::Recursive)
-                    .expect("Failed to add watch");
-            }
+        let (tx, rx) = mpsc::channel::<Event>();
+        let base_dirs = groups
+            .iter()
+            .map(|group| group.base_dir.clone())
+            .collect::<HashSet<_>>();
+        for dir in &base_dirs {
+            debug!("Watching {}", dir.display());
         }
+        let watcher = watch_dirs(&base_dirs, RecursiveMode::Recursive, move |event| {
+            let _ = tx.send(event);
+        })
+        .expect("Failed to create watcher");
 
         debug!("Monitored groups {:?}", groups.len());
-        // Main event loop.
-        for res in rx {
-            match res {
-                Ok(event) => {
-                    if !matches!(event.kind, EventKind::Modify(ModifyKind::Data(_))) {
-                        continue;
-                    }
+        // Main event loop. Commands run once changes to a group's files settle.
+        let mut debouncer = Debouncer::new(DEBOUNCE_DURATION);
+        loop {
+            let event = match debouncer.remaining() {
+                Some(wait) => rx.recv_timeout(wait),
+                None => rx.recv().map_err(|_| RecvTimeoutError::Disconnected),
+            };
+            match event {
+                Ok(event) if is_change(&event) => {
                     debug!("Event fired {:?}", event);
-                    let now = Instant::now();
-                    for group in &mut groups {
-                        for path in event.paths.iter() {
-                            if let Ok(rel_path) = path.strip_prefix(&group.base_dir) {
-                                if group.glob_set.is_match(rel_path)
-                                    || rel_path.to_str().is_some_and(|s| s == group.pattern)
-                                {
-                                    debug!("Matched pattern: {:?}", group.pattern);
-                                    // Check if we should debounce this event
-                                    if let Some(last_triggered) = group.last_triggered {
-                                        if now.duration_since(last_triggered) < DEBOUNCE_DURATION {
-                                            // Skip this event as we've recently triggered for this pattern
-                                            continue;
-                                        }
-                                    }
-
-                                    // Update the last triggered time
-                                    group.last_triggered = Some(now);
-
-                                    // Execute the commands for this group.
-                                    for command in &group.commands {
-                                        if command.is_empty() {
-                                            continue;
-                                        }
-                                        let mut cmd = Command::new(&command[0]);
-                                        if command.len() > 1 {
-                                            cmd.args(&command[1..]);
-                                        }
-                                        debug!(
-                                            "Executing command: {:?} due to change in {:?}",
-                                            command, path
-                                        );
-                                        match cmd.spawn() {
-                                            Ok(mut child) => {
-                                                if let Err(e) = child.wait() {
-                                                    eprintln!(
-                                                        "Command {:?} failed: {:?}",
-                                                        command, e
-                                                    );
-                                                }
-                                            }
-                                            Err(e) => {
-                                                eprintln!(
-                                                    "Failed to execute command {:?}: {:?}",
-                                                    command, e
-                                                );
-                                            }
-                                        }
-                                    }
-                                    break;
-                                }
-                            }
-                        }
-                    }
+                    debouncer.record(groups.iter().enumerate().filter_map(|(index, group)| {
+                        event
+                            .paths
+                            .iter()
+                            .any(|path| group.matches(path))
+                            .then_some(index)
+                    }));
                 }
-                Err(e) => println!("Watch error: {:?}", e),
+                Ok(_) | Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => break,
+            }
+            for index in debouncer.take_settled() {
+                groups[index].run_commands();
             }
         }
 
-        // Clean up the watches.
-        for group in &groups {
-            watcher
-                .unwatch(&group.base_dir)
-                .expect("Failed to remove watch");
-        }
         drop(watcher);
         std::process::exit(0);
     } else {
@@ -21,6 +21,7 @@ use nix::{
     unistd::{close, dup},
 };
 use parking_lot::{Mutex, RwLock};
+use socket2::TcpKeepalive;
 use std::{
     collections::HashMap,
     os::fd::{AsRawFd, OwnedFd, RawFd},
@@ -78,13 +79,17 @@ pub struct ServerParams {
     pub max_local_error_reset_streams: Option<usize>,
     pub max_header_list_size: u32,
     pub max_send_buf_size: usize,
+    pub max_connections: Option<usize>,
+    pub max_connections_per_ip: Option<usize>,
+    pub keep_alive_timeout: Option<Duration>,
+    pub max_requests_per_connection: Option<u64>,
     pub binds: Vec<Bind>,
     #[debug(skip)]
     pub(crate) listeners: Mutex<Vec<Listener>>,
     listener_info: Mutex<HashMap<String, i32>>,
     pub itsi_server_token_preference: ItsiServerTokenPreference,
     pub preloaded: AtomicBool,
-    socket_opts: SocketOpts,
+    pub(crate) socket_opts: SocketOpts,
     preexisting_listeners: Option<String>,
 }
 
@@ -116,6 +121,12 @@ pub struct SocketOpts {
     pub nodelay: bool,
     pub recv_buffer_size: usize,
     pub send_buffer_size: usize,
+    pub tcp_keepalive: Option<TcpKeepalive>,
+    pub tcp_fastopen: Option<u32>,
+    pub tcp_defer_accept: Option<u32>,
+    pub socket_mark: Option<u32>,
+    pub ip_freebind: bool,
+    pub ip_transparent: bool,
 }
 
 impl ServerParams {
@@ -258,6 +269,31 @@ impl ServerParams {
         let send_buffer_size: usize = rb_param_hash
             .fetch::<_, Option<usize>>("send_buffer_size")?
             .unwrap_or(262_144);
+        let tcp_keepalive = rb_param_hash
+            .fetch::<_, Option<RHash>>("tcp_keepalive")?
+            .map(|keepalive| -> Result<TcpKeepalive> {
+                let mut tcp_keepalive = TcpKeepalive::new();
+                if let Some(idle) = keepalive.fetch::<_, Option<f64>>("idle")? {
+                    tcp_keepalive = tcp_keepalive.with_time(Duration::from_secs_f64(idle));
+                }
+                if let Some(interval) = keepalive.fetch::<_, Option<f64>>("interval")? {
+                    tcp_keepalive = tcp_keepalive.with_interval(Duration::from_secs_f64(interval));
+                }
+                if let Some(count) = keepalive.fetch::<_, Option<u32>>("count")? {
+                    tcp_keepalive = tcp_keepalive.with_retries(count);
+                }
+                Ok(tcp_keepalive)
+            })
+            .transpose()?;
+        let tcp_fastopen: Option<u32> = rb_param_hash.fetch("tcp_fastopen")?;
+        let tcp_defer_accept: Option<u32> = rb_param_hash.fetch("tcp_defer_accept")?;
+        let socket_mark: Option<u32> = rb_param_hash.fetch("socket_mark")?;
+        let ip_freebind: bool = rb_param_hash
+            .fetch::<_, Option<bool>>("ip_freebind")?
+            .unwrap_or(false);
+        let ip_transparent: bool = rb_param_hash
+            .fetch::<_, Option<bool>>("ip_transparent")?
+            .unwrap_or(false);
 
         if let Some(level) = log_level {
             set_level(&level);
@@ -294,6 +330,14 @@ impl ServerParams {
             rb_param_hash.fetch("max_local_error_reset_streams")?;
         let max_header_list_size: u32 = rb_param_hash.fetch("max_header_list_size")?;
         let max_send_buf_size: usize = rb_param_hash.fetch("max_send_buf_size")?;
+        let max_connections: Option<usize> = rb_param_hash.fetch("max_connections")?;
+        let max_connections_per_ip: Option<usize> =
+            rb_param_hash.fetch("max_connections_per_ip")?;
+        let keep_alive_timeout: Option<Duration> = rb_param_hash
+            .fetch::<_, Option<f64>>("keep_alive_timeout")?
+            .map(Duration::from_secs_f64);
+        let max_requests_per_connection: Option<u64> =
+            rb_param_hash.fetch("max_requests_per_connection")?;
 
         let binds: Option<Vec<String>> = rb_param_hash.fetch("binds")?;
         let binds = binds
@@ -315,6 +359,12 @@ impl ServerParams {
             nodelay,
             recv_buffer_size,
             send_buffer_size,
+            tcp_keepalive,
+            tcp_fastopen,
+            tcp_defer_accept,
+            socket_mark,
+            ip_freebind,
+            ip_transparent,
         };
         let preexisting_listeners = rb_param_hash.delete::<_, Option<String>>("listeners")?;
 
@@ -342,6 +392,10 @@ impl ServerParams {
             max_local_error_reset_streams,
             max_header_list_size,
             max_send_buf_size,
+            max_connections,
+            max_connections_per_ip,
+            keep_alive_timeout,
+            max_requests_per_connection,
             binds,
             itsi_server_token_preference,
             socket_opts,
@@ -357,7 +411,8 @@ impl ServerParams {
     }
 
     pub fn setup_listeners(&self) -> Result<()> {
-        let listeners = if let Some(preexisting_listeners) = self.preexisting_listeners.as_ref() {
+        let mut listeners = if let Some(preexisting_listeners) = self.preexisting_listeners.as_ref()
+        {
             let bind_to_fd_map: HashMap<String, i32> = serde_json::from_str(preexisting_listeners)
                 .map_err(|e| {
                     magnus::Error::new(
@@ -388,6 +443,7 @@ impl ServerParams {
                 .into_iter()
                 .collect::<Vec<_>>()
         };
+        Listener::share_acme_acceptors(&mut listeners);
 
         let listener_info = listeners
             .iter()
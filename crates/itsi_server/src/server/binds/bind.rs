@@ -1,6 +1,7 @@
 use super::{
     bind_protocol::BindProtocol,
-    tls::{configure_tls, ItsiTlsAcceptor},
+    proxy_protocol::ProxyProtocol,
+    tls::{configure_tls, uses_acme_http01, ItsiTlsAcceptor},
 };
 use crate::prelude::*;
 use itsi_error::ItsiError;
@@ -9,6 +10,7 @@ use std::{
     net::{IpAddr, Ipv4Addr, Ipv6Addr, ToSocketAddrs},
     path::PathBuf,
     str::FromStr,
+    time::Duration,
 };
 use tracing::{instrument, Level};
 
@@ -16,6 +18,8 @@ use tracing::{instrument, Level};
 pub enum BindAddress {
     Ip(IpAddr),
     UnixSocket(PathBuf),
+    /// A Linux abstract-namespace Unix socket, written `unix://@name`.
+    AbstractUnixSocket(String),
 }
 
 impl Default for BindAddress {
@@ -31,6 +35,15 @@ pub struct Bind {
     pub port: Option<u16>, // None for Unix Sockets
     pub protocol: BindProtocol,
     pub tls_config: Option<TlsOptions>,
+    pub proxy_protocol: Option<ProxyProtocol>,
+    /// The name of an inherited socket (e.g. from systemd's `LISTEN_FDNAMES`) to listen on.
+    pub fd_name: Option<String>,
+    /// The maximum number of connections open on this bind, per worker.
+    pub max_connections: Option<usize>,
+    /// Overrides the server's `keep_alive_timeout` for connections on this bind.
+    pub keep_alive_timeout: Option<Duration>,
+    /// Overrides the server's `max_requests_per_connection` for connections on this bind.
+    pub max_requests_per_connection: Option<u64>,
 }
 
 #[derive(Default, Clone)]
@@ -48,12 +61,31 @@ impl TlsOptions {
 impl Bind {
     pub fn listener_address_string(&self) -> String {
         match &self.address {
-            BindAddress::Ip(ip) => format!("tcp://{}:{}", ip.to_canonical(), self.port.unwrap()),
+            BindAddress::Ip(ip) => match self.protocol {
+                BindProtocol::Http3 => {
+                    format!("udp://{}:{}", ip.to_canonical(), self.port.unwrap())
+                }
+                _ => format!("tcp://{}:{}", ip.to_canonical(), self.port.unwrap()),
+            },
             BindAddress::UnixSocket(path) => {
                 format!("unix://{}", path.as_path().to_str().unwrap())
             }
+            BindAddress::AbstractUnixSocket(name) => format!("unix://@{}", name),
         }
     }
+
+    /// Whether this bind orders its ACME certificate using the http-01 challenge.
+    pub fn uses_acme_http01(&self) -> bool {
+        self.tls_config
+            .as_ref()
+            .is_some_and(|tls| uses_acme_http01(&tls.options))
+    }
+
+    /// Whether this bind answers ACME http-01 challenges, given some bind of the server uses them.
+    /// CAs validate over plain HTTP, and may follow redirects to the bind that ordered the certificate.
+    pub fn answers_acme_http01(&self) -> bool {
+        matches!(self.protocol, BindProtocol::Http | BindProtocol::Unix) || self.uses_acme_http01()
+    }
 }
 
 impl std::fmt::Debug for Bind {
@@ -74,6 +106,7 @@ impl std::fmt::Debug for Bind {
             BindAddress::UnixSocket(path) => {
                 write!(f, "{}://{}", self.protocol, path.display())
             }
+            BindAddress::AbstractUnixSocket(name) => write!(f, "{}://@{}", self.protocol, name),
         }
     }
 }
@@ -82,8 +115,14 @@ impl std::fmt::Debug for Bind {
 /// E.g.
 /// *`https://example.com:443?tls_cert=/path/to/cert.pem&tls_key=/path/to/key.pem`
 /// *`unix:///path/to/socket.sock`
+/// *`unix://@itsi` (an abstract socket, Linux only)
 /// *`http://example.com:80`
 /// *`https://[::]:80`
+/// *`h3://0.0.0.0:443?cert=/path/to/cert.pem&key=/path/to/key.pem`
+/// *`http://0.0.0.0:80?proxy_protocol=v2&proxy_protocol_trusted=10.0.0.0/8`
+/// *`http://0.0.0.0:80?fd_name=web`
+/// *`http://0.0.0.0:80?max_connections=1000`
+/// *`http://0.0.0.0:80?keep_alive_timeout=5&max_requests_per_connection=1000`
 impl FromStr for Bind {
     type Err = ItsiError;
 
@@ -134,26 +173,67 @@ impl FromStr for Bind {
             BindAddress::Ip(ip)
         } else {
             match protocol {
-                BindProtocol::Https | BindProtocol::Http => resolve_hostname(host)
-                    .map(BindAddress::Ip)
-                    .ok_or(ItsiError::ArgumentError(format!(
-                        "Failed to resolve hostname {}",
-                        host
-                    )))?,
-                BindProtocol::Unix | BindProtocol::Unixs => BindAddress::UnixSocket(host.into()),
+                BindProtocol::Https | BindProtocol::Http | BindProtocol::Http3 => {
+                    resolve_hostname(host)
+                        .map(BindAddress::Ip)
+                        .ok_or(ItsiError::ArgumentError(format!(
+                            "Failed to resolve hostname {}",
+                            host
+                        )))?
+                }
+                BindProtocol::Unix | BindProtocol::Unixs => match host.strip_prefix('@') {
+                    Some(name) if cfg!(any(target_os = "linux", target_os = "android")) => {
+                        BindAddress::AbstractUnixSocket(name.to_owned())
+                    }
+                    Some(_) => {
+                        return Err(ItsiError::ArgumentError(
+                            "Abstract Unix sockets are only supported on Linux".to_owned(),
+                        ))
+                    }
+                    None => BindAddress::UnixSocket(host.into()),
+                },
             }
         };
 
         let port = match protocol {
             BindProtocol::Http => port.or(Some(80)),
-            BindProtocol::Https => port.or(Some(443)),
+            BindProtocol::Https | BindProtocol::Http3 => port.or(Some(443)),
             BindProtocol::Unix => None,
             BindProtocol::Unixs => None,
         };
 
+        let proxy_protocol = ProxyProtocol::from_options(&options)?;
+        if proxy_protocol.is_some() && !matches!(protocol, BindProtocol::Http | BindProtocol::Https)
+        {
+            return Err(ItsiError::ArgumentError(
+                "proxy_protocol is only supported on http and https binds".to_owned(),
+            ));
+        }
+
+        let fd_name = options.get("fd_name").cloned();
+        let max_connections = positive_option::<usize>(&options, "max_connections")?;
+        let max_requests_per_connection =
+            positive_option::<u64>(&options, "max_requests_per_connection")?;
+        let keep_alive_timeout = options
+            .get("keep_alive_timeout")
+            .map(|timeout| {
+                timeout
+                    .parse::<f64>()
+                    .ok()
+                    .filter(|timeout| timeout.is_finite() && *timeout > 0.0)
+                    .map(Duration::from_secs_f64)
+                    .ok_or_else(|| {
+                        ItsiError::ArgumentError(format!(
+                            "Invalid keep_alive_timeout {}. Expected a positive number of seconds",
+                            timeout
+                        ))
+                    })
+            })
+            .transpose()?;
+
         let tls_config = match protocol {
             BindProtocol::Http => None,
-            BindProtocol::Https => Some(TlsOptions {
+            BindProtocol::Https | BindProtocol::Http3 => Some(TlsOptions {
                 host: host.to_owned(),
                 options,
             }),
@@ -168,6 +248,11 @@ impl FromStr for Bind {
             port,
             protocol,
             tls_config,
+            proxy_protocol,
+            fd_name,
+            max_connections,
+            keep_alive_timeout,
+            max_requests_per_connection,
         };
         Ok(bind)
     }
@@ -181,6 +266,28 @@ fn parse_bind_options(query: &str) -> HashMap<String, String> {
         .collect()
 }
 
+/// Parses an optional bind option that must be a positive integer.
+fn positive_option<T: FromStr + Default + PartialOrd>(
+    options: &HashMap<String, String>,
+    name: &str,
+) -> Result<Option<T>> {
+    options
+        .get(name)
+        .map(|value| {
+            value
+                .parse::<T>()
+                .ok()
+                .filter(|parsed| *parsed > T::default())
+                .ok_or_else(|| {
+                    ItsiError::ArgumentError(format!(
+                        "Invalid {} {}. Expected a positive integer",
+                        name, value
+                    ))
+                })
+        })
+        .transpose()
+}
+
 /// Attempts to resolve a hostname into an IP address.
 #[instrument(ret(level = Level::DEBUG))]
 fn resolve_hostname(hostname: &str) -> Option<IpAddr> {
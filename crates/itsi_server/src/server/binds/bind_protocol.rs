@@ -8,6 +8,7 @@ pub enum BindProtocol {
     Http,
     Unix,
     Unixs,
+    Http3,
 }
 
 impl FromStr for BindProtocol {
@@ -19,6 +20,7 @@ impl FromStr for BindProtocol {
             "https" => Ok(BindProtocol::Https),
             "unix" => Ok(BindProtocol::Unix),
             "tls" => Ok(BindProtocol::Unixs),
+            "h3" | "quic" => Ok(BindProtocol::Http3),
             _ => Err(ItsiError::UnsupportedProtocol(s.to_string())),
         }
     }
@@ -31,6 +33,7 @@ impl std::fmt::Display for BindProtocol {
             BindProtocol::Http => "http",
             BindProtocol::Unix => "unix",
             BindProtocol::Unixs => "tls",
+            BindProtocol::Http3 => "h3",
         };
         write!(f, "{}", s)
     }
@@ -0,0 +1,60 @@
+use rustls::pki_types::CertificateDer;
+use sha2::{Digest, Sha256};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use x509_parser::{extensions::GeneralName, prelude::*};
+
+/// The verified certificate a client presented during a mutual TLS handshake.
+#[derive(Debug, Clone)]
+pub struct ClientCertificate {
+    pub subject: String,
+    pub sans: Vec<String>,
+    /// Lowercase hex SHA-256 digest of the DER encoded certificate.
+    pub fingerprint: String,
+}
+
+impl ClientCertificate {
+    /// Builds from the peer chain of a completed handshake. The leaf certificate comes first.
+    pub fn from_peer_certificates(certificates: Option<&[CertificateDer]>) -> Option<Self> {
+        Self::from_der(certificates?.first()?.as_ref())
+    }
+
+    pub fn from_der(der: &[u8]) -> Option<Self> {
+        let (_, certificate) = X509Certificate::from_der(der).ok()?;
+        let sans = certificate
+            .subject_alternative_name()
+            .ok()
+            .flatten()
+            .map(|extension| {
+                extension
+                    .value
+                    .general_names
+                    .iter()
+                    .filter_map(general_name_to_string)
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        Some(Self {
+            subject: certificate.subject().to_string(),
+            sans,
+            fingerprint: Sha256::digest(der)
+                .iter()
+                .map(|b| format!("{:02x}", b))
+                .collect(),
+        })
+    }
+}
+
+fn general_name_to_string(name: &GeneralName) -> Option<String> {
+    match name {
+        GeneralName::DNSName(name) => Some(name.to_string()),
+        GeneralName::RFC822Name(email) => Some(email.to_string()),
+        GeneralName::URI(uri) => Some(uri.to_string()),
+        GeneralName::IPAddress(bytes) => match bytes.len() {
+            4 => Some(IpAddr::V4(Ipv4Addr::from(<[u8; 4]>::try_from(*bytes).ok()?)).to_string()),
+            16 => Some(IpAddr::V6(Ipv6Addr::from(<[u8; 16]>::try_from(*bytes).ok()?)).to_string()),
+            _ => None,
+        },
+        _ => None,
+    }
+}
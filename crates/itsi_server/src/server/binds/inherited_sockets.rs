@@ -0,0 +1,182 @@
+use super::{
+    bind::{Bind, BindAddress},
+    bind_protocol::BindProtocol,
+    listener::unix_socket_name,
+};
+use itsi_error::{ItsiError, Result};
+use itsi_tracing::info;
+use nix::{
+    fcntl::{fcntl, FcntlArg, FdFlag},
+    unistd::{dup, getpid},
+};
+use socket2::{SockRef, Type};
+use std::{
+    env,
+    os::fd::{AsRawFd, BorrowedFd, RawFd},
+    sync::LazyLock,
+};
+
+/// systemd passes sockets starting from this descriptor.
+const SD_LISTEN_FDS_START: RawFd = 3;
+
+/// Listening sockets passed to us by a supervisor, rather than bound by us.
+/// Read once, in the master process, before any worker is forked.
+static INHERITED_SOCKETS: LazyLock<Vec<InheritedSocket>> = LazyLock::new(take_inherited_sockets);
+
+#[derive(Debug)]
+struct InheritedSocket {
+    fd: BorrowedFd<'static>,
+    name: Option<String>,
+}
+
+impl InheritedSocket {
+    fn local_addr(&self) -> Option<socket2::SockAddr> {
+        self.socket().local_addr().ok()
+    }
+
+    fn socket(&self) -> SockRef<'_> {
+        SockRef::from(&self.fd)
+    }
+
+    fn is_datagram(&self) -> bool {
+        self.socket().r#type().is_ok_and(|kind| kind == Type::DGRAM)
+    }
+
+    fn describe(&self) -> String {
+        let address = match self.local_addr() {
+            Some(addr) => match (addr.as_socket(), addr.as_pathname()) {
+                (Some(addr), _) => addr.to_string(),
+                (_, Some(path)) => path.display().to_string(),
+                _ => unix_socket_name(&addr).unwrap_or_else(|| "unknown address".to_owned()),
+            },
+            None => "unknown address".to_owned(),
+        };
+        match &self.name {
+            Some(name) => format!("fd {} ({}, {})", self.fd.as_raw_fd(), name, address),
+            None => format!("fd {} ({})", self.fd.as_raw_fd(), address),
+        }
+    }
+
+    /// Whether this socket is listening on the address of `bind`.
+    /// A wildcard address matches any other wildcard address on the same port,
+    /// so `http://0.0.0.0:80` matches a dual-stack systemd socket on `[::]:80`.
+    fn matches_address(&self, bind: &Bind) -> bool {
+        let Some(local_addr) = self.local_addr() else {
+            return false;
+        };
+        match &bind.address {
+            BindAddress::Ip(ip) => local_addr.as_socket().is_some_and(|addr| {
+                self.is_datagram() == matches!(bind.protocol, BindProtocol::Http3)
+                    && Some(addr.port()) == bind.port
+                    && (addr.ip().to_canonical() == ip.to_canonical()
+                        || (addr.ip().is_unspecified() && ip.is_unspecified()))
+            }),
+            BindAddress::UnixSocket(path) => local_addr.as_pathname() == Some(path.as_path()),
+            BindAddress::AbstractUnixSocket(name) => {
+                unix_socket_name(&local_addr) == Some(format!("@{}", name))
+            }
+        }
+    }
+}
+
+/// Reads sockets passed using the systemd protocol (`LISTEN_FDS`, `LISTEN_FDNAMES`),
+/// also used by s6 and others, or by einhorn (`EINHORN_FD_COUNT`, `EINHORN_FD_<n>`).
+///
+/// The systemd variables are removed, so they aren't mistaken as meant for processes we spawn.
+fn take_inherited_sockets() -> Vec<InheritedSocket> {
+    let systemd_fds = env::var("LISTEN_FDS").ok().and_then(|count| {
+        let for_us = env::var("LISTEN_PID")
+            .map(|pid| pid.parse::<i32>().ok() == Some(getpid().as_raw()))
+            .unwrap_or(true);
+        for_us.then(|| count.parse::<RawFd>().ok()).flatten()
+    });
+    let sockets = if let Some(count) = systemd_fds {
+        let names = env::var("LISTEN_FDNAMES").unwrap_or_default();
+        let mut names = names.split(':').map(str::to_owned);
+        (SD_LISTEN_FDS_START..SD_LISTEN_FDS_START + count)
+            .map(|fd| InheritedSocket {
+                fd: unsafe { BorrowedFd::borrow_raw(fd) },
+                name: names.next().filter(|name| !name.is_empty()),
+            })
+            .collect::<Vec<_>>()
+    } else if let Some(count) = env::var("EINHORN_FD_COUNT")
+        .ok()
+        .and_then(|count| count.parse::<usize>().ok())
+    {
+        (0..count)
+            .filter_map(|index| env::var(format!("EINHORN_FD_{}", index)).ok())
+            .filter_map(|fd| fd.parse::<RawFd>().ok())
+            .map(|fd| InheritedSocket {
+                fd: unsafe { BorrowedFd::borrow_raw(fd) },
+                name: None,
+            })
+            .collect::<Vec<_>>()
+    } else {
+        vec![]
+    };
+
+    for var in ["LISTEN_PID", "LISTEN_FDS", "LISTEN_FDNAMES"] {
+        env::remove_var(var);
+    }
+
+    for socket in sockets.iter() {
+        // We hand out duplicates of these, so the originals shouldn't leak into exec'd processes.
+        fcntl(socket.fd.as_raw_fd(), FcntlArg::F_SETFD(FdFlag::FD_CLOEXEC)).ok();
+        info!("Inherited listening socket {}", socket.describe());
+    }
+    sockets
+}
+
+/// Finds the inherited socket for `bind`, returning a duplicate of its descriptor.
+///
+/// Binds with an `fd_name` option must match a socket by name, other binds match by address.
+/// Once we've been passed sockets, every bind must match one. We're usually unable to bind the
+/// address ourselves (e.g. a privileged port), and the supervisor owns the socket's lifecycle.
+pub fn inherited_fd(bind: &Bind) -> Result<Option<RawFd>> {
+    let socket = match &bind.fd_name {
+        Some(name) => INHERITED_SOCKETS
+            .iter()
+            .find(|socket| socket.name.as_ref() == Some(name))
+            .ok_or_else(|| {
+                ItsiError::ArgumentError(format!(
+                    "Bind {:?} requires an inherited socket named {:?}, but {}",
+                    bind,
+                    name,
+                    available()
+                ))
+            })?,
+        None if INHERITED_SOCKETS.is_empty() => return Ok(None),
+        None => INHERITED_SOCKETS
+            .iter()
+            .find(|socket| socket.matches_address(bind))
+            .ok_or_else(|| {
+                ItsiError::ArgumentError(format!(
+                    "Bind {:?} has no matching inherited socket. {}. Use fd_name to select a socket by name",
+                    bind,
+                    available()
+                ))
+            })?,
+    };
+    if socket.is_datagram() != matches!(bind.protocol, BindProtocol::Http3) {
+        return Err(ItsiError::ArgumentError(format!(
+            "Inherited socket {} is not of the right type for bind {:?}",
+            socket.describe(),
+            bind
+        )));
+    }
+    Ok(Some(dup(socket.fd.as_raw_fd())?))
+}
+
+fn available() -> String {
+    if INHERITED_SOCKETS.is_empty() {
+        return "no sockets were inherited (LISTEN_FDS is not set)".to_owned();
+    }
+    format!(
+        "inherited sockets are: {}",
+        INHERITED_SOCKETS
+            .iter()
+            .map(InheritedSocket::describe)
+            .collect::<Vec<_>>()
+            .join(", ")
+    )
+}
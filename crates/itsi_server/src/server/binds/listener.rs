@@ -5,16 +5,21 @@ use crate::server::serve_strategy::single_mode::RunningPhase;
 
 use super::bind::{Bind, BindAddress};
 use super::bind_protocol::BindProtocol;
+use super::inherited_sockets;
+use super::proxy_protocol::ProxyProtocol;
 
 use super::tls::ItsiTlsAcceptor;
 use itsi_error::{ItsiError, Result};
 use itsi_tracing::info;
 use socket2::{Domain, Protocol, SockRef, Socket, Type};
+use std::ffi::OsStr;
 use std::fmt::Display;
-use std::net::{IpAddr, SocketAddr, TcpListener};
-use std::os::fd::{AsRawFd, FromRawFd, RawFd};
+use std::net::{IpAddr, SocketAddr, TcpListener, UdpSocket};
+use std::os::fd::{AsFd, AsRawFd, FromRawFd, RawFd};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::net::UnixListener;
 use std::sync::Arc;
-use std::{os::unix::net::UnixListener, path::PathBuf};
+use std::time::Duration;
 use tokio::net::TcpListener as TokioTcpListener;
 use tokio::net::UnixListener as TokioUnixListener;
 use tokio::net::{unix, TcpStream, UnixStream};
@@ -23,17 +28,30 @@ use tokio_rustls::TlsAcceptor;
 use tokio_stream::StreamExt;
 
 pub(crate) enum Listener {
-    Tcp(TcpListener),
-    TcpTls((TcpListener, ItsiTlsAcceptor)),
+    Tcp((TcpListener, Option<ProxyProtocol>)),
+    TcpTls((TcpListener, ItsiTlsAcceptor, Option<ProxyProtocol>)),
     Unix(UnixListener),
     UnixTls((UnixListener, ItsiTlsAcceptor)),
+    Quic((UdpSocket, ItsiTlsAcceptor)),
 }
 
 pub(crate) enum TokioListener {
-    Tcp(TokioTcpListener),
-    TcpTls(TokioTcpListener, ItsiTlsAcceptor),
+    Tcp(TokioTcpListener, Option<Arc<ProxyProtocol>>),
+    TcpTls(
+        TokioTcpListener,
+        ItsiTlsAcceptor,
+        Option<Arc<ProxyProtocol>>,
+    ),
     Unix(TokioUnixListener),
     UnixTls(TokioUnixListener, ItsiTlsAcceptor),
+    Quic(quinn::Endpoint),
+}
+
+/// A connection accepted by a [`TokioListener`].
+/// QUIC connections are multiplexed over a shared UDP socket, so they don't yield an [`IoStream`].
+pub(crate) enum Accepted {
+    Stream(PendingStream),
+    Quic(quinn::Incoming),
 }
 
 #[derive(Debug, Clone)]
@@ -46,7 +64,7 @@ pub struct ListenerInfo {
 impl TokioListener {
     pub fn listener_info(&self) -> ListenerInfo {
         match self {
-            TokioListener::Tcp(listener) => ListenerInfo {
+            TokioListener::Tcp(listener, _) => ListenerInfo {
                 host: listener
                     .local_addr()
                     .unwrap()
@@ -56,7 +74,7 @@ impl TokioListener {
                 port: listener.local_addr().unwrap().port(),
                 scheme: "http".to_string(),
             },
-            TokioListener::TcpTls(listener, _) => ListenerInfo {
+            TokioListener::TcpTls(listener, _, _) => ListenerInfo {
                 host: listener
                     .local_addr()
                     .unwrap()
@@ -67,54 +85,76 @@ impl TokioListener {
                 scheme: "https".to_string(),
             },
             TokioListener::Unix(listener) => ListenerInfo {
-                host: listener
-                    .local_addr()
-                    .unwrap()
-                    .as_pathname()
-                    .unwrap()
-                    .to_str()
-                    .unwrap()
-                    .to_owned(),
+                host: local_unix_socket_name(listener),
                 port: 0,
                 scheme: "unix".to_string(),
             },
             TokioListener::UnixTls(listener, _) => ListenerInfo {
-                host: listener
-                    .local_addr()
-                    .unwrap()
-                    .as_pathname()
-                    .unwrap()
-                    .to_str()
-                    .unwrap()
-                    .to_owned(),
+                host: local_unix_socket_name(listener),
                 port: 0,
                 scheme: "ssl".to_string(),
             },
+            TokioListener::Quic(endpoint) => ListenerInfo {
+                host: endpoint
+                    .local_addr()
+                    .unwrap()
+                    .ip()
+                    .to_canonical()
+                    .to_string(),
+                port: endpoint.local_addr().unwrap().port(),
+                scheme: "https".to_string(),
+            },
         }
     }
 
-    pub(crate) async fn accept(&self) -> Result<IoStream> {
+    /// The UDP port of a QUIC listener, used to advertise HTTP/3 via `Alt-Svc`.
+    pub fn quic_port(&self) -> Option<u16> {
         match self {
-            TokioListener::Tcp(listener) => TokioListener::accept_tcp(listener).await,
-            TokioListener::TcpTls(listener, acceptor) => {
-                TokioListener::accept_tls(listener, acceptor).await
-            }
-            TokioListener::Unix(listener) => TokioListener::accept_unix(listener).await,
-            TokioListener::UnixTls(listener, acceptor) => {
-                TokioListener::accept_unix_tls(listener, acceptor).await
-            }
+            TokioListener::Quic(endpoint) => endpoint.local_addr().ok().map(|addr| addr.port()),
+            _ => None,
         }
     }
 
-    async fn accept_tcp(listener: &TokioTcpListener) -> Result<IoStream> {
-        let tcp_stream = listener.accept().await?;
-        Self::to_tokio_io(Stream::TcpStream(tcp_stream), None).await
+    /// Accepts the next connection. Anything that waits on the client (reading a PROXY protocol header,
+    /// or a TLS handshake) is left to [`PendingStream::establish`], in the connection's own task,
+    /// so a client that connects and then stalls can't hold up accepting other connections.
+    pub(crate) async fn accept(&self) -> Result<Accepted> {
+        let pending = match self {
+            TokioListener::Tcp(listener, proxy_protocol) => PendingStream {
+                stream: Stream::TcpStream(listener.accept().await?),
+                proxy_protocol: proxy_protocol.clone(),
+                tls_acceptor: None,
+            },
+            TokioListener::TcpTls(listener, acceptor, proxy_protocol) => PendingStream {
+                stream: Stream::TcpStream(listener.accept().await?),
+                proxy_protocol: proxy_protocol.clone(),
+                tls_acceptor: Some(acceptor.clone()),
+            },
+            TokioListener::Unix(listener) => PendingStream {
+                stream: Stream::UnixStream(listener.accept().await?),
+                proxy_protocol: None,
+                tls_acceptor: None,
+            },
+            TokioListener::UnixTls(listener, acceptor) => PendingStream {
+                stream: Stream::UnixStream(listener.accept().await?),
+                proxy_protocol: None,
+                tls_acceptor: Some(acceptor.clone()),
+            },
+            TokioListener::Quic(endpoint) => {
+                return match endpoint.accept().await {
+                    Some(incoming) => Ok(Accepted::Quic(incoming)),
+                    None => Err(ItsiError::Pass),
+                }
+            }
+        };
+        Ok(Accepted::Stream(pending))
     }
 
     pub async fn spawn_acme_event_task(&self, mut shutdown_receiver: Receiver<RunningPhase>) {
         if let TokioListener::TcpTls(
             _,
             ItsiTlsAcceptor::Automatic(_acme_acceptor, state, _server_config),
+            _,
         ) = self
         {
             let mut state = state.lock().await;
@@ -133,56 +173,84 @@ impl TokioListener {
             }
         }
     }
+}
 
-    async fn accept_tls(
-        listener: &TokioTcpListener,
-        acceptor: &ItsiTlsAcceptor,
-    ) -> Result<IoStream> {
-        let tcp_stream = listener.accept().await?;
-        match acceptor {
-            ItsiTlsAcceptor::Manual(tls_acceptor) => {
-                Self::to_tokio_io(Stream::TcpStream(tcp_stream), Some(tls_acceptor)).await
-            }
-            ItsiTlsAcceptor::Automatic(acme_acceptor, _, rustls_config) => {
-                let accept_future = acme_acceptor.accept(tcp_stream.0);
-                match accept_future.await {
-                    Ok(None) => Err(ItsiError::Pass),
-                    Ok(Some(start_handshake)) => {
-                        let tls_stream = start_handshake.into_stream(rustls_config.clone()).await?;
-                        Ok(IoStream::TcpTls {
-                            stream: tls_stream,
-                            addr: SockAddr::Tcp(Arc::new(tcp_stream.1)),
-                        })
-                    }
-                    Err(error) => {
-                        error!(error = format!("{:?}", error));
-                        Err(ItsiError::Pass)
-                    }
-                }
-            }
+/// How long a client has to complete its TLS handshake.
+const TLS_HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A connection accepted by a [`TokioListener`], that isn't ready to serve requests yet.
+pub(crate) struct PendingStream {
+    stream: Stream,
+    proxy_protocol: Option<Arc<ProxyProtocol>>,
+    tls_acceptor: Option<ItsiTlsAcceptor>,
+}
+
+impl PendingStream {
+    /// The address this connection is counted under by `max_connections_per_ip`: the socket peer,
+    /// or, for a load balancer sending the PROXY protocol, nothing until
+    /// [`PendingStream::read_proxy_header`] finds the client address.
+    pub(crate) fn client_ip(&self) -> Option<IpAddr> {
+        match &self.stream {
+            Stream::TcpStream((_, addr)) => match self.proxy_protocol.as_ref() {
+                Some(proxy_protocol) if proxy_protocol.expects_header(addr) => None,
+                _ => Some(addr.ip().to_canonical()),
+            },
+            Stream::UnixStream(_) => None,
         }
     }
 
-    async fn accept_unix(listener: &TokioUnixListener) -> Result<IoStream> {
-        let unix_stream = listener.accept().await?;
-        Self::to_tokio_io(Stream::UnixStream(unix_stream), None).await
+    /// Reads the PROXY protocol header, if the bind uses the PROXY protocol,
+    /// replacing the peer address with the client address sent by the load balancer.
+    pub(crate) async fn read_proxy_header(&mut self) -> Result<()> {
+        if let (Some(proxy_protocol), Stream::TcpStream((tcp_stream, addr))) =
+            (self.proxy_protocol.as_ref(), &mut self.stream)
+        {
+            *addr = proxy_protocol.accept(tcp_stream, *addr).await?;
+        }
+        Ok(())
     }
 
-    async fn accept_unix_tls(
-        listener: &TokioUnixListener,
-        acceptor: &ItsiTlsAcceptor,
-    ) -> Result<IoStream> {
-        let unix_stream = listener.accept().await?;
-        match acceptor {
-            ItsiTlsAcceptor::Manual(tls_acceptor) => {
-                Self::to_tokio_io(Stream::UnixStream(unix_stream), Some(tls_acceptor)).await
-            }
-            ItsiTlsAcceptor::Automatic(_, _, _) => {
-                error!("Automatic TLS not supported on Unix sockets");
-                Err(ItsiError::UnsupportedProtocol(
-                    "Automatic TLS on Unix Sockets".to_owned(),
-                ))
+    /// Completes the TLS handshake, if the bind uses TLS.
+    /// Clients that don't finish the handshake within [`TLS_HANDSHAKE_TIMEOUT`] are disconnected.
+    pub(crate) async fn establish(self) -> Result<IoStream> {
+        tokio::time::timeout(TLS_HANDSHAKE_TIMEOUT, self.handshake())
+            .await
+            .map_err(|_| {
+                ItsiError::InvalidInput("Timed out waiting for TLS handshake".to_owned())
+            })?
+    }
+
+    async fn handshake(self) -> Result<IoStream> {
+        let stream = self.stream;
+        match self.tls_acceptor {
+            None => Self::to_tokio_io(stream, None).await,
+            Some(ItsiTlsAcceptor::Manual(tls_acceptor)) => {
+                Self::to_tokio_io(stream, Some(&tls_acceptor)).await
             }
+            Some(ItsiTlsAcceptor::Automatic(acme_acceptor, _, rustls_config)) => match stream {
+                Stream::TcpStream((tcp_stream, addr)) => {
+                    match acme_acceptor.accept(tcp_stream).await {
+                        Ok(None) => Err(ItsiError::Pass),
+                        Ok(Some(start_handshake)) => {
+                            let tls_stream = start_handshake.into_stream(rustls_config).await?;
+                            Ok(IoStream::TcpTls {
+                                stream: tls_stream,
+                                addr: SockAddr::Tcp(Arc::new(addr)),
+                            })
+                        }
+                        Err(error) => {
+                            error!(error = format!("{:?}", error));
+                            Err(ItsiError::Pass)
+                        }
+                    }
+                }
+                Stream::UnixStream(_) => {
+                    error!("Automatic TLS not supported on Unix sockets");
+                    Err(ItsiError::UnsupportedProtocol(
+                        "Automatic TLS on Unix Sockets".to_owned(),
+                    ))
+                }
+            },
         }
     }
 
@@ -249,7 +317,7 @@ impl std::fmt::Display for SockAddr {
 impl Display for Listener {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Listener::Tcp(listener) | Listener::TcpTls((listener, _)) => write!(
+            Listener::Tcp((listener, _)) | Listener::TcpTls((listener, _, _)) => write!(
                 f,
                 "{}",
                 listener
@@ -258,23 +326,24 @@ impl Display for Listener {
                     .unwrap_or_else(|_| "".to_string())
             ),
 
-            Listener::Unix(listener) | Listener::UnixTls((listener, _)) => write!(
+            Listener::Quic((socket, _)) => write!(
                 f,
                 "{}",
-                listener
+                socket
                     .local_addr()
-                    .map(|addr| addr
-                        .as_pathname()
-                        .map(|path| path.to_str().unwrap_or("").to_owned())
-                        .unwrap_or_default())
+                    .map(|addr| addr.to_string())
                     .unwrap_or_else(|_| "".to_string())
             ),
+
+            Listener::Unix(listener) | Listener::UnixTls((listener, _)) => {
+                write!(f, "{}", local_unix_socket_name(listener))
+            }
         }
     }
 }
 
 impl Listener {
-    pub fn rebind_listener(listener: TcpListener) -> TcpListener {
+    pub fn rebind_listener(listener: TcpListener, socket_opts: &SocketOpts) -> TcpListener {
         let sock = SockRef::from(&listener);
         let (reuse_address, reuse_port) = (
             sock.reuse_address().unwrap_or(true),
@@ -292,74 +361,119 @@ impl Listener {
             .map(|addr| (addr.ip(), addr.port()))
             .unwrap();
 
-        let socket_opts = SocketOpts {
-            reuse_address: sock.reuse_address().unwrap_or(true), // default: true
-            reuse_port: sock.reuse_port().unwrap_or(false),      // default: false
-            nodelay: sock.nodelay().unwrap_or(false),            // default: false
-            recv_buffer_size: sock.recv_buffer_size().unwrap_or(0),
-            send_buffer_size: sock.send_buffer_size().unwrap_or(0),
-            listen_backlog: 1024, // cannot query – pick sane default
-        };
+        connect_tcp_socket(ip, port, socket_opts).unwrap()
+    }
+
+    pub fn rebind_udp_socket(socket: UdpSocket, socket_opts: &SocketOpts) -> UdpSocket {
+        let sock = SockRef::from(&socket);
+        if !sock.reuse_port().unwrap_or(false) {
+            return socket;
+        }
 
-        connect_tcp_socket(ip, port, &socket_opts).unwrap()
+        let addr = sock.local_addr().unwrap().as_socket().unwrap();
+        connect_udp_socket(addr.ip(), addr.port(), socket_opts).unwrap()
     }
 
-    pub fn into_tokio_listener(self, no_rebind: bool) -> TokioListener {
-        match self {
-            Listener::Tcp(mut listener) => {
+    /// ACME challenges are answered over TCP, so a QUIC listener using ACME certificates
+    /// shares the ACME state of the HTTPS listener bound to the same port.
+    pub fn share_acme_acceptors(listeners: &mut [Listener]) {
+        let acme_acceptors = listeners
+            .iter()
+            .filter_map(|listener| match listener {
+                Listener::TcpTls((listener, acceptor @ ItsiTlsAcceptor::Automatic(..), _)) => {
+                    Some((listener.local_addr().ok()?.port(), acceptor.clone()))
+                }
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        for listener in listeners.iter_mut() {
+            if let Listener::Quic((socket, acceptor @ ItsiTlsAcceptor::Automatic(..))) = listener {
+                let port = socket.local_addr().map(|addr| addr.port()).unwrap_or(0);
+                match acme_acceptors.iter().find(|(tcp_port, _)| *tcp_port == port) {
+                    Some((_, shared)) => *acceptor = shared.clone(),
+                    None => warn!(
+                        "h3 bind on port {} uses ACME certificates, but there is no https bind on the same port to complete ACME challenges",
+                        port
+                    ),
+                }
+            }
+        }
+    }
+
+    pub fn into_tokio_listener(
+        self,
+        no_rebind: bool,
+        socket_opts: &SocketOpts,
+    ) -> Result<TokioListener> {
+        Ok(match self {
+            Listener::Tcp((mut listener, proxy_protocol)) => {
                 if cfg!(target_os = "linux") && !no_rebind {
-                    listener = Listener::rebind_listener(listener);
+                    listener = Listener::rebind_listener(listener, socket_opts);
                 }
-                TokioListener::Tcp(TokioTcpListener::from_std(listener).unwrap())
+                TokioListener::Tcp(
+                    TokioTcpListener::from_std(listener)?,
+                    proxy_protocol.map(Arc::new),
+                )
             }
-            Listener::TcpTls((mut listener, acceptor)) => {
+            Listener::TcpTls((mut listener, acceptor, proxy_protocol)) => {
                 if cfg!(target_os = "linux") && !no_rebind {
-                    listener = Listener::rebind_listener(listener);
+                    listener = Listener::rebind_listener(listener, socket_opts);
                 }
                 TokioListener::TcpTls(
-                    TokioTcpListener::from_std(listener).unwrap(),
+                    TokioTcpListener::from_std(listener)?,
                     acceptor.clone(),
+                    proxy_protocol.map(Arc::new),
                 )
             }
-            Listener::Unix(listener) => {
-                TokioListener::Unix(TokioUnixListener::from_std(listener).unwrap())
+            Listener::Unix(listener) => TokioListener::Unix(TokioUnixListener::from_std(listener)?),
+            Listener::UnixTls((listener, acceptor)) => {
+                TokioListener::UnixTls(TokioUnixListener::from_std(listener)?, acceptor.clone())
             }
-            Listener::UnixTls((listener, acceptor)) => TokioListener::UnixTls(
-                TokioUnixListener::from_std(listener).unwrap(),
-                acceptor.clone(),
-            ),
-        }
+            Listener::Quic((mut socket, acceptor)) => {
+                if cfg!(target_os = "linux") && !no_rebind {
+                    socket = Listener::rebind_udp_socket(socket, socket_opts);
+                }
+                TokioListener::Quic(quinn::Endpoint::new(
+                    quinn::EndpointConfig::default(),
+                    Some(acceptor.quic_server_config()?),
+                    socket,
+                    Arc::new(quinn::TokioRuntime),
+                )?)
+            }
+        })
     }
 
     /// Handover information when using exec to hand over the listener to a replacement process.
     pub fn handover(&self) -> Result<(String, i32)> {
         match self {
-            Listener::Tcp(listener) => {
+            Listener::Tcp((listener, _)) => {
                 let addr = listener.local_addr()?;
                 Ok((
                     format!("tcp://{}:{}", addr.ip().to_canonical(), addr.port()),
                     listener.as_raw_fd(),
                 ))
             }
-            Listener::TcpTls((listener, _)) => {
+            Listener::TcpTls((listener, _, _)) => {
                 let addr = listener.local_addr()?;
                 Ok((
                     format!("tcp://{}:{}", addr.ip().to_canonical(), addr.port()),
                     listener.as_raw_fd(),
                 ))
             }
-            Listener::Unix(listener) => {
-                let addr = listener.local_addr()?;
+            Listener::Unix(listener) => Ok((
+                format!("unix://{}", local_unix_socket_name(listener)),
+                listener.as_raw_fd(),
+            )),
+            Listener::UnixTls((listener, _)) => Ok((
+                format!("unix://{}", local_unix_socket_name(listener)),
+                listener.as_raw_fd(),
+            )),
+            Listener::Quic((socket, _)) => {
+                let addr = socket.local_addr()?;
                 Ok((
-                    format!("unix://{}", addr.as_pathname().unwrap().to_str().unwrap()),
-                    listener.as_raw_fd(),
-                ))
-            }
-            Listener::UnixTls((listener, _)) => {
-                let addr = listener.local_addr()?;
-                Ok((
-                    format!("unix://{}", addr.as_pathname().unwrap().to_str().unwrap()),
-                    listener.as_raw_fd(),
+                    format!("udp://{}:{}", addr.ip().to_canonical(), addr.port()),
+                    socket.as_raw_fd(),
                 ))
             }
         }
@@ -368,23 +482,32 @@ impl Listener {
     pub fn inherit_fd(bind: Bind, fd: RawFd, socket_opts: &SocketOpts) -> Result<Self> {
         let bound = match bind.address {
             BindAddress::Ip(_) => match bind.protocol {
-                BindProtocol::Http => Listener::Tcp(revive_tcp_socket(fd, socket_opts)?),
+                BindProtocol::Http => {
+                    Listener::Tcp((revive_tcp_socket(fd, socket_opts)?, bind.proxy_protocol))
+                }
                 BindProtocol::Https => {
                     let tcp_listener = revive_tcp_socket(fd, socket_opts)?;
                     Listener::TcpTls((
                         tcp_listener,
                         bind.tls_config.unwrap().build_acceptor().unwrap(),
+                        bind.proxy_protocol,
                     ))
                 }
-                _ => unreachable!(),
-            },
-            BindAddress::UnixSocket(_) => match bind.tls_config {
-                Some(tls_config) => Listener::UnixTls((
-                    revive_unix_socket(fd, socket_opts)?,
-                    tls_config.build_acceptor().unwrap(),
+                BindProtocol::Http3 => Listener::Quic((
+                    revive_udp_socket(fd, socket_opts)?,
+                    bind.tls_config.unwrap().build_acceptor()?,
                 )),
-                None => Listener::Unix(revive_unix_socket(fd, socket_opts)?),
+                _ => unreachable!(),
             },
+            BindAddress::UnixSocket(_) | BindAddress::AbstractUnixSocket(_) => {
+                match bind.tls_config {
+                    Some(tls_config) => Listener::UnixTls((
+                        revive_unix_socket(fd, socket_opts)?,
+                        tls_config.build_acceptor().unwrap(),
+                    )),
+                    None => Listener::Unix(revive_unix_socket(fd, socket_opts)?),
+                }
+            }
         };
         Ok(bound)
     }
@@ -392,27 +515,43 @@ impl Listener {
 
 impl Listener {
     pub fn build(bind: Bind, socket_opts: &SocketOpts) -> Result<Self> {
+        if let Some(fd) = inherited_sockets::inherited_fd(&bind)? {
+            return Self::inherit_fd(bind, fd, socket_opts);
+        }
         let bound = match bind.address {
             BindAddress::Ip(addr) => match bind.protocol {
-                BindProtocol::Http => {
-                    Listener::Tcp(connect_tcp_socket(addr, bind.port.unwrap(), socket_opts)?)
-                }
+                BindProtocol::Http => Listener::Tcp((
+                    connect_tcp_socket(addr, bind.port.unwrap(), socket_opts)?,
+                    bind.proxy_protocol,
+                )),
                 BindProtocol::Https => {
                     let tcp_listener = connect_tcp_socket(addr, bind.port.unwrap(), socket_opts)?;
                     Listener::TcpTls((
                         tcp_listener,
                         bind.tls_config.unwrap().build_acceptor().unwrap(),
+                        bind.proxy_protocol,
+                    ))
+                }
+                BindProtocol::Http3 => {
+                    let acceptor = bind.tls_config.unwrap().build_acceptor()?;
+                    // Fail early on certificates that can't be used for QUIC.
+                    acceptor.quic_server_config()?;
+                    Listener::Quic((
+                        connect_udp_socket(addr, bind.port.unwrap(), socket_opts)?,
+                        acceptor,
                     ))
                 }
                 _ => unreachable!(),
             },
-            BindAddress::UnixSocket(path) => match bind.tls_config {
-                Some(tls_config) => Listener::UnixTls((
-                    connect_unix_socket(&path, socket_opts)?,
-                    tls_config.build_acceptor().unwrap(),
-                )),
-                None => Listener::Unix(connect_unix_socket(&path, socket_opts)?),
-            },
+            BindAddress::UnixSocket(_) | BindAddress::AbstractUnixSocket(_) => {
+                let listener = connect_unix_socket(&bind.address, socket_opts)?;
+                match bind.tls_config {
+                    Some(tls_config) => {
+                        Listener::UnixTls((listener, tls_config.build_acceptor().unwrap()))
+                    }
+                    None => Listener::Unix(listener),
+                }
+            }
         };
         Ok(bound)
     }
@@ -427,11 +566,25 @@ fn revive_tcp_socket(fd: RawFd, socket_opts: &SocketOpts) -> Result<TcpListener>
     socket
         .set_recv_buffer_size(socket_opts.recv_buffer_size)
         .ok();
+    set_extended_socket_opts(&socket, socket_opts, true)?;
     socket.set_cloexec(true)?;
     socket.listen(socket_opts.listen_backlog as i32)?;
     Ok(socket.into())
 }
 
+fn revive_udp_socket(fd: RawFd, socket_opts: &SocketOpts) -> Result<UdpSocket> {
+    let socket = unsafe { Socket::from_raw_fd(fd) };
+    socket.set_reuse_port(socket_opts.reuse_port).ok();
+    socket.set_reuse_address(socket_opts.reuse_address).ok();
+    socket.set_nonblocking(true).ok();
+    socket
+        .set_recv_buffer_size(socket_opts.recv_buffer_size)
+        .ok();
+    set_extended_socket_opts(&socket, socket_opts, false)?;
+    socket.set_cloexec(true)?;
+    Ok(socket.into())
+}
+
 fn revive_unix_socket(fd: RawFd, socket_opts: &SocketOpts) -> Result<UnixListener> {
     let socket = unsafe { Socket::from_raw_fd(fd) };
     socket.set_nonblocking(true).ok();
@@ -459,6 +612,7 @@ fn connect_tcp_socket(addr: IpAddr, port: u16, socket_opts: &SocketOpts) -> Resu
         .set_recv_buffer_size(socket_opts.recv_buffer_size)
         .ok();
     socket.set_only_v6(false).ok();
+    set_extended_socket_opts(&socket, socket_opts, true)?;
     if let Err(e) = socket.bind(&socket_address.into()) {
         error!("Failed to bind socket: {}", e);
     };
@@ -468,12 +622,44 @@ fn connect_tcp_socket(addr: IpAddr, port: u16, socket_opts: &SocketOpts) -> Resu
     Ok(socket.into())
 }
 
-fn connect_unix_socket(path: &PathBuf, socket_opts: &SocketOpts) -> Result<UnixListener> {
-    let _ = std::fs::remove_file(path);
-    let socket = Socket::new(Domain::UNIX, Type::STREAM, None)?;
+fn connect_udp_socket(addr: IpAddr, port: u16, socket_opts: &SocketOpts) -> Result<UdpSocket> {
+    let domain = match addr {
+        IpAddr::V4(_) => Domain::IPV4,
+        IpAddr::V6(_) => Domain::IPV6,
+    };
+    let socket = Socket::new(domain, Type::DGRAM, Some(Protocol::UDP))?;
+    let socket_address: SocketAddr = SocketAddr::new(addr, port);
+    socket.set_reuse_address(socket_opts.reuse_address).ok();
+    socket.set_reuse_port(socket_opts.reuse_port).ok();
     socket.set_nonblocking(true).ok();
+    socket
+        .set_send_buffer_size(socket_opts.send_buffer_size)
+        .ok();
+    socket
+        .set_recv_buffer_size(socket_opts.recv_buffer_size)
+        .ok();
+    socket.set_only_v6(false).ok();
+    set_extended_socket_opts(&socket, socket_opts, false)?;
+    socket.bind(&socket_address.into())?;
+    Ok(socket.into())
+}
 
-    let socket_address = socket2::SockAddr::unix(path)?;
+fn connect_unix_socket(address: &BindAddress, socket_opts: &SocketOpts) -> Result<UnixListener> {
+    let socket_address = match address {
+        BindAddress::UnixSocket(path) => {
+            let _ = std::fs::remove_file(path);
+            socket2::SockAddr::unix(path)?
+        }
+        // Abstract socket names start with a NUL byte, and vanish with the last socket using them.
+        BindAddress::AbstractUnixSocket(name) => {
+            let mut bytes = vec![0];
+            bytes.extend_from_slice(name.as_bytes());
+            socket2::SockAddr::unix(OsStr::from_bytes(&bytes))?
+        }
+        BindAddress::Ip(_) => unreachable!(),
+    };
+    let socket = Socket::new(Domain::UNIX, Type::STREAM, None)?;
+    socket.set_nonblocking(true).ok();
 
     if let Err(e) = socket.bind(&socket_address) {
         error!("Failed to bind socket: {}", e);
@@ -483,3 +669,97 @@ fn connect_unix_socket(path: &PathBuf, socket_opts: &SocketOpts) -> Result<UnixL
     };
     Ok(socket.into())
 }
+
+/// Applies the socket options that aren't set on every listener.
+/// `IP_FREEBIND` and `IP_TRANSPARENT` must be set before binding, and `TCP_FASTOPEN` before listening.
+fn set_extended_socket_opts(socket: &Socket, socket_opts: &SocketOpts, tcp: bool) -> Result<()> {
+    if let (true, Some(keepalive)) = (tcp, socket_opts.tcp_keepalive.as_ref()) {
+        socket.set_tcp_keepalive(keepalive)?;
+    }
+    set_linux_socket_opts(socket, socket_opts, tcp)
+}
+
+#[cfg(target_os = "linux")]
+fn set_linux_socket_opts(socket: &Socket, socket_opts: &SocketOpts, tcp: bool) -> Result<()> {
+    use nix::libc::{TCP_DEFER_ACCEPT, TCP_FASTOPEN};
+
+    let failed = |option: &'static str| {
+        move |err: std::io::Error| {
+            ItsiError::ArgumentError(format!("Failed to set {} on socket: {}", option, err))
+        }
+    };
+    if let Some(mark) = socket_opts.socket_mark {
+        socket.set_mark(mark).map_err(failed("SO_MARK"))?;
+    }
+    if socket_opts.ip_freebind {
+        socket.set_freebind(true).map_err(failed("IP_FREEBIND"))?;
+    }
+    if socket_opts.ip_transparent {
+        socket
+            .set_ip_transparent(true)
+            .map_err(failed("IP_TRANSPARENT"))?;
+    }
+    if tcp {
+        if let Some(queue_length) = socket_opts.tcp_fastopen {
+            set_tcp_option(socket, TCP_FASTOPEN, queue_length).map_err(failed("TCP_FASTOPEN"))?;
+        }
+        if let Some(timeout) = socket_opts.tcp_defer_accept {
+            set_tcp_option(socket, TCP_DEFER_ACCEPT, timeout)
+                .map_err(failed("TCP_DEFER_ACCEPT"))?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn set_linux_socket_opts(_socket: &Socket, socket_opts: &SocketOpts, _tcp: bool) -> Result<()> {
+    if socket_opts.socket_mark.is_some()
+        || socket_opts.ip_freebind
+        || socket_opts.ip_transparent
+        || socket_opts.tcp_fastopen.is_some()
+        || socket_opts.tcp_defer_accept.is_some()
+    {
+        warn!("socket_mark, ip_freebind, ip_transparent, tcp_fastopen and tcp_defer_accept are only supported on Linux, and are ignored");
+    }
+    Ok(())
+}
+
+/// Sets an integer `IPPROTO_TCP` option that socket2 doesn't expose.
+#[cfg(target_os = "linux")]
+fn set_tcp_option(socket: &Socket, option: nix::libc::c_int, value: u32) -> std::io::Result<()> {
+    let value = value as nix::libc::c_int;
+    let result = unsafe {
+        nix::libc::setsockopt(
+            socket.as_raw_fd(),
+            nix::libc::IPPROTO_TCP,
+            option,
+            &value as *const nix::libc::c_int as *const nix::libc::c_void,
+            std::mem::size_of::<nix::libc::c_int>() as nix::libc::socklen_t,
+        )
+    };
+    if result == 0 {
+        Ok(())
+    } else {
+        Err(std::io::Error::last_os_error())
+    }
+}
+
+/// The path of a Unix socket address, or `@name` for an abstract socket.
+pub(super) fn unix_socket_name(addr: &socket2::SockAddr) -> Option<String> {
+    if let Some(path) = addr.as_pathname() {
+        return path.to_str().map(str::to_owned);
+    }
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    if let Some(name) = addr.as_abstract_namespace() {
+        return Some(format!("@{}", String::from_utf8_lossy(name)));
+    }
+    None
+}
+
+fn local_unix_socket_name(listener: &impl AsFd) -> String {
+    SockRef::from(listener)
+        .local_addr()
+        .ok()
+        .and_then(|addr| unix_socket_name(&addr))
+        .unwrap_or_default()
+}
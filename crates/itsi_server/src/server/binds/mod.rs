@@ -1,4 +1,7 @@
 pub mod bind;
 pub mod bind_protocol;
+pub mod client_certificate;
+pub mod inherited_sockets;
 pub mod listener;
+pub mod proxy_protocol;
 pub mod tls;
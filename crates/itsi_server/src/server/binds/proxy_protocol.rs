@@ -0,0 +1,187 @@
+use ipnet::IpNet;
+use itsi_error::{ItsiError, Result};
+use std::{
+    collections::HashMap,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    time::Duration,
+};
+use tokio::{io::AsyncReadExt, net::TcpStream};
+
+const V2_SIGNATURE: [u8; 12] = *b"\r\n\r\n\0\r\nQUIT\n";
+const V1_MAX_LENGTH: usize = 107;
+const HEADER_READ_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ProxyProtocolVersion {
+    V1,
+    V2,
+    Any,
+}
+
+/// PROXY protocol settings for a TCP bind.
+/// Load balancers in `trusted_sources` must prefix each connection with a PROXY protocol header,
+/// which tells us the address of the client they're forwarding.
+/// Connections from any other source are treated as direct client connections.
+#[derive(Debug, Clone)]
+pub struct ProxyProtocol {
+    pub version: ProxyProtocolVersion,
+    pub trusted_sources: Vec<IpNet>,
+}
+
+impl ProxyProtocol {
+    /// Reads the `proxy_protocol` and `proxy_protocol_trusted` bind options.
+    pub fn from_options(options: &HashMap<String, String>) -> Result<Option<Self>> {
+        let version = match options.get("proxy_protocol").map(String::as_str) {
+            None | Some("false") => return Ok(None),
+            Some("v1") => ProxyProtocolVersion::V1,
+            Some("v2") => ProxyProtocolVersion::V2,
+            Some("true") | Some("any") => ProxyProtocolVersion::Any,
+            Some(other) => {
+                return Err(ItsiError::ArgumentError(format!(
+                    "Invalid proxy_protocol {}. Expected one of v1, v2 or any",
+                    other
+                )))
+            }
+        };
+
+        // Trusting every peer would let any client spoof its address, so the sources must be explicit.
+        let trusted_sources = options
+            .get("proxy_protocol_trusted")
+            .map(|sources| {
+                sources
+                    .split(',')
+                    .filter(|source| !source.is_empty())
+                    .map(parse_source)
+                    .collect::<Result<Vec<_>>>()
+            })
+            .transpose()?
+            .unwrap_or_default();
+        if trusted_sources.is_empty() {
+            return Err(ItsiError::ArgumentError(
+                "proxy_protocol requires proxy_protocol_trusted, the addresses of your load balancers"
+                    .to_owned(),
+            ));
+        }
+
+        Ok(Some(Self {
+            version,
+            trusted_sources,
+        }))
+    }
+
+    /// Whether a connection from `peer` must begin with a PROXY protocol header.
+    pub fn expects_header(&self, peer: &SocketAddr) -> bool {
+        let ip = peer.ip().to_canonical();
+        self.trusted_sources.iter().any(|net| net.contains(&ip))
+    }
+
+    /// Consumes the PROXY protocol header from a newly accepted connection,
+    /// and returns the address of the client it was sent on behalf of.
+    pub async fn accept(&self, stream: &mut TcpStream, peer: SocketAddr) -> Result<SocketAddr> {
+        if !self.expects_header(&peer) {
+            return Ok(peer);
+        }
+        tokio::time::timeout(HEADER_READ_TIMEOUT, self.read_header(stream, peer))
+            .await
+            .map_err(|_| {
+                ItsiError::InvalidInput(format!(
+                    "Timed out reading PROXY protocol header from {}",
+                    peer
+                ))
+            })?
+    }
+
+    async fn read_header(&self, stream: &mut TcpStream, peer: SocketAddr) -> Result<SocketAddr> {
+        // Both header versions are at least 12 bytes long, so this never over-reads.
+        let mut prefix = [0u8; 12];
+        stream.read_exact(&mut prefix).await?;
+        if prefix == V2_SIGNATURE && self.version != ProxyProtocolVersion::V1 {
+            read_v2(stream, peer).await
+        } else if prefix.starts_with(b"PROXY ") && self.version != ProxyProtocolVersion::V2 {
+            read_v1(stream, &prefix, peer).await
+        } else {
+            Err(invalid_header(&format!(
+                "Missing PROXY protocol header from {}",
+                peer
+            )))
+        }
+    }
+}
+
+fn parse_source(source: &str) -> Result<IpNet> {
+    source
+        .parse::<IpNet>()
+        .or_else(|_| source.parse::<IpAddr>().map(IpNet::from))
+        .map_err(|_| {
+            ItsiError::ArgumentError(format!(
+                "Invalid proxy_protocol_trusted source {}. Expected an IP address or CIDR",
+                source
+            ))
+        })
+}
+
+fn invalid_header(message: &str) -> ItsiError {
+    ItsiError::InvalidInput(message.to_owned())
+}
+
+/// Reads a human-readable header, e.g. `PROXY TCP4 192.0.2.1 198.51.100.1 56324 443\r\n`.
+async fn read_v1(stream: &mut TcpStream, prefix: &[u8], peer: SocketAddr) -> Result<SocketAddr> {
+    let mut line = prefix.to_vec();
+    while !line.ends_with(b"\r\n") {
+        if line.len() >= V1_MAX_LENGTH {
+            return Err(invalid_header("PROXY protocol v1 header is too long"));
+        }
+        line.push(stream.read_u8().await?);
+    }
+
+    let line = std::str::from_utf8(&line[..line.len() - 2])
+        .map_err(|_| invalid_header("Malformed PROXY protocol v1 header"))?;
+    match line.split(' ').collect::<Vec<_>>().as_slice() {
+        ["PROXY", "TCP4" | "TCP6", source, _destination, source_port, _destination_port] => {
+            let ip = source
+                .parse::<IpAddr>()
+                .map_err(|_| invalid_header("Malformed PROXY protocol v1 source address"))?;
+            let port = source_port
+                .parse::<u16>()
+                .map_err(|_| invalid_header("Malformed PROXY protocol v1 source port"))?;
+            Ok(SocketAddr::new(ip, port))
+        }
+        ["PROXY", "UNKNOWN", ..] => Ok(peer),
+        _ => Err(invalid_header("Malformed PROXY protocol v1 header")),
+    }
+}
+
+/// Reads the binary header that follows the v2 signature.
+async fn read_v2(stream: &mut TcpStream, peer: SocketAddr) -> Result<SocketAddr> {
+    let mut header = [0u8; 4];
+    stream.read_exact(&mut header).await?;
+    let [version_command, family, length_hi, length_lo] = header;
+    let mut payload = vec![0u8; u16::from_be_bytes([length_hi, length_lo]) as usize];
+    stream.read_exact(&mut payload).await?;
+
+    if version_command >> 4 != 2 {
+        return Err(invalid_header("Unsupported PROXY protocol version"));
+    }
+    match version_command & 0x0F {
+        // LOCAL connections (e.g. health checks from the balancer itself) carry no client address.
+        0x0 => return Ok(peer),
+        0x1 => {}
+        _ => return Err(invalid_header("Unsupported PROXY protocol command")),
+    }
+
+    match family >> 4 {
+        0x1 if payload.len() >= 12 => {
+            let ip = Ipv4Addr::from(<[u8; 4]>::try_from(&payload[0..4]).unwrap());
+            let port = u16::from_be_bytes([payload[8], payload[9]]);
+            Ok(SocketAddr::new(IpAddr::V4(ip), port))
+        }
+        0x2 if payload.len() >= 36 => {
+            let ip = Ipv6Addr::from(<[u8; 16]>::try_from(&payload[0..16]).unwrap());
+            let port = u16::from_be_bytes([payload[32], payload[33]]);
+            Ok(SocketAddr::new(IpAddr::V6(ip), port))
+        }
+        0x1 | 0x2 => Err(invalid_header("Truncated PROXY protocol v2 address block")),
+        // AF_UNSPEC and AF_UNIX sources have no address we could use.
+        _ => Ok(peer),
+    }
+}
@@ -1,33 +1,79 @@
 use base64::{engine::general_purpose, Engine as _};
-use itsi_acme::{AcmeAcceptor, AcmeConfig, AcmeState};
+use itsi_acme::{
+    challenges::{ExecDns01Provider, Http01Challenges},
+    AcmeAcceptor, AcmeChallenge, AcmeConfig, AcmeState, KeyType,
+};
 use itsi_error::Result;
 use itsi_tracing::info;
 use locked_dir_cache::LockedDirCache;
-use rcgen::ExtendedKeyUsagePurpose;
-use rcgen::{
-    BasicConstraints, CertificateParams, DistinguishedName, DnType, IsCa, KeyPair, KeyUsagePurpose,
-    SanType,
-};
+use quinn::crypto::rustls::QuicServerConfig;
 use rustls::{
+    crypto::CryptoProvider,
     pki_types::{CertificateDer, PrivateKeyDer},
-    ClientConfig, RootCertStore,
+    server::{
+        danger::ClientCertVerifier, NoServerSessionStorage, ResolvesServerCert,
+        WebPkiClientVerifier,
+    },
+    version::{TLS12, TLS13},
+    ClientConfig, ConfigBuilder, RootCertStore, SupportedProtocolVersion, WantsServerCert,
 };
 use rustls_pemfile::{certs, pkcs8_private_keys};
 use std::{
     collections::HashMap,
+    fmt::Debug,
     fs,
     io::{BufReader, Error},
-    sync::Arc,
+    path::{Path, PathBuf},
+    sync::{Arc, LazyLock},
+    time::Duration,
 };
 use tokio::sync::Mutex;
 use tokio_rustls::{rustls::ServerConfig, TlsAcceptor};
 
 use crate::env::{
-    ITSI_ACME_CACHE_DIR, ITSI_ACME_CA_PEM_PATH, ITSI_ACME_CONTACT_EMAIL, ITSI_ACME_DIRECTORY_URL,
-    ITSI_LOCAL_CA_DIR,
+    itsi_acme_ca_pem_path, itsi_acme_cache_dir, itsi_acme_directory_url, ITSI_ACME_CONTACT_EMAIL,
 };
 
+mod cert_watcher;
+pub mod dev_ca;
 mod locked_dir_cache;
+mod ocsp_stapler;
+mod redis_cache;
+mod session_tickets;
+mod sni_resolver;
+
+pub use cert_watcher::watch_certificate_files;
+use cert_watcher::FileCertResolver;
+pub use ocsp_stapler::refresh_ocsp_staples;
+use ocsp_stapler::OcspStaplingResolver;
+use redis_cache::RedisAcmeCache;
+use session_tickets::SharedTicketer;
+pub use sni_resolver::reload_sni_certificates;
+use sni_resolver::{CertificateSources, SniCertResolver};
+
+/// Key authorizations for pending http-01 challenges, by directory.
+static ACME_HTTP01_CHALLENGES: LazyLock<
+    parking_lot::Mutex<HashMap<PathBuf, Arc<Http01Challenges>>>,
+> = LazyLock::new(Default::default);
+
+/// Returns the http-01 challenge store for the current ACME cache dir.
+/// Challenges are written to the cache dir, so any worker can answer a challenge started by another.
+pub fn acme_http01_challenges() -> Arc<Http01Challenges> {
+    let dir = Path::new(&itsi_acme_cache_dir()).join("http-01");
+    ACME_HTTP01_CHALLENGES
+        .lock()
+        .entry(dir)
+        .or_insert_with_key(|dir| Arc::new(Http01Challenges::with_dir(dir)))
+        .clone()
+}
+
+/// Whether the bind orders its ACME certificate using `acme_challenge=http-01`.
+pub fn uses_acme_http01(query_params: &HashMap<String, String>) -> bool {
+    query_params.get("cert").is_some_and(|cert| cert == "acme")
+        && query_params
+            .get("acme_challenge")
+            .is_some_and(|challenge| challenge == "http-01")
+}
 
 #[derive(Clone)]
 pub enum ItsiTlsAcceptor {
@@ -39,8 +85,25 @@ pub enum ItsiTlsAcceptor {
     ),
 }
 
+impl ItsiTlsAcceptor {
+    /// Builds a QUIC server config that serves the same certificates as this acceptor.
+    pub fn quic_server_config(&self) -> Result<quinn::ServerConfig> {
+        let base = match self {
+            ItsiTlsAcceptor::Manual(acceptor) => acceptor.config().clone(),
+            ItsiTlsAcceptor::Automatic(_, _, server_config) => server_config.clone(),
+        };
+        let mut server_config = (*base).clone();
+        server_config.alpn_protocols = vec![b"h3".to_vec()];
+        let crypto = QuicServerConfig::try_from(server_config).map_err(|e| {
+            itsi_error::ItsiError::ArgumentError(format!("Invalid TLS config for QUIC: {}", e))
+        })?;
+        Ok(quinn::ServerConfig::with_crypto(Arc::new(crypto)))
+    }
+}
+
 /// Generates a TLS configuration based on either :
 /// * Input "cert" and "key" options (either paths or Base64-encoded strings) or
+/// * Input "certs" or "certs_dir" options, to select between several certificates by SNI or
 /// * Performs automatic certificate generation/retrieval. Generated certs use an internal self-signed Isti CA.
 ///
 /// If a non-local host or optional domain parameter is provided,
@@ -56,7 +119,10 @@ pub fn configure_tls(
 
     if query_params.get("cert").is_some_and(|c| c == "acme") {
         if let Some(domains) = domains {
-            let directory_url = &*ITSI_ACME_DIRECTORY_URL;
+            let directory_url = query_params
+                .get("acme_directory")
+                .cloned()
+                .unwrap_or_else(itsi_acme_directory_url);
             info!(
                 domains = format!("{:?}", domains),
                 directory_url, "Requesting acme cert"
@@ -69,12 +135,22 @@ pub fn configure_tls(
                     "acme_email query param or ITSI_ACME_CONTACT_EMAIL must be set before you can auto-generate let's encrypt certificates".to_string(),
                 ))?;
 
-            let acme_config = AcmeConfig::new(domains)
-                .contact([format!("mailto:{}", acme_contact_email)])
-                .cache(LockedDirCache::new(&*ITSI_ACME_CACHE_DIR))
-                .directory(directory_url);
+            let acme_config = acme_options(
+                AcmeConfig::new(domains).contact([format!("mailto:{}", acme_contact_email)]),
+                query_params,
+            )?;
+            let acme_config = match query_params.get("acme_cache") {
+                Some(url) if url.starts_with("redis://") || url.starts_with("rediss://") => {
+                    acme_config.cache(RedisAcmeCache::new(url)?)
+                }
+                Some(dir) => acme_config.cache(LockedDirCache::new(dir.clone())),
+                None => acme_config.cache(LockedDirCache::new(itsi_acme_cache_dir())),
+            };
+            let acme_config = acme_config
+                .directory(directory_url)
+                .challenge(acme_challenge(query_params)?);
 
-            let acme_state = if let Ok(ca_pem_path) = &*ITSI_ACME_CA_PEM_PATH {
+            let acme_state = if let Ok(ca_pem_path) = itsi_acme_ca_pem_path() {
                 let mut root_cert_store = RootCertStore::empty();
 
                 let ca_pem = fs::read(ca_pem_path).expect("failed to read CA pem file");
@@ -99,11 +175,19 @@ pub fn configure_tls(
                 acme_config.state()
             };
 
-            let mut rustls_config = ServerConfig::builder()
-                .with_no_client_auth()
-                .with_cert_resolver(acme_state.resolver());
+            let mut rustls_config = match CertificateSources::from_options(query_params)? {
+                Some(sources) => {
+                    server_config_builder(query_params)?.with_cert_resolver(SniCertResolver::new(
+                        sources,
+                        Some(acme_state.resolver() as Arc<dyn ResolvesServerCert>),
+                    )?)
+                }
+                None => {
+                    server_config_builder(query_params)?.with_cert_resolver(acme_state.resolver())
+                }
+            };
 
-            rustls_config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+            apply_bind_options(&mut rustls_config, query_params)?;
 
             let acceptor = acme_state.acceptor();
             return Ok(ItsiTlsAcceptor::Automatic(
@@ -113,53 +197,298 @@ pub fn configure_tls(
             ));
         }
     }
+    if let Some(sources) = CertificateSources::from_options(query_params)? {
+        let mut config = server_config_builder(query_params)?
+            .with_cert_resolver(SniCertResolver::new(sources, None)?);
+        apply_bind_options(&mut config, query_params)?;
+        return Ok(ItsiTlsAcceptor::Manual(TlsAcceptor::from(Arc::new(config))));
+    }
+
     let (certs, key) = if let (Some(cert_path), Some(key_path)) =
         (query_params.get("cert"), query_params.get("key"))
     {
-        // Load from file or Base64
+        // Certificates loaded from files are swapped for new handshakes when the files change.
+        if !cert_path.starts_with("base64:") && !key_path.starts_with("base64:") {
+            let mut config = server_config_builder(query_params)?
+                .with_cert_resolver(FileCertResolver::new(cert_path, key_path)?);
+            apply_bind_options(&mut config, query_params)?;
+            return Ok(ItsiTlsAcceptor::Manual(TlsAcceptor::from(Arc::new(config))));
+        }
         let certs = load_certs(cert_path);
         let key = load_private_key(key_path);
         (certs, key)
     } else {
-        generate_ca_signed_cert(domains.unwrap_or(vec![host.to_owned()]))?
+        dev_ca::generate_ca_signed_cert(domains.unwrap_or(vec![host.to_owned()]))?
     };
 
-    let mut config = ServerConfig::builder()
-        .with_no_client_auth()
+    let mut config = server_config_builder(query_params)?
         .with_single_cert(certs, key)
         .expect("Failed to build TLS config");
 
-    config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+    apply_bind_options(&mut config, query_params)?;
     Ok(ItsiTlsAcceptor::Manual(TlsAcceptor::from(Arc::new(config))))
 }
 
+/// Applies the `alpn`, `tls_ticket_keys` and `tls_session_resumption` bind options, and OCSP stapling.
+fn apply_bind_options(
+    config: &mut ServerConfig,
+    query_params: &HashMap<String, String>,
+) -> Result<()> {
+    config.alpn_protocols = match query_params.get("alpn") {
+        Some(protocols) => protocols
+            .split(',')
+            .filter(|protocol| !protocol.is_empty())
+            .map(|protocol| protocol.as_bytes().to_vec())
+            .collect(),
+        None => vec![b"h2".to_vec(), b"http/1.1".to_vec()],
+    };
+    if query_params
+        .get("tls_session_resumption")
+        .is_some_and(|v| v == "false")
+    {
+        config.session_storage = Arc::new(NoServerSessionStorage {});
+        config.send_tls13_tickets = 0;
+    } else if let Some(paths) = query_params.get("tls_ticket_keys") {
+        config.ticketer = Arc::new(SharedTicketer::from_files(paths)?);
+    }
+    staple_ocsp(config, query_params);
+    Ok(())
+}
+
+/// Staples OCSP responses to certificates that name a responder, if the bind sets `ocsp_stapling=true`.
+fn staple_ocsp(config: &mut ServerConfig, query_params: &HashMap<String, String>) {
+    if !query_params
+        .get("ocsp_stapling")
+        .is_some_and(|v| v == "true")
+    {
+        return;
+    }
+    config.cert_resolver = OcspStaplingResolver::new(config.cert_resolver.clone());
+}
+
+/// Reads the `acme_eab_kid`, `acme_eab_hmac_key`, `acme_key_type` and `acme_renew_days` bind options.
+fn acme_options<EC: Debug + 'static, EA: Debug + 'static>(
+    mut acme_config: AcmeConfig<EC, EA>,
+    query_params: &HashMap<String, String>,
+) -> Result<AcmeConfig<EC, EA>> {
+    match (
+        query_params.get("acme_eab_kid"),
+        query_params.get("acme_eab_hmac_key"),
+    ) {
+        (Some(kid), Some(hmac_key)) => {
+            // CAs hand out the HMAC key base64url encoded, some with padding.
+            let hmac_key = general_purpose::URL_SAFE_NO_PAD
+                .decode(hmac_key.trim_end_matches('='))
+                .map_err(|e| {
+                    itsi_error::ItsiError::ArgumentError(format!(
+                        "Invalid acme_eab_hmac_key, expected base64url: {}",
+                        e
+                    ))
+                })?;
+            acme_config = acme_config.external_account_binding(kid, hmac_key);
+        }
+        (None, None) => {}
+        _ => {
+            return Err(itsi_error::ItsiError::ArgumentError(
+                "acme_eab_kid and acme_eab_hmac_key must be set together".to_owned(),
+            ))
+        }
+    }
+    if let Some(key_type) = query_params.get("acme_key_type") {
+        acme_config = acme_config.key_type(
+            key_type
+                .parse::<KeyType>()
+                .map_err(itsi_error::ItsiError::ArgumentError)?,
+        );
+    }
+    if let Some(days) = query_params.get("acme_renew_days") {
+        let renew_before = days
+            .parse::<u64>()
+            .ok()
+            .and_then(|days| days.checked_mul(24 * 3600))
+            .ok_or_else(|| {
+                itsi_error::ItsiError::ArgumentError(format!(
+                    "Invalid acme_renew_days {}. Expected a number of days",
+                    days
+                ))
+            })?;
+        acme_config = acme_config.renew_before(Duration::from_secs(renew_before));
+    }
+    Ok(acme_config)
+}
+
+/// Reads the `acme_challenge` and `acme_dns_hook` bind options.
+fn acme_challenge(query_params: &HashMap<String, String>) -> Result<AcmeChallenge> {
+    match query_params.get("acme_challenge").map(String::as_str) {
+        None | Some("tls-alpn-01") => Ok(AcmeChallenge::TlsAlpn01),
+        Some("http-01") => Ok(AcmeChallenge::Http01(acme_http01_challenges())),
+        Some("dns-01") => {
+            let hook = query_params.get("acme_dns_hook").ok_or_else(|| {
+                itsi_error::ItsiError::ArgumentError(
+                    "acme_challenge=dns-01 requires an acme_dns_hook script".to_owned(),
+                )
+            })?;
+            Ok(AcmeChallenge::Dns01(Arc::new(ExecDns01Provider::new(hook))))
+        }
+        Some(other) => Err(itsi_error::ItsiError::ArgumentError(format!(
+            "Invalid acme_challenge {}. Expected one of tls-alpn-01, http-01 or dns-01",
+            other
+        ))),
+    }
+}
+
+/// Starts a server config restricted to the protocol versions, cipher suites and key exchange groups the bind allows,
+/// that verifies client certificates if the bind sets `client_ca`.
+fn server_config_builder(
+    query_params: &HashMap<String, String>,
+) -> Result<ConfigBuilder<ServerConfig, WantsServerCert>> {
+    let builder = ServerConfig::builder_with_provider(Arc::new(crypto_provider(query_params)?))
+        .with_protocol_versions(protocol_versions(query_params)?)
+        .map_err(|e| itsi_error::ItsiError::ArgumentError(format!("Invalid TLS options: {}", e)))?;
+    Ok(match client_cert_verifier(query_params)? {
+        Some(verifier) => builder.with_client_cert_verifier(verifier),
+        None => builder.with_no_client_auth(),
+    })
+}
+
+/// Reads the `tls_min_version` bind option.
+fn protocol_versions(
+    query_params: &HashMap<String, String>,
+) -> Result<&'static [&'static SupportedProtocolVersion]> {
+    match query_params.get("tls_min_version").map(String::as_str) {
+        None | Some("1.2") => Ok(&[&TLS13, &TLS12]),
+        Some("1.3") => Ok(&[&TLS13]),
+        Some(other) => Err(itsi_error::ItsiError::ArgumentError(format!(
+            "Invalid tls_min_version {}. Expected 1.2 or 1.3",
+            other
+        ))),
+    }
+}
+
+/// The installed crypto provider, limited to the `tls_ciphers` and `tls_groups` bind options.
+fn crypto_provider(query_params: &HashMap<String, String>) -> Result<CryptoProvider> {
+    let mut provider = CryptoProvider::get_default()
+        .map(|provider| (**provider).clone())
+        .unwrap_or_else(rustls::crypto::aws_lc_rs::default_provider);
+    if let Some(ciphers) = query_params.get("tls_ciphers") {
+        provider.cipher_suites =
+            allow_list(ciphers, &provider.cipher_suites, "tls_ciphers", |suite| {
+                format!("{:?}", suite.suite())
+            })?;
+    }
+    if let Some(groups) = query_params.get("tls_groups") {
+        provider.kx_groups = allow_list(groups, &provider.kx_groups, "tls_groups", |group| {
+            format!("{:?}", group.name())
+        })?;
+    }
+    Ok(provider)
+}
+
+/// Picks the named items, in the order given, which is also the server's order of preference.
+fn allow_list<T: Copy>(
+    names: &str,
+    available: &[T],
+    option: &str,
+    name_of: impl Fn(&T) -> String,
+) -> Result<Vec<T>> {
+    names
+        .split(',')
+        .filter(|name| !name.is_empty())
+        .map(|name| {
+            available
+                .iter()
+                .find(|item| name_of(item).eq_ignore_ascii_case(name.trim()))
+                .copied()
+                .ok_or_else(|| {
+                    itsi_error::ItsiError::ArgumentError(format!(
+                        "Unsupported {} entry {}. Expected one of {}",
+                        option,
+                        name,
+                        available
+                            .iter()
+                            .map(&name_of)
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    ))
+                })
+        })
+        .collect()
+}
+
+/// Verifies client certificates against the `client_ca` bundle.
+/// With `client_auth=optional`, clients may also connect without a certificate.
+fn client_cert_verifier(
+    query_params: &HashMap<String, String>,
+) -> Result<Option<Arc<dyn ClientCertVerifier>>> {
+    let Some(client_ca) = query_params.get("client_ca") else {
+        return Ok(None);
+    };
+
+    let mut roots = RootCertStore::empty();
+    for cert in try_load_certs(client_ca)? {
+        roots.add(cert).map_err(|e| {
+            itsi_error::ItsiError::ArgumentError(format!("Invalid client_ca certificate: {}", e))
+        })?;
+    }
+
+    let builder = WebPkiClientVerifier::builder(Arc::new(roots));
+    let builder = match query_params.get("client_auth").map(String::as_str) {
+        None | Some("required") => builder,
+        Some("optional") => builder.allow_unauthenticated(),
+        Some(other) => {
+            return Err(itsi_error::ItsiError::ArgumentError(format!(
+                "Invalid client_auth {}. Expected required or optional",
+                other
+            )))
+        }
+    };
+
+    builder
+        .build()
+        .map(Some)
+        .map_err(|e| itsi_error::ItsiError::ArgumentError(format!("Invalid client_ca: {}", e)))
+}
+
 pub fn load_certs(path: &str) -> Vec<CertificateDer<'static>> {
+    try_load_certs(path).unwrap_or_else(|e| panic!("{}", e))
+}
+
+/// Loads certificates from a file or Base64, returning an error rather than panicking
+/// if they can't be read.
+pub fn try_load_certs(path: &str) -> Result<Vec<CertificateDer<'static>>> {
     let data = if let Some(stripped) = path.strip_prefix("base64:") {
-        general_purpose::STANDARD
-            .decode(stripped)
-            .expect("Invalid base64 certificate")
+        general_purpose::STANDARD.decode(stripped).map_err(|e| {
+            itsi_error::ItsiError::ArgumentError(format!("Invalid base64 certificate: {}", e))
+        })?
     } else {
-        fs::read(path).expect("Failed to read certificate file")
+        fs::read(path).map_err(|e| {
+            itsi_error::ItsiError::ArgumentError(format!(
+                "Failed to read certificate file {}: {}",
+                path, e
+            ))
+        })?
     };
 
     if data.starts_with(b"-----BEGIN ") {
         let mut reader = BufReader::new(&data[..]);
         let certs_der: Vec<Vec<u8>> = certs(&mut reader)
-            .map(|r| {
-                r.map(|der| der.as_ref().to_vec())
-                    .map_err(itsi_error::ItsiError::from)
-            })
-            .collect::<Result<_>>()
-            .expect("Failed to parse certificate file");
-        certs_der
+            .map(|r| r.map(|der| der.as_ref().to_vec()))
+            .collect::<std::result::Result<_, _>>()
+            .map_err(|e| {
+                itsi_error::ItsiError::ArgumentError(format!(
+                    "Failed to parse certificate file {}: {}",
+                    path, e
+                ))
+            })?;
+        Ok(certs_der
             .into_iter()
             .map(|vec| {
                 // Convert the owned Vec<u8> into a CertificateDer and force 'static.
                 unsafe { std::mem::transmute(CertificateDer::from(vec)) }
             })
-            .collect()
+            .collect())
     } else {
-        vec![CertificateDer::from(data)]
+        Ok(vec![CertificateDer::from(data)])
     }
 }
 
@@ -188,91 +517,3 @@ pub fn load_private_key(path: &str) -> PrivateKeyDer<'static> {
     }
     PrivateKeyDer::try_from(key_data).unwrap()
 }
-
-pub fn generate_ca_signed_cert(
-    domains: Vec<String>,
-) -> Result<(Vec<CertificateDer<'static>>, PrivateKeyDer<'static>)> {
-    info!(
-        domains = format!("{}", domains.join(", ")),
-        "Self signed cert",
-    );
-    info!(
-        "Add {} to your system's trusted cert store to resolve certificate errors.",
-        format!("{}/itsi_dev_ca.crt", ITSI_LOCAL_CA_DIR.to_str().unwrap())
-    );
-    info!("Dev CA path can be overridden by setting env var: `ITSI_LOCAL_CA_DIR`.");
-    let (ca_key_pem, ca_cert_pem) = get_or_create_local_dev_ca()?;
-
-    let ca_kp = KeyPair::from_pem(&ca_key_pem).expect("Failed to load CA key");
-    let ca_cert = CertificateParams::from_ca_cert_pem(&ca_cert_pem)
-        .expect("Failed to parse embedded CA certificate")
-        .self_signed(&ca_kp)
-        .expect("Failed to self-sign embedded CA cert");
-
-    let ee_key = KeyPair::generate_for(&rcgen::PKCS_ECDSA_P256_SHA256).unwrap();
-    let mut ee_params = CertificateParams::default();
-
-    use std::net::IpAddr;
-
-    ee_params.subject_alt_names = domains
-        .iter()
-        .map(|domain| {
-            if let Ok(ip) = domain.parse::<IpAddr>() {
-                SanType::IpAddress(ip)
-            } else {
-                SanType::DnsName(domain.clone().try_into().unwrap())
-            }
-        })
-        .collect();
-
-    ee_params
-        .distinguished_name
-        .push(DnType::CommonName, domains[0].clone());
-
-    ee_params.use_authority_key_identifier_extension = true;
-    ee_params.extended_key_usages = vec![ExtendedKeyUsagePurpose::ServerAuth];
-
-    let ee_cert = ee_params.signed_by(&ee_key, &ca_cert, &ca_kp).unwrap();
-    let ee_cert_der = ee_cert.der().to_vec();
-    let ee_cert = CertificateDer::from(ee_cert_der);
-
-    Ok((
-        vec![ee_cert],
-        PrivateKeyDer::try_from(ee_key.serialize_der()).unwrap(),
-    ))
-}
-
-fn get_or_create_local_dev_ca() -> Result<(String, String)> {
-    let ca_dir = &*ITSI_LOCAL_CA_DIR;
-    fs::create_dir_all(ca_dir)?;
-
-    let key_path = ca_dir.join("itsi_dev_ca.key");
-    let cert_path = ca_dir.join("itsi_dev_ca.crt");
-
-    if key_path.exists() && cert_path.exists() {
-        // Already have a local CA
-        let key_pem = fs::read_to_string(&key_path)?;
-        let cert_pem = fs::read_to_string(&cert_path)?;
-
-        Ok((key_pem, cert_pem))
-    } else {
-        let subject_alt_names = vec!["ca.itsi.fyi".to_string(), "localhost".to_string()];
-        let mut params = CertificateParams::new(subject_alt_names)?;
-        let mut distinguished_name = DistinguishedName::new();
-        distinguished_name.push(DnType::CommonName, "ca.itsi.fyi");
-        params.distinguished_name = distinguished_name;
-        params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
-        params.key_usages = vec![
-            KeyUsagePurpose::KeyCertSign,
-            KeyUsagePurpose::CrlSign,
-            KeyUsagePurpose::DigitalSignature, // useful for OCSP/CRL signing
-        ];
-        let key_pair = KeyPair::generate()?;
-        let cert = params.self_signed(&key_pair)?;
-
-        fs::write(&key_path, key_pair.serialize_pem())?;
-        fs::write(&cert_path, cert.pem())?;
-
-        Ok((key_pair.serialize_pem(), cert.pem()))
-    }
-}
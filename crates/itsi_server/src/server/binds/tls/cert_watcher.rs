@@ -0,0 +1,207 @@
+use itsi_error::{ItsiError, Result};
+use itsi_tracing::{debug, error, info};
+use notify::{Event, RecursiveMode};
+use parking_lot::{Mutex, RwLock};
+use rustls::{
+    server::{ClientHello, ResolvesServerCert},
+    sign::CertifiedKey,
+    Error as RustlsError, InconsistentKeys,
+};
+use std::{
+    collections::HashSet,
+    fs,
+    path::{Path, PathBuf},
+    sync::{Arc, LazyLock, Weak},
+    time::Duration,
+};
+use tokio::{
+    sync::{mpsc, watch::Receiver},
+    time::sleep,
+};
+
+use super::sni_resolver::{load_certified_key, sni_resolvers, SniCertResolver};
+use crate::ruby_types::itsi_server::file_watcher::{is_change, watch_dirs, Debouncer};
+use crate::server::serve_strategy::single_mode::RunningPhase;
+
+/// Every live file-backed resolver, so a single task per process can watch their files.
+static FILE_CERT_RESOLVERS: LazyLock<Mutex<Vec<Weak<FileCertResolver>>>> =
+    LazyLock::new(|| Mutex::new(Vec::new()));
+
+/// Writers (and cert-manager's symlink swaps) touch several files in quick succession.
+/// We wait for changes to settle before reloading.
+const DEBOUNCE_DURATION: Duration = Duration::from_millis(500);
+
+/// Serves the certificate and key given by the `cert` and `key` bind options,
+/// and swaps in a new pair for new handshakes when the files change.
+#[derive(Debug)]
+pub struct FileCertResolver {
+    cert_path: PathBuf,
+    key_path: PathBuf,
+    certified_key: RwLock<Arc<CertifiedKey>>,
+}
+
+impl FileCertResolver {
+    pub fn new(cert_path: &str, key_path: &str) -> Result<Arc<Self>> {
+        let (cert_path, key_path) = (PathBuf::from(cert_path), PathBuf::from(key_path));
+        let resolver = Arc::new(Self {
+            certified_key: RwLock::new(load_matching_pair(&cert_path, &key_path)?),
+            cert_path,
+            key_path,
+        });
+        let mut resolvers = FILE_CERT_RESOLVERS.lock();
+        resolvers.retain(|resolver| resolver.strong_count() > 0);
+        resolvers.push(Arc::downgrade(&resolver));
+        Ok(resolver)
+    }
+
+    /// Reloads the certificate from disk. If the new files are invalid, we keep serving the old certificate.
+    pub fn reload(&self) -> Result<()> {
+        let certified_key = load_matching_pair(&self.cert_path, &self.key_path)?;
+        *self.certified_key.write() = certified_key;
+        Ok(())
+    }
+}
+
+impl ResolvesServerCert for FileCertResolver {
+    fn resolve(&self, _client_hello: ClientHello) -> Option<Arc<CertifiedKey>> {
+        Some(self.certified_key.read().clone())
+    }
+}
+
+/// Loads a certificate and key, rejecting a key that doesn't belong to the certificate
+/// (e.g. when the certificate has been replaced, but the key not yet).
+fn load_matching_pair(cert_path: &Path, key_path: &Path) -> Result<Arc<CertifiedKey>> {
+    let (certified_key, _) = load_certified_key(cert_path, key_path)?;
+    match certified_key.keys_match() {
+        Ok(()) | Err(RustlsError::InconsistentKeys(InconsistentKeys::Unknown)) => Ok(certified_key),
+        Err(e) => Err(ItsiError::ArgumentError(format!(
+            "Key {:?} does not match certificate {:?}: {}",
+            key_path, cert_path, e
+        ))),
+    }
+}
+
+/// A resolver whose certificates are loaded from files we should watch.
+enum Watched {
+    File(Arc<FileCertResolver>),
+    Sni(Arc<SniCertResolver>),
+}
+
+impl Watched {
+    fn all() -> Vec<Watched> {
+        let files = FILE_CERT_RESOLVERS
+            .lock()
+            .iter()
+            .filter_map(Weak::upgrade)
+            .map(Watched::File)
+            .collect::<Vec<_>>();
+        files
+            .into_iter()
+            .chain(sni_resolvers().into_iter().map(Watched::Sni))
+            .collect()
+    }
+
+    fn paths(&self) -> Vec<PathBuf> {
+        match self {
+            Watched::File(resolver) => vec![resolver.cert_path.clone(), resolver.key_path.clone()],
+            Watched::Sni(resolver) => resolver.watched_paths(),
+        }
+    }
+
+    fn reload(&self) {
+        let (kind, result) = match self {
+            Watched::File(resolver) => ("certificate", resolver.reload()),
+            Watched::Sni(resolver) => ("SNI certificates", resolver.reload()),
+        };
+        match result {
+            Ok(()) => info!("Reloaded {} after a file change", kind),
+            Err(e) => error!("Failed to reload {}, keeping the previous: {}", kind, e),
+        }
+    }
+}
+
+/// The directory to watch for a certificate file.
+/// We watch directories rather than files, as files replaced by a rename or symlink swap
+/// (as done by cert-manager and Vault agent) would otherwise drop out of the watch.
+fn watch_dir(path: &Path) -> PathBuf {
+    if path.is_dir() {
+        return canonical(path);
+    }
+    parent_dir(path)
+}
+
+fn parent_dir(path: &Path) -> PathBuf {
+    match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => canonical(parent),
+        _ => canonical(Path::new(".")),
+    }
+}
+
+fn canonical(path: &Path) -> PathBuf {
+    fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// Reloads manually configured certificates in this process when their files change, until shutdown.
+///
+/// Runs in each worker, as the file watcher's thread doesn't survive a fork.
+pub async fn watch_certificate_files(mut shutdown_receiver: Receiver<RunningPhase>) {
+    let watched = Watched::all();
+    let dirs = watched
+        .iter()
+        .flat_map(Watched::paths)
+        .map(|path| watch_dir(&path))
+        .collect::<HashSet<_>>();
+    if dirs.is_empty() {
+        return;
+    }
+
+    let (sender, mut receiver) = mpsc::unbounded_channel::<Event>();
+    for dir in &dirs {
+        debug!("Watching {:?} for certificate changes", dir);
+    }
+    let watcher = match watch_dirs(&dirs, RecursiveMode::NonRecursive, move |event| {
+        let _ = sender.send(event);
+    }) {
+        Ok(watcher) => watcher,
+        Err(e) => {
+            error!("Failed to create certificate file watcher: {}", e);
+            return;
+        }
+    };
+
+    let mut debouncer = Debouncer::new(DEBOUNCE_DURATION);
+    loop {
+        let wait = debouncer.remaining();
+        let settle = async move {
+            match wait {
+                Some(wait) => sleep(wait).await,
+                None => std::future::pending().await,
+            }
+        };
+        tokio::select! {
+            event = receiver.recv() => match event {
+                Some(event) if is_change(&event) => {
+                    debouncer.record(event.paths.iter().map(|path| parent_dir(path)));
+                }
+                Some(_) => {}
+                None => break,
+            },
+            _ = settle => {},
+            _ = shutdown_receiver.changed() => break,
+        }
+        let changed_dirs = debouncer.take_settled();
+        if changed_dirs.is_empty() {
+            continue;
+        }
+        for resolver in Watched::all() {
+            if resolver
+                .paths()
+                .iter()
+                .any(|path| changed_dirs.contains(&watch_dir(path)))
+            {
+                resolver.reload();
+            }
+        }
+    }
+    drop(watcher);
+}
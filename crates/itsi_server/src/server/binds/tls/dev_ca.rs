@@ -0,0 +1,407 @@
+use chrono::{DateTime, Datelike, Duration, Utc};
+use itsi_error::{ItsiError, Result};
+use itsi_tracing::{debug, info};
+use nix::unistd::geteuid;
+use rcgen::{
+    date_time_ymd, BasicConstraints, CertificateParams, DistinguishedName, DnType,
+    ExtendedKeyUsagePurpose, IsCa, KeyPair, KeyUsagePurpose, SanType,
+};
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use rustls_pemfile::{certs, private_key};
+use sha2::{Digest, Sha256};
+use std::{
+    fs,
+    io::{BufReader, Write},
+    net::IpAddr,
+    os::unix::fs::{OpenOptionsExt, PermissionsExt},
+    path::{Path, PathBuf},
+    process::Command,
+};
+use x509_parser::{extensions::GeneralName, prelude::*};
+
+use crate::env::itsi_local_ca_dir;
+
+/// Browsers (Safari in particular) reject server certificates valid for longer than this.
+const LEAF_VALIDITY_DAYS: i64 = 825;
+/// Cached leaf certificates are reissued once they get this close to expiring.
+const LEAF_RENEW_DAYS: i64 = 30;
+const CA_NICKNAME: &str = "Itsi Development CA";
+
+/// A leaf certificate issued by the development CA, as listed by `itsi dev_ca list`.
+pub struct IssuedCertificate {
+    pub hosts: Vec<String>,
+    pub path: PathBuf,
+    pub not_after: DateTime<Utc>,
+}
+
+pub fn ca_cert_path() -> PathBuf {
+    itsi_local_ca_dir().join("itsi_dev_ca.crt")
+}
+
+fn ca_key_path() -> PathBuf {
+    itsi_local_ca_dir().join("itsi_dev_ca.key")
+}
+
+fn leaf_dir() -> PathBuf {
+    itsi_local_ca_dir().join("certs")
+}
+
+/// Returns the PEM encoded key and certificate of the development CA, creating it on first use.
+pub fn get_or_create_local_dev_ca() -> Result<(String, String)> {
+    fs::create_dir_all(itsi_local_ca_dir())?;
+
+    let key_path = ca_key_path();
+    let cert_path = ca_cert_path();
+
+    if key_path.exists() && cert_path.exists() {
+        // Already have a local CA
+        let key_pem = fs::read_to_string(&key_path)?;
+        let cert_pem = fs::read_to_string(&cert_path)?;
+
+        Ok((key_pem, cert_pem))
+    } else {
+        let subject_alt_names = vec!["ca.itsi.fyi".to_string(), "localhost".to_string()];
+        let mut params = CertificateParams::new(subject_alt_names)?;
+        let mut distinguished_name = DistinguishedName::new();
+        distinguished_name.push(DnType::CommonName, "ca.itsi.fyi");
+        params.distinguished_name = distinguished_name;
+        params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+        params.key_usages = vec![
+            KeyUsagePurpose::KeyCertSign,
+            KeyUsagePurpose::CrlSign,
+            KeyUsagePurpose::DigitalSignature, // useful for OCSP/CRL signing
+        ];
+        let key_pair = KeyPair::generate()?;
+        let cert = params.self_signed(&key_pair)?;
+
+        write_private_key(&key_path, &key_pair.serialize_pem())?;
+        fs::write(&cert_path, cert.pem())?;
+
+        Ok((key_pair.serialize_pem(), cert.pem()))
+    }
+}
+
+/// Returns a certificate for `domains` signed by the development CA.
+///
+/// Certificates are cached per set of hosts in `ITSI_LOCAL_CA_DIR/certs`, and reissued
+/// when they near expiry or the CA changes.
+pub fn generate_ca_signed_cert(
+    domains: Vec<String>,
+) -> Result<(Vec<CertificateDer<'static>>, PrivateKeyDer<'static>)> {
+    let (ca_key_pem, ca_cert_pem) = get_or_create_local_dev_ca()?;
+    let ca_der = pem_certificates(ca_cert_pem.as_bytes())?
+        .into_iter()
+        .next()
+        .ok_or_else(|| ItsiError::InternalServerError("Dev CA certificate is empty".to_owned()))?;
+
+    let (cert_path, key_path) = leaf_paths(&domains);
+    if let Some(cached) = load_cached_leaf(&cert_path, &key_path, &ca_der) {
+        debug!(domains = domains.join(", "), "Using cached dev certificate");
+        return Ok(cached);
+    }
+
+    info!(domains = domains.join(", "), "Self signed cert");
+    info!(
+        "Run `itsi dev_ca install` to trust {} and resolve certificate errors.",
+        ca_cert_path().display()
+    );
+    info!("Dev CA path can be overridden by setting env var: `ITSI_LOCAL_CA_DIR`.");
+
+    let ca_kp = KeyPair::from_pem(&ca_key_pem)?;
+    let ca_cert = CertificateParams::from_ca_cert_pem(&ca_cert_pem)?.self_signed(&ca_kp)?;
+
+    let ee_key = KeyPair::generate_for(&rcgen::PKCS_ECDSA_P256_SHA256)?;
+    let mut ee_params = CertificateParams::default();
+    ee_params.subject_alt_names = domains
+        .iter()
+        .map(|domain| match domain.parse::<IpAddr>() {
+            Ok(ip) => Ok(SanType::IpAddress(ip)),
+            Err(_) => Ok(SanType::DnsName(domain.clone().try_into()?)),
+        })
+        .collect::<std::result::Result<_, rcgen::Error>>()?;
+    ee_params
+        .distinguished_name
+        .push(DnType::CommonName, domains[0].clone());
+    let not_before = Utc::now() - Duration::days(1);
+    let not_after = not_before + Duration::days(LEAF_VALIDITY_DAYS);
+    ee_params.not_before = date_time_ymd(
+        not_before.year(),
+        not_before.month() as u8,
+        not_before.day() as u8,
+    );
+    ee_params.not_after = date_time_ymd(
+        not_after.year(),
+        not_after.month() as u8,
+        not_after.day() as u8,
+    );
+    ee_params.use_authority_key_identifier_extension = true;
+    ee_params.extended_key_usages = vec![ExtendedKeyUsagePurpose::ServerAuth];
+
+    let ee_cert = ee_params.signed_by(&ee_key, &ca_cert, &ca_kp)?;
+
+    // The CA is stored after the leaf, so we can tell when a cached leaf was issued by an older CA.
+    fs::create_dir_all(leaf_dir())?;
+    fs::write(&cert_path, [ee_cert.pem(), ca_cert_pem].concat())?;
+    write_private_key(&key_path, &ee_key.serialize_pem())?;
+
+    Ok((
+        vec![ee_cert.der().clone()],
+        PrivateKeyDer::try_from(ee_key.serialize_der()).map_err(ItsiError::from)?,
+    ))
+}
+
+/// Writes a private key readable only by its owner.
+fn write_private_key(path: &Path, pem: &str) -> Result<()> {
+    let mut file = fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(path)?;
+    // Keys written by earlier versions may have looser permissions.
+    file.set_permissions(fs::Permissions::from_mode(0o600))?;
+    file.write_all(pem.as_bytes())?;
+    Ok(())
+}
+
+/// Cached leaf certificates are named after the hosts they cover.
+fn leaf_paths(domains: &[String]) -> (PathBuf, PathBuf) {
+    let mut hosts = domains
+        .iter()
+        .map(|domain| domain.to_ascii_lowercase())
+        .collect::<Vec<_>>();
+    hosts.sort();
+    hosts.dedup();
+    let mut stem = hosts
+        .join("+")
+        .replace('*', "_wildcard")
+        .chars()
+        .map(|c| match c {
+            'a'..='z' | '0'..='9' | '.' | '-' | '+' | '_' => c,
+            _ => '_',
+        })
+        .collect::<String>();
+    if stem.len() > 64 {
+        let digest = Sha256::digest(hosts.join(",").as_bytes());
+        stem.truncate(48);
+        stem.push_str(&format!(
+            "-{}",
+            digest[..4]
+                .iter()
+                .map(|b| format!("{:02x}", b))
+                .collect::<String>()
+        ));
+    }
+    let dir = leaf_dir();
+    (
+        dir.join(format!("{}.crt", stem)),
+        dir.join(format!("{}.key", stem)),
+    )
+}
+
+fn pem_certificates(pem: &[u8]) -> Result<Vec<CertificateDer<'static>>> {
+    Ok(certs(&mut BufReader::new(pem)).collect::<std::result::Result<Vec<_>, _>>()?)
+}
+
+fn load_cached_leaf(
+    cert_path: &Path,
+    key_path: &Path,
+    ca_der: &CertificateDer,
+) -> Option<(Vec<CertificateDer<'static>>, PrivateKeyDer<'static>)> {
+    let chain = pem_certificates(&fs::read(cert_path).ok()?).ok()?;
+    if chain.get(1) != Some(ca_der) {
+        return None;
+    }
+    let (_, leaf) = X509Certificate::from_der(chain[0].as_ref()).ok()?;
+    let renew_at = Utc::now() + Duration::days(LEAF_RENEW_DAYS);
+    if leaf.validity().not_after.timestamp() < renew_at.timestamp() {
+        return None;
+    }
+    let key = private_key(&mut BufReader::new(&fs::read(key_path).ok()?[..])).ok()??;
+    Some((vec![chain[0].clone()], key))
+}
+
+/// Lists the cached leaf certificates issued by the development CA.
+pub fn issued_certificates() -> Result<Vec<IssuedCertificate>> {
+    let dir = leaf_dir();
+    if !dir.exists() {
+        return Ok(vec![]);
+    }
+    let mut issued = fs::read_dir(&dir)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|extension| extension == "crt"))
+        .filter_map(|path| {
+            let chain = pem_certificates(&fs::read(&path).ok()?).ok()?;
+            let (_, leaf) = X509Certificate::from_der(chain.first()?.as_ref()).ok()?;
+            let hosts = leaf
+                .subject_alternative_name()
+                .ok()
+                .flatten()
+                .map(|extension| {
+                    extension
+                        .value
+                        .general_names
+                        .iter()
+                        .filter_map(|name| match name {
+                            GeneralName::DNSName(name) => Some(name.to_string()),
+                            GeneralName::IPAddress(bytes) => ip_address(bytes),
+                            _ => None,
+                        })
+                        .collect::<Vec<_>>()
+                })
+                .unwrap_or_default();
+            let not_after = DateTime::from_timestamp(leaf.validity().not_after.timestamp(), 0)?;
+            Some(IssuedCertificate {
+                hosts,
+                path,
+                not_after,
+            })
+        })
+        .collect::<Vec<_>>();
+    issued.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(issued)
+}
+
+fn ip_address(bytes: &[u8]) -> Option<String> {
+    match bytes.len() {
+        4 => Some(IpAddr::from(<[u8; 4]>::try_from(bytes).ok()?).to_string()),
+        16 => Some(IpAddr::from(<[u8; 16]>::try_from(bytes).ok()?).to_string()),
+        _ => None,
+    }
+}
+
+/// Where the system trust store expects extra anchors, and the command that rebuilds it.
+struct SystemTrustStore {
+    anchor_path: &'static str,
+    update_command: &'static [&'static str],
+}
+
+const SYSTEM_TRUST_STORES: &[SystemTrustStore] = &[
+    // Debian, Ubuntu, Alpine
+    SystemTrustStore {
+        anchor_path: "/usr/local/share/ca-certificates/itsi_dev_ca.crt",
+        update_command: &["update-ca-certificates"],
+    },
+    // Fedora, RHEL, CentOS
+    SystemTrustStore {
+        anchor_path: "/etc/pki/ca-trust/source/anchors/itsi_dev_ca.crt",
+        update_command: &["update-ca-trust", "extract"],
+    },
+];
+
+/// Adds the development CA to the system trust store and to any NSS databases (Chrome, Firefox)
+/// of the current user. Returns a line describing the outcome of each step.
+pub fn install() -> Result<Vec<String>> {
+    get_or_create_local_dev_ca()?;
+    let ca_path = ca_cert_path();
+    let mut outcomes = vec![];
+
+    match SYSTEM_TRUST_STORES.iter().find(|store| {
+        Path::new(store.anchor_path)
+            .parent()
+            .is_some_and(Path::exists)
+    }) {
+        Some(store) => {
+            let copied =
+                run_privileged(&["cp", ca_path.to_string_lossy().as_ref(), store.anchor_path])
+                    .and_then(|_| run_privileged(store.update_command));
+            outcomes.push(match copied {
+                Ok(()) => format!("Installed {} to {}", ca_path.display(), store.anchor_path),
+                Err(e) => format!("Failed to install to the system trust store: {}", e),
+            });
+        }
+        None => outcomes.push(format!(
+            "No supported system trust store found. Add {} to your trust store manually.",
+            ca_path.display()
+        )),
+    }
+
+    let databases = nss_databases();
+    if databases.is_empty() {
+        return Ok(outcomes);
+    }
+    if !command_exists("certutil") {
+        outcomes.push(
+            "certutil not found, skipping browser trust stores (install libnss3-tools or nss-tools)"
+                .to_owned(),
+        );
+        return Ok(outcomes);
+    }
+    for database in databases {
+        let database = format!("sql:{}", database.display());
+        let status = Command::new("certutil")
+            .args(["-d", &database, "-A", "-t", "C,,", "-n", CA_NICKNAME, "-i"])
+            .arg(&ca_path)
+            .status();
+        outcomes.push(match status {
+            Ok(status) if status.success() => format!("Installed to NSS database {}", database),
+            Ok(status) => format!("certutil failed for {} ({})", database, status),
+            Err(e) => format!("certutil failed for {}: {}", database, e),
+        });
+    }
+    Ok(outcomes)
+}
+
+/// NSS databases used by Chrome/Chromium and Firefox profiles.
+fn nss_databases() -> Vec<PathBuf> {
+    let Some(home) = dirs::home_dir() else {
+        return vec![];
+    };
+    let mut databases = vec![home.join(".pki/nssdb")];
+    for profiles in [
+        home.join(".mozilla/firefox"),
+        home.join("snap/firefox/common/.mozilla/firefox"),
+    ] {
+        if let Ok(entries) = fs::read_dir(profiles) {
+            databases.extend(entries.filter_map(|entry| entry.ok().map(|entry| entry.path())));
+        }
+    }
+    databases.retain(|database| database.join("cert9.db").exists());
+    databases
+}
+
+fn command_exists(command: &str) -> bool {
+    std::env::var_os("PATH")
+        .is_some_and(|paths| std::env::split_paths(&paths).any(|dir| dir.join(command).is_file()))
+}
+
+/// Runs a command as root, through sudo unless we already are.
+fn run_privileged(command: &[&str]) -> Result<()> {
+    let mut command = command.to_vec();
+    if !geteuid().is_root() {
+        command.insert(0, "sudo");
+    }
+    let status = Command::new(command[0]).args(&command[1..]).status()?;
+    if !status.success() {
+        return Err(ItsiError::InternalServerError(format!(
+            "`{}` failed ({})",
+            command.join(" "),
+            status
+        )));
+    }
+    Ok(())
+}
+
+/// Returns the path of the development CA certificate, creating the CA if needed.
+pub fn dev_ca_path() -> magnus::error::Result<String> {
+    get_or_create_local_dev_ca()?;
+    Ok(ca_cert_path().to_string_lossy().into_owned())
+}
+
+pub fn install_dev_ca() -> magnus::error::Result<Vec<String>> {
+    Ok(install()?)
+}
+
+/// Returns `[hosts, path, expires_at]` for each cached certificate issued by the development CA.
+pub fn dev_ca_certificates() -> magnus::error::Result<Vec<(Vec<String>, String, i64)>> {
+    Ok(issued_certificates()?
+        .into_iter()
+        .map(|issued| {
+            (
+                issued.hosts,
+                issued.path.to_string_lossy().into_owned(),
+                issued.not_after.timestamp(),
+            )
+        })
+        .collect())
+}
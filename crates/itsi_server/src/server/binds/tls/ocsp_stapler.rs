@@ -0,0 +1,219 @@
+use chrono::{DateTime, Duration, Utc};
+use itsi_acme::ocsp::{self, OcspResponse};
+use itsi_tracing::{debug, info, warn};
+use parking_lot::{Mutex, RwLock};
+use rustls::{
+    pki_types::CertificateDer,
+    server::{ClientHello, ResolvesServerCert},
+    sign::CertifiedKey,
+};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::{Arc, LazyLock, Weak},
+};
+use tokio::sync::{watch::Receiver, Notify};
+
+use crate::{env::itsi_acme_cache_dir, server::serve_strategy::single_mode::RunningPhase};
+
+/// Every live stapling resolver, so a single task per process can keep their responses fresh.
+static STAPLING_RESOLVERS: LazyLock<Mutex<Vec<Weak<OcspStaplingResolver>>>> =
+    LazyLock::new(|| Mutex::new(Vec::new()));
+/// Wakes the refresh task when a handshake uses a certificate we haven't stapled yet.
+static NEW_CERTIFICATE: LazyLock<Notify> = LazyLock::new(Notify::new);
+
+const REFRESH_CHECK_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60);
+const RETRY_INTERVAL: i64 = 5 * 60;
+/// Stops a responder that hands out nearly expired responses from being polled in a tight loop.
+const MIN_REFRESH_INTERVAL: i64 = 1;
+
+struct Staple {
+    certified_key: Arc<CertifiedKey>,
+    stapled: Option<(Arc<CertifiedKey>, OcspResponse)>,
+    /// When to next fetch a response. `None` if the certificate has no OCSP responder.
+    next_attempt: Option<DateTime<Utc>>,
+}
+
+/// Staples OCSP responses to the certificates chosen by another resolver.
+///
+/// Certificates are picked up the first time a handshake uses them,
+/// so manual, SNI and ACME certificates (including renewals) are all covered.
+pub struct OcspStaplingResolver {
+    inner: Arc<dyn ResolvesServerCert>,
+    staples: RwLock<HashMap<Vec<u8>, Staple>>,
+}
+
+impl std::fmt::Debug for OcspStaplingResolver {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("OcspStaplingResolver")
+            .field("inner", &self.inner)
+            .finish()
+    }
+}
+
+impl OcspStaplingResolver {
+    pub fn new(inner: Arc<dyn ResolvesServerCert>) -> Arc<Self> {
+        let resolver = Arc::new(Self {
+            inner,
+            staples: RwLock::new(HashMap::new()),
+        });
+        let mut resolvers = STAPLING_RESOLVERS.lock();
+        resolvers.retain(|resolver| resolver.strong_count() > 0);
+        resolvers.push(Arc::downgrade(&resolver));
+        resolver
+    }
+
+    fn track(&self, leaf: Vec<u8>, certified_key: &Arc<CertifiedKey>) -> Arc<CertifiedKey> {
+        let mut staple = Staple {
+            certified_key: certified_key.clone(),
+            stapled: None,
+            next_attempt: ocsp::responder_url(&certified_key.cert).map(|_| Utc::now()),
+        };
+        // A response cached by an earlier run lets us staple from the very first handshake.
+        if staple.next_attempt.is_some() {
+            if let Some(response) = read_cached_response(&leaf, &certified_key.cert) {
+                staple.next_attempt = Some(response.refresh_at());
+                staple.stapled = Some((with_ocsp(certified_key, &response), response));
+            }
+        }
+        let resolved = staple
+            .stapled
+            .as_ref()
+            .map_or_else(|| certified_key.clone(), |(stapled, _)| stapled.clone());
+        if staple.next_attempt.is_some() {
+            NEW_CERTIFICATE.notify_one();
+        }
+        self.staples.write().insert(leaf, staple);
+        resolved
+    }
+
+    /// Fetches the responses that are due, and returns when the next one will be.
+    async fn refresh(&self) -> Option<DateTime<Utc>> {
+        let now = Utc::now();
+        let due = self
+            .staples
+            .read()
+            .iter()
+            .filter(|(_, staple)| staple.next_attempt.is_some_and(|at| at <= now))
+            .map(|(leaf, staple)| (leaf.clone(), staple.certified_key.clone()))
+            .collect::<Vec<_>>();
+
+        for (leaf, certified_key) in due {
+            let result = ocsp::fetch(&certified_key.cert).await;
+            let mut staples = self.staples.write();
+            let Some(staple) = staples.get_mut(&leaf) else {
+                continue;
+            };
+            match result {
+                Ok(response) => {
+                    debug!(
+                        "Fetched OCSP response, valid until {:?}",
+                        response.next_update
+                    );
+                    write_cached_response(&leaf, &response);
+                    staple.next_attempt = Some(
+                        response
+                            .refresh_at()
+                            .max(Utc::now() + Duration::seconds(MIN_REFRESH_INTERVAL)),
+                    );
+                    staple.stapled = Some((with_ocsp(&certified_key, &response), response));
+                }
+                Err(e) => {
+                    warn!("Failed to fetch OCSP response: {}", e);
+                    staple.next_attempt = Some(Utc::now() + Duration::seconds(RETRY_INTERVAL));
+                    if staple
+                        .stapled
+                        .as_ref()
+                        .is_some_and(|(_, response)| response.is_expired())
+                    {
+                        info!("Stapled OCSP response expired, serving certificate without it");
+                        staple.stapled = None;
+                    }
+                }
+            }
+        }
+        self.staples
+            .read()
+            .values()
+            .filter_map(|staple| staple.next_attempt)
+            .min()
+    }
+}
+
+impl ResolvesServerCert for OcspStaplingResolver {
+    fn resolve(&self, client_hello: ClientHello) -> Option<Arc<CertifiedKey>> {
+        let certified_key = self.inner.resolve(client_hello)?;
+        if certified_key.ocsp.is_some() {
+            return Some(certified_key);
+        }
+        let leaf = certified_key.end_entity_cert().ok()?.as_ref();
+        if let Some(staple) = self.staples.read().get(leaf) {
+            return Some(match &staple.stapled {
+                Some((stapled, _)) => stapled.clone(),
+                None => certified_key.clone(),
+            });
+        }
+        Some(self.track(leaf.to_vec(), &certified_key))
+    }
+}
+
+fn with_ocsp(certified_key: &CertifiedKey, response: &OcspResponse) -> Arc<CertifiedKey> {
+    let mut stapled = certified_key.clone();
+    stapled.ocsp = Some(response.der.clone());
+    Arc::new(stapled)
+}
+
+/// Responses are cached in the ACME cache dir, by the SHA-256 digest of the leaf certificate.
+fn cached_response_path(leaf: &[u8]) -> PathBuf {
+    let digest = Sha256::digest(leaf)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect::<String>();
+    Path::new(&itsi_acme_cache_dir())
+        .join("ocsp")
+        .join(format!("{}.der", digest))
+}
+
+fn read_cached_response(leaf: &[u8], chain: &[CertificateDer]) -> Option<OcspResponse> {
+    let der = fs::read(cached_response_path(leaf)).ok()?;
+    OcspResponse::from_der(der, chain)
+        .ok()
+        .filter(|response| !response.is_expired())
+}
+
+fn write_cached_response(leaf: &[u8], response: &OcspResponse) {
+    let path = cached_response_path(leaf);
+    let result = path
+        .parent()
+        .map_or(Ok(()), fs::create_dir_all)
+        .and_then(|_| fs::write(&path, &response.der));
+    if let Err(e) = result {
+        warn!("Failed to cache OCSP response at {:?}: {}", path, e);
+    }
+}
+
+/// Keeps the OCSP responses of every stapling resolver in this process fresh, until shutdown.
+/// Wakes at least every minute, or sooner if a response is due before then.
+pub async fn refresh_ocsp_staples(mut shutdown_receiver: Receiver<RunningPhase>) {
+    loop {
+        let resolvers = STAPLING_RESOLVERS
+            .lock()
+            .iter()
+            .filter_map(Weak::upgrade)
+            .collect::<Vec<_>>();
+        let mut next_check = REFRESH_CHECK_INTERVAL;
+        for resolver in resolvers {
+            if let Some(next_attempt) = resolver.refresh().await {
+                let until = (next_attempt - Utc::now()).to_std().unwrap_or_default();
+                next_check = next_check.min(until);
+            }
+        }
+        tokio::select! {
+            _ = tokio::time::sleep(next_check) => {},
+            _ = NEW_CERTIFICATE.notified() => {},
+            _ = shutdown_receiver.changed() => break,
+        }
+    }
+}
@@ -0,0 +1,218 @@
+use async_trait::async_trait;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use itsi_acme::{AccountCache, CertCache};
+use itsi_error::{ItsiError, Result};
+use itsi_tracing::info;
+use parking_lot::Mutex;
+use redis::{aio::ConnectionManager, AsyncCommands, Script};
+use sha2::{Digest, Sha256};
+use std::{io::Error as IoError, time::Duration};
+use tokio::sync::OnceCell;
+
+use crate::services::cache_store::RedisCacheStore;
+
+const KEY_PREFIX: &str = "itsi:acme";
+/// Long enough for an order to complete. If the host holding the lock dies, another takes over after this.
+const LOCK_TTL: Duration = Duration::from_secs(600);
+const LOCK_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// An ACME cache shared by several hosts through Redis.
+///
+/// Before ordering a certificate, a host takes a lock in Redis.
+/// Other hosts wait for the lock, and deploy the certificate the lock holder stores.
+pub struct RedisAcmeCache {
+    connection_url: String,
+    connection: OnceCell<ConnectionManager>,
+    /// The token of the order lock we hold, if any.
+    lock_token: Mutex<Option<String>>,
+}
+
+impl RedisAcmeCache {
+    /// Validates the connection URL. The connection itself is made on first use.
+    pub fn new(connection_url: &str) -> Result<Self> {
+        redis::Client::open(connection_url).map_err(|e| {
+            ItsiError::ArgumentError(format!("Invalid acme_cache Redis URL: {}", e))
+        })?;
+        Ok(Self {
+            connection_url: connection_url.to_owned(),
+            connection: OnceCell::new(),
+            lock_token: Mutex::new(None),
+        })
+    }
+
+    async fn connection(&self) -> std::result::Result<ConnectionManager, IoError> {
+        self.connection
+            .get_or_try_init(|| async {
+                RedisCacheStore::new(&self.connection_url)
+                    .await
+                    .map(|store| store.connection())
+                    .map_err(|e| IoError::other(format!("{:?}", e)))
+            })
+            .await
+            .cloned()
+    }
+
+    async fn get(&self, key: &str) -> std::result::Result<Option<Vec<u8>>, IoError> {
+        self.connection()
+            .await?
+            .get(key)
+            .await
+            .map_err(IoError::other)
+    }
+
+    async fn set(&self, key: &str, value: &[u8]) -> std::result::Result<(), IoError> {
+        self.connection()
+            .await?
+            .set(key, value)
+            .await
+            .map_err(IoError::other)
+    }
+
+    /// Waits until we hold the order lock for `cert_key`,
+    /// or until another host stores a certificate other than `current`.
+    async fn lock_or_load(
+        &self,
+        cert_key: &str,
+        current: Option<&[u8]>,
+    ) -> std::result::Result<Option<Vec<u8>>, IoError> {
+        let lock_key = cert_key.replacen(":cert:", ":lock:", 1);
+        let token = format!("{:016x}", rand::random::<u64>());
+        let mut waiting = false;
+        loop {
+            if let Some(cert) = self.get(cert_key).await? {
+                if current != Some(cert.as_slice()) {
+                    return Ok(Some(cert));
+                }
+            }
+            let acquired: Option<String> = redis::cmd("SET")
+                .arg(&lock_key)
+                .arg(&token)
+                .arg("NX")
+                .arg("PX")
+                .arg(LOCK_TTL.as_millis() as u64)
+                .query_async(&mut self.connection().await?)
+                .await
+                .map_err(IoError::other)?;
+            if acquired.is_some() {
+                *self.lock_token.lock() = Some(token);
+                // Another host may have stored a certificate just before releasing the lock.
+                return match self.get(cert_key).await? {
+                    Some(cert) if current != Some(cert.as_slice()) => {
+                        self.unlock(cert_key).await?;
+                        Ok(Some(cert))
+                    }
+                    _ => Ok(None),
+                };
+            }
+            if !waiting {
+                info!("Waiting for another host to complete the ACME order");
+                waiting = true;
+            }
+            tokio::time::sleep(LOCK_POLL_INTERVAL).await;
+        }
+    }
+
+    async fn unlock(&self, cert_key: &str) -> std::result::Result<(), IoError> {
+        let Some(token) = self.lock_token.lock().take() else {
+            return Ok(());
+        };
+        // Only delete the lock if it is still ours, it may have expired and been taken by another host.
+        let _: i64 = Script::new(
+            r#"
+            if redis.call('GET', KEYS[1]) == ARGV[1] then
+                return redis.call('DEL', KEYS[1])
+            end
+            return 0
+            "#,
+        )
+        .key(cert_key.replacen(":cert:", ":lock:", 1))
+        .arg(token)
+        .invoke_async(&mut self.connection().await?)
+        .await
+        .map_err(IoError::other)?;
+        Ok(())
+    }
+}
+
+fn cache_key(kind: &str, names: &[String], directory_url: &str) -> String {
+    let mut hasher = Sha256::new();
+    for name in names {
+        hasher.update(name.as_bytes());
+        hasher.update([0]);
+    }
+    hasher.update(directory_url.as_bytes());
+    format!(
+        "{}:{}:{}",
+        KEY_PREFIX,
+        kind,
+        URL_SAFE_NO_PAD.encode(hasher.finalize())
+    )
+}
+
+#[async_trait]
+impl CertCache for RedisAcmeCache {
+    type EC = IoError;
+
+    async fn load_cert(
+        &self,
+        domains: &[String],
+        directory_url: &str,
+    ) -> std::result::Result<Option<Vec<u8>>, Self::EC> {
+        self.lock_or_load(&cache_key("cert", domains, directory_url), None)
+            .await
+    }
+
+    async fn store_cert(
+        &self,
+        domains: &[String],
+        directory_url: &str,
+        cert: &[u8],
+    ) -> std::result::Result<(), Self::EC> {
+        let cert_key = cache_key("cert", domains, directory_url);
+        self.set(&cert_key, cert).await?;
+        self.unlock(&cert_key).await
+    }
+
+    async fn load_renewed_cert(
+        &self,
+        domains: &[String],
+        directory_url: &str,
+        current: &[u8],
+    ) -> std::result::Result<Option<Vec<u8>>, Self::EC> {
+        self.lock_or_load(&cache_key("cert", domains, directory_url), Some(current))
+            .await
+    }
+
+    async fn order_failed(
+        &self,
+        domains: &[String],
+        directory_url: &str,
+    ) -> std::result::Result<(), Self::EC> {
+        self.unlock(&cache_key("cert", domains, directory_url))
+            .await
+    }
+}
+
+#[async_trait]
+impl AccountCache for RedisAcmeCache {
+    type EA = IoError;
+
+    async fn load_account(
+        &self,
+        contact: &[String],
+        directory_url: &str,
+    ) -> std::result::Result<Option<Vec<u8>>, Self::EA> {
+        self.get(&cache_key("account", contact, directory_url))
+            .await
+    }
+
+    async fn store_account(
+        &self,
+        contact: &[String],
+        directory_url: &str,
+        account: &[u8],
+    ) -> std::result::Result<(), Self::EA> {
+        self.set(&cache_key("account", contact, directory_url), account)
+            .await
+    }
+}
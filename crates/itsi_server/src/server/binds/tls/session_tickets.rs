@@ -0,0 +1,137 @@
+use itsi_error::{ItsiError, Result};
+use ring::{
+    aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM, NONCE_LEN},
+    rand::{SecureRandom, SystemRandom},
+};
+use rustls::server::ProducesTickets;
+use sha2::{Digest, Sha256};
+use std::fs;
+
+/// Tickets stay valid for 12 hours.
+const TICKET_LIFETIME: u32 = 12 * 3600;
+const KEY_NAME_LEN: usize = 16;
+const MIN_KEY_FILE_LEN: usize = 32;
+
+struct TicketKey {
+    name: [u8; KEY_NAME_LEN],
+    key: LessSafeKey,
+}
+
+/// Encrypts session tickets with keys read from files, rather than keys generated per process.
+///
+/// Every worker (and every host) configured with the same key files can resume sessions started by another.
+/// The first key encrypts new tickets, the rest only decrypt, so keys can be rotated without dropping sessions.
+pub struct SharedTicketer {
+    keys: Vec<TicketKey>,
+    random: SystemRandom,
+}
+
+impl std::fmt::Debug for SharedTicketer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SharedTicketer")
+            .field("keys", &self.keys.len())
+            .finish()
+    }
+}
+
+impl SharedTicketer {
+    /// Reads a comma separated list of key files, each holding at least 32 random bytes.
+    pub fn from_files(paths: &str) -> Result<Self> {
+        let keys = paths
+            .split(',')
+            .filter(|path| !path.is_empty())
+            .map(|path| {
+                let material = fs::read(path)?;
+                if material.len() < MIN_KEY_FILE_LEN {
+                    return Err(ItsiError::ArgumentError(format!(
+                        "Session ticket key file {} must hold at least {} bytes",
+                        path, MIN_KEY_FILE_LEN
+                    )));
+                }
+                Ok(TicketKey::derive(&material))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        if keys.is_empty() {
+            return Err(ItsiError::ArgumentError(
+                "tls_ticket_keys requires at least one key file".to_owned(),
+            ));
+        }
+        Ok(Self {
+            keys,
+            random: SystemRandom::new(),
+        })
+    }
+}
+
+impl TicketKey {
+    fn derive(material: &[u8]) -> Self {
+        let derive = |label: &[u8]| {
+            Sha256::new()
+                .chain_update(label)
+                .chain_update(material)
+                .finalize()
+        };
+        let mut name = [0u8; KEY_NAME_LEN];
+        name.copy_from_slice(&derive(b"itsi session ticket name")[..KEY_NAME_LEN]);
+        let key = UnboundKey::new(&AES_256_GCM, &derive(b"itsi session ticket key"))
+            .expect("SHA-256 output is a valid AES-256 key");
+        Self {
+            name,
+            key: LessSafeKey::new(key),
+        }
+    }
+}
+
+impl ProducesTickets for SharedTicketer {
+    fn enabled(&self) -> bool {
+        true
+    }
+
+    fn lifetime(&self) -> u32 {
+        TICKET_LIFETIME
+    }
+
+    /// Tickets are `key name || nonce || AES-256-GCM(plain)`, authenticating the key name.
+    fn encrypt(&self, plain: &[u8]) -> Option<Vec<u8>> {
+        let key = &self.keys[0];
+        let mut nonce = [0u8; NONCE_LEN];
+        self.random.fill(&mut nonce).ok()?;
+
+        let mut sealed = plain.to_vec();
+        key.key
+            .seal_in_place_append_tag(
+                Nonce::assume_unique_for_key(nonce),
+                Aad::from(&key.name),
+                &mut sealed,
+            )
+            .ok()?;
+
+        let mut ticket = Vec::with_capacity(KEY_NAME_LEN + NONCE_LEN + sealed.len());
+        ticket.extend_from_slice(&key.name);
+        ticket.extend_from_slice(&nonce);
+        ticket.extend_from_slice(&sealed);
+        Some(ticket)
+    }
+
+    fn decrypt(&self, ticket: &[u8]) -> Option<Vec<u8>> {
+        if ticket.len() < KEY_NAME_LEN + NONCE_LEN {
+            return None;
+        }
+        let (name, rest) = ticket.split_at(KEY_NAME_LEN);
+        let (nonce, sealed) = rest.split_at(NONCE_LEN);
+        let key = self.keys.iter().find(|key| key.name == name)?;
+
+        let mut plain = sealed.to_vec();
+        let length = key
+            .key
+            .open_in_place(
+                Nonce::try_assume_unique_for_key(nonce).ok()?,
+                Aad::from(&key.name),
+                &mut plain,
+            )
+            .ok()?
+            .len();
+        plain.truncate(length);
+        Some(plain)
+    }
+}
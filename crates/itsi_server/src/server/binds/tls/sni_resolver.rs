@@ -0,0 +1,295 @@
+use itsi_error::{ItsiError, Result};
+use itsi_tracing::{error, info, warn};
+use parking_lot::{Mutex, RwLock};
+use rustls::{
+    crypto::CryptoProvider,
+    pki_types::{CertificateDer, PrivateKeyDer},
+    server::{ClientHello, ResolvesServerCert},
+    sign::CertifiedKey,
+};
+use rustls_pemfile::{certs, private_key};
+use std::{
+    collections::HashMap,
+    fs,
+    io::BufReader,
+    path::{Path, PathBuf},
+    sync::{Arc, LazyLock, Weak},
+};
+use x509_parser::{extensions::GeneralName, prelude::*};
+
+/// Every live resolver, so their certificates can be reloaded on `LifecycleEvent::Reload`.
+static SNI_RESOLVERS: LazyLock<Mutex<Vec<Weak<SniCertResolver>>>> =
+    LazyLock::new(|| Mutex::new(Vec::new()));
+
+/// Where a bind loads its SNI certificates from.
+#[derive(Debug, Clone, Default)]
+pub struct CertificateSources {
+    /// `cert:key` path pairs from the `certs` bind option.
+    pub pairs: Vec<(PathBuf, PathBuf)>,
+    /// Directory of `<name>.crt|.pem` files with matching `<name>.key` files.
+    pub dir: Option<PathBuf>,
+    /// The `cert` and `key` bind options, served when no name matches.
+    pub default: Option<(PathBuf, PathBuf)>,
+}
+
+impl CertificateSources {
+    pub fn from_options(query_params: &HashMap<String, String>) -> Result<Option<Self>> {
+        let pairs = match query_params.get("certs") {
+            Some(certs) => certs
+                .split(',')
+                .filter(|pair| !pair.is_empty())
+                .map(|pair| {
+                    pair.split_once(':')
+                        .map(|(cert, key)| (PathBuf::from(cert), PathBuf::from(key)))
+                        .ok_or_else(|| {
+                            ItsiError::ArgumentError(format!(
+                                "Invalid certs entry {}. Expected cert_path:key_path",
+                                pair
+                            ))
+                        })
+                })
+                .collect::<Result<Vec<_>>>()?,
+            None => vec![],
+        };
+        let dir = query_params.get("certs_dir").map(PathBuf::from);
+        if pairs.is_empty() && dir.is_none() {
+            return Ok(None);
+        }
+
+        let default = match (query_params.get("cert"), query_params.get("key")) {
+            (Some(cert), Some(key)) if cert != "acme" => {
+                Some((PathBuf::from(cert), PathBuf::from(key)))
+            }
+            _ => None,
+        };
+
+        Ok(Some(Self {
+            pairs,
+            dir,
+            default,
+        }))
+    }
+
+    fn load(&self) -> Result<SniCertificates> {
+        let mut certificates = SniCertificates::default();
+        let mut pairs = self.pairs.clone();
+        if let Some(dir) = &self.dir {
+            pairs.extend(read_certificate_dir(dir)?);
+        }
+
+        for (cert_path, key_path) in pairs {
+            let (certified_key, names) = load_certified_key(&cert_path, &key_path)?;
+            if names.is_empty() {
+                warn!("Certificate {:?} has no DNS names to match", cert_path);
+            }
+            for name in names {
+                certificates
+                    .by_name
+                    .insert(name.to_ascii_lowercase(), certified_key.clone());
+            }
+        }
+
+        if let Some((cert_path, key_path)) = &self.default {
+            certificates.default = Some(load_certified_key(cert_path, key_path)?.0);
+        }
+        Ok(certificates)
+    }
+}
+
+#[derive(Debug, Default)]
+struct SniCertificates {
+    by_name: HashMap<String, Arc<CertifiedKey>>,
+    default: Option<Arc<CertifiedKey>>,
+}
+
+impl SniCertificates {
+    fn find(&self, server_name: &str) -> Option<Arc<CertifiedKey>> {
+        let server_name = server_name.to_ascii_lowercase();
+        self.by_name
+            .get(&server_name)
+            .or_else(|| {
+                let (_, parent) = server_name.split_once('.')?;
+                self.by_name.get(&format!("*.{}", parent))
+            })
+            .cloned()
+    }
+}
+
+/// Selects a certificate by SNI from a set of static certificates.
+/// Names without a static certificate go to the `fallback` resolver (ACME) if there is one,
+/// and otherwise get the default certificate.
+#[derive(Debug)]
+pub struct SniCertResolver {
+    sources: CertificateSources,
+    certificates: RwLock<SniCertificates>,
+    fallback: Option<Arc<dyn ResolvesServerCert>>,
+}
+
+impl SniCertResolver {
+    pub fn new(
+        sources: CertificateSources,
+        fallback: Option<Arc<dyn ResolvesServerCert>>,
+    ) -> Result<Arc<Self>> {
+        let resolver = Arc::new(Self {
+            certificates: RwLock::new(sources.load()?),
+            sources,
+            fallback,
+        });
+        let mut resolvers = SNI_RESOLVERS.lock();
+        resolvers.retain(|resolver| resolver.strong_count() > 0);
+        resolvers.push(Arc::downgrade(&resolver));
+        Ok(resolver)
+    }
+
+    /// Reloads certificates from disk. Established connections are unaffected,
+    /// and if any certificate fails to load we keep serving the previous set.
+    pub fn reload(&self) -> Result<()> {
+        let certificates = self.sources.load()?;
+        *self.certificates.write() = certificates;
+        Ok(())
+    }
+
+    /// The certificate files and directory this resolver loads from.
+    pub fn watched_paths(&self) -> Vec<PathBuf> {
+        self.sources
+            .pairs
+            .iter()
+            .chain(self.sources.default.iter())
+            .flat_map(|(cert, key)| [cert.clone(), key.clone()])
+            .chain(self.sources.dir.clone())
+            .collect()
+    }
+}
+
+impl ResolvesServerCert for SniCertResolver {
+    fn resolve(&self, client_hello: ClientHello) -> Option<Arc<CertifiedKey>> {
+        if let Some(certified_key) = client_hello
+            .server_name()
+            .and_then(|server_name| self.certificates.read().find(server_name))
+        {
+            return Some(certified_key);
+        }
+        match &self.fallback {
+            Some(fallback) => fallback.resolve(client_hello),
+            None => self.certificates.read().default.clone(),
+        }
+    }
+}
+
+pub(super) fn sni_resolvers() -> Vec<Arc<SniCertResolver>> {
+    SNI_RESOLVERS
+        .lock()
+        .iter()
+        .filter_map(Weak::upgrade)
+        .collect()
+}
+
+/// Reloads the certificates of every SNI resolver in this process.
+pub fn reload_sni_certificates() {
+    for resolver in sni_resolvers() {
+        match resolver.reload() {
+            Ok(()) => info!("Reloaded SNI certificates"),
+            Err(e) => error!("Failed to reload SNI certificates: {}", e),
+        }
+    }
+}
+
+fn read_certificate_dir(dir: &Path) -> Result<Vec<(PathBuf, PathBuf)>> {
+    let mut pairs = fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| {
+            path.extension()
+                .is_some_and(|extension| extension == "crt" || extension == "pem")
+        })
+        .filter_map(|cert_path| {
+            let key_path = cert_path.with_extension("key");
+            if key_path.exists() {
+                Some((cert_path, key_path))
+            } else {
+                warn!(
+                    "Skipping certificate {:?} with no matching .key file",
+                    cert_path
+                );
+                None
+            }
+        })
+        .collect::<Vec<_>>();
+    pairs.sort();
+    Ok(pairs)
+}
+
+/// Loads a certificate chain and key, and returns them with the DNS names the leaf certificate covers.
+pub(super) fn load_certified_key(
+    cert_path: &Path,
+    key_path: &Path,
+) -> Result<(Arc<CertifiedKey>, Vec<String>)> {
+    let chain = read_certs(cert_path)?;
+    let leaf = chain.first().ok_or_else(|| {
+        ItsiError::ArgumentError(format!("No certificates found in {:?}", cert_path))
+    })?;
+    let names = certificate_names(leaf);
+
+    let key = read_private_key(key_path)?;
+    let provider = CryptoProvider::get_default().ok_or_else(|| {
+        ItsiError::InternalServerError("No default crypto provider installed".to_owned())
+    })?;
+    let signing_key = provider
+        .key_provider
+        .load_private_key(key)
+        .map_err(|e| ItsiError::ArgumentError(format!("Invalid key {:?}: {}", key_path, e)))?;
+
+    Ok((Arc::new(CertifiedKey::new(chain, signing_key)), names))
+}
+
+/// Reads a certificate chain from a PEM file, or a single certificate from a raw DER file.
+fn read_certs(path: &Path) -> Result<Vec<CertificateDer<'static>>> {
+    let data = fs::read(path)?;
+    if !data.starts_with(b"-----BEGIN ") {
+        return Ok(vec![CertificateDer::from(data)]);
+    }
+    Ok(certs(&mut BufReader::new(&data[..]))
+        .collect::<std::result::Result<Vec<CertificateDer<'static>>, _>>()?)
+}
+
+/// Reads a private key from a PEM file, or from a raw DER (PKCS#1, SEC1 or PKCS#8) file.
+fn read_private_key(path: &Path) -> Result<PrivateKeyDer<'static>> {
+    let data = fs::read(path)?;
+    let key = if data.starts_with(b"-----BEGIN ") {
+        private_key(&mut BufReader::new(&data[..]))?
+    } else {
+        PrivateKeyDer::try_from(data).ok()
+    };
+    key.ok_or_else(|| ItsiError::ArgumentError(format!("No private key found in {:?}", path)))
+}
+
+fn certificate_names(leaf: &CertificateDer) -> Vec<String> {
+    let Ok((_, certificate)) = X509Certificate::from_der(leaf.as_ref()) else {
+        return vec![];
+    };
+    let mut names = certificate
+        .subject_alternative_name()
+        .ok()
+        .flatten()
+        .map(|extension| {
+            extension
+                .value
+                .general_names
+                .iter()
+                .filter_map(|name| match name {
+                    GeneralName::DNSName(name) => Some(name.to_string()),
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    if names.is_empty() {
+        names.extend(
+            certificate
+                .subject()
+                .iter_common_name()
+                .filter_map(|common_name| common_name.as_str().ok())
+                .map(String::from),
+        );
+    }
+    names
+}
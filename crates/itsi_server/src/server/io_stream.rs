@@ -5,10 +5,11 @@ use tokio_rustls::server::TlsStream;
 use std::io::{self, IoSlice};
 use std::os::unix::io::{AsRawFd, RawFd};
 use std::pin::Pin;
+use std::sync::Arc;
 use std::task::{Context, Poll};
 use tokio::io::{AsyncRead, AsyncWrite};
 
-use super::binds::listener::SockAddr;
+use super::binds::{client_certificate::ClientCertificate, listener::SockAddr};
 
 #[pin_project(project = IoStreamEnumProj)]
 pub enum IoStream {
@@ -43,6 +44,16 @@ impl IoStream {
             IoStream::UnixTls { addr, .. } => addr.to_string(),
         }
     }
+
+    /// The certificate presented by the client, on binds that verify client certificates.
+    pub fn client_certificate(&self) -> Option<Arc<ClientCertificate>> {
+        let certificates = match self {
+            IoStream::TcpTls { stream, .. } => stream.get_ref().1.peer_certificates(),
+            IoStream::UnixTls { stream, .. } => stream.get_ref().1.peer_certificates(),
+            _ => None,
+        };
+        ClientCertificate::from_peer_certificates(certificates).map(Arc::new)
+    }
 }
 
 impl AsyncRead for IoStream {
@@ -16,6 +16,8 @@ pub enum Middleware {
     AuthAPIKey(Arc<AuthAPIKey>),
     AuthBasic(Arc<AuthBasic>),
     AuthJwt(Arc<AuthJwt>),
+    AuthOidc(Arc<AuthOidc>),
+    AuthRequest(Arc<AuthRequest>),
     CacheControl(Arc<CacheControl>),
     Compression(Arc<Compression>),
     Cors(Arc<Cors>),
@@ -29,6 +31,7 @@ pub enum Middleware {
     RateLimit(Arc<RateLimit>),
     Redirect(Arc<Redirect>),
     RequestHeaders(Arc<RequestHeaders>),
+    ResponseCache(Arc<ResponseCache>),
     ResponseHeaders(Arc<ResponseHeaders>),
     RubyApp(Arc<RubyApp>),
     StaticAssets(Arc<StaticAssets>),
@@ -44,7 +47,9 @@ impl MiddlewareLayer for Middleware {
             Middleware::AllowList(filter) => filter.initialize().await,
             Middleware::AuthBasic(filter) => filter.initialize().await,
             Middleware::AuthJwt(filter) => filter.initialize().await,
+            Middleware::AuthOidc(filter) => filter.initialize().await,
             Middleware::AuthAPIKey(filter) => filter.initialize().await,
+            Middleware::AuthRequest(filter) => filter.initialize().await,
             Middleware::IntrusionProtection(filter) => filter.initialize().await,
             Middleware::MaxBody(filter) => filter.initialize().await,
             Middleware::RateLimit(filter) => filter.initialize().await,
@@ -57,6 +62,7 @@ impl MiddlewareLayer for Middleware {
             Middleware::StaticAssets(filter) => filter.initialize().await,
             Middleware::StaticResponse(filter) => filter.initialize().await,
             Middleware::Compression(filter) => filter.initialize().await,
+            Middleware::ResponseCache(filter) => filter.initialize().await,
             Middleware::LogRequests(filter) => filter.initialize().await,
             Middleware::Redirect(filter) => filter.initialize().await,
             Middleware::Proxy(filter) => filter.initialize().await,
@@ -74,7 +80,9 @@ impl MiddlewareLayer for Middleware {
             Middleware::AllowList(filter) => filter.before(req, context).await,
             Middleware::AuthBasic(filter) => filter.before(req, context).await,
             Middleware::AuthJwt(filter) => filter.before(req, context).await,
+            Middleware::AuthOidc(filter) => filter.before(req, context).await,
             Middleware::AuthAPIKey(filter) => filter.before(req, context).await,
+            Middleware::AuthRequest(filter) => filter.before(req, context).await,
             Middleware::IntrusionProtection(filter) => filter.before(req, context).await,
             Middleware::MaxBody(filter) => filter.before(req, context).await,
             Middleware::RequestHeaders(filter) => filter.before(req, context).await,
@@ -87,6 +95,7 @@ impl MiddlewareLayer for Middleware {
             Middleware::StaticAssets(filter) => filter.before(req, context).await,
             Middleware::StaticResponse(filter) => filter.before(req, context).await,
             Middleware::Compression(filter) => filter.before(req, context).await,
+            Middleware::ResponseCache(filter) => filter.before(req, context).await,
             Middleware::LogRequests(filter) => filter.before(req, context).await,
             Middleware::Redirect(filter) => filter.before(req, context).await,
             Middleware::Proxy(filter) => filter.before(req, context).await,
@@ -100,7 +109,9 @@ impl MiddlewareLayer for Middleware {
             Middleware::AllowList(filter) => filter.after(res, context).await,
             Middleware::AuthBasic(filter) => filter.after(res, context).await,
             Middleware::AuthJwt(filter) => filter.after(res, context).await,
+            Middleware::AuthOidc(filter) => filter.after(res, context).await,
             Middleware::AuthAPIKey(filter) => filter.after(res, context).await,
+            Middleware::AuthRequest(filter) => filter.after(res, context).await,
             Middleware::IntrusionProtection(filter) => filter.after(res, context).await,
             Middleware::MaxBody(filter) => filter.after(res, context).await,
             Middleware::RateLimit(filter) => filter.after(res, context).await,
@@ -113,6 +124,7 @@ impl MiddlewareLayer for Middleware {
             Middleware::StaticAssets(filter) => filter.after(res, context).await,
             Middleware::StaticResponse(filter) => filter.after(res, context).await,
             Middleware::Compression(filter) => filter.after(res, context).await,
+            Middleware::ResponseCache(filter) => filter.after(res, context).await,
             Middleware::LogRequests(filter) => filter.after(res, context).await,
             Middleware::Redirect(filter) => filter.after(res, context).await,
             Middleware::Proxy(filter) => filter.after(res, context).await,
@@ -135,16 +147,19 @@ impl Middleware {
             Middleware::MaxBody(_) => 8,
             Middleware::AuthBasic(_) => 9,
             Middleware::AuthJwt(_) => 10,
-            Middleware::AuthAPIKey(_) => 11,
-            Middleware::RateLimit(_) => 12,
-            Middleware::ETag(_) => 13,
-            Middleware::Csp(_) => 14,
-            Middleware::Compression(_) => 15,
-            Middleware::Proxy(_) => 16,
-            Middleware::Cors(_) => 17,
-            Middleware::StaticResponse(_) => 18,
-            Middleware::StaticAssets(_) => 19,
-            Middleware::RubyApp(_) => 20,
+            Middleware::AuthOidc(_) => 11,
+            Middleware::AuthAPIKey(_) => 12,
+            Middleware::AuthRequest(_) => 13,
+            Middleware::RateLimit(_) => 14,
+            Middleware::ETag(_) => 15,
+            Middleware::Csp(_) => 16,
+            Middleware::Compression(_) => 17,
+            Middleware::ResponseCache(_) => 18,
+            Middleware::Proxy(_) => 19,
+            Middleware::Cors(_) => 20,
+            Middleware::StaticResponse(_) => 21,
+            Middleware::StaticAssets(_) => 22,
+            Middleware::RubyApp(_) => 23,
         }
     }
 }
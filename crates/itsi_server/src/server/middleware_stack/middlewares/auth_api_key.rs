@@ -1,11 +1,17 @@
-use std::collections::HashMap;
+use std::{
+    collections::HashMap,
+    sync::{Arc, OnceLock},
+};
 
 use crate::{
     server::http_message_types::{HttpRequest, HttpResponse, RequestExt},
     services::{itsi_http_service::HttpRequestContext, password_hasher},
 };
 
-use super::{error_response::ErrorResponse, token_source::TokenSource, FromValue, MiddlewareLayer};
+use super::{
+    credentials_file::CredentialsFile, error_response::ErrorResponse, token_source::TokenSource,
+    FromValue, MiddlewareLayer,
+};
 
 use async_trait::async_trait;
 use either::Either;
@@ -17,10 +23,15 @@ type PasswordHash = String;
 
 /// A simple API key filter.
 /// The API key can be given inside the header or a query string
-/// Keys are validated against a list of allowed key values (Changing these requires a restart)
-#[derive(Debug, Clone, Deserialize)]
+/// Keys are validated against a list of allowed key values, and/or a credentials file
+/// of `key_id:hash` lines, which is reloaded when it changes.
+#[derive(Debug, Deserialize)]
 pub struct AuthAPIKey {
+    #[serde(default)]
     pub valid_keys: HashMap<String, PasswordHash>,
+    pub credentials_file: Option<String>,
+    #[serde(skip_deserializing)]
+    pub file_keys: OnceLock<Arc<CredentialsFile>>,
     pub key_id_source: Option<TokenSource>,
     pub token_source: TokenSource,
     #[serde(default = "unauthorized_error_response")]
@@ -33,10 +44,17 @@ fn unauthorized_error_response() -> ErrorResponse {
 
 #[async_trait]
 impl MiddlewareLayer for AuthAPIKey {
+    async fn initialize(&self) -> Result<()> {
+        if let Some(path) = self.credentials_file.as_ref() {
+            self.file_keys.set(CredentialsFile::load(path)?).ok();
+        }
+        Ok(())
+    }
+
     async fn before(
         &self,
         req: HttpRequest,
-        _context: &mut HttpRequestContext,
+        context: &mut HttpRequestContext,
     ) -> Result<Either<HttpRequest, HttpResponse>> {
         if let Some(submitted_key) = match &self.token_source {
             TokenSource::Header { name, prefix } => {
@@ -70,16 +88,27 @@ impl MiddlewareLayer for AuthAPIKey {
                     TokenSource::Query(query_name) => req.query_param(query_name),
                 };
                 debug!(target: "middleware::auth_api_key", "Key ID Retrieved");
-                if let Some(hash) = key_id.and_then(|kid| self.valid_keys.get(kid)) {
+                if let Some(hash) = key_id.and_then(|kid| self.hash_for(kid)) {
                     debug!(target: "middleware::auth_api_key", "Key for ID found");
-                    if password_hasher::verify_password_hash(submitted_key, hash).is_ok_and(|v| v) {
+                    if password_hasher::verify_password_hash(submitted_key, &hash).is_ok_and(|v| v)
+                    {
+                        context.set_authenticated();
                         return Ok(Either::Left(req));
                     }
                 }
-            } else if self.valid_keys.values().any(|key| {
-                password_hasher::verify_password_hash(submitted_key, key).is_ok_and(|v| v)
-            }) {
-                return Ok(Either::Left(req));
+            } else {
+                let file_keys = self.file_keys.get().map(|file| file.credentials());
+                if self
+                    .valid_keys
+                    .values()
+                    .chain(file_keys.iter().flat_map(|keys| keys.values()))
+                    .any(|key| {
+                        password_hasher::verify_password_hash(submitted_key, key).is_ok_and(|v| v)
+                    })
+                {
+                    context.set_authenticated();
+                    return Ok(Either::Left(req));
+                }
             }
         }
 
@@ -91,4 +120,15 @@ impl MiddlewareLayer for AuthAPIKey {
         ))
     }
 }
+
+impl AuthAPIKey {
+    fn hash_for(&self, key_id: &str) -> Option<PasswordHash> {
+        self.valid_keys.get(key_id).cloned().or_else(|| {
+            self.file_keys
+                .get()
+                .and_then(|file| file.credentials().get(key_id).cloned())
+        })
+    }
+}
+
 impl FromValue for AuthAPIKey {}
@@ -4,9 +4,10 @@ use bytes::Bytes;
 use either::Either;
 use http::{Response, StatusCode};
 use magnus::error::Result;
-use serde::{Deserialize, Serialize};
+use serde::Deserialize;
 use std::collections::HashMap;
 use std::str;
+use std::sync::{Arc, OnceLock};
 use tracing::debug;
 
 use crate::{
@@ -14,18 +15,31 @@ use crate::{
     services::{itsi_http_service::HttpRequestContext, password_hasher::verify_password_hash},
 };
 
-use super::{FromValue, MiddlewareLayer};
+use super::{credentials_file::CredentialsFile, FromValue, MiddlewareLayer};
 
 type PasswordHash = String;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Deserialize)]
 pub struct AuthBasic {
     pub realm: String,
     /// Maps usernames to passwords.
+    #[serde(default)]
     pub credential_pairs: HashMap<String, PasswordHash>,
+    /// An htpasswd file of further `username:hash` pairs, reloaded when it changes.
+    pub credentials_file: Option<String>,
+    #[serde(skip_deserializing)]
+    pub file_credentials: OnceLock<Arc<CredentialsFile>>,
 }
 
 impl AuthBasic {
+    fn password_hash_for(&self, username: &str) -> Option<PasswordHash> {
+        self.credential_pairs.get(username).cloned().or_else(|| {
+            self.file_credentials
+                .get()
+                .and_then(|file| file.credentials().get(username).cloned())
+        })
+    }
+
     fn basic_auth_failed_response(&self) -> HttpResponse {
         Response::builder()
             .status(StatusCode::UNAUTHORIZED)
@@ -39,10 +53,17 @@ impl AuthBasic {
 }
 #[async_trait]
 impl MiddlewareLayer for AuthBasic {
+    async fn initialize(&self) -> Result<()> {
+        if let Some(path) = self.credentials_file.as_ref() {
+            self.file_credentials.set(CredentialsFile::load(path)?).ok();
+        }
+        Ok(())
+    }
+
     async fn before(
         &self,
         req: HttpRequest,
-        _context: &mut HttpRequestContext,
+        context: &mut HttpRequestContext,
     ) -> Result<Either<HttpRequest, HttpResponse>> {
         // Retrieve the Authorization header.
         let auth_header = req.header("Authorization");
@@ -75,10 +96,13 @@ impl MiddlewareLayer for AuthBasic {
         let username = parts.next().unwrap_or("");
         let password = parts.next().unwrap_or("");
 
-        match self.credential_pairs.get(username) {
+        match self.password_hash_for(username) {
             Some(expected_password_hash) => {
-                match verify_password_hash(password, expected_password_hash) {
-                    Ok(true) => Ok(Either::Left(req)),
+                match verify_password_hash(password, &expected_password_hash) {
+                    Ok(true) => {
+                        context.set_authenticated();
+                        Ok(Either::Left(req))
+                    }
                     _ => Ok(Either::Right(self.basic_auth_failed_response())),
                 }
             }
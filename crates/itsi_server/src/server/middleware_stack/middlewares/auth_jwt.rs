@@ -1,3 +1,5 @@
+pub(super) mod jwks;
+
 use super::{error_response::ErrorResponse, token_source::TokenSource, FromValue, MiddlewareLayer};
 use crate::{
     server::http_message_types::{HttpRequest, HttpResponse, RequestExt},
@@ -8,23 +10,36 @@ use async_trait::async_trait;
 use base64::{engine::general_purpose, Engine};
 use derive_more::Debug;
 use either::Either;
+use http::{HeaderName, HeaderValue};
 use itsi_error::ItsiError;
 use jsonwebtoken::{
     decode, decode_header, Algorithm as JwtAlg, DecodingKey, TokenData, Validation,
 };
+use jwks::{refresh_periodically, Jwks};
 use magnus::error::Result;
+use percent_encoding::{utf8_percent_encode, AsciiSet, CONTROLS};
 use serde::Deserialize;
+use serde_json::{Map, Value};
 use std::{
     collections::{HashMap, HashSet},
-    sync::OnceLock,
+    sync::{Arc, OnceLock},
+    time::Duration,
 };
-use tracing::debug;
+use tracing::{debug, warn};
 
 #[derive(Debug, Clone, Deserialize)]
 pub struct AuthJwt {
     pub token_source: TokenSource,
     // The verifiers map still holds base64-encoded key strings keyed by algorithm.
+    #[serde(default)]
     pub verifiers: HashMap<JwtAlgorithm, Vec<String>>,
+    /// A JSON Web Key Set to verify tokens with, alongside any static verifiers.
+    pub jwks_url: Option<String>,
+    /// How often to refetch the key set, in seconds.
+    #[serde(default = "default_jwks_refresh_interval")]
+    pub jwks_refresh_interval: u64,
+    #[serde(skip_deserializing)]
+    pub jwks: OnceLock<Arc<Jwks>>,
     // We now store jsonwebtoken’s DecodingKey in our OnceLock.
     #[serde(skip_deserializing)]
     #[debug(skip)]
@@ -37,14 +52,131 @@ pub struct AuthJwt {
     #[serde(skip_deserializing)]
     pub issuer_vec: OnceLock<Option<Vec<String>>>,
     pub leeway: Option<u64>,
+    /// Claims (or dotted paths into nested claims) to forward to the app, and the request header to forward each in.
+    #[serde(default)]
+    pub claims_to_headers: HashMap<String, String>,
+    #[serde(skip_deserializing)]
+    pub claim_headers: OnceLock<Vec<(String, HeaderName)>>,
+    /// Claims that must be present, and equal or contain a value.
+    #[serde(default)]
+    pub required_claims: HashMap<String, ClaimRequirement>,
     #[serde(default = "unauthorized_error_response")]
     pub error_response: ErrorResponse,
 }
 
+#[derive(Debug, Clone, Deserialize)]
+pub enum ClaimRequirement {
+    #[serde(rename(deserialize = "equals"))]
+    Equals(Value),
+    /// An array claim containing the value,
+    /// or a space-delimited string claim (like the OAuth `scope` claim) containing it as a word.
+    #[serde(rename(deserialize = "contains"))]
+    Contains(Value),
+}
+
+impl ClaimRequirement {
+    fn is_met_by(&self, claim: Option<&Value>) -> bool {
+        let Some(claim) = claim else {
+            return false;
+        };
+        match self {
+            ClaimRequirement::Equals(expected) => claim == expected,
+            ClaimRequirement::Contains(expected) => match claim {
+                Value::Array(values) => values.contains(expected),
+                Value::String(words) => expected
+                    .as_str()
+                    .is_some_and(|expected| words.split_whitespace().any(|word| word == expected)),
+                _ => false,
+            },
+        }
+    }
+}
+
+/// Looks up a claim by name, or by a dotted path into nested claims (e.g. `realm_access.roles`).
+pub fn claim_at<'a>(claims: &'a Map<String, Value>, path: &str) -> Option<&'a Value> {
+    let mut parts = path.split('.');
+    let mut value = claims.get(parts.next()?)?;
+    for part in parts {
+        value = value.get(part)?;
+    }
+    Some(value)
+}
+
+/// Formats a claim for use in a header or log line. Arrays are joined with commas.
+pub fn claim_to_string(value: &Value) -> Option<String> {
+    match value {
+        Value::Null => None,
+        Value::String(string) => Some(string.clone()),
+        Value::Array(values) => Some(
+            values
+                .iter()
+                .filter_map(claim_to_string)
+                .collect::<Vec<_>>()
+                .join(","),
+        ),
+        other => Some(other.to_string()),
+    }
+}
+
+/// Parses a `claims_to_headers` mapping, from claim paths to header names.
+pub(super) fn parse_claim_headers(
+    claims_to_headers: &HashMap<String, String>,
+) -> itsi_error::Result<Vec<(String, HeaderName)>> {
+    claims_to_headers
+        .iter()
+        .map(|(claim, header)| {
+            header
+                .parse::<HeaderName>()
+                .map(|header| (claim.clone(), header))
+                .map_err(|_| ItsiError::new(format!("Invalid header name {}", header)))
+        })
+        .collect()
+}
+
+/// Characters percent-encoded in forwarded claims. `%` itself is encoded so values decode unambiguously.
+const CLAIM_ENCODE_SET: &AsciiSet = &CONTROLS.add(b'%');
+
+/// Sets a header for each mapped claim present in `claims`.
+/// Non-ASCII characters, control characters and `%` are percent-encoded, so every claim can be forwarded.
+pub(super) fn forward_claims(
+    req: &mut HttpRequest,
+    claim_headers: &[(String, HeaderName)],
+    claims: &Map<String, Value>,
+) {
+    let headers = req.headers_mut();
+    for (claim, header) in claim_headers {
+        // Never pass on a client's own value for a claim header.
+        headers.remove(header);
+        let Some(value) = claim_at(claims, claim).and_then(claim_to_string) else {
+            continue;
+        };
+        let encoded = utf8_percent_encode(&value, CLAIM_ENCODE_SET).to_string();
+        if encoded != value {
+            debug!(
+                target: "middleware::auth_jwt",
+                "Percent-encoded claim {} for header {}", claim, header
+            );
+        }
+        match HeaderValue::from_str(&encoded) {
+            Ok(value) => {
+                headers.insert(header.clone(), value);
+            }
+            Err(_) => warn!(
+                target: "middleware::auth_jwt",
+                "Dropped claim {} for header {}: not a valid header value", claim, header
+            ),
+        }
+    }
+}
+
 fn unauthorized_error_response() -> ErrorResponse {
     ErrorResponse::unauthorized()
 }
 
+fn default_jwks_refresh_interval() -> u64 {
+    300
+}
+
 #[derive(Debug, Clone, Deserialize, PartialEq, Eq, Hash)]
 pub enum JwtAlgorithm {
     #[serde(rename(deserialize = "hs256"))]
@@ -69,6 +201,8 @@ pub enum JwtAlgorithm {
     Ps384,
     #[serde(rename(deserialize = "ps512"))]
     Ps512,
+    #[serde(rename(deserialize = "eddsa"))]
+    EdDsa,
 }
 
 // Allow conversion from jsonwebtoken’s Algorithm to our JwtAlgorithm.
@@ -86,7 +220,7 @@ impl From<JwtAlg> for JwtAlgorithm {
             JwtAlg::PS256 => JwtAlgorithm::Ps256,
             JwtAlg::PS384 => JwtAlgorithm::Ps384,
             JwtAlg::PS512 => JwtAlgorithm::Ps512,
-            _ => panic!("Unsupported algorithm"),
+            JwtAlg::EdDSA => JwtAlgorithm::EdDsa,
         }
     }
 }
@@ -116,35 +250,19 @@ impl JwtAlgorithm {
                 DecodingKey::from_ec_pem(base64.trim_ascii().as_bytes())
                     .map_err(|e| ItsiError::new(e.to_string()))
             }
+            // For EdDSA, expect a PEM-formatted Ed25519 key.
+            JwtAlgorithm::EdDsa => DecodingKey::from_ed_pem(base64.trim_ascii().as_bytes())
+                .map_err(|e| ItsiError::new(e.to_string())),
         }
     }
 }
 
-#[derive(Debug, Deserialize)]
-#[serde(untagged)]
-#[allow(dead_code)]
-enum Audience {
-    Single(String),
-    Multiple(Vec<String>),
-}
-
-#[derive(Debug, Deserialize)]
-#[allow(dead_code)]
-struct Claims {
-    // Here we assume the token includes an expiration.
-    exp: usize,
-    // The audience claim may be a single string or an array.
-    aud: Option<Audience>,
-    sub: Option<String>,
-    iss: Option<String>,
-}
-
 #[async_trait]
 impl MiddlewareLayer for AuthJwt {
     async fn initialize(&self) -> Result<()> {
         debug!(
             target: "middleware::auth_jwt",
-            "Instantiating auth_jwt with {} verifiers", self.verifiers.len()
+            "Instantiating auth_jwt with {} verifiers, jwks_url: {:?}", self.verifiers.len(), self.jwks_url
         );
 
         let keys: HashMap<JwtAlgorithm, Vec<DecodingKey>> = self
@@ -177,6 +295,22 @@ impl MiddlewareLayer for AuthJwt {
             .set(keys)
             .map_err(|_| ItsiError::new("Failed to set keys"))?;
 
+        if let Some(jwks_url) = self.jwks_url.as_ref() {
+            let jwks = Arc::new(Jwks::new(jwks_url.clone())?);
+            // A failed fetch is retried on the first token, so an identity provider outage
+            // doesn't prevent startup.
+            jwks.refresh().await;
+            tokio::spawn(refresh_periodically(
+                Arc::downgrade(&jwks),
+                Duration::from_secs(self.jwks_refresh_interval.max(1)),
+            ));
+            self.jwks.set(jwks).ok();
+        }
+
+        self.claim_headers
+            .set(parse_claim_headers(&self.claims_to_headers)?)
+            .ok();
+
         if let Some(audiences) = self.audiences.as_ref() {
             self.audience_vec
                 .set(Some(audiences.iter().cloned().collect::<Vec<_>>()))
@@ -192,8 +326,8 @@ impl MiddlewareLayer for AuthJwt {
 
     async fn before(
         &self,
-        req: HttpRequest,
-        _: &mut HttpRequestContext,
+        mut req: HttpRequest,
+        context: &mut HttpRequestContext,
     ) -> Result<Either<HttpRequest, HttpResponse>> {
         // Retrieve the JWT token from either a header or a query parameter.
         let token_str = match &self.token_source {
@@ -253,14 +387,19 @@ impl MiddlewareLayer for AuthJwt {
           target: "middleware::auth_jwt",
             "Matched algorithm {:?}", alg
         );
-        if !self.verifiers.contains_key(&alg) {
+        let jwks_keys = match self.jwks.get() {
+            Some(jwks) => jwks.keys_for(header.kid.as_deref(), header.alg).await,
+            None => vec![],
+        };
+        let static_keys = self.keys.get().unwrap().get(&alg);
+        if static_keys.is_none() && jwks_keys.is_empty() {
             return Ok(Either::Right(
                 self.error_response
                     .to_http_response(req.accept().into())
                     .await,
             ));
         }
-        let keys = self.keys.get().unwrap().get(&alg).unwrap();
+        let keys = static_keys.into_iter().flatten().chain(jwks_keys.iter());
 
         // Build validation based on the algorithm and optional leeway.
         let mut validation = Validation::new(match alg {
@@ -275,6 +414,7 @@ impl MiddlewareLayer for AuthJwt {
             JwtAlgorithm::Ps256 => JwtAlg::PS256,
             JwtAlgorithm::Ps384 => JwtAlg::PS384,
             JwtAlgorithm::Ps512 => JwtAlg::PS512,
+            JwtAlgorithm::EdDsa => JwtAlg::EdDSA,
         });
 
         if let Some(leeway) = self.leeway {
@@ -297,15 +437,14 @@ impl MiddlewareLayer for AuthJwt {
             validation.required_spec_claims.insert("sub".to_owned());
         }
 
-        let token_data: Option<TokenData<Claims>> =
-            keys.iter()
-                .find_map(|key| match decode::<Claims>(token_str, key, &validation) {
-                    Ok(data) => Some(data),
-                    Err(e) => {
-                        debug!("Token validation failed: {:?}", e);
-                        None
-                    }
-                });
+        let token_data: Option<TokenData<Map<String, Value>>> =
+            keys.find_map(|key| match decode(token_str, key, &validation) {
+                Ok(data) => Some(data),
+                Err(e) => {
+                    debug!("Token validation failed: {:?}", e);
+                    None
+                }
+            });
 
         let token_data = if let Some(data) = token_data {
             data
@@ -320,7 +459,7 @@ impl MiddlewareLayer for AuthJwt {
         let claims = token_data.claims;
 
         if let Some(expected_subjects) = &self.subjects {
-            if let Some(sub) = &claims.sub {
+            if let Some(sub) = claims.get("sub").and_then(Value::as_str) {
                 if !expected_subjects.contains(sub) {
                     debug!(
                         target: "middleware::auth_jwt",
@@ -336,6 +475,24 @@ impl MiddlewareLayer for AuthJwt {
             }
         }
 
+        for (claim, requirement) in self.required_claims.iter() {
+            if !requirement.is_met_by(claim_at(&claims, claim)) {
+                debug!(
+                    target: "middleware::auth_jwt",
+                    "Required claim {} not met: {:?}", claim, requirement
+                );
+                return Ok(Either::Right(
+                    self.error_response
+                        .to_http_response(req.accept().into())
+                        .await,
+                ));
+            }
+        }
+
+        forward_claims(&mut req, self.claim_headers.get().unwrap(), &claims);
+        context.set_jwt_claims(claims);
+        context.set_authenticated();
+
         Ok(Either::Left(req))
     }
 }
@@ -0,0 +1,170 @@
+use std::{
+    sync::Weak,
+    time::{Duration, Instant},
+};
+
+use derive_more::Debug;
+use itsi_error::{ItsiError, Result};
+use jsonwebtoken::{
+    jwk::{Jwk, PublicKeyUse},
+    Algorithm as JwtAlg, DecodingKey,
+};
+use parking_lot::RwLock;
+use reqwest::Client;
+use serde::Deserialize;
+use tracing::{debug, warn};
+
+/// We refetch the key set at most this often when we see a token signed with an unknown `kid`,
+/// so tokens with made-up key IDs can't be used to hammer the identity provider.
+const MIN_REFRESH_INTERVAL: Duration = Duration::from_secs(10);
+const FETCH_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A JSON Web Key Set fetched from `jwks_url`, refreshed periodically
+/// and whenever a token refers to a key we haven't seen.
+#[derive(Debug)]
+pub struct Jwks {
+    url: String,
+    #[debug(skip)]
+    client: Client,
+    #[debug(skip)]
+    keys: RwLock<Vec<JwksKey>>,
+    /// When we last refetched the key set for a token with an unknown `kid`.
+    /// Held across the refetch, so concurrent requests wait for it rather than fetching again.
+    /// Tracked separately from the startup and periodic fetches, so those don't delay picking up a rotated key.
+    last_unknown_kid_fetch: tokio::sync::Mutex<Option<Instant>>,
+}
+
+struct JwksKey {
+    kid: Option<String>,
+    /// The `alg` the key is restricted to, if the key set specifies one.
+    algorithm: Option<String>,
+    key: DecodingKey,
+}
+
+/// Parsed loosely, so one key of a type we don't support doesn't invalidate the whole set.
+#[derive(Deserialize)]
+struct RawJwkSet {
+    keys: Vec<serde_json::Value>,
+}
+
+impl Jwks {
+    pub fn new(url: String) -> Result<Self> {
+        let client = Client::builder()
+            .timeout(FETCH_TIMEOUT)
+            .build()
+            .map_err(|e| ItsiError::new(format!("Failed to build JWKS client: {}", e)))?;
+        Ok(Self {
+            url,
+            client,
+            keys: RwLock::new(Vec::new()),
+            last_unknown_kid_fetch: tokio::sync::Mutex::new(None),
+        })
+    }
+
+    /// The keys that may have signed a token with the given `kid` and `alg`.
+    /// Tokens without a `kid` are checked against every key.
+    pub async fn keys_for(&self, kid: Option<&str>, alg: JwtAlg) -> Vec<DecodingKey> {
+        let keys = self.matching_keys(kid, alg);
+        if !keys.is_empty() || kid.is_none() {
+            return keys;
+        }
+        // The identity provider may have rotated in a new key since we last looked.
+        self.refresh_for_unknown_kid().await;
+        self.matching_keys(kid, alg)
+    }
+
+    /// Refetches the key set, unless a token with an unknown `kid` already triggered a fetch
+    /// in the last [MIN_REFRESH_INTERVAL].
+    async fn refresh_for_unknown_kid(&self) {
+        let mut last_fetch = self.last_unknown_kid_fetch.lock().await;
+        if last_fetch.is_some_and(|fetched| fetched.elapsed() < MIN_REFRESH_INTERVAL) {
+            return;
+        }
+        *last_fetch = Some(Instant::now());
+        self.refresh().await;
+    }
+
+    fn matching_keys(&self, kid: Option<&str>, alg: JwtAlg) -> Vec<DecodingKey> {
+        let alg = format!("{:?}", alg);
+        self.keys
+            .read()
+            .iter()
+            .filter(|key| kid.is_none() || key.kid.as_deref() == kid)
+            .filter(|key| key.algorithm.as_ref().is_none_or(|key_alg| *key_alg == alg))
+            .map(|key| key.key.clone())
+            .collect()
+    }
+
+    /// Fetches the key set. If the fetch fails, we keep the keys we have.
+    pub async fn refresh(&self) {
+        match self.fetch().await {
+            Ok(keys) => {
+                debug!(
+                    target: "middleware::auth_jwt",
+                    "Loaded {} keys from {}", keys.len(), self.url
+                );
+                *self.keys.write() = keys;
+            }
+            Err(e) => warn!("Failed to refresh JWKS from {}: {}", self.url, e),
+        }
+    }
+
+    async fn fetch(&self) -> Result<Vec<JwksKey>> {
+        let response = self
+            .client
+            .get(&self.url)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| ItsiError::new(e.to_string()))?;
+        let body = response
+            .bytes()
+            .await
+            .map_err(|e| ItsiError::new(e.to_string()))?;
+        let key_set: RawJwkSet = serde_json::from_slice(&body)
+            .map_err(|e| ItsiError::new(format!("Invalid JWKS: {}", e)))?;
+
+        Ok(key_set
+            .keys
+            .into_iter()
+            .filter_map(|value| {
+                let jwk: Jwk = serde_json::from_value(value)
+                    .inspect_err(|e| {
+                        debug!(target: "middleware::auth_jwt", "Skipping unsupported JWK: {}", e)
+                    })
+                    .ok()?;
+                if matches!(jwk.common.public_key_use, Some(PublicKeyUse::Encryption)) {
+                    return None;
+                }
+                let key = DecodingKey::from_jwk(&jwk)
+                    .inspect_err(
+                        |e| debug!(target: "middleware::auth_jwt", "Skipping invalid JWK: {}", e),
+                    )
+                    .ok()?;
+                Some(JwksKey {
+                    kid: jwk.common.key_id,
+                    algorithm: jwk
+                        .common
+                        .key_algorithm
+                        .map(|algorithm| format!("{:?}", algorithm)),
+                    key,
+                })
+            })
+            .collect())
+    }
+}
+
+/// Refetches the key set every `interval`, until the middleware that owns it is dropped
+/// (e.g. on config reload).
+pub async fn refresh_periodically(jwks: Weak<Jwks>, interval: Duration) {
+    let mut ticker = tokio::time::interval(interval);
+    // The first tick completes immediately, and we've just fetched the keys.
+    ticker.tick().await;
+    loop {
+        ticker.tick().await;
+        let Some(jwks) = jwks.upgrade() else {
+            break;
+        };
+        jwks.refresh().await;
+    }
+}
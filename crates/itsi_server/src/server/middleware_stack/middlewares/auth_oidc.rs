@@ -0,0 +1,558 @@
+mod provider;
+mod session;
+
+use super::{
+    auth_jwt::{forward_claims, parse_claim_headers},
+    error_response::ErrorResponse,
+    FromValue, MiddlewareLayer,
+};
+use crate::{
+    server::http_message_types::{HttpBody, HttpRequest, HttpResponse, RequestExt},
+    services::itsi_http_service::HttpRequestContext,
+};
+
+use async_trait::async_trait;
+use derive_more::Debug;
+use either::Either;
+use http::{
+    header::{LOCATION, SET_COOKIE},
+    HeaderName, HeaderValue, Method, Response, StatusCode,
+};
+use itsi_error::ItsiError;
+use magnus::error::Result;
+use parking_lot::Mutex;
+use provider::Provider;
+use reqwest::Client;
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use session::{
+    code_challenge, now, random_token, request_cookie, set_cookie, CookieSealer, LoginFlow, Session,
+};
+use std::{
+    collections::HashMap,
+    sync::{Arc, OnceLock},
+    time::{Duration, Instant},
+};
+use tokio::sync::OnceCell;
+use tracing::{debug, warn};
+
+/// How long a user has to complete a login at the identity provider.
+const LOGIN_FLOW_LIFETIME: u64 = 600;
+/// Sessions are used for this long if the identity provider doesn't say when tokens expire.
+const DEFAULT_SESSION_LIFETIME: u64 = 3600;
+/// Browsers may drop cookies larger than this.
+const MAX_COOKIE_SIZE: usize = 4096;
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+/// Requests that still carry a session's old cookie reuse its refreshed session for this long,
+/// rather than presenting a refresh token the identity provider may have rotated.
+const REFRESH_GRACE_PERIOD: Duration = Duration::from_secs(30);
+
+type SessionRefresh = Arc<OnceCell<Option<Session>>>;
+
+#[derive(Debug, Deserialize)]
+pub struct AuthOidc {
+    /// The identity provider's issuer URL. Its endpoints are discovered from `.well-known/openid-configuration`.
+    pub issuer: String,
+    pub client_id: String,
+    #[debug(skip)]
+    pub client_secret: Option<String>,
+    #[serde(default = "default_scopes")]
+    pub scopes: Vec<String>,
+    /// The path the identity provider redirects back to.
+    #[serde(default = "default_callback_path")]
+    pub callback_path: String,
+    /// Overrides the redirect URI otherwise built from the request's scheme and host, and `callback_path`.
+    pub redirect_uri: Option<String>,
+    #[serde(default = "default_logout_path")]
+    pub logout_path: String,
+    /// Where to send users after they log out.
+    #[serde(default = "default_logout_redirect")]
+    pub logout_redirect: String,
+    #[serde(default = "default_cookie_name")]
+    pub cookie_name: String,
+    #[debug(skip)]
+    pub cookie_secret: String,
+    #[serde(default = "default_cookie_secure")]
+    pub cookie_secure: bool,
+    pub leeway: Option<u64>,
+    /// Claims (or dotted paths into nested claims) to forward to the app, and the request header to forward each in.
+    #[serde(default)]
+    pub claims_to_headers: HashMap<String, String>,
+    #[serde(skip_deserializing)]
+    pub claim_headers: OnceLock<Vec<(String, HeaderName)>>,
+    #[serde(default = "unauthorized_error_response")]
+    pub error_response: ErrorResponse,
+    #[serde(skip_deserializing)]
+    #[debug(skip)]
+    pub sealer: OnceLock<CookieSealer>,
+    #[serde(skip_deserializing)]
+    #[debug(skip)]
+    pub client: OnceLock<Client>,
+    /// Discovered on first use, so an identity provider outage doesn't prevent startup.
+    #[serde(skip_deserializing)]
+    pub provider: OnceCell<Arc<Provider>>,
+    /// Refreshes in progress or recently completed in this worker, by refresh token.
+    #[serde(skip_deserializing)]
+    #[debug(skip)]
+    pub refreshes: Mutex<HashMap<String, (Instant, SessionRefresh)>>,
+}
+
+fn default_scopes() -> Vec<String> {
+    vec![
+        "openid".to_owned(),
+        "profile".to_owned(),
+        "email".to_owned(),
+    ]
+}
+
+fn default_callback_path() -> String {
+    "/auth/callback".to_owned()
+}
+
+fn default_logout_path() -> String {
+    "/auth/logout".to_owned()
+}
+
+fn default_logout_redirect() -> String {
+    "/".to_owned()
+}
+
+fn default_cookie_name() -> String {
+    "itsi_session".to_owned()
+}
+
+fn default_cookie_secure() -> bool {
+    true
+}
+
+fn unauthorized_error_response() -> ErrorResponse {
+    ErrorResponse::unauthorized()
+}
+
+#[async_trait]
+impl MiddlewareLayer for AuthOidc {
+    async fn initialize(&self) -> Result<()> {
+        debug!(
+            target: "middleware::auth_oidc",
+            "Instantiating auth_oidc for issuer {}, client_id {}", self.issuer, self.client_id
+        );
+        self.claim_headers
+            .set(parse_claim_headers(&self.claims_to_headers)?)
+            .ok();
+        self.sealer.set(CookieSealer::new(&self.cookie_secret)).ok();
+        let client = Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .build()
+            .map_err(|e| ItsiError::new(format!("Failed to build OIDC client: {}", e)))?;
+        self.client.set(client).ok();
+        Ok(())
+    }
+
+    async fn before(
+        &self,
+        req: HttpRequest,
+        context: &mut HttpRequestContext,
+    ) -> Result<Either<HttpRequest, HttpResponse>> {
+        let path = req.uri().path();
+        if path == self.callback_path {
+            return Ok(Either::Right(self.callback(&req, context).await));
+        }
+        if path == self.logout_path {
+            return Ok(Either::Right(self.logout(&req, context).await));
+        }
+
+        let sealer = self.sealer.get().unwrap();
+        let session = request_cookie(&req, &self.cookie_name)
+            .and_then(|cookie| sealer.open::<Session>(&self.cookie_name, cookie));
+
+        let session = match session {
+            Some(session) if session.expires_at > now() => Some(session),
+            Some(session) => self.refresh(session, context).await,
+            None => None,
+        };
+
+        match session {
+            Some(session) => {
+                let mut req = req;
+                forward_claims(&mut req, self.claim_headers.get().unwrap(), &session.claims);
+                context.set_jwt_claims(session.claims);
+                context.set_authenticated();
+                Ok(Either::Left(req))
+            }
+            None => Ok(Either::Right(self.login(&req, context).await)),
+        }
+    }
+
+    async fn after(
+        &self,
+        mut resp: HttpResponse,
+        context: &mut HttpRequestContext,
+    ) -> HttpResponse {
+        if let Some(cookie) = context.session_cookie() {
+            resp.headers_mut().append(SET_COOKIE, cookie.clone());
+        }
+        resp
+    }
+}
+
+impl AuthOidc {
+    async fn provider(&self) -> Option<&Arc<Provider>> {
+        self.provider
+            .get_or_try_init(|| async {
+                Provider::discover(&self.issuer, self.client.get().unwrap().clone())
+                    .await
+                    .map(Arc::new)
+            })
+            .await
+            .inspect_err(|e| warn!("{}", e))
+            .ok()
+    }
+
+    fn redirect_uri(&self, req: &HttpRequest, context: &HttpRequestContext) -> String {
+        self.redirect_uri
+            .clone()
+            .unwrap_or_else(|| self.absolute_url(req, context, &self.callback_path))
+    }
+
+    fn absolute_url(&self, req: &HttpRequest, context: &HttpRequestContext, path: &str) -> String {
+        if path.contains("://") {
+            return path.to_owned();
+        }
+        let scheme = req
+            .uri()
+            .scheme_str()
+            .unwrap_or(&context.listener_info.scheme);
+        let host = req
+            .uri()
+            .authority()
+            .map(|authority| authority.as_str())
+            .or_else(|| req.header("Host"))
+            .unwrap_or(&context.listener_info.host);
+        format!("{}://{}{}", scheme, host, path)
+    }
+
+    /// Sends browsers to the identity provider to log in. Other clients get the error response.
+    async fn login(&self, req: &HttpRequest, context: &HttpRequestContext) -> HttpResponse {
+        let is_navigation = (req.method() == Method::GET || req.method() == Method::HEAD)
+            && req
+                .accept()
+                .is_some_and(|accept| accept.contains("text/html"));
+        if !is_navigation {
+            return self.unauthorized(req).await;
+        }
+        let Some(provider) = self.provider().await else {
+            return ErrorResponse::bad_gateway()
+                .to_http_response(req.accept().into())
+                .await;
+        };
+
+        let flow = LoginFlow {
+            state: random_token(),
+            nonce: random_token(),
+            code_verifier: random_token(),
+            return_to: req
+                .uri()
+                .path_and_query()
+                .map(|path_and_query| path_and_query.to_string())
+                .unwrap_or_else(|| "/".to_owned()),
+            expires_at: now() + LOGIN_FLOW_LIFETIME,
+        };
+        let flow_cookie_name = self.flow_cookie_name();
+        let Some(flow_cookie) = self.sealer.get().unwrap().seal(&flow_cookie_name, &flow) else {
+            return ErrorResponse::internal_server_error()
+                .to_http_response(req.accept().into())
+                .await;
+        };
+
+        let location = url::Url::parse_with_params(
+            &provider.metadata.authorization_endpoint,
+            &[
+                ("response_type", "code"),
+                ("client_id", self.client_id.as_str()),
+                ("redirect_uri", self.redirect_uri(req, context).as_str()),
+                ("scope", self.scopes.join(" ").as_str()),
+                ("state", flow.state.as_str()),
+                ("nonce", flow.nonce.as_str()),
+                (
+                    "code_challenge",
+                    code_challenge(&flow.code_verifier).as_str(),
+                ),
+                ("code_challenge_method", "S256"),
+            ],
+        );
+        let Ok(location) = location else {
+            return ErrorResponse::bad_gateway()
+                .to_http_response(req.accept().into())
+                .await;
+        };
+
+        debug!(target: "middleware::auth_oidc", "Redirecting to {} to log in", location);
+        redirect(
+            location.as_str(),
+            &[set_cookie(
+                &flow_cookie_name,
+                &flow_cookie,
+                Some(LOGIN_FLOW_LIFETIME),
+                self.cookie_secure,
+            )],
+        )
+    }
+
+    /// Completes a login: exchanges the authorization code for tokens,
+    /// verifies the ID token and starts a session.
+    async fn callback(&self, req: &HttpRequest, context: &HttpRequestContext) -> HttpResponse {
+        let params: HashMap<String, String> = req
+            .uri()
+            .query()
+            .map(|query| {
+                url::form_urlencoded::parse(query.as_bytes())
+                    .into_owned()
+                    .collect()
+            })
+            .unwrap_or_default();
+        let flow_cookie_name = self.flow_cookie_name();
+        let flow = request_cookie(req, &flow_cookie_name).and_then(|cookie| {
+            self.sealer
+                .get()
+                .unwrap()
+                .open::<LoginFlow>(&flow_cookie_name, cookie)
+        });
+
+        let (Some(flow), Some(code), Some(state)) = (flow, params.get("code"), params.get("state"))
+        else {
+            debug!(
+                target: "middleware::auth_oidc",
+                "Login failed: {}", params.get("error").map(String::as_str).unwrap_or("missing code, state or login flow")
+            );
+            return self.unauthorized(req).await;
+        };
+        if flow.state != *state || flow.expires_at < now() {
+            debug!(target: "middleware::auth_oidc", "Login failed: state mismatch or expired flow");
+            return self.unauthorized(req).await;
+        }
+        let Some(provider) = self.provider().await else {
+            return ErrorResponse::bad_gateway()
+                .to_http_response(req.accept().into())
+                .await;
+        };
+
+        let redirect_uri = self.redirect_uri(req, context);
+        let session: itsi_error::Result<Session> = async {
+            let tokens = provider
+                .token_request(
+                    &[
+                        ("grant_type", "authorization_code"),
+                        ("code", code.as_str()),
+                        ("redirect_uri", redirect_uri.as_str()),
+                        ("code_verifier", flow.code_verifier.as_str()),
+                    ],
+                    &self.client_id,
+                    self.client_secret.as_deref(),
+                )
+                .await?;
+            let id_token = tokens
+                .id_token
+                .as_deref()
+                .ok_or_else(|| ItsiError::new("Token response has no ID token"))?;
+            let claims = provider
+                .verify_id_token(
+                    id_token,
+                    &self.client_id,
+                    self.client_secret.as_deref(),
+                    self.leeway.unwrap_or_default(),
+                )
+                .await?;
+            if claims.get("nonce").and_then(Value::as_str) != Some(flow.nonce.as_str()) {
+                return Err(ItsiError::new("ID token nonce mismatch"));
+            }
+            Ok(new_session(claims, tokens.expires_in, tokens.refresh_token))
+        }
+        .await;
+
+        let session = match session {
+            Ok(session) => session,
+            Err(e) => {
+                debug!(target: "middleware::auth_oidc", "Login failed: {}", e);
+                return self.unauthorized(req).await;
+            }
+        };
+        let Some(session_cookie) = self.session_cookie(&session) else {
+            return ErrorResponse::internal_server_error()
+                .to_http_response(req.accept().into())
+                .await;
+        };
+
+        // Only ever return to a local path, so the flow can't be used as an open redirect.
+        let return_to = if flow.return_to.starts_with('/') && !flow.return_to.starts_with("//") {
+            flow.return_to.as_str()
+        } else {
+            "/"
+        };
+        redirect(
+            return_to,
+            &[
+                session_cookie,
+                set_cookie(&flow_cookie_name, "", Some(0), self.cookie_secure),
+            ],
+        )
+    }
+
+    /// Ends the session, and the identity provider's session if it supports RP-initiated logout.
+    async fn logout(&self, req: &HttpRequest, context: &HttpRequestContext) -> HttpResponse {
+        let clear_cookie = set_cookie(&self.cookie_name, "", Some(0), self.cookie_secure);
+        let provider_logout =
+            match self.provider().await {
+                Some(provider) => provider.metadata.end_session_endpoint.as_ref().and_then(
+                    |end_session_endpoint| {
+                        url::Url::parse_with_params(
+                            end_session_endpoint,
+                            &[
+                                ("client_id", self.client_id.as_str()),
+                                (
+                                    "post_logout_redirect_uri",
+                                    self.absolute_url(req, context, &self.logout_redirect)
+                                        .as_str(),
+                                ),
+                            ],
+                        )
+                        .ok()
+                    },
+                ),
+                None => None,
+            };
+        match provider_logout {
+            Some(location) => redirect(location.as_str(), &[clear_cookie]),
+            None => redirect(&self.logout_redirect, &[clear_cookie]),
+        }
+    }
+
+    /// Refreshes an expired session, and sets the new session cookie on the response.
+    /// Concurrent requests with the same session share a single refresh.
+    async fn refresh(&self, session: Session, context: &HttpRequestContext) -> Option<Session> {
+        let refresh_token = session.refresh_token.clone()?;
+        let refresh = {
+            let mut refreshes = self.refreshes.lock();
+            refreshes.retain(|_, (started_at, _)| started_at.elapsed() < REFRESH_GRACE_PERIOD);
+            refreshes
+                .entry(refresh_token.clone())
+                .or_insert_with(|| (Instant::now(), Arc::new(OnceCell::new())))
+                .1
+                .clone()
+        };
+        let session = refresh
+            .get_or_init(|| self.exchange_refresh_token(session, refresh_token))
+            .await
+            .clone()?;
+        context.set_session_cookie(self.session_cookie(&session)?);
+        Some(session)
+    }
+
+    /// Exchanges an expired session's refresh token for new tokens.
+    async fn exchange_refresh_token(
+        &self,
+        session: Session,
+        refresh_token: String,
+    ) -> Option<Session> {
+        let provider = self.provider().await?;
+        let tokens = provider
+            .token_request(
+                &[
+                    ("grant_type", "refresh_token"),
+                    ("refresh_token", refresh_token.as_str()),
+                ],
+                &self.client_id,
+                self.client_secret.as_deref(),
+            )
+            .await
+            .inspect_err(|e| debug!(target: "middleware::auth_oidc", "Refresh failed: {}", e))
+            .ok()?;
+
+        // Providers may or may not issue a new ID token (or refresh token) on refresh.
+        let claims = match tokens.id_token.as_deref() {
+            Some(id_token) => {
+                let claims = provider
+                    .verify_id_token(
+                        id_token,
+                        &self.client_id,
+                        self.client_secret.as_deref(),
+                        self.leeway.unwrap_or_default(),
+                    )
+                    .await
+                    .inspect_err(
+                        |e| debug!(target: "middleware::auth_oidc", "Refresh failed: {}", e),
+                    )
+                    .ok()?;
+                if claims.get("sub") != session.claims.get("sub") {
+                    warn!("auth_oidc refresh returned an ID token for a different subject");
+                    return None;
+                }
+                claims
+            }
+            None => session.claims,
+        };
+        Some(new_session(
+            claims,
+            tokens.expires_in,
+            tokens.refresh_token.or(Some(refresh_token)),
+        ))
+    }
+
+    fn session_cookie(&self, session: &Session) -> Option<HeaderValue> {
+        let cookie = self
+            .sealer
+            .get()
+            .unwrap()
+            .seal(&self.cookie_name, session)?;
+        if cookie.len() > MAX_COOKIE_SIZE {
+            warn!(
+                "auth_oidc session cookie is {} bytes, and may be rejected by browsers. Request fewer scopes to reduce it",
+                cookie.len()
+            );
+        }
+        Some(set_cookie(
+            &self.cookie_name,
+            &cookie,
+            None,
+            self.cookie_secure,
+        ))
+    }
+
+    fn flow_cookie_name(&self) -> String {
+        format!("{}_flow", self.cookie_name)
+    }
+
+    async fn unauthorized(&self, req: &HttpRequest) -> HttpResponse {
+        self.error_response
+            .to_http_response(req.accept().into())
+            .await
+    }
+}
+
+/// Sessions last until the tokens expire: per `expires_in`, or else the ID token's `exp`.
+fn new_session(
+    claims: Map<String, Value>,
+    expires_in: Option<u64>,
+    refresh_token: Option<String>,
+) -> Session {
+    let expires_at = expires_in
+        .map(|expires_in| now() + expires_in)
+        .or_else(|| claims.get("exp").and_then(Value::as_u64))
+        .unwrap_or_else(|| now() + DEFAULT_SESSION_LIFETIME);
+    Session {
+        claims,
+        expires_at,
+        refresh_token,
+    }
+}
+
+fn redirect(location: &str, cookies: &[HeaderValue]) -> HttpResponse {
+    let mut response = Response::builder()
+        .status(StatusCode::FOUND)
+        .header(LOCATION, location);
+    for cookie in cookies {
+        response = response.header(SET_COOKIE, cookie.clone());
+    }
+    response.body(HttpBody::empty()).unwrap()
+}
+
+impl FromValue for AuthOidc {}
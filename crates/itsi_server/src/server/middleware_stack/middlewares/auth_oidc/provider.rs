@@ -0,0 +1,135 @@
+use std::time::Duration;
+
+use super::super::auth_jwt::jwks::Jwks;
+use derive_more::Debug;
+use itsi_error::{ItsiError, Result};
+use jsonwebtoken::{decode, decode_header, Algorithm as JwtAlg, DecodingKey, Validation};
+use reqwest::{header::CONTENT_TYPE, Client};
+use serde::{de::DeserializeOwned, Deserialize};
+use serde_json::{Map, Value};
+use tracing::debug;
+
+/// The parts of the issuer's `.well-known/openid-configuration` we use.
+#[derive(Debug, Deserialize)]
+pub struct ProviderMetadata {
+    pub issuer: String,
+    pub authorization_endpoint: String,
+    pub token_endpoint: String,
+    pub jwks_uri: String,
+    pub end_session_endpoint: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TokenResponse {
+    pub id_token: Option<String>,
+    pub refresh_token: Option<String>,
+    pub expires_in: Option<u64>,
+}
+
+/// An OpenID provider, discovered from its issuer URL.
+#[derive(Debug)]
+pub struct Provider {
+    pub metadata: ProviderMetadata,
+    jwks: Jwks,
+    #[debug(skip)]
+    client: Client,
+}
+
+impl Provider {
+    pub async fn discover(issuer: &str, client: Client) -> Result<Self> {
+        let url = format!(
+            "{}/.well-known/openid-configuration",
+            issuer.trim_end_matches('/')
+        );
+        let metadata: ProviderMetadata = fetch_json(client.get(&url))
+            .await
+            .map_err(|e| ItsiError::new(format!("OIDC discovery from {} failed: {}", url, e)))?;
+        if metadata.issuer.trim_end_matches('/') != issuer.trim_end_matches('/') {
+            return Err(ItsiError::new(format!(
+                "OIDC discovery from {} returned issuer {}",
+                url, metadata.issuer
+            )));
+        }
+        debug!(target: "middleware::auth_oidc", "Discovered OIDC provider {:?}", metadata);
+
+        let jwks = Jwks::new(metadata.jwks_uri.clone())?;
+        // A failed fetch is retried when we first see a token's `kid`.
+        jwks.refresh(Duration::ZERO).await;
+        Ok(Self {
+            metadata,
+            jwks,
+            client,
+        })
+    }
+
+    /// Calls the token endpoint, authenticating with `client_secret_basic` if we have a secret.
+    pub async fn token_request(
+        &self,
+        params: &[(&str, &str)],
+        client_id: &str,
+        client_secret: Option<&str>,
+    ) -> Result<TokenResponse> {
+        let body = url::form_urlencoded::Serializer::new(String::new())
+            .extend_pairs(params)
+            .append_pair("client_id", client_id)
+            .finish();
+        let mut request = self
+            .client
+            .post(&self.metadata.token_endpoint)
+            .header(CONTENT_TYPE, "application/x-www-form-urlencoded")
+            .body(body);
+        if let Some(client_secret) = client_secret {
+            request = request.basic_auth(client_id, Some(client_secret));
+        }
+        fetch_json(request).await
+    }
+
+    /// Verifies an ID token's signature, issuer, audience and expiry, returning its claims.
+    pub async fn verify_id_token(
+        &self,
+        id_token: &str,
+        client_id: &str,
+        client_secret: Option<&str>,
+        leeway: u64,
+    ) -> Result<Map<String, Value>> {
+        let header = decode_header(id_token)
+            .map_err(|e| ItsiError::new(format!("Invalid ID token: {}", e)))?;
+
+        let keys = match header.alg {
+            // HMAC-signed ID tokens use the client secret as the key.
+            JwtAlg::HS256 | JwtAlg::HS384 | JwtAlg::HS512 => client_secret
+                .map(|secret| vec![DecodingKey::from_secret(secret.as_bytes())])
+                .unwrap_or_default(),
+            alg => self.jwks.keys_for(header.kid.as_deref(), alg).await,
+        };
+
+        let mut validation = Validation::new(header.alg);
+        validation.set_issuer(&[&self.metadata.issuer]);
+        validation.set_audience(&[client_id]);
+        validation.leeway = leeway;
+
+        keys.iter()
+            .find_map(|key| {
+                decode::<Map<String, Value>>(id_token, key, &validation)
+                    .inspect_err(|e| {
+                        debug!(target: "middleware::auth_oidc", "ID token validation failed: {:?}", e)
+                    })
+                    .ok()
+            })
+            .map(|token_data| token_data.claims)
+            .ok_or_else(|| ItsiError::new("ID token could not be verified"))
+    }
+}
+
+async fn fetch_json<T: DeserializeOwned>(request: reqwest::RequestBuilder) -> Result<T> {
+    let response = request
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| ItsiError::new(e.to_string()))?;
+    let body = response
+        .bytes()
+        .await
+        .map_err(|e| ItsiError::new(e.to_string()))?;
+    serde_json::from_slice(&body).map_err(|e| ItsiError::new(format!("Invalid response: {}", e)))
+}
@@ -0,0 +1,135 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use http::{header::COOKIE, HeaderValue};
+use ring::{
+    aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM, NONCE_LEN},
+    rand::{SecureRandom, SystemRandom},
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::{Map, Value};
+use sha2::{Digest, Sha256};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::server::http_message_types::HttpRequest;
+
+/// A logged in user, stored (encrypted) in the session cookie.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Session {
+    pub claims: Map<String, Value>,
+    /// Unix time at which the session must be refreshed, or the user must log in again.
+    pub expires_at: u64,
+    pub refresh_token: Option<String>,
+}
+
+/// An authorization-code flow in progress, stored (encrypted) in the flow cookie
+/// between redirecting to the identity provider and handling its callback.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LoginFlow {
+    pub state: String,
+    pub nonce: String,
+    pub code_verifier: String,
+    /// Where to send the user once they've logged in.
+    pub return_to: String,
+    pub expires_at: u64,
+}
+
+/// Encrypts and authenticates cookie values with AES-256-GCM,
+/// so clients can neither read nor forge them.
+pub struct CookieSealer {
+    key: LessSafeKey,
+    random: SystemRandom,
+}
+
+impl CookieSealer {
+    pub fn new(secret: &str) -> Self {
+        let key = Sha256::new()
+            .chain_update(b"itsi oidc cookie key")
+            .chain_update(secret.as_bytes())
+            .finalize();
+        let key =
+            UnboundKey::new(&AES_256_GCM, &key).expect("SHA-256 output is a valid AES-256 key");
+        Self {
+            key: LessSafeKey::new(key),
+            random: SystemRandom::new(),
+        }
+    }
+
+    /// Cookie values are `base64url(nonce || AES-256-GCM(json))`.
+    /// The cookie name is authenticated too, so one cookie can't be swapped for another.
+    pub fn seal<T: Serialize>(&self, cookie_name: &str, value: &T) -> Option<String> {
+        let mut nonce = [0u8; NONCE_LEN];
+        self.random.fill(&mut nonce).ok()?;
+
+        let mut sealed = serde_json::to_vec(value).ok()?;
+        self.key
+            .seal_in_place_append_tag(
+                Nonce::assume_unique_for_key(nonce),
+                Aad::from(cookie_name.as_bytes()),
+                &mut sealed,
+            )
+            .ok()?;
+
+        let mut cookie = Vec::with_capacity(NONCE_LEN + sealed.len());
+        cookie.extend_from_slice(&nonce);
+        cookie.extend_from_slice(&sealed);
+        Some(URL_SAFE_NO_PAD.encode(cookie))
+    }
+
+    pub fn open<T: DeserializeOwned>(&self, cookie_name: &str, cookie: &str) -> Option<T> {
+        let mut nonce = URL_SAFE_NO_PAD.decode(cookie).ok()?;
+        if nonce.len() < NONCE_LEN {
+            return None;
+        }
+        let mut sealed = nonce.split_off(NONCE_LEN);
+        let plain = self
+            .key
+            .open_in_place(
+                Nonce::try_assume_unique_for_key(&nonce).ok()?,
+                Aad::from(cookie_name.as_bytes()),
+                &mut sealed,
+            )
+            .ok()?;
+        serde_json::from_slice(plain).ok()
+    }
+}
+
+/// A random, URL-safe value for `state`, `nonce` and PKCE code verifiers.
+pub fn random_token() -> String {
+    URL_SAFE_NO_PAD.encode(rand::random::<[u8; 32]>())
+}
+
+/// The S256 PKCE challenge for a code verifier.
+pub fn code_challenge(code_verifier: &str) -> String {
+    URL_SAFE_NO_PAD.encode(Sha256::digest(code_verifier.as_bytes()))
+}
+
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or_default()
+}
+
+pub fn request_cookie<'a>(req: &'a HttpRequest, name: &str) -> Option<&'a str> {
+    req.headers()
+        .get_all(COOKIE)
+        .iter()
+        .filter_map(|header| header.to_str().ok())
+        .flat_map(|header| header.split(';'))
+        .filter_map(|cookie| cookie.trim().split_once('='))
+        .find(|(cookie_name, _)| *cookie_name == name)
+        .map(|(_, value)| value)
+}
+
+/// A `Set-Cookie` header. Cookies are `SameSite=Lax`, so they're sent along with
+/// the identity provider's redirect back to the callback.
+/// A `max_age` of `Some(0)` clears the cookie.
+pub fn set_cookie(name: &str, value: &str, max_age: Option<u64>, secure: bool) -> HeaderValue {
+    let mut cookie = format!("{}={}; Path=/; HttpOnly; SameSite=Lax", name, value);
+    if secure {
+        cookie.push_str("; Secure");
+    }
+    if let Some(max_age) = max_age {
+        cookie.push_str(&format!("; Max-Age={}", max_age));
+    }
+    HeaderValue::from_str(&cookie).expect("cookie names and sealed values are valid header values")
+}
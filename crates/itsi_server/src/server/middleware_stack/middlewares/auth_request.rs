@@ -0,0 +1,260 @@
+use super::{error_response::ErrorResponse, FromValue, MiddlewareLayer};
+use crate::{
+    server::{
+        http_message_types::{HttpBody, HttpRequest, HttpResponse, RequestExt},
+        middleware_stack::Middleware,
+    },
+    services::itsi_http_service::HttpRequestContext,
+};
+
+use async_trait::async_trait;
+use derive_more::Debug;
+use either::Either;
+use http::{
+    header::{CONNECTION, HOST, TRANSFER_ENCODING},
+    HeaderMap, HeaderName, HeaderValue, Method, Request, Response, StatusCode,
+};
+use itsi_error::ItsiError;
+use magnus::error::Result;
+use reqwest::{redirect::Policy, Client};
+use serde::Deserialize;
+use std::{sync::OnceLock, time::Duration};
+use tracing::{debug, warn};
+
+/// Asks an auth service whether to let each request through (like nginx's `auth_request`).
+#[derive(Debug, Deserialize)]
+pub struct AuthRequest {
+    /// An HTTP(S) URL, or the path of a route served by the Ruby app.
+    pub endpoint: String,
+    /// Headers copied from the original request onto the subrequest.
+    pub request_headers: Vec<String>,
+    /// Headers copied from a successful auth response onto the original request.
+    #[serde(default)]
+    pub auth_response_headers: Vec<String>,
+    /// Seconds to wait for the auth service to answer.
+    #[serde(default = "default_timeout")]
+    pub timeout: u64,
+    #[serde(default = "bad_gateway_error_response")]
+    pub error_response: ErrorResponse,
+    #[serde(skip_deserializing)]
+    pub request_header_names: OnceLock<Vec<HeaderName>>,
+    #[serde(skip_deserializing)]
+    pub auth_response_header_names: OnceLock<Vec<HeaderName>>,
+    #[serde(skip_deserializing)]
+    #[debug(skip)]
+    pub client: OnceLock<Client>,
+}
+
+fn default_timeout() -> u64 {
+    5
+}
+
+fn bad_gateway_error_response() -> ErrorResponse {
+    ErrorResponse::bad_gateway()
+}
+
+fn parse_header_names(names: &[String]) -> itsi_error::Result<Vec<HeaderName>> {
+    names
+        .iter()
+        .map(|name| {
+            name.parse::<HeaderName>()
+                .map_err(|_| ItsiError::new(format!("Invalid header name {}", name)))
+        })
+        .collect()
+}
+
+#[async_trait]
+impl MiddlewareLayer for AuthRequest {
+    async fn initialize(&self) -> Result<()> {
+        self.request_header_names
+            .set(parse_header_names(&self.request_headers)?)
+            .ok();
+        self.auth_response_header_names
+            .set(parse_header_names(&self.auth_response_headers)?)
+            .ok();
+        // Redirects are for the client (e.g. to a login page), so we relay rather than follow them.
+        let client = Client::builder()
+            .redirect(Policy::none())
+            .build()
+            .map_err(|e| ItsiError::new(format!("Failed to build auth_request client: {}", e)))?;
+        self.client.set(client).ok();
+        Ok(())
+    }
+
+    async fn before(
+        &self,
+        mut req: HttpRequest,
+        context: &mut HttpRequestContext,
+    ) -> Result<Either<HttpRequest, HttpResponse>> {
+        let headers = self.subrequest_headers(&req, context);
+        let response = tokio::time::timeout(Duration::from_secs(self.timeout), async {
+            if self.endpoint.starts_with('/') {
+                self.call_local(headers, context).await
+            } else {
+                self.call_remote(headers).await
+            }
+        })
+        .await
+        .unwrap_or_else(|_| Err(ItsiError::new("Timed out")));
+
+        let response = match response {
+            Ok(response) => response,
+            Err(e) => {
+                warn!("auth_request to {} failed: {}", self.endpoint, e);
+                return Ok(Either::Right(
+                    self.error_response
+                        .to_http_response(req.accept().into())
+                        .await,
+                ));
+            }
+        };
+
+        let status = response.status();
+        debug!(target: "middleware::auth_request", "{} answered {}", self.endpoint, status);
+        if status.is_success() {
+            let headers = req.headers_mut();
+            for name in self.auth_response_header_names.get().unwrap() {
+                // Only the auth service may set these.
+                headers.remove(name);
+                for value in response.headers().get_all(name) {
+                    headers.append(name.clone(), value.clone());
+                }
+            }
+            context.set_authenticated();
+            Ok(Either::Left(req))
+        } else if status == StatusCode::UNAUTHORIZED
+            || status == StatusCode::FORBIDDEN
+            || status.is_redirection()
+        {
+            Ok(Either::Right(response))
+        } else {
+            warn!(
+                "auth_request to {} answered unexpected status {}",
+                self.endpoint, status
+            );
+            Ok(Either::Right(
+                self.error_response
+                    .to_http_response(req.accept().into())
+                    .await,
+            ))
+        }
+    }
+}
+
+impl AuthRequest {
+    /// The selected request headers, and `X-Forwarded-*` headers describing the original request.
+    fn subrequest_headers(&self, req: &HttpRequest, context: &HttpRequestContext) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for name in self.request_header_names.get().unwrap() {
+            for value in req.headers().get_all(name) {
+                headers.append(name.clone(), value.clone());
+            }
+        }
+
+        let host = req
+            .uri()
+            .authority()
+            .map(|authority| authority.as_str())
+            .or_else(|| req.header("Host"));
+        let forwarded = [
+            ("X-Forwarded-Method", Some(req.method().as_str())),
+            (
+                "X-Forwarded-Proto",
+                Some(
+                    req.uri()
+                        .scheme_str()
+                        .unwrap_or(&context.listener_info.scheme),
+                ),
+            ),
+            ("X-Forwarded-Host", host),
+            (
+                "X-Forwarded-Uri",
+                req.uri()
+                    .path_and_query()
+                    .map(|path_and_query| path_and_query.as_str()),
+            ),
+            ("X-Forwarded-For", Some(context.addr.as_str())),
+        ];
+        for (name, value) in forwarded {
+            if let Some(value) = value.and_then(|value| HeaderValue::from_str(value).ok()) {
+                headers.insert(name, value);
+            }
+        }
+
+        // Routes on the local app may be matched by host.
+        if self.endpoint.starts_with('/') && !headers.contains_key(HOST) {
+            if let Some(host) = req.headers().get(HOST) {
+                headers.insert(HOST, host.clone());
+            }
+        }
+        headers
+    }
+
+    /// Calls the Ruby app serving the endpoint's route directly, skipping that route's other middleware.
+    /// The subrequest gets its own request context, so it neither sees nor changes the parent request's state
+    /// (claims, cache state, session cookies). It shares the parent's Ruby flag, so request timeouts still apply.
+    async fn call_local(
+        &self,
+        headers: HeaderMap,
+        context: &HttpRequestContext,
+    ) -> itsi_error::Result<HttpResponse> {
+        let mut subrequest = Request::builder()
+            .method(Method::GET)
+            .uri(&self.endpoint)
+            .body(())
+            .map_err(|e| ItsiError::new(format!("Invalid endpoint: {}", e)))?;
+        *subrequest.headers_mut() = headers;
+
+        let (stack, matching_pattern) = context
+            .server_params
+            .middleware
+            .get()
+            .unwrap()
+            .stack_for(&subrequest)
+            .map_err(|e| ItsiError::new(e.to_string()))?;
+        let app = stack
+            .iter()
+            .find_map(|middleware| match middleware {
+                Middleware::RubyApp(app) => Some(app.clone()),
+                _ => None,
+            })
+            .ok_or_else(|| ItsiError::new("No Ruby app serves this route"))?;
+
+        let subrequest_context = HttpRequestContext::new(
+            context.service.clone(),
+            matching_pattern,
+            context.accept,
+            context.is_ruby_request.clone(),
+        );
+        let (parts, _) = subrequest.into_parts();
+        app.call_without_body(parts, &subrequest_context).await
+    }
+
+    async fn call_remote(&self, headers: HeaderMap) -> itsi_error::Result<HttpResponse> {
+        let response = self
+            .client
+            .get()
+            .unwrap()
+            .get(&self.endpoint)
+            .headers(headers)
+            .send()
+            .await
+            .map_err(|e| ItsiError::new(e.to_string()))?;
+
+        let mut builder = Response::builder().status(response.status());
+        for (name, value) in response.headers() {
+            if name != CONNECTION && name != TRANSFER_ENCODING {
+                builder = builder.header(name, value);
+            }
+        }
+        let body = response
+            .bytes()
+            .await
+            .map_err(|e| ItsiError::new(e.to_string()))?;
+        builder
+            .body(HttpBody::full(body))
+            .map_err(|e| ItsiError::new(e.to_string()))
+    }
+}
+
+impl FromValue for AuthRequest {}
@@ -0,0 +1,136 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::{Arc, Weak},
+    time::{Duration, SystemTime},
+};
+
+use itsi_error::{ItsiError, Result};
+use parking_lot::RwLock;
+use tracing::{info, warn};
+
+use crate::services::password_hasher::is_supported_hash;
+
+/// How often we check whether the file has changed.
+const CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Credentials loaded from a file of `id:hash` lines (the format of Apache `htpasswd` files),
+/// reloaded when the file changes, so credentials can be rotated without a restart.
+///
+/// We compare the file's modification time and size rather than using a file watcher,
+/// so this works across forked workers, and with secret mounts that atomically swap a symlink.
+/// Checks run in a background task, so requests never wait on the filesystem.
+#[derive(Debug)]
+pub struct CredentialsFile {
+    path: PathBuf,
+    loaded: RwLock<Loaded>,
+}
+
+#[derive(Debug)]
+struct Loaded {
+    version: Option<(SystemTime, u64)>,
+    credentials: Arc<HashMap<String, String>>,
+}
+
+impl CredentialsFile {
+    /// Loads the file, and starts checking it for changes until the returned value is dropped.
+    /// Must be called from within the Tokio runtime.
+    pub fn load(path: &str) -> Result<Arc<Self>> {
+        let path = PathBuf::from(path);
+        let (version, credentials) = read(&path)?;
+        let credentials_file = Arc::new(Self {
+            path,
+            loaded: RwLock::new(Loaded {
+                version,
+                credentials: Arc::new(credentials),
+            }),
+        });
+        tokio::spawn(reload_periodically(Arc::downgrade(&credentials_file)));
+        Ok(credentials_file)
+    }
+
+    /// The current credentials, mapping IDs (usernames or key IDs) to password hashes.
+    pub fn credentials(&self) -> Arc<HashMap<String, String>> {
+        self.loaded.read().credentials.clone()
+    }
+
+    async fn reload_if_changed(&self) {
+        let path = self.path.clone();
+        let loaded_version = self.loaded.read().version;
+        let reloaded = tokio::task::spawn_blocking(move || {
+            (version(&path) != loaded_version).then(|| read(&path))
+        })
+        .await;
+        // If the file is missing or unreadable (e.g. mid-rotation), we keep the credentials we have.
+        match reloaded.ok().flatten() {
+            None => {}
+            Some(Ok((version, credentials))) => {
+                info!(
+                    "Reloaded {} credentials from {:?}",
+                    credentials.len(),
+                    self.path
+                );
+                *self.loaded.write() = Loaded {
+                    version,
+                    credentials: Arc::new(credentials),
+                };
+            }
+            Some(Err(e)) => warn!("Failed to reload credentials: {}", e),
+        }
+    }
+}
+
+async fn reload_periodically(credentials_file: Weak<CredentialsFile>) {
+    let mut ticker = tokio::time::interval(CHECK_INTERVAL);
+    // The first tick completes immediately, and we've just loaded the file.
+    ticker.tick().await;
+    loop {
+        ticker.tick().await;
+        let Some(credentials_file) = credentials_file.upgrade() else {
+            break;
+        };
+        credentials_file.reload_if_changed().await;
+    }
+}
+
+fn version(path: &Path) -> Option<(SystemTime, u64)> {
+    let metadata = fs::metadata(path).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
+fn read(path: &Path) -> Result<(Option<(SystemTime, u64)>, HashMap<String, String>)> {
+    let version = version(path);
+    let contents = fs::read_to_string(path).map_err(|e| {
+        ItsiError::new(format!("Failed to read credentials file {:?}: {}", path, e))
+    })?;
+    Ok((version, parse(path, &contents)))
+}
+
+/// Blank lines and `#` comments are ignored.
+fn parse(path: &Path, contents: &str) -> HashMap<String, String> {
+    let mut credentials = HashMap::new();
+    for (index, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        match line.split_once(':') {
+            Some((id, hash)) if is_supported_hash(hash) => {
+                credentials.insert(id.to_owned(), hash.to_owned());
+            }
+            Some((id, _)) => warn!(
+                "Skipping {:?} in {:?} (line {}): unsupported hash. Use bcrypt (htpasswd -B), sha256, sha512 or argon2",
+                id,
+                path,
+                index + 1
+            ),
+            None => warn!(
+                "Skipping line {} of {:?}: expected id:hash",
+                index + 1,
+                path
+            ),
+        }
+    }
+    credentials
+}
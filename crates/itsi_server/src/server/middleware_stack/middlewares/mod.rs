@@ -2,9 +2,12 @@ mod allow_list;
 mod auth_api_key;
 mod auth_basic;
 mod auth_jwt;
+mod auth_oidc;
+mod auth_request;
 mod cache_control;
 mod compression;
 mod cors;
+mod credentials_file;
 mod csp;
 mod deny_list;
 mod error_response;
@@ -17,6 +20,7 @@ mod proxy;
 mod rate_limit;
 mod redirect;
 mod request_headers;
+mod response_cache;
 mod response_headers;
 mod ruby_app;
 mod static_assets;
@@ -31,7 +35,9 @@ pub use allow_list::AllowList;
 use async_trait::async_trait;
 pub use auth_api_key::AuthAPIKey;
 pub use auth_basic::AuthBasic;
-pub use auth_jwt::AuthJwt;
+pub use auth_jwt::{claim_at, claim_to_string, AuthJwt};
+pub use auth_oidc::AuthOidc;
+pub use auth_request::AuthRequest;
 pub use cache_control::CacheControl;
 pub use compression::Compression;
 pub use compression::CompressionAlgorithm;
@@ -51,6 +57,7 @@ pub use proxy::Proxy;
 pub use rate_limit::RateLimit;
 pub use redirect::Redirect;
 pub use request_headers::RequestHeaders;
+pub use response_cache::ResponseCache;
 pub use response_headers::ResponseHeaders;
 pub use ruby_app::RubyApp;
 use serde::Deserialize;
@@ -7,9 +7,14 @@ use std::{
         atomic::{AtomicUsize, Ordering},
         Arc, LazyLock, OnceLock,
     },
-    time::Duration,
+    time::{Duration, Instant},
 };
 
+mod health_check;
+mod load_balancer;
+mod retry;
+mod upgrade;
+
 use super::{string_rewrite::StringRewrite, ErrorResponse, FromValue, MiddlewareLayer};
 use crate::{
     server::{
@@ -23,16 +28,22 @@ use async_trait::async_trait;
 use bytes::{Bytes, BytesMut};
 use either::Either;
 use futures::TryStreamExt;
+use health_check::{
+    health_check_client, run_health_checks, Backend, HealthCheck, PassiveHealthCheck,
+};
 use http::{HeaderMap, Method, Response, StatusCode};
 use http_body_util::BodyExt;
+use load_balancer::{ConsistentHashKey, HashRing, InFlightGuard, PinnedResolver};
 use magnus::error::Result;
 use rand::Rng;
 use reqwest::{
     dns::{Name, Resolve},
     Body, Client, Url,
 };
+use retry::RetryPolicy;
 use serde::Deserialize;
 use tracing::debug;
+use upgrade::{is_upgrade_request, splice};
 
 #[derive(Debug, Clone, Deserialize)]
 pub struct Proxy {
@@ -42,9 +53,25 @@ pub struct Proxy {
     pub headers: HashMap<String, Option<StringRewrite>>,
     pub verify_ssl: bool,
     pub timeout: u64,
+    #[serde(default)]
+    pub connect_timeout: Option<u64>,
+    #[serde(default)]
+    pub read_timeout: Option<u64>,
+    #[serde(default)]
+    pub retry: Option<RetryPolicy>,
     pub tls_sni: bool,
+    #[serde(default)]
+    pub health_check: Option<HealthCheck>,
+    #[serde(default)]
+    pub passive_health_check: Option<PassiveHealthCheck>,
     #[serde(skip_deserializing)]
     pub client: OnceLock<Client>,
+    #[serde(skip_deserializing)]
+    pub backend_clients: OnceLock<Vec<Client>>,
+    #[serde(skip_deserializing)]
+    pub upgrade_clients: OnceLock<UpgradeClients>,
+    #[serde(skip_deserializing)]
+    pub resolver: OnceLock<Resolver>,
     #[serde(default = "bad_gateway_error_response")]
     pub error_response: ErrorResponse,
 }
@@ -61,32 +88,109 @@ pub enum BackendPriority {
     Ordered,
     #[serde(rename(deserialize = "random"))]
     Random,
+    #[serde(rename(deserialize = "least_connections"))]
+    LeastConnections,
+    #[serde(rename(deserialize = "consistent_hash"))]
+    ConsistentHash { key: ConsistentHashKey },
+}
+
+impl BackendPriority {
+    /// Whether the backend is chosen for each request, rather than for each new connection.
+    fn is_per_request(&self) -> bool {
+        matches!(
+            self,
+            BackendPriority::LeastConnections | BackendPriority::ConsistentHash { .. }
+        )
+    }
+}
+
+/// HTTP/1.1-only counterparts of the proxy clients, used to forward protocol upgrades
+/// (HTTP/2 has no `Upgrade` mechanism, so we must not negotiate it with the backend).
+#[derive(Debug)]
+pub struct UpgradeClients {
+    shared: Client,
+    pinned: Vec<Client>,
 }
 
 #[derive(Debug, Clone)]
 pub struct Resolver {
-    backends: Arc<Vec<SocketAddr>>,
+    backends: Arc<Vec<Backend>>,
     counter: Arc<AtomicUsize>,
     backend_priority: BackendPriority,
+    ring: Option<Arc<HashRing>>,
+    /// Whether we pick a backend for each request, and send it through a client pinned to that backend.
+    /// Otherwise the backend is picked by DNS resolution, once per pooled connection.
+    pinned: bool,
+}
+
+impl Resolver {
+    /// Unhealthy backends are skipped, unless every backend is unhealthy,
+    /// in which case we fail open and try them all.
+    fn skip_unhealthy(&self) -> bool {
+        self.backends.iter().any(|backend| backend.is_healthy())
+    }
+
+    /// When backends are picked per request, returns the indices of the backends to try, in order.
+    /// Checking health here, rather than when resolving, stops pooled connections to an ejected backend being reused.
+    fn candidates(&self, req: &HttpRequest, context: &HttpRequestContext) -> Option<Vec<usize>> {
+        if !self.pinned || self.backends.is_empty() {
+            return None;
+        }
+        let len = self.backends.len();
+        let skip_unhealthy = self.skip_unhealthy();
+        let rotation = || {
+            let start = self.counter.fetch_add(1, Ordering::Relaxed);
+            (0..len).map(move |offset| (start + offset) % len)
+        };
+
+        let candidates: Vec<usize> = match &self.backend_priority {
+            BackendPriority::ConsistentHash { key } => match key.extract(req, context) {
+                Some(key) => self.ring.as_ref().unwrap().candidates(key, len),
+                None => rotation().collect(),
+            },
+            BackendPriority::Ordered => (0..len).collect(),
+            BackendPriority::Random => {
+                let start = rand::rng().random_range(0..len);
+                (0..len).map(|offset| (start + offset) % len).collect()
+            }
+            BackendPriority::RoundRobin => rotation().collect(),
+            BackendPriority::LeastConnections => {
+                // Rotate before sorting so ties are shared between backends.
+                let mut candidates: Vec<usize> = rotation().collect();
+                candidates.sort_by_key(|&index| self.backends[index].in_flight());
+                candidates
+            }
+        };
+
+        Some(
+            candidates
+                .into_iter()
+                .filter(|&index| !skip_unhealthy || self.backends[index].is_healthy())
+                .collect(),
+        )
+    }
 }
 
 pub struct StatefulResolverIter {
-    backends: Arc<Vec<SocketAddr>>,
+    backends: Arc<Vec<Backend>>,
     start_index: usize,
     current: usize,
+    skip_unhealthy: bool,
 }
 
 impl Iterator for StatefulResolverIter {
     type Item = SocketAddr;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.current < self.backends.len() {
+        while self.current < self.backends.len() {
             let index = (self.start_index + self.current) % self.backends.len();
             self.current += 1;
-            Some(self.backends[index])
-        } else {
-            None
+            let backend = &self.backends[index];
+            if !self.skip_unhealthy || backend.is_healthy() {
+                return Some(backend.addr);
+            }
         }
+        None
     }
 }
 
@@ -94,11 +198,16 @@ impl Resolve for Resolver {
     fn resolve(&self, _name: Name) -> reqwest::dns::Resolving {
         let backends = self.backends.clone();
         let len = backends.len();
+        let skip_unhealthy = self.skip_unhealthy();
 
         let start_index = match self.backend_priority {
             BackendPriority::Ordered => 0,
             BackendPriority::Random => rand::rng().random_range(0..len),
-            BackendPriority::RoundRobin => self.counter.fetch_add(1, Ordering::Relaxed) % len,
+            BackendPriority::RoundRobin
+            | BackendPriority::LeastConnections
+            | BackendPriority::ConsistentHash { .. } => {
+                self.counter.fetch_add(1, Ordering::Relaxed) % len
+            }
         };
 
         let fut = async move {
@@ -106,6 +215,7 @@ impl Resolve for Resolver {
                 backends,
                 start_index,
                 current: 0,
+                skip_unhealthy,
             };
             Ok(Box::new(iter) as Box<dyn Iterator<Item = SocketAddr> + Send>)
         };
@@ -162,17 +272,14 @@ impl Proxy {
     /// (unless overridden) with the precomputed overriding headers.
     fn build_reqwest_request_info(
         &self,
+        client: &Client,
         req_info: &RequestInfo,
         url: &str,
         host_str: &str,
         body: Body,
         overriding_headers: &http::HeaderMap,
     ) -> reqwest::RequestBuilder {
-        let mut builder = self
-            .client
-            .get()
-            .unwrap()
-            .request(req_info.method.clone(), url);
+        let mut builder = client.request(req_info.method.clone(), url);
 
         // Forward headers from the original request unless they are overridden.
         for (name, value) in req_info.headers.iter() {
@@ -195,55 +302,190 @@ impl Proxy {
         builder.body(body)
     }
 
-    /// Sends an idempotent request using a replayable (buffered) body.
-    async fn send_request_idempotent(
+    /// Picks the client to use for the given attempt.
+    /// Per-request strategies use a client pinned to the candidate backend,
+    /// and hold an in-flight guard against it.
+    fn upstream(
+        &self,
+        candidates: Option<&[usize]>,
+        attempt: usize,
+        upgrade: bool,
+    ) -> (&Client, Option<usize>, Option<InFlightGuard>) {
+        let (shared, pinned) = if upgrade {
+            let clients = self.upgrade_clients.get().unwrap();
+            (&clients.shared, clients.pinned.as_slice())
+        } else {
+            (
+                self.client.get().unwrap(),
+                self.backend_clients
+                    .get()
+                    .map(|clients| clients.as_slice())
+                    .unwrap_or_default(),
+            )
+        };
+        let candidate = candidates
+            .filter(|candidates| !candidates.is_empty())
+            .map(|candidates| candidates[attempt % candidates.len()]);
+        match candidate {
+            Some(index) => (
+                &pinned[index],
+                Some(index),
+                Some(InFlightGuard::new(
+                    &self.resolver.get().unwrap().backends,
+                    index,
+                )),
+            ),
+            None => (shared, None, None),
+        }
+    }
+
+    /// Feeds the outcome of a single attempt into passive health checking.
+    fn record_outcome(
+        &self,
+        result: &std::result::Result<reqwest::Response, reqwest::Error>,
+        backend: Option<usize>,
+    ) {
+        // Backends are always picked per request when passive health checking is enabled.
+        let (Some(passive), Some(index)) = (self.passive_health_check.as_ref(), backend) else {
+            return;
+        };
+        let backend = &self.resolver.get().unwrap().backends[index];
+        match result {
+            Ok(response) => {
+                if passive
+                    .failure_statuses
+                    .contains(&response.status().as_u16())
+                {
+                    backend.record_failure(passive);
+                } else {
+                    backend.record_success();
+                }
+            }
+            Err(e) if e.is_connect() => backend.record_failure(passive),
+            Err(_) => {}
+        }
+    }
+
+    /// Whether we pick a backend for each request, rather than for each new connection.
+    /// Health checks need this, so that pooled connections to an unhealthy backend aren't reused.
+    fn pins_backends(&self) -> bool {
+        self.backend_priority.is_per_request()
+            || self.health_check.is_some()
+            || self.passive_health_check.is_some()
+    }
+
+    /// Sends a request using a replayable (buffered) body,
+    /// retrying according to the retry policy until the total timeout is spent.
+    #[allow(clippy::too_many_arguments)]
+    async fn send_request_replayable(
         &self,
         req_info: &RequestInfo,
         url: &str,
         host_str: &str,
-        max_attempts: usize,
+        candidates: Option<&[usize]>,
+        upgrade: bool,
         replayable_bytes: Bytes,
         overriding_headers: &http::HeaderMap,
-    ) -> std::result::Result<reqwest::Response, reqwest::Error> {
-        let mut last_err = None;
-        for attempt in 0..max_attempts {
+    ) -> std::result::Result<(reqwest::Response, Option<InFlightGuard>), reqwest::Error> {
+        let default_policy;
+        let policy = match self.retry.as_ref() {
+            Some(policy) => policy,
+            None => {
+                let backend_count =
+                    candidates.map_or(self.backends.len(), |candidates| candidates.len());
+                default_policy = RetryPolicy::default_for(backend_count);
+                &default_policy
+            }
+        };
+        let total_timeout = Duration::from_secs(self.timeout);
+        let started = Instant::now();
+        let mut attempt = 0;
+        loop {
+            let (client, backend, in_flight) = self.upstream(candidates, attempt, upgrade);
             let body = Body::from(replayable_bytes.clone());
-            let builder =
-                self.build_reqwest_request_info(req_info, url, host_str, body, overriding_headers);
-            match builder.send().await {
-                Ok(response) => return Ok(response),
-                Err(e) => {
-                    // Retry for connectivity-related errors.
-                    if e.is_connect() {
-                        debug!(target: "middleware::proxy", "Connection error, retrying");
-                        last_err = Some(e);
-                        if attempt + 1 < max_attempts {
-                            continue;
-                        } else {
-                            break;
-                        }
-                    } else {
-                        return Err(e);
-                    }
-                }
+            let builder = self.build_reqwest_request_info(
+                client,
+                req_info,
+                url,
+                host_str,
+                body,
+                overriding_headers,
+            );
+            let remaining = total_timeout.saturating_sub(started.elapsed());
+            let result = builder.timeout(policy.try_timeout(remaining)).send().await;
+            self.record_outcome(&result, backend);
+            attempt += 1;
+
+            if attempt >= policy.max_attempts
+                || !policy.should_retry(&result)
+                || started.elapsed() >= total_timeout
+            {
+                return result.map(|response| (response, in_flight));
+            }
+            debug!(target: "middleware::proxy", "Attempt {} failed, retrying", attempt);
+            drop(in_flight);
+            if let Some(backoff) = policy.backoff() {
+                tokio::time::sleep(backoff.min(total_timeout.saturating_sub(started.elapsed())))
+                    .await;
             }
         }
-        Err(last_err.expect("At least one attempt should have set last_err"))
     }
 
-    /// Sends a non-idempotent request once using its streaming body.
+    /// Sends a request once using its streaming body.
+    #[allow(clippy::too_many_arguments)]
     async fn send_request_non_idempotent(
         &self,
         req: HttpRequest,
         req_info: &RequestInfo,
         url: &str,
         host_str: &str,
+        candidates: Option<&[usize]>,
+        upgrade: bool,
         overriding_headers: &http::HeaderMap,
-    ) -> std::result::Result<reqwest::Response, reqwest::Error> {
+    ) -> std::result::Result<(reqwest::Response, Option<InFlightGuard>), reqwest::Error> {
+        let (client, backend, in_flight) = self.upstream(candidates, 0, upgrade);
         let body = Body::wrap_stream(req.into_data_stream());
-        let builder =
-            self.build_reqwest_request_info(req_info, url, host_str, body, overriding_headers);
-        builder.send().await
+        let builder = self.build_reqwest_request_info(
+            client,
+            req_info,
+            url,
+            host_str,
+            body,
+            overriding_headers,
+        );
+        let result = builder.send().await;
+        self.record_outcome(&result, backend);
+        result.map(|response| (response, in_flight))
+    }
+
+    fn build_client<R: Resolve + 'static>(
+        &self,
+        resolver: Arc<R>,
+        http1_only: bool,
+    ) -> Result<Client> {
+        let mut builder = Client::builder();
+        if http1_only {
+            builder = builder.http1_only();
+        }
+        if let Some(connect_timeout) = self.connect_timeout {
+            builder = builder.connect_timeout(Duration::from_secs(connect_timeout));
+        }
+        if let Some(read_timeout) = self.read_timeout {
+            builder = builder.read_timeout(Duration::from_secs(read_timeout));
+        }
+        builder
+            .timeout(Duration::from_secs(self.timeout))
+            .danger_accept_invalid_certs(!self.verify_ssl)
+            .danger_accept_invalid_hostnames(!self.verify_ssl)
+            .dns_resolver(resolver)
+            .tls_sni(self.tls_sni)
+            .build()
+            .map_err(|e| {
+                magnus::Error::new(
+                    magnus::exception::runtime_error(),
+                    format!("Failed to build Reqwest client: {}", e),
+                )
+            })
     }
 }
 
@@ -255,36 +497,93 @@ impl MiddlewareLayer for Proxy {
             .iter()
             .filter_map(|be| {
                 let bind: Bind = be.parse().ok()?;
+                let scheme = if be.starts_with("https://") {
+                    "https"
+                } else {
+                    "http"
+                };
                 match (bind.address, bind.port) {
-                    (BindAddress::Ip(ip_addr), port) => {
-                        Some(SocketAddr::new(ip_addr, port.unwrap()))
-                    }
-                    (BindAddress::UnixSocket(_), _) => None,
+                    (BindAddress::Ip(ip_addr), port) => Some(Backend::new(
+                        SocketAddr::new(ip_addr, port.unwrap()),
+                        scheme,
+                    )),
+                    (BindAddress::UnixSocket(_) | BindAddress::AbstractUnixSocket(_), _) => None,
                 }
             })
             .collect::<Vec<_>>();
         debug!(target: "middleware::proxy", "backends: {:?}", backends);
+        let backends = Arc::new(backends);
 
-        self.client
-            .set(
-                Client::builder()
-                    .timeout(Duration::from_secs(self.timeout))
-                    .danger_accept_invalid_certs(!self.verify_ssl)
-                    .danger_accept_invalid_hostnames(!self.verify_ssl)
-                    .dns_resolver(Arc::new(Resolver {
-                        backends: Arc::new(backends),
-                        counter: Arc::new(AtomicUsize::new(0)),
-                        backend_priority: self.backend_priority.clone(),
-                    }))
-                    .tls_sni(self.tls_sni)
-                    .build()
-                    .map_err(|e| {
-                        magnus::Error::new(
-                            magnus::exception::runtime_error(),
-                            format!("Failed to build Reqwest client: {}", e),
-                        )
-                    })?,
+        if let Some(health_check) = self.health_check.as_ref() {
+            let client = health_check_client(health_check, self.verify_ssl).map_err(|e| {
+                magnus::Error::new(
+                    magnus::exception::runtime_error(),
+                    format!("Failed to build health check client: {}", e),
+                )
+            })?;
+            tokio::spawn(run_health_checks(
+                Arc::downgrade(&backends),
+                health_check.clone(),
+                client,
+            ));
+        }
+
+        let pinned_clients = |http1_only| {
+            if !self.pins_backends() {
+                return Ok(vec![]);
+            }
+            backends
+                .iter()
+                .map(|backend| {
+                    self.build_client(Arc::new(PinnedResolver(backend.addr)), http1_only)
+                })
+                .collect::<Result<Vec<_>>>()
+        };
+        self.backend_clients
+            .set(pinned_clients(false)?)
+            .map_err(|_e| {
+                magnus::Error::new(
+                    magnus::exception::standard_error(),
+                    "Failed to save backend clients",
+                )
+            })?;
+        let upgrade_pinned = pinned_clients(true)?;
+
+        let ring = matches!(
+            self.backend_priority,
+            BackendPriority::ConsistentHash { .. }
+        )
+        .then(|| Arc::new(HashRing::new(&backends)));
+
+        let resolver = Resolver {
+            backends,
+            counter: Arc::new(AtomicUsize::new(0)),
+            backend_priority: self.backend_priority.clone(),
+            ring,
+            pinned: self.pins_backends(),
+        };
+        self.resolver.set(resolver.clone()).map_err(|_e| {
+            magnus::Error::new(
+                magnus::exception::standard_error(),
+                "Failed to save resolver",
             )
+        })?;
+
+        let resolver = Arc::new(resolver);
+        self.upgrade_clients
+            .set(UpgradeClients {
+                shared: self.build_client(resolver.clone(), true)?,
+                pinned: upgrade_pinned,
+            })
+            .map_err(|_e| {
+                magnus::Error::new(
+                    magnus::exception::standard_error(),
+                    "Failed to save upgrade clients",
+                )
+            })?;
+
+        self.client
+            .set(self.build_client(resolver, false)?)
             .map_err(|_e| {
                 magnus::Error::new(
                     magnus::exception::standard_error(),
@@ -296,7 +595,7 @@ impl MiddlewareLayer for Proxy {
 
     async fn before(
         &self,
-        req: HttpRequest,
+        mut req: HttpRequest,
         context: &mut HttpRequestContext,
     ) -> Result<Either<HttpRequest, HttpResponse>> {
         let url = self.to.rewrite_request(&req, context);
@@ -331,10 +630,27 @@ impl MiddlewareLayer for Proxy {
         // Precompute the overriding headers from the full request.
         let overriding_headers = self.build_overriding_headers(&req, context);
 
-        // Determine max_attempts based on the number of backends.
-        let max_attempts = self.backends.len();
-
-        let reqwest_response_result = if is_idempotent(&req_info.method) {
+        let candidates = self.resolver.get().unwrap().candidates(&req, context);
+        let candidates = candidates.as_deref();
+
+        // Claim the client side of an upgrade before the request is consumed.
+        let on_upgrade = is_upgrade_request(&req).then(|| hyper::upgrade::on(&mut req));
+        let upgrade = on_upgrade.is_some();
+
+        // Idempotent requests are always buffered so they can be retried.
+        // Other requests are only buffered if the retry policy allows it and the body is small enough.
+        let replayable = is_idempotent(&req_info.method)
+            || self
+                .retry
+                .as_ref()
+                .and_then(|policy| policy.buffer_limit)
+                .is_some_and(|limit| {
+                    hyper::body::Body::size_hint(req.body())
+                        .upper()
+                        .is_some_and(|size| size <= limit)
+                });
+
+        let reqwest_response_result = if replayable {
             let (_parts, body) = req.into_parts();
             let replayable_bytes = match body.into_data_stream().try_collect::<Vec<Bytes>>().await {
                 Ok(chunks) => {
@@ -349,22 +665,31 @@ impl MiddlewareLayer for Proxy {
                     return Ok(Either::Right(error_response));
                 }
             };
-            self.send_request_idempotent(
+            self.send_request_replayable(
                 &req_info,
                 &url,
                 host_str,
-                max_attempts,
+                candidates,
+                upgrade,
                 replayable_bytes,
                 &overriding_headers,
             )
             .await
         } else {
-            self.send_request_non_idempotent(req, &req_info, &url, host_str, &overriding_headers)
-                .await
+            self.send_request_non_idempotent(
+                req,
+                &req_info,
+                &url,
+                host_str,
+                candidates,
+                upgrade,
+                &overriding_headers,
+            )
+            .await
         };
 
         let response = match reqwest_response_result {
-            Ok(response) => {
+            Ok((response, in_flight)) => {
                 debug!(target: "middleware::proxy", "Response {} received", response.status());
 
                 let status = response.status();
@@ -372,10 +697,27 @@ impl MiddlewareLayer for Proxy {
                 for (hn, hv) in response.headers() {
                     builder = builder.header(hn, hv);
                 }
-                let response =
-                    builder.body(HttpBody::stream(response.bytes_stream().map_err(
-                        |_| -> Infallible { unreachable!("We handle IO errors above") },
-                    )));
+
+                if let Some(on_upgrade) =
+                    on_upgrade.filter(|_| status == StatusCode::SWITCHING_PROTOCOLS)
+                {
+                    // Hyper completes the client upgrade once this 101 response is written.
+                    tokio::spawn(splice(on_upgrade, response, in_flight));
+                    return Ok(Either::Right(
+                        builder.body(HttpBody::empty()).unwrap_or(error_response),
+                    ));
+                }
+
+                // The backend stays in-flight until the response body has been streamed.
+                let response = builder.body(HttpBody::stream(
+                    response
+                        .bytes_stream()
+                        .map_ok(move |chunk| {
+                            let _in_flight = &in_flight;
+                            chunk
+                        })
+                        .map_err(|_| -> Infallible { unreachable!("We handle IO errors above") }),
+                ));
                 response.unwrap_or(error_response)
             }
             Err(e) => {
@@ -0,0 +1,159 @@
+use std::{
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicBool, AtomicU32, AtomicU64, AtomicUsize, Ordering},
+        Weak,
+    },
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use futures::future::join_all;
+use reqwest::Client;
+use serde::Deserialize;
+use tracing::{debug, info, warn};
+
+/// Periodically probes each backend over HTTP.
+#[derive(Debug, Clone, Deserialize)]
+pub struct HealthCheck {
+    pub path: String,
+    pub interval: u64,
+    pub timeout: u64,
+    pub expected_statuses: Vec<u16>,
+    pub healthy_threshold: u32,
+    pub unhealthy_threshold: u32,
+}
+
+/// Ejects backends that fail consecutive proxied requests.
+#[derive(Debug, Clone, Deserialize)]
+pub struct PassiveHealthCheck {
+    pub max_failures: u32,
+    pub cooldown: u64,
+    pub failure_statuses: Vec<u16>,
+}
+
+#[derive(Debug)]
+pub struct Backend {
+    pub addr: SocketAddr,
+    pub scheme: &'static str,
+    probe_healthy: AtomicBool,
+    probe_successes: AtomicU32,
+    probe_failures: AtomicU32,
+    consecutive_failures: AtomicU32,
+    /// Milliseconds since the UNIX epoch until which the backend is ejected.
+    ejected_until: AtomicU64,
+    /// Requests currently being proxied to this backend.
+    pub in_flight: AtomicUsize,
+}
+
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+impl Backend {
+    pub fn new(addr: SocketAddr, scheme: &'static str) -> Self {
+        Self {
+            addr,
+            scheme,
+            probe_healthy: AtomicBool::new(true),
+            probe_successes: AtomicU32::new(0),
+            probe_failures: AtomicU32::new(0),
+            consecutive_failures: AtomicU32::new(0),
+            ejected_until: AtomicU64::new(0),
+            in_flight: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn is_healthy(&self) -> bool {
+        self.probe_healthy.load(Ordering::Relaxed)
+            && self.ejected_until.load(Ordering::Relaxed) <= now_millis()
+    }
+
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.load(Ordering::Relaxed)
+    }
+
+    pub fn record_success(&self) {
+        self.consecutive_failures.store(0, Ordering::Relaxed);
+    }
+
+    pub fn record_failure(&self, passive: &PassiveHealthCheck) {
+        let failures = self.consecutive_failures.fetch_add(1, Ordering::Relaxed) + 1;
+        if failures >= passive.max_failures {
+            self.consecutive_failures.store(0, Ordering::Relaxed);
+            self.ejected_until
+                .store(now_millis() + passive.cooldown * 1000, Ordering::Relaxed);
+            warn!(
+                "Proxy backend {} ejected for {}s after {} consecutive failures",
+                self.addr, passive.cooldown, failures
+            );
+        }
+    }
+
+    fn record_probe(&self, success: bool, check: &HealthCheck) {
+        if success {
+            self.probe_failures.store(0, Ordering::Relaxed);
+            let successes = self.probe_successes.fetch_add(1, Ordering::Relaxed) + 1;
+            if successes >= check.healthy_threshold
+                && !self.probe_healthy.swap(true, Ordering::Relaxed)
+            {
+                info!("Proxy backend {} is healthy again", self.addr);
+            }
+        } else {
+            self.probe_successes.store(0, Ordering::Relaxed);
+            let failures = self.probe_failures.fetch_add(1, Ordering::Relaxed) + 1;
+            if failures >= check.unhealthy_threshold
+                && self.probe_healthy.swap(false, Ordering::Relaxed)
+            {
+                warn!(
+                    "Proxy backend {} marked unhealthy after {} failed health checks",
+                    self.addr, failures
+                );
+            }
+        }
+    }
+
+    async fn probe(&self, client: &Client, check: &HealthCheck) -> bool {
+        let url = format!("{}://{}{}", self.scheme, self.addr, check.path);
+        match client.get(&url).send().await {
+            Ok(response) => {
+                let success = check
+                    .expected_statuses
+                    .contains(&response.status().as_u16());
+                debug!(target: "middleware::proxy", "Health check {} returned {}", url, response.status());
+                success
+            }
+            Err(e) => {
+                debug!(target: "middleware::proxy", "Health check {} failed: {}", url, e);
+                false
+            }
+        }
+    }
+}
+
+/// Probes all backends every `check.interval` seconds,
+/// until the proxy that owns them is dropped (e.g. on config reload).
+pub async fn run_health_checks(backends: Weak<Vec<Backend>>, check: HealthCheck, client: Client) {
+    let mut interval = tokio::time::interval(Duration::from_secs(check.interval));
+    loop {
+        interval.tick().await;
+        let Some(backends) = backends.upgrade() else {
+            break;
+        };
+        join_all(backends.iter().map(|backend| async {
+            let success = backend.probe(&client, &check).await;
+            backend.record_probe(success, &check);
+        }))
+        .await;
+    }
+}
+
+pub fn health_check_client(check: &HealthCheck, verify_ssl: bool) -> reqwest::Result<Client> {
+    Client::builder()
+        .timeout(Duration::from_secs(check.timeout))
+        .danger_accept_invalid_certs(!verify_ssl)
+        .danger_accept_invalid_hostnames(!verify_ssl)
+        .build()
+}
@@ -0,0 +1,122 @@
+use std::{
+    net::SocketAddr,
+    sync::{atomic::Ordering, Arc},
+};
+
+use reqwest::dns::{Name, Resolve, Resolving};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+
+use super::{super::token_source::TokenSource, health_check::Backend};
+use crate::{
+    server::http_message_types::HttpRequest, services::itsi_http_service::HttpRequestContext,
+};
+
+/// Number of points each backend occupies on the hash ring.
+const VIRTUAL_NODES: usize = 160;
+
+#[derive(Debug, Clone, Deserialize)]
+pub enum ConsistentHashKey {
+    #[serde(rename(deserialize = "address"))]
+    Address,
+    #[serde(untagged)]
+    Token(TokenSource),
+}
+
+impl ConsistentHashKey {
+    pub fn extract<'a>(
+        &self,
+        req: &'a HttpRequest,
+        context: &'a HttpRequestContext,
+    ) -> Option<&'a str> {
+        match self {
+            ConsistentHashKey::Address => Some(context.addr.as_str()),
+            ConsistentHashKey::Token(source) => source.extract_token(req),
+        }
+    }
+}
+
+fn hash(value: &str) -> u64 {
+    let digest = Sha256::digest(value.as_bytes());
+    u64::from_be_bytes(digest[..8].try_into().unwrap())
+}
+
+/// A consistent hash ring over the proxy backends.
+/// Points are derived from each backend's address (not its position in the list),
+/// so adding or removing a backend only remaps the keys that it owns.
+#[derive(Debug)]
+pub struct HashRing {
+    points: Vec<(u64, usize)>,
+}
+
+impl HashRing {
+    pub fn new(backends: &[Backend]) -> Self {
+        let mut points = backends
+            .iter()
+            .enumerate()
+            .flat_map(|(index, backend)| {
+                (0..VIRTUAL_NODES)
+                    .map(move |vnode| (hash(&format!("{}#{}", backend.addr, vnode)), index))
+            })
+            .collect::<Vec<_>>();
+        points.sort_unstable();
+        Self { points }
+    }
+
+    /// Returns every backend index, in ring order starting from the owner of `key`.
+    pub fn candidates(&self, key: &str, backend_count: usize) -> Vec<usize> {
+        let mut candidates = Vec::with_capacity(backend_count);
+        if self.points.is_empty() {
+            return candidates;
+        }
+        let start = self.points.partition_point(|(point, _)| *point < hash(key));
+        for offset in 0..self.points.len() {
+            let (_, index) = self.points[(start + offset) % self.points.len()];
+            if !candidates.contains(&index) {
+                candidates.push(index);
+                if candidates.len() == backend_count {
+                    break;
+                }
+            }
+        }
+        candidates
+    }
+}
+
+/// Counts a request against a backend for as long as it is alive.
+pub struct InFlightGuard {
+    backends: Arc<Vec<Backend>>,
+    index: usize,
+}
+
+impl InFlightGuard {
+    pub fn new(backends: &Arc<Vec<Backend>>, index: usize) -> Self {
+        backends[index].in_flight.fetch_add(1, Ordering::Relaxed);
+        Self {
+            backends: backends.clone(),
+            index,
+        }
+    }
+}
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        self.backends[self.index]
+            .in_flight
+            .fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Resolves every host to a single backend.
+/// Used for strategies that choose a backend per request rather than per connection.
+#[derive(Debug)]
+pub struct PinnedResolver(pub SocketAddr);
+
+impl Resolve for PinnedResolver {
+    fn resolve(&self, _name: Name) -> Resolving {
+        let addr = self.0;
+        Box::pin(async move {
+            Ok(Box::new(std::iter::once(addr)) as Box<dyn Iterator<Item = SocketAddr> + Send>)
+        })
+    }
+}
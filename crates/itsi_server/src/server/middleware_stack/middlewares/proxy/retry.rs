@@ -0,0 +1,56 @@
+use std::time::Duration;
+
+use serde::Deserialize;
+
+/// Controls when a failed attempt to reach a backend is retried.
+/// Only requests with a replayable (buffered) body are ever retried.
+#[derive(Debug, Clone, Deserialize)]
+pub struct RetryPolicy {
+    pub max_attempts: usize,
+    #[serde(default)]
+    pub per_try_timeout: Option<u64>,
+    pub retry_on_connect_error: bool,
+    pub retry_on_timeout: bool,
+    pub retry_on_statuses: Vec<u16>,
+    pub backoff: f64,
+    /// Non-idempotent requests with a known body size up to this many bytes
+    /// are buffered, which makes them eligible for retries.
+    #[serde(default)]
+    pub buffer_limit: Option<u64>,
+}
+
+impl RetryPolicy {
+    /// Without an explicit policy, we try each backend once on connection errors.
+    pub fn default_for(backend_count: usize) -> Self {
+        Self {
+            max_attempts: backend_count.max(1),
+            per_try_timeout: None,
+            retry_on_connect_error: true,
+            retry_on_timeout: false,
+            retry_on_statuses: vec![],
+            backoff: 0.0,
+            buffer_limit: None,
+        }
+    }
+
+    pub fn should_retry(&self, result: &Result<reqwest::Response, reqwest::Error>) -> bool {
+        match result {
+            Ok(response) => self.retry_on_statuses.contains(&response.status().as_u16()),
+            Err(e) => {
+                (e.is_connect() && self.retry_on_connect_error)
+                    || (e.is_timeout() && self.retry_on_timeout)
+            }
+        }
+    }
+
+    /// The timeout for a single attempt, bounded by what is left of the total timeout.
+    pub fn try_timeout(&self, remaining: Duration) -> Duration {
+        self.per_try_timeout.map_or(remaining, |timeout| {
+            Duration::from_secs(timeout).min(remaining)
+        })
+    }
+
+    pub fn backoff(&self) -> Option<Duration> {
+        (self.backoff > 0.0).then(|| Duration::from_secs_f64(self.backoff))
+    }
+}
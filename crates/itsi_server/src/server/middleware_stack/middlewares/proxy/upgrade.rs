@@ -0,0 +1,53 @@
+use http::{header, Version};
+use hyper::upgrade::OnUpgrade;
+use hyper_util::rt::TokioIo;
+use tracing::debug;
+
+use super::load_balancer::InFlightGuard;
+use crate::server::http_message_types::HttpRequest;
+
+/// Whether the request asks to switch protocols (e.g. `Upgrade: websocket`).
+/// Only HTTP/1.1 supports the Upgrade mechanism.
+pub fn is_upgrade_request(req: &HttpRequest) -> bool {
+    req.version() == Version::HTTP_11
+        && req.headers().contains_key(header::UPGRADE)
+        && req
+            .headers()
+            .get_all(header::CONNECTION)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .any(|token| token.trim().eq_ignore_ascii_case("upgrade"))
+}
+
+/// Once the client side of the connection has been upgraded,
+/// splices it with the upgraded backend connection until either side closes.
+pub async fn splice(
+    client: OnUpgrade,
+    backend: reqwest::Response,
+    in_flight: Option<InFlightGuard>,
+) {
+    let mut backend = match backend.upgrade().await {
+        Ok(upgraded) => upgraded,
+        Err(e) => {
+            debug!(target: "middleware::proxy", "Backend upgrade failed: {}", e);
+            return;
+        }
+    };
+    let mut client = match client.await {
+        Ok(upgraded) => TokioIo::new(upgraded),
+        Err(e) => {
+            debug!(target: "middleware::proxy", "Client upgrade failed: {}", e);
+            return;
+        }
+    };
+    match tokio::io::copy_bidirectional(&mut client, &mut backend).await {
+        Ok((to_backend, to_client)) => {
+            debug!(target: "middleware::proxy", "Upgraded connection closed ({} bytes sent, {} bytes received)", to_backend, to_client);
+        }
+        Err(e) => {
+            debug!(target: "middleware::proxy", "Upgraded connection closed with error: {}", e);
+        }
+    }
+    drop(in_flight);
+}
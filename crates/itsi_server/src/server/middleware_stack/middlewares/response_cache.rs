@@ -0,0 +1,272 @@
+use super::{FromValue, MiddlewareLayer};
+use crate::{
+    server::http_message_types::{HttpBody, HttpRequest, HttpResponse},
+    services::{
+        itsi_http_service::HttpRequestContext,
+        response_cache::{
+            mark_cache_status, CachePolicy, CachedResponse, Freshness, ResponseCacheState,
+            ResponseCacheStore, CACHE_HIT, CACHE_MISS, CACHE_STALE,
+        },
+    },
+};
+use async_trait::async_trait;
+use bytes::{Bytes, BytesMut};
+use either::Either;
+use futures::TryStreamExt;
+use http::{header, Method, Response, StatusCode};
+use http_body_util::BodyExt;
+use hyper::body::Body;
+use ipnet::IpNet;
+use itsi_error::ItsiError;
+use magnus::error::Result;
+use parking_lot::Mutex;
+use serde::Deserialize;
+use std::{
+    net::IpAddr,
+    path::PathBuf,
+    sync::{Arc, OnceLock},
+    time::Duration,
+};
+use tracing::debug;
+
+#[derive(Debug, Deserialize)]
+pub struct ResponseCache {
+    pub max_entries: usize,
+    pub max_body_size: u64,
+    #[serde(default)]
+    pub disk_path: Option<PathBuf>,
+    #[serde(default)]
+    pub default_ttl: Option<u64>,
+    pub cacheable_statuses: Vec<u16>,
+    pub coalesce_timeout: u64,
+    #[serde(default)]
+    pub allow_purge: bool,
+    /// Caches responses to authenticated requests too, for routes whose responses are the same
+    /// for every user.
+    #[serde(default)]
+    pub cache_authenticated: bool,
+    /// The client addresses (IPs or CIDRs) allowed to send `PURGE` requests.
+    #[serde(default)]
+    pub purge_allowed_from: Vec<String>,
+    #[serde(skip_deserializing)]
+    pub purge_sources: OnceLock<Vec<IpNet>>,
+    #[serde(skip_deserializing)]
+    pub store: OnceLock<Arc<ResponseCacheStore>>,
+}
+
+impl ResponseCache {
+    fn cache_key(req: &HttpRequest, method: &str) -> String {
+        let host = req
+            .headers()
+            .get(header::HOST)
+            .and_then(|h| h.to_str().ok())
+            .or_else(|| req.uri().authority().map(|a| a.as_str()))
+            .unwrap_or("");
+        let path_and_query = req
+            .uri()
+            .path_and_query()
+            .map(|pq| pq.as_str())
+            .unwrap_or("/");
+        ResponseCacheStore::key_for(method, host, path_and_query)
+    }
+
+    fn purge(&self, req: &HttpRequest, context: &HttpRequestContext) -> HttpResponse {
+        let allowed = context.addr.parse::<IpAddr>().is_ok_and(|addr| {
+            self.purge_sources
+                .get()
+                .unwrap()
+                .iter()
+                .any(|net| net.contains(&addr))
+        });
+        if !allowed {
+            debug!(target: "middleware::response_cache", "Rejected PURGE from {}", context.addr);
+            let mut response = Response::new(HttpBody::empty());
+            *response.status_mut() = StatusCode::FORBIDDEN;
+            return response;
+        }
+        let keys = [
+            Self::cache_key(req, Method::GET.as_str()),
+            Self::cache_key(req, Method::HEAD.as_str()),
+        ];
+        let purged = self.store.get().unwrap().purge(&keys);
+        debug!(target: "middleware::response_cache", "Purged {} entries for {}", purged, keys[0]);
+        let mut response = Response::new(HttpBody::empty());
+        *response.status_mut() = if purged > 0 {
+            StatusCode::OK
+        } else {
+            StatusCode::NOT_FOUND
+        };
+        response
+    }
+}
+
+#[async_trait]
+impl MiddlewareLayer for ResponseCache {
+    async fn initialize(&self) -> Result<()> {
+        let store = ResponseCacheStore::new(self.max_entries, self.disk_path.clone())
+            .map_err(|e| ItsiError::new(format!("Failed to create response cache: {}", e)))?;
+        self.store
+            .set(store)
+            .map_err(|_| ItsiError::new("Failed to set response cache store"))?;
+        let purge_sources = self
+            .purge_allowed_from
+            .iter()
+            .map(|source| {
+                source
+                    .parse::<IpNet>()
+                    .or_else(|_| source.parse::<IpAddr>().map(IpNet::from))
+                    .map_err(|_| {
+                        ItsiError::new(format!(
+                            "Invalid purge_allowed_from source {}. Expected an IP address or CIDR",
+                            source
+                        ))
+                    })
+            })
+            .collect::<itsi_error::Result<Vec<_>>>()?;
+        self.purge_sources.set(purge_sources).ok();
+        Ok(())
+    }
+
+    async fn before(
+        &self,
+        req: HttpRequest,
+        context: &mut HttpRequestContext,
+    ) -> Result<Either<HttpRequest, HttpResponse>> {
+        if self.allow_purge && req.method().as_str() == "PURGE" {
+            return Ok(Either::Right(self.purge(&req, context)));
+        }
+
+        // Only safe methods are cacheable, and a shared cache must not reuse responses
+        // to authenticated requests (by credentials, a session cookie, or an earlier auth middleware)
+        // unless the route opts in.
+        if !matches!(*req.method(), Method::GET | Method::HEAD) {
+            return Ok(Either::Left(req));
+        }
+        if !self.cache_authenticated
+            && (req.headers().contains_key(header::AUTHORIZATION)
+                || req.headers().contains_key(header::COOKIE)
+                || context.is_authenticated())
+        {
+            return Ok(Either::Left(req));
+        }
+
+        let store = self.store.get().unwrap();
+        let key = Self::cache_key(&req, req.method().as_str());
+
+        let mut cached = store.lookup(&key, req.headers()).await;
+        let lease = match cached.as_ref().map(|entry| entry.freshness()) {
+            Some(Freshness::Fresh) => {
+                debug!(target: "middleware::response_cache", "Cache hit for {}", key);
+                let entry = cached.unwrap();
+                return Ok(Either::Right(entry.to_http_response(&CACHE_HIT)));
+            }
+            Some(Freshness::Revalidate) => match store.try_lease(&key) {
+                Ok(lease) => Some(lease),
+                Err(_) => {
+                    // Another request is already revalidating, serve stale meanwhile.
+                    debug!(target: "middleware::response_cache", "Serving stale {} while revalidating", key);
+                    let entry = cached.unwrap();
+                    return Ok(Either::Right(entry.to_http_response(&CACHE_STALE)));
+                }
+            },
+            _ => match store.try_lease(&key) {
+                Ok(lease) => Some(lease),
+                Err(mut in_flight) => {
+                    // Coalesce with the request already fetching this key.
+                    debug!(target: "middleware::response_cache", "Waiting on in-flight fetch for {}", key);
+                    tokio::time::timeout(
+                        Duration::from_secs(self.coalesce_timeout),
+                        in_flight.changed(),
+                    )
+                    .await
+                    .ok();
+                    cached = store.lookup(&key, req.headers()).await;
+                    if let Some(entry) = cached.as_ref() {
+                        if matches!(entry.freshness(), Freshness::Fresh) {
+                            return Ok(Either::Right(entry.to_http_response(&CACHE_HIT)));
+                        }
+                    }
+                    None
+                }
+            },
+        };
+
+        context.set_response_cache_state(ResponseCacheState {
+            key,
+            request_headers: req.headers().clone(),
+            lease: Mutex::new(lease),
+            stale: cached,
+        });
+
+        Ok(Either::Left(req))
+    }
+
+    async fn after(
+        &self,
+        mut resp: HttpResponse,
+        context: &mut HttpRequestContext,
+    ) -> HttpResponse {
+        let Some(state) = context.response_cache_state() else {
+            return resp;
+        };
+
+        // Hold the lease until the response is stored, so coalesced requests find it.
+        let _lease = state.lease.lock().take();
+
+        if resp.status().is_server_error() {
+            if let Some(stale) = state.stale.as_ref().filter(|stale| stale.usable_on_error()) {
+                debug!(target: "middleware::response_cache", "Serving stale {} on upstream error", state.key);
+                return stale.to_http_response(&CACHE_STALE);
+            }
+        }
+
+        if !self.cacheable_statuses.contains(&resp.status().as_u16()) {
+            mark_cache_status(&mut resp, &CACHE_MISS);
+            return resp;
+        }
+
+        let Some(policy) = CachePolicy::from_response_headers(resp.headers(), self.default_ttl)
+        else {
+            debug!(target: "middleware::response_cache", "Response for {} is not storable", state.key);
+            mark_cache_status(&mut resp, &CACHE_MISS);
+            return resp;
+        };
+
+        match resp.size_hint().exact() {
+            Some(size) if size <= self.max_body_size => {}
+            _ => {
+                debug!(target: "middleware::response_cache", "Skipping streaming or oversized response for {}", state.key);
+                mark_cache_status(&mut resp, &CACHE_MISS);
+                return resp;
+            }
+        }
+
+        let (parts, body) = resp.into_parts();
+        let full_bytes: Bytes = match body
+            .into_data_stream()
+            .try_fold(BytesMut::new(), |mut acc, chunk| async move {
+                acc.extend_from_slice(&chunk);
+                Ok(acc)
+            })
+            .await
+        {
+            Ok(bytes_mut) => bytes_mut.freeze(),
+            Err(_) => return Response::from_parts(parts, HttpBody::empty()),
+        };
+
+        let entry = CachedResponse::new(
+            parts.status,
+            parts.headers.clone(),
+            full_bytes.clone(),
+            &state.request_headers,
+            policy,
+        );
+        self.store.get().unwrap().store(&state.key, entry).await;
+
+        let mut resp = Response::from_parts(parts, HttpBody::full(full_bytes));
+        mark_cache_status(&mut resp, &CACHE_MISS);
+        resp
+    }
+}
+
+impl FromValue for ResponseCache {}
@@ -7,6 +7,8 @@ use crate::services::static_file_server::ROOT_STATIC_FILE_SERVER;
 use async_trait::async_trait;
 use derive_more::Debug;
 use either::Either;
+use http::request::Parts;
+use itsi_error::ItsiError;
 use itsi_rb_helpers::{HeapVal, HeapValue};
 use magnus::{block::Proc, error::Result, value::ReprValue, Symbol};
 use regex::Regex;
@@ -74,6 +76,38 @@ impl RubyApp {
             base_path,
         }))
     }
+
+    fn script_name_for(&self, path: &str) -> String {
+        self.script_name.clone().unwrap_or_else(|| {
+            self.base_path
+                .captures(path)
+                .and_then(|caps| caps.name("base_path"))
+                .map(|m| m.as_str())
+                .unwrap_or("/")
+                .to_owned()
+        })
+    }
+
+    /// Calls the app with a request that has no body, such as an `auth_request` subrequest.
+    pub async fn call_without_body(
+        &self,
+        parts: Parts,
+        context: &HttpRequestContext,
+    ) -> itsi_error::Result<HttpResponse> {
+        if matches!(self.request_type, RequestType::Grpc) {
+            return Err(ItsiError::new("Can't send a subrequest to a gRPC app"));
+        }
+        context.is_ruby_request.store(true, Ordering::SeqCst);
+        let script_name = self.script_name_for(parts.uri.path());
+        ItsiHttpRequest::process_bodyless_request(
+            self.app.clone(),
+            parts,
+            context,
+            script_name,
+            self.nonblocking,
+        )
+        .await
+    }
 }
 
 #[async_trait]
@@ -86,15 +120,7 @@ impl MiddlewareLayer for RubyApp {
         context.is_ruby_request.store(true, Ordering::SeqCst);
         match self.request_type {
             RequestType::Http => {
-                let uri = req.uri().path();
-                let script_name = self.script_name.clone().unwrap_or_else(|| {
-                    self.base_path
-                        .captures(uri)
-                        .and_then(|caps| caps.name("base_path"))
-                        .map(|m| m.as_str())
-                        .unwrap_or("/")
-                        .to_owned()
-                });
+                let script_name = self.script_name_for(req.uri().path());
                 ItsiHttpRequest::process_request(
                     self.app.clone(),
                     req,
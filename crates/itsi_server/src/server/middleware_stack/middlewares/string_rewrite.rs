@@ -109,6 +109,21 @@ impl StringRewrite {
                         "path" => req.uri().path().to_string(),
                         "addr" => context.addr.to_owned(),
                         "host" => req.uri().host().unwrap_or("localhost").to_string(),
+                        "client_cert_subject" => context
+                            .client_cert
+                            .as_ref()
+                            .map(|cert| cert.subject.clone())
+                            .unwrap_or_default(),
+                        "client_cert_sans" => context
+                            .client_cert
+                            .as_ref()
+                            .map(|cert| cert.sans.join(","))
+                            .unwrap_or_default(),
+                        "client_cert_fingerprint" => context
+                            .client_cert
+                            .as_ref()
+                            .map(|cert| cert.fingerprint.clone())
+                            .unwrap_or_default(),
                         "path_and_query" => req
                             .uri()
                             .path_and_query()
@@ -135,8 +150,12 @@ impl StringRewrite {
                             }
                         }
                         other => {
-                            // headers first
-                            if let Some(hv) = req.headers().get(other) {
+                            // claims of a verified JWT
+                            if let Some(claim) = other.strip_prefix("jwt.") {
+                                context.jwt_claim(claim).unwrap_or_default()
+                            }
+                            // then headers
+                            else if let Some(hv) = req.headers().get(other) {
                                 hv.to_str().unwrap_or("").to_string()
                             }
                             // then any regex‐capture
@@ -196,7 +215,9 @@ impl StringRewrite {
                             }
                         }
                         other => {
-                            if let Some(hv) = resp.headers().get(other) {
+                            if let Some(claim) = other.strip_prefix("jwt.") {
+                                context.jwt_claim(claim).unwrap_or_default()
+                            } else if let Some(hv) = resp.headers().get(other) {
                                 hv.to_str().unwrap_or("").to_string()
                             } else {
                                 format!("{{{}}}", other)
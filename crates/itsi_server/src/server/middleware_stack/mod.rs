@@ -1,6 +1,9 @@
 mod middleware;
 mod middlewares;
-use http::header::{ACCEPT, CONTENT_TYPE, HOST};
+use http::{
+    header::{ACCEPT, CONTENT_TYPE, HOST},
+    Request,
+};
 use itsi_rb_helpers::HeapVal;
 use magnus::{
     error::Result, rb_sys::AsRawValue, value::ReprValue, RArray, RHash, Ruby, TryConvert, Value,
@@ -14,8 +17,6 @@ use std::{
 };
 use tracing::debug;
 
-use super::http_message_types::HttpRequest;
-
 #[derive(Debug)]
 pub struct MiddlewareSet {
     pub route_set: RegexSet,
@@ -68,7 +69,7 @@ impl StringMatch {
 }
 
 impl MiddlewareStack {
-    pub fn matches(&self, request: &HttpRequest) -> bool {
+    pub fn matches<B>(&self, request: &Request<B>) -> bool {
         if let Some(methods) = &self.methods {
             let method = request.method().as_str();
             if !methods.iter().any(|m| m.matches(method)) {
@@ -260,9 +261,9 @@ impl MiddlewareSet {
         }
     }
 
-    pub fn stack_for(
+    pub fn stack_for<B: std::fmt::Debug>(
         &self,
-        request: &HttpRequest,
+        request: &Request<B>,
     ) -> Result<(&Vec<Middleware>, Option<Arc<Regex>>)> {
         let binding = self.route_set.matches(request.uri().path());
         let matches = binding.iter();
@@ -303,7 +304,11 @@ impl MiddlewareSet {
                 "allow_list" => Ok(Middleware::AllowList(AllowList::from_value(parameters)?)),
                 "auth_basic" => Ok(Middleware::AuthBasic(AuthBasic::from_value(parameters)?)),
                 "auth_jwt" => Ok(Middleware::AuthJwt(AuthJwt::from_value(parameters)?)),
+                "auth_oidc" => Ok(Middleware::AuthOidc(AuthOidc::from_value(parameters)?)),
                 "auth_api_key" => Ok(Middleware::AuthAPIKey(AuthAPIKey::from_value(parameters)?)),
+                "auth_request" => Ok(Middleware::AuthRequest(AuthRequest::from_value(
+                    parameters,
+                )?)),
                 "cache_control" => Ok(Middleware::CacheControl(CacheControl::from_value(
                     parameters,
                 )?)),
@@ -334,6 +339,9 @@ impl MiddlewareSet {
                 "log_requests" => Ok(Middleware::LogRequests(LogRequests::from_value(
                     parameters,
                 )?)),
+                "response_cache" => Ok(Middleware::ResponseCache(ResponseCache::from_value(
+                    parameters,
+                )?)),
                 "redirect" => Ok(Middleware::Redirect(Redirect::from_value(parameters)?)),
                 "app" => Ok(Middleware::RubyApp(RubyApp::from_value(parameters.into())?)),
                 "proxy" => Ok(Middleware::Proxy(Proxy::from_value(parameters)?)),
@@ -1,15 +1,25 @@
+use http::{header::CONNECTION, HeaderValue, StatusCode, Version};
 use hyper_util::rt::TokioIo;
-use std::{ops::Deref, pin::Pin, sync::Arc, time::Duration};
+use itsi_acme::challenges::Http01Challenges;
+use std::{net::IpAddr, ops::Deref, pin::Pin, sync::Arc, time::Duration};
 use tokio::task::JoinSet;
 use tracing::debug;
 
 use crate::{
     ruby_types::itsi_server::itsi_server_config::ServerParams,
-    server::{binds::listener::ListenerInfo, io_stream::IoStream, request_job::RequestJob},
+    server::{
+        binds::listener::{ListenerInfo, PendingStream},
+        io_stream::IoStream,
+        request_job::RequestJob,
+    },
     services::itsi_http_service::{ItsiHttpService, ItsiHttpServiceInner},
 };
 
-use super::single_mode::{RunningPhase, SingleMode};
+use super::{
+    connection_limiter::{ConnectionLimiter, ConnectionPermit},
+    http3,
+    single_mode::{RunningPhase, SingleMode},
+};
 
 pub struct Acceptor {
     pub acceptor_args: Arc<AcceptorArgs>,
@@ -31,26 +41,121 @@ pub struct AcceptorArgs {
     pub job_sender: async_channel::Sender<RequestJob>,
     pub nonblocking_sender: async_channel::Sender<RequestJob>,
     pub server_params: Arc<ServerParams>,
+    /// Advertises HTTP/3 listeners on responses served over HTTPS.
+    pub alt_svc: Option<HeaderValue>,
+    /// Answers ACME http-01 challenges, if this bind can be asked to.
+    pub acme_http01: Option<Arc<Http01Challenges>>,
+    /// Connections open on this bind, limited by its `max_connections` option.
+    pub bind_connections: Arc<ConnectionLimiter>,
+    /// Closes connections idle in keep-alive for longer than this.
+    pub keep_alive_timeout: Option<Duration>,
+    /// Closes connections once they've served this many requests.
+    pub max_requests_per_connection: Option<u64>,
+}
+
+impl AcceptorArgs {
+    fn admit(&self, ip: Option<IpAddr>) -> Option<ConnectionPermit> {
+        let permit =
+            ConnectionPermit::acquire(&[&self.strategy.connections, &self.bind_connections], ip);
+        if permit.is_none() {
+            debug!(
+                "Rejected connection from {:?}: max_connections_per_ip reached",
+                ip
+            );
+        }
+        permit
+    }
 }
 
 impl Acceptor {
-    pub(crate) async fn serve_connection(&mut self, stream: IoStream) {
-        let addr = stream.addr();
-        let io: TokioIo<Pin<Box<IoStream>>> = TokioIo::new(Box::pin(stream));
+    /// Waits until both the worker and this bind have room for another connection.
+    pub(crate) async fn wait_for_capacity(acceptor_args: &AcceptorArgs) {
+        acceptor_args.strategy.connections.wait_for_capacity().await;
+        acceptor_args.bind_connections.wait_for_capacity().await;
+    }
+
+    pub(crate) async fn serve_connection(&mut self, mut pending: PendingStream) {
         let mut shutdown_channel = self.shutdown_receiver.clone();
         let acceptor_args = self.acceptor_args.clone();
-        let service = ItsiHttpService {
-            inner: Arc::new(ItsiHttpServiceInner {
-                acceptor_args: acceptor_args.clone(),
-                addr,
-            }),
+
+        // Admitted before the PROXY header and TLS handshake, so connections that stall
+        // during either still count against the connection limits.
+        let peer_ip = pending.client_ip();
+        let Some(mut permit) = self.admit(peer_ip) else {
+            return;
         };
 
         self.join_set.spawn(async move {
+            // Done here rather than in the accept loop, so a stalled client only holds up its own connection.
+            if let Err(e) = pending.read_proxy_header().await {
+                debug!("Failed to read PROXY protocol header: {:?}", e);
+                return;
+            }
+            let client_ip = pending.client_ip();
+            if client_ip != peer_ip && !permit.reassign(client_ip) {
+                debug!(
+                    "Rejected connection from {:?}: max_connections_per_ip reached",
+                    client_ip
+                );
+                return;
+            }
+            let connection = permit.connection.clone();
+            // A connection still handshaking is idle, so it's closed first when making room for others.
+            let stream = tokio::select! {
+                result = pending.establish() => match result {
+                    Ok(stream) => stream,
+                    Err(e) => {
+                        debug!("Failed to establish connection: {:?}", e);
+                        return;
+                    }
+                },
+                _ = connection.close_requested() => {
+                    debug!("Closing connection before its handshake completed");
+                    return;
+                }
+            };
+            let addr = stream.addr();
+            let client_cert = stream.client_certificate();
+            let io: TokioIo<Pin<Box<IoStream>>> = TokioIo::new(Box::pin(stream));
+            let service = ItsiHttpService {
+                inner: Arc::new(ItsiHttpServiceInner {
+                    acceptor_args: acceptor_args.clone(),
+                    addr,
+                    client_cert,
+                }),
+            };
+
+            // Counts this connection against the connection limits until it closes.
+            let _permit = permit;
             let executor = &acceptor_args.strategy.executor;
+            let keep_alive_timeout = acceptor_args.keep_alive_timeout;
+            let tracked = connection.clone();
+            let max_requests = acceptor_args.max_requests_per_connection;
             let svc = hyper::service::service_fn(move |req| {
                 let service = service.clone();
-                async move { service.handle_request(req).await }
+                let tracked = tracked.clone();
+                let active_request = tracked.start_request();
+                async move {
+                    let is_last = max_requests.is_some_and(|max| active_request.number >= max);
+                    let is_http1 = req.version() < Version::HTTP_2;
+                    let mut response = service.handle_request(req).await;
+                    if is_last {
+                        match response.as_mut() {
+                            // An upgraded connection serves no further requests.
+                            Ok(response) if response.status() == StatusCode::SWITCHING_PROTOCOLS => {}
+                            // Hyper closes the connection once this response is written.
+                            Ok(response) if is_http1 => {
+                                response
+                                    .headers_mut()
+                                    .insert(CONNECTION, HeaderValue::from_static("close"));
+                            }
+                            // HTTP/2 sends a GOAWAY, and lets streams already in flight complete.
+                            _ => tracked.request_close(),
+                        }
+                    }
+                    drop(active_request);
+                    response
+                }
             });
 
             let mut serve = Box::pin(executor.serve_connection_with_upgrades(io, svc));
@@ -67,8 +172,15 @@ impl Acceptor {
                         }
                     }
                 },
-                // A lifecycle event triggers shutdown.
-                _ = shutdown_channel.changed() => {
+                // A lifecycle event triggers shutdown, we're near the connection limit,
+                // or this connection has been idle in keep-alive for too long, or served its last request.
+                _ = async {
+                    tokio::select! {
+                        _ = shutdown_channel.changed() => {},
+                        _ = connection.close_requested() => debug!("Closing connection on request"),
+                        _ = connection.idle_timeout(keep_alive_timeout) => debug!("Closing connection idle in keep-alive"),
+                    }
+                } => {
                     // Initiate graceful shutdown.
                     serve.as_mut().graceful_shutdown();
 
@@ -81,6 +193,18 @@ impl Acceptor {
         });
     }
 
+    pub(crate) async fn serve_quic_connection(&mut self, incoming: quinn::Incoming) {
+        let Some(permit) = self.admit(Some(incoming.remote_address().ip().to_canonical())) else {
+            incoming.refuse();
+            return;
+        };
+        self.join_set.spawn(http3::serve_quic_connection(
+            incoming,
+            self.acceptor_args.clone(),
+            permit,
+        ));
+    }
+
     pub async fn join(&mut self) {
         // Join all acceptor tasks with timeout
 
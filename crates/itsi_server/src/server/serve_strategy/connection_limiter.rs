@@ -0,0 +1,304 @@
+use parking_lot::Mutex;
+use std::{
+    collections::HashMap,
+    net::IpAddr,
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc, LazyLock,
+    },
+    time::{Duration, Instant},
+};
+use tokio::{sync::Notify, time::sleep};
+
+/// Idle times are measured from here, so they can be compared across connections.
+static EPOCH: LazyLock<Instant> = LazyLock::new(Instant::now);
+
+/// Counts the open connections of a worker (or a single bind), and enforces
+/// `max_connections` and `max_connections_per_ip`.
+///
+/// Once `max_connections` is reached we stop accepting, so further connections queue in the listen backlog.
+/// Connections over `max_connections_per_ip` are closed as soon as they're accepted.
+pub struct ConnectionLimiter {
+    max_connections: Option<usize>,
+    max_connections_per_ip: Option<usize>,
+    state: Mutex<LimiterState>,
+    released: Notify,
+}
+
+#[derive(Default)]
+struct LimiterState {
+    next_id: u64,
+    per_ip: HashMap<IpAddr, usize>,
+    connections: HashMap<u64, Arc<TrackedConnection>>,
+}
+
+/// A connection counted by one or more limiters, which tracks whether it's idle in keep-alive
+/// and how many requests it has served.
+pub struct TrackedConnection {
+    active_requests: AtomicUsize,
+    requests: AtomicU64,
+    /// Milliseconds since `EPOCH`, as of the end of the last request.
+    idle_since: AtomicU64,
+    close: Notify,
+}
+
+/// Holds a connection's place in a limiter until dropped.
+pub struct Registration {
+    limiter: Arc<ConnectionLimiter>,
+    id: u64,
+    ip: Option<IpAddr>,
+}
+
+/// A connection's place in the limits of its worker and its bind.
+pub struct ConnectionPermit {
+    pub connection: Arc<TrackedConnection>,
+    registrations: Vec<Registration>,
+}
+
+/// Marks a connection as busy while a request is in flight.
+pub struct ActiveRequest {
+    connection: Arc<TrackedConnection>,
+    /// Counts from 1 for the first request on the connection.
+    pub number: u64,
+}
+
+impl ConnectionLimiter {
+    pub fn new(max_connections: Option<usize>, max_connections_per_ip: Option<usize>) -> Arc<Self> {
+        Arc::new(Self {
+            max_connections,
+            max_connections_per_ip,
+            state: Mutex::new(LimiterState::default()),
+            released: Notify::new(),
+        })
+    }
+
+    /// Once this many connections are open, we start closing connections idle in keep-alive
+    /// to make room, longest idle first.
+    fn reap_threshold(max_connections: usize) -> usize {
+        max_connections - max_connections / 10
+    }
+
+    /// Waits until there's room for another connection.
+    pub async fn wait_for_capacity(&self) {
+        let Some(max_connections) = self.max_connections else {
+            return;
+        };
+        loop {
+            let released = self.released.notified();
+            {
+                let state = self.state.lock();
+                let open = state.connections.len();
+                if open < max_connections {
+                    return;
+                }
+                Self::reap_idle(&state, open + 1 - Self::reap_threshold(max_connections));
+            }
+            released.await;
+        }
+    }
+
+    /// Counts a newly accepted connection from `ip`.
+    /// Returns `None` if the client already has `max_connections_per_ip` connections open.
+    pub fn admit(
+        self: &Arc<Self>,
+        ip: Option<IpAddr>,
+        connection: &Arc<TrackedConnection>,
+    ) -> Option<Registration> {
+        let mut state = self.state.lock();
+        if let (Some(ip), Some(max_per_ip)) = (ip, self.max_connections_per_ip) {
+            let count = state.per_ip.entry(ip).or_default();
+            if *count >= max_per_ip {
+                return None;
+            }
+            *count += 1;
+        } else if let Some(ip) = ip {
+            *state.per_ip.entry(ip).or_default() += 1;
+        }
+        let id = state.next_id;
+        state.next_id += 1;
+        state.connections.insert(id, connection.clone());
+
+        if let Some(max_connections) = self.max_connections {
+            let threshold = Self::reap_threshold(max_connections);
+            let open = state.connections.len();
+            if open > threshold {
+                Self::reap_idle(&state, open - threshold);
+            }
+        }
+        Some(Registration {
+            limiter: self.clone(),
+            id,
+            ip,
+        })
+    }
+
+    /// Moves a connection counted under its socket peer to the client address it turned out to be
+    /// acting for (e.g. from a PROXY protocol header).
+    /// Returns `false`, leaving the registration as it was, if the client already has
+    /// `max_connections_per_ip` connections open.
+    fn reassign(&self, registration: &mut Registration, ip: Option<IpAddr>) -> bool {
+        let mut state = self.state.lock();
+        if let (Some(ip), Some(max_per_ip)) = (ip, self.max_connections_per_ip) {
+            if state
+                .per_ip
+                .get(&ip)
+                .is_some_and(|count| *count >= max_per_ip)
+            {
+                return false;
+            }
+        }
+        if let Some(previous) = registration.ip {
+            state.release_ip(previous);
+        }
+        if let Some(ip) = ip {
+            *state.per_ip.entry(ip).or_default() += 1;
+        }
+        registration.ip = ip;
+        true
+    }
+
+    /// Asks up to `count` connections idle in keep-alive to close, longest idle first.
+    fn reap_idle(state: &LimiterState, count: usize) {
+        let mut idle = state
+            .connections
+            .values()
+            .filter(|connection| connection.is_idle())
+            .collect::<Vec<_>>();
+        idle.sort_by_key(|connection| connection.idle_since.load(Ordering::Relaxed));
+        for connection in idle.into_iter().take(count) {
+            connection.request_close();
+        }
+    }
+
+    /// The number of open connections, and the clients with the most connections open.
+    pub fn summary(&self, top: usize) -> (usize, Vec<(IpAddr, usize)>) {
+        let state = self.state.lock();
+        let mut per_ip = state
+            .per_ip
+            .iter()
+            .map(|(ip, count)| (*ip, *count))
+            .collect::<Vec<_>>();
+        per_ip.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        per_ip.truncate(top);
+        (state.connections.len(), per_ip)
+    }
+
+    pub fn max_connections(&self) -> Option<usize> {
+        self.max_connections
+    }
+}
+
+impl LimiterState {
+    fn release_ip(&mut self, ip: IpAddr) {
+        if let Some(count) = self.per_ip.get_mut(&ip) {
+            *count -= 1;
+            if *count == 0 {
+                self.per_ip.remove(&ip);
+            }
+        }
+    }
+}
+
+impl ConnectionPermit {
+    /// Counts a new connection from `ip` against each limiter, or returns `None` if any rejects it.
+    pub fn acquire(limiters: &[&Arc<ConnectionLimiter>], ip: Option<IpAddr>) -> Option<Self> {
+        let connection = TrackedConnection::new();
+        let registrations = limiters
+            .iter()
+            .map(|limiter| limiter.admit(ip, &connection))
+            .collect::<Option<Vec<_>>>()?;
+        Some(Self {
+            connection,
+            registrations,
+        })
+    }
+
+    /// Counts the connection under `ip` instead of the address it was admitted from.
+    /// Returns `false` if any limiter rejects it, in which case the connection should be closed.
+    pub fn reassign(&mut self, ip: Option<IpAddr>) -> bool {
+        self.registrations
+            .iter_mut()
+            .all(|registration| registration.limiter.clone().reassign(registration, ip))
+    }
+}
+
+impl Drop for Registration {
+    fn drop(&mut self) {
+        let mut state = self.limiter.state.lock();
+        state.connections.remove(&self.id);
+        if let Some(ip) = self.ip {
+            state.release_ip(ip);
+        }
+        drop(state);
+        self.limiter.released.notify_waiters();
+    }
+}
+
+impl TrackedConnection {
+    pub fn new() -> Arc<Self> {
+        Arc::new(Self {
+            active_requests: AtomicUsize::new(0),
+            requests: AtomicU64::new(0),
+            idle_since: AtomicU64::new(elapsed_millis()),
+            close: Notify::new(),
+        })
+    }
+
+    fn is_idle(&self) -> bool {
+        self.active_requests.load(Ordering::Relaxed) == 0
+    }
+
+    pub fn start_request(self: &Arc<Self>) -> ActiveRequest {
+        self.active_requests.fetch_add(1, Ordering::Relaxed);
+        ActiveRequest {
+            connection: self.clone(),
+            number: self.requests.fetch_add(1, Ordering::Relaxed) + 1,
+        }
+    }
+
+    /// Asks the connection to close gracefully, once its in-flight requests complete.
+    pub fn request_close(&self) {
+        self.close.notify_one();
+    }
+
+    /// Resolves when the connection has been asked to close, either by a limiter making room
+    /// for others, or because it has served its last allowed request.
+    pub async fn close_requested(&self) {
+        self.close.notified().await
+    }
+
+    /// Resolves once the connection has been idle in keep-alive for `timeout`.
+    /// Never resolves if `timeout` is `None`.
+    pub async fn idle_timeout(&self, timeout: Option<Duration>) {
+        let Some(timeout) = timeout else {
+            return std::future::pending().await;
+        };
+        loop {
+            if !self.is_idle() {
+                sleep(timeout).await;
+                continue;
+            }
+            let idle_since = Duration::from_millis(self.idle_since.load(Ordering::Relaxed));
+            let idle_for = EPOCH.elapsed().saturating_sub(idle_since);
+            if idle_for >= timeout {
+                return;
+            }
+            sleep(timeout - idle_for).await;
+        }
+    }
+}
+
+impl Drop for ActiveRequest {
+    fn drop(&mut self) {
+        self.connection
+            .idle_since
+            .store(elapsed_millis(), Ordering::Relaxed);
+        self.connection
+            .active_requests
+            .fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+fn elapsed_millis() -> u64 {
+    EPOCH.elapsed().as_millis() as u64
+}
@@ -0,0 +1,182 @@
+use bytes::{Buf, Bytes};
+use http::{Request, Response, Version};
+use http_body_util::{BodyExt, StreamBody};
+use hyper::{body::Frame, client::conn::http2};
+use hyper_util::rt::{TokioExecutor, TokioIo};
+use rustls::pki_types::CertificateDer;
+use std::sync::Arc;
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tracing::debug;
+
+use crate::{
+    server::binds::{client_certificate::ClientCertificate, listener::SockAddr},
+    services::itsi_http_service::{ItsiHttpService, ItsiHttpServiceInner},
+};
+
+use super::{acceptor::AcceptorArgs, connection_limiter::ConnectionPermit};
+
+/// Size of the in-memory pipe carrying the bridged HTTP/2 connection.
+const BRIDGE_BUFFER_SIZE: usize = 64 * 1024;
+
+type BridgedBody = StreamBody<ReceiverStream<Result<Frame<Bytes>, std::io::Error>>>;
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Serves a single QUIC connection.
+///
+/// The middleware stack works with hyper's request types, which only hyper's own connection
+/// handling can produce. We therefore bridge each HTTP/3 connection to an in-memory HTTP/2
+/// connection, served by the same executor as our TCP listeners, and forward every HTTP/3
+/// request as an HTTP/2 stream.
+///
+/// The connection counts towards the worker's and bind's connection limits until `_permit` is dropped.
+pub async fn serve_quic_connection(
+    incoming: quinn::Incoming,
+    acceptor_args: Arc<AcceptorArgs>,
+    _permit: ConnectionPermit,
+) {
+    let connection = match incoming.await {
+        Ok(connection) => connection,
+        Err(e) => {
+            debug!("QUIC handshake failed: {:?}", e);
+            return;
+        }
+    };
+    let addr = SockAddr::Tcp(Arc::new(connection.remote_address())).to_string();
+    let client_cert = connection
+        .peer_identity()
+        .and_then(|identity| identity.downcast::<Vec<CertificateDer<'static>>>().ok())
+        .and_then(|certificates| ClientCertificate::from_peer_certificates(Some(&certificates)))
+        .map(Arc::new);
+
+    let mut h3_connection: h3::server::Connection<h3_quinn::Connection, Bytes> =
+        match h3::server::Connection::new(h3_quinn::Connection::new(connection)).await {
+            Ok(h3_connection) => h3_connection,
+            Err(e) => {
+                debug!("HTTP/3 connection setup failed: {:?}", e);
+                return;
+            }
+        };
+
+    let (client_io, server_io) = tokio::io::duplex(BRIDGE_BUFFER_SIZE);
+    let service = ItsiHttpService {
+        inner: Arc::new(ItsiHttpServiceInner {
+            acceptor_args: acceptor_args.clone(),
+            addr,
+            client_cert,
+        }),
+    };
+    let svc = hyper::service::service_fn(move |req| {
+        let service = service.clone();
+        async move { service.handle_request(req).await }
+    });
+    let mut serve = Box::pin(
+        acceptor_args
+            .strategy
+            .executor
+            .serve_connection(TokioIo::new(server_io), svc),
+    );
+
+    let mut shutdown_channel = acceptor_args.shutdown_receiver.clone();
+    let accept_requests = async {
+        let (sender, bridge) =
+            http2::handshake::<_, _, BridgedBody>(TokioExecutor::new(), TokioIo::new(client_io))
+                .await?;
+        tokio::spawn(async move {
+            if let Err(e) = bridge.await {
+                debug!("HTTP/3 bridge closed abruptly: {:?}", e);
+            }
+        });
+
+        loop {
+            tokio::select! {
+                accepted = h3_connection.accept() => {
+                    match accepted? {
+                        Some(resolver) => {
+                            let sender = sender.clone();
+                            tokio::spawn(async move {
+                                if let Err(e) = forward_request(resolver, sender).await {
+                                    debug!("HTTP/3 request failed: {:?}", e);
+                                }
+                            });
+                        }
+                        None => break,
+                    }
+                },
+                _ = shutdown_channel.changed() => {
+                    h3_connection.shutdown(0).await?;
+                    break;
+                }
+            }
+        }
+        Ok::<(), BoxError>(())
+    };
+
+    tokio::select! {
+        res = accept_requests => {
+            if let Err(e) = res {
+                debug!("HTTP/3 connection closed abruptly: {:?}", e);
+            }
+            // Let in-flight requests finish before the QUIC connection is dropped.
+            if let Err(e) = serve.await {
+                debug!("Connection closed abruptly: {:?}", e);
+            }
+        },
+        res = &mut serve => {
+            if let Err(e) = res {
+                debug!("Connection closed abruptly: {:?}", e);
+            }
+        }
+    }
+}
+
+async fn forward_request(
+    resolver: h3::server::RequestResolver<h3_quinn::Connection, Bytes>,
+    mut sender: http2::SendRequest<BridgedBody>,
+) -> Result<(), BoxError> {
+    let (req, stream) = resolver.resolve_request().await?;
+    let (mut send_stream, mut recv_stream) = stream.split();
+
+    let (body_sender, body_receiver) = mpsc::channel(16);
+    tokio::spawn(async move {
+        loop {
+            let frame = match recv_stream.recv_data().await {
+                Ok(Some(mut chunk)) => Ok(Frame::data(chunk.copy_to_bytes(chunk.remaining()))),
+                Ok(None) => {
+                    if let Ok(Some(trailers)) = recv_stream.recv_trailers().await {
+                        body_sender.send(Ok(Frame::trailers(trailers))).await.ok();
+                    }
+                    break;
+                }
+                Err(e) => Err(std::io::Error::other(e.to_string())),
+            };
+            let failed = frame.is_err();
+            if body_sender.send(frame).await.is_err() || failed {
+                break;
+            }
+        }
+    });
+
+    let (mut parts, ()) = req.into_parts();
+    parts.version = Version::HTTP_2;
+    let req = Request::from_parts(parts, StreamBody::new(ReceiverStream::new(body_receiver)));
+
+    sender.ready().await?;
+    let (parts, mut body) = sender.send_request(req).await?.into_parts();
+    send_stream
+        .send_response(Response::from_parts(parts, ()))
+        .await?;
+
+    while let Some(frame) = body.frame().await {
+        match frame?.into_data() {
+            Ok(data) => send_stream.send_data(data).await?,
+            Err(frame) => {
+                if let Ok(trailers) = frame.into_trailers() {
+                    send_stream.send_trailers(trailers).await?;
+                }
+            }
+        }
+    }
+    send_stream.finish().await?;
+    Ok(())
+}
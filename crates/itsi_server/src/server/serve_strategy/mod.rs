@@ -6,6 +6,8 @@ use single_mode::SingleMode;
 
 pub mod acceptor;
 pub mod cluster_mode;
+pub mod connection_limiter;
+pub mod http3;
 pub mod single_mode;
 
 #[derive(Clone)]
@@ -1,9 +1,20 @@
 use crate::{
     ruby_types::itsi_server::itsi_server_config::ItsiServerConfig,
     server::{
+        binds::{
+            bind::Bind,
+            listener::Accepted,
+            tls::{
+                acme_http01_challenges, refresh_ocsp_staples, reload_sni_certificates,
+                watch_certificate_files,
+            },
+        },
         lifecycle_event::LifecycleEvent,
         request_job::RequestJob,
-        serve_strategy::acceptor::{Acceptor, AcceptorArgs},
+        serve_strategy::{
+            acceptor::{Acceptor, AcceptorArgs},
+            connection_limiter::ConnectionLimiter,
+        },
         signal::{
             send_lifecycle_event, subscribe_runtime_to_signals, unsubscribe_runtime,
             SHUTDOWN_REQUESTED,
@@ -11,6 +22,7 @@ use crate::{
         thread_worker::{build_thread_workers, ThreadWorker},
     },
 };
+use http::HeaderValue;
 use hyper_util::{
     rt::{TokioExecutor, TokioTimer},
     server::conn::auto::Builder,
@@ -43,6 +55,10 @@ pub struct SingleMode {
     pub server_config: Arc<ItsiServerConfig>,
     pub restart_requested: AtomicBool,
     pub status: RwLock<HashMap<u8, (u64, u64)>>,
+    /// Connections open in this worker, across all binds.
+    pub connections: Arc<ConnectionLimiter>,
+    /// Connections open on each bind.
+    pub bind_connections: RwLock<Vec<(String, Arc<ConnectionLimiter>)>>,
 }
 
 #[derive(PartialEq, Debug)]
@@ -56,6 +72,13 @@ impl SingleMode {
     #[instrument(parent=None, skip_all)]
     pub fn new(server_config: Arc<ItsiServerConfig>, worker_id: usize) -> Result<Self> {
         server_config.server_params.read().preload_ruby()?;
+        let connections = {
+            let server_params = server_config.server_params.read();
+            ConnectionLimiter::new(
+                server_params.max_connections,
+                server_params.max_connections_per_ip,
+            )
+        };
         let executor = {
             let mut executor = Builder::new(TokioExecutor::new());
             let server_params = server_config.server_params.read();
@@ -86,6 +109,8 @@ impl SingleMode {
             server_config,
             restart_requested: AtomicBool::new(false),
             status: RwLock::new(HashMap::new()),
+            connections,
+            bind_connections: RwLock::new(Vec::new()),
         })
     }
 
@@ -151,6 +176,28 @@ impl SingleMode {
                 .read()
                 .oob_gc_responses_threshold
         );
+        let (open, top_clients) = self.connections.summary(10);
+        println!(
+            "    ─ connections: {} (max: {:?}, per IP: {:?})",
+            open,
+            self.connections.max_connections(),
+            self.server_config
+                .server_params
+                .read()
+                .max_connections_per_ip
+        );
+        for (bind, connections) in self.bind_connections.read().iter() {
+            let (open, _) = connections.summary(0);
+            println!(
+                "      - bind {}: {} (max: {:?})",
+                bind,
+                open,
+                connections.max_connections()
+            );
+        }
+        for (ip, count) in top_clients {
+            println!("      - client {}: {}", ip, count);
+        }
         for worker in thread_workers.iter() {
             println!("   └─ - Thread : {:?}", worker.id);
             println!("       - # Requests Processed: {:?}", worker.request_id);
@@ -216,7 +263,12 @@ impl SingleMode {
                                   }
                                   lifecycle_event = lifecycle_rx.recv() => {
                                       match lifecycle_event {
-                                          Ok(LifecycleEvent::Restart) | Ok(LifecycleEvent::Reload) => {
+                                          Ok(LifecycleEvent::Restart) => {
+                                              receiver.restart().await.ok();
+                                          }
+                                          Ok(LifecycleEvent::Reload) => {
+                                              // Swap certificates in-process first, so new handshakes use them even if the config check fails and we stay up.
+                                              reload_sni_certificates();
                                               receiver.restart().await.ok();
                                           }
                                           Ok(LifecycleEvent::Shutdown) => {
@@ -283,24 +335,87 @@ impl SingleMode {
                 .listeners
                 .lock()
                 .drain(..)
-                .map(|list| Arc::new(list.into_tokio_listener(is_zero_worker)))
+                // Listeners are built from binds in order.
+                .zip(server_params.binds.iter())
+                .map(|(list, bind)| {
+                    let bind_connections = ConnectionLimiter::new(bind.max_connections, None);
+                    Ok((
+                        Arc::new(list.into_tokio_listener(is_zero_worker, &server_params.socket_opts)?),
+                        bind_connections,
+                        bind.clone(),
+                    ))
+                })
+                .collect::<Result<Vec<_>>>();
+            let tokio_listeners = match tokio_listeners {
+                Ok(tokio_listeners) => tokio_listeners,
+                Err(err) => {
+                    error!("Failed to start listeners: {}", err);
+                    return Err(err);
+                }
+            };
+            *self.bind_connections.write() = tokio_listeners
+                .iter()
+                .map(|(listener, connections, _)| {
+                    let info = listener.listener_info();
+                    (
+                        format!("{}://{}:{}", info.scheme, info.host, info.port),
+                        connections.clone(),
+                    )
+                })
+                .collect();
+
+            listener_task_set.spawn(refresh_ocsp_staples(shutdown_sender.subscribe()));
+            listener_task_set.spawn(watch_certificate_files(shutdown_sender.subscribe()));
+
+            // HTTPS listeners advertise any HTTP/3 listeners, so clients can upgrade to QUIC.
+            let alt_svc = tokio_listeners
+                .iter()
+                .filter_map(|(listener, _, _)| listener.quic_port())
+                .map(|port| format!("h3=\":{}\"; ma=86400", port))
                 .collect::<Vec<_>>();
+            let alt_svc = (!alt_svc.is_empty())
+                .then(|| HeaderValue::from_str(&alt_svc.join(", ")).ok())
+                .flatten();
+
+            // Only servers with an http-01 ACME bind answer challenges, and only on the binds the CA can reach.
+            let acme_http01 = server_params
+                .binds
+                .iter()
+                .any(Bind::uses_acme_http01)
+                .then(acme_http01_challenges);
 
-            tokio_listeners.iter().cloned().for_each(|listener| {
+            tokio_listeners.iter().cloned().for_each(|(listener, bind_connections, bind)| {
                 let shutdown_sender = shutdown_sender.clone();
                 let job_sender = job_sender.clone();
                 let nonblocking_sender = nonblocking_sender.clone();
 
                 let mut lifecycle_rx = subscribe_runtime_to_signals();
                 let mut shutdown_receiver = shutdown_sender.subscribe();
+                let listener_info = listener.listener_info();
+                let alt_svc = if listener_info.scheme == "https" && listener.quic_port().is_none() {
+                    alt_svc.clone()
+                } else {
+                    None
+                };
                 let mut acceptor = Acceptor {
                     acceptor_args: Arc::new(AcceptorArgs {
                         strategy: self.clone(),
-                        listener_info: listener.listener_info(),
+                        listener_info,
                         shutdown_receiver: shutdown_sender.subscribe(),
                         job_sender: job_sender.clone(),
                         nonblocking_sender: nonblocking_sender.clone(),
                         server_params: server_params.clone(),
+                        alt_svc,
+                        acme_http01: acme_http01
+                            .clone()
+                            .filter(|_| bind.answers_acme_http01()),
+                        bind_connections,
+                        keep_alive_timeout: bind
+                            .keep_alive_timeout
+                            .or(server_params.keep_alive_timeout),
+                        max_requests_per_connection: bind
+                            .max_requests_per_connection
+                            .or(server_params.max_requests_per_connection),
                     }),
                     join_set: JoinSet::new(),
                 };
@@ -326,11 +441,17 @@ impl SingleMode {
 
                 listener_task_set.spawn(async move {
                     loop {
+                        let acceptor_args = acceptor.acceptor_args.clone();
                         // Process any pending signals before select
                         tokio::select! {
-                            accept_result = listener.accept() => {
+                            // Once we reach max_connections, connections queue in the listen backlog.
+                            accept_result = async {
+                                Acceptor::wait_for_capacity(&acceptor_args).await;
+                                listener.accept().await
+                            } => {
                                 match accept_result {
-                                    Ok(accepted) => acceptor.serve_connection(accepted).await,
+                                    Ok(Accepted::Stream(pending)) => acceptor.serve_connection(pending).await,
+                                    Ok(Accepted::Quic(incoming)) => acceptor.serve_quic_connection(incoming).await,
                                     Err(e) => debug!("Listener.accept failed: {:?}", e)
                                 }
                                 if cfg!(target_os = "macos") {
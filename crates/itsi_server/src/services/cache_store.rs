@@ -44,6 +44,11 @@ impl RedisCacheStore {
             connection: Arc::new(connection_manager),
         })
     }
+
+    /// A handle to the underlying connection, for stores that need more than [CacheStore].
+    pub fn connection(&self) -> ConnectionManager {
+        (*self.connection).clone()
+    }
 }
 
 #[async_trait]
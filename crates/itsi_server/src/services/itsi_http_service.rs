@@ -1,18 +1,23 @@
 use crate::default_responses::{NOT_FOUND_RESPONSE, TIMEOUT_RESPONSE};
 use crate::ruby_types::itsi_server::itsi_server_config::ItsiServerTokenPreference;
+use crate::server::binds::client_certificate::ClientCertificate;
 use crate::server::http_message_types::{
-    ConversionExt, HttpRequest, HttpResponse, RequestExt, ResponseFormat,
+    ConversionExt, HttpBody, HttpRequest, HttpResponse, RequestExt, ResponseFormat,
 };
 use crate::server::lifecycle_event::LifecycleEvent;
-use crate::server::middleware_stack::MiddlewareLayer;
+use crate::server::middleware_stack::{claim_at, claim_to_string, MiddlewareLayer};
 use crate::server::serve_strategy::acceptor::AcceptorArgs;
 use crate::server::signal::{send_lifecycle_event, SHUTDOWN_REQUESTED};
+use crate::services::response_cache::ResponseCacheState;
+use bytes::Bytes;
 use chrono::{self, DateTime, Local};
 use either::Either;
-use http::header::ACCEPT_ENCODING;
-use http::{HeaderValue, Request};
+use http::header::{ACCEPT_ENCODING, ALT_SVC, CONTENT_TYPE};
+use http::{HeaderValue, Request, Response};
 use hyper::body::Incoming;
+use itsi_acme::challenges::{Http01Challenges, HTTP_01_CHALLENGE_PATH};
 use regex::Regex;
+use serde_json::{Map, Value};
 use smallvec::SmallVec;
 use std::ops::Deref;
 use std::sync::atomic::{AtomicBool, Ordering};
@@ -37,6 +42,7 @@ impl Deref for ItsiHttpService {
 pub struct ItsiHttpServiceInner {
     pub acceptor_args: Arc<AcceptorArgs>,
     pub addr: String,
+    pub client_cert: Option<Arc<ClientCertificate>>,
 }
 
 impl Deref for ItsiHttpServiceInner {
@@ -79,6 +85,13 @@ pub struct RequestContextInner {
     pub start_instant: Instant,
     pub if_none_match: OnceLock<Option<String>>,
     pub supported_encoding_set: OnceLock<AcceptEncodingSet>,
+    pub response_cache_state: OnceLock<ResponseCacheState>,
+    /// The claims of a JWT verified by `auth_jwt`.
+    pub jwt_claims: OnceLock<Map<String, Value>>,
+    /// A session cookie refreshed by `auth_oidc`, to set on the response.
+    pub session_cookie: OnceLock<HeaderValue>,
+    /// Set once an auth middleware has authenticated the request.
+    pub authenticated: AtomicBool,
     pub is_ruby_request: Arc<AtomicBool>,
 }
 
@@ -103,6 +116,10 @@ impl HttpRequestContext {
                 start_instant: Instant::now(),
                 if_none_match: OnceLock::new(),
                 supported_encoding_set: OnceLock::new(),
+                response_cache_state: OnceLock::new(),
+                jwt_claims: OnceLock::new(),
+                session_cookie: OnceLock::new(),
+                authenticated: AtomicBool::new(false),
                 is_ruby_request,
             }),
         }
@@ -132,6 +149,39 @@ impl HttpRequestContext {
         self.inner.if_none_match.get().cloned().flatten()
     }
 
+    pub fn set_response_cache_state(&self, state: ResponseCacheState) {
+        self.inner.response_cache_state.set(state).ok();
+    }
+
+    pub fn response_cache_state(&self) -> Option<&ResponseCacheState> {
+        self.inner.response_cache_state.get()
+    }
+
+    pub fn set_jwt_claims(&self, claims: Map<String, Value>) {
+        self.inner.jwt_claims.set(claims).ok();
+    }
+
+    /// A verified JWT claim, formatted for use in a header or log line.
+    pub fn jwt_claim(&self, path: &str) -> Option<String> {
+        claim_at(self.inner.jwt_claims.get()?, path).and_then(claim_to_string)
+    }
+
+    pub fn set_session_cookie(&self, cookie: HeaderValue) {
+        self.inner.session_cookie.set(cookie).ok();
+    }
+
+    pub fn session_cookie(&self) -> Option<&HeaderValue> {
+        self.inner.session_cookie.get()
+    }
+
+    pub fn set_authenticated(&self) {
+        self.inner.authenticated.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_authenticated(&self) -> bool {
+        self.inner.authenticated.load(Ordering::Relaxed)
+    }
+
     pub fn short_request_id(&self) -> String {
         format!("{:08x}", self.inner.request_id & 0xffff_ffff)
     }
@@ -188,6 +238,12 @@ impl ItsiHttpService {
         let token_preference = self.server_params.itsi_server_token_preference;
 
         let service_future = async move {
+            if let Some(challenges) = self.acme_http01.as_deref() {
+                if let Some(resp) = acme_http01_response(&req, challenges).await {
+                    return Ok(resp);
+                }
+            }
+
             let middleware_stack = self
                 .server_params
                 .middleware
@@ -236,6 +292,12 @@ impl ItsiHttpService {
                 ItsiServerTokenPreference::None => {}
             }
 
+            if let Some(alt_svc) = self.acceptor_args.alt_svc.as_ref() {
+                if !resp.headers().contains_key(ALT_SVC) {
+                    resp.headers_mut().insert(ALT_SVC, alt_svc.clone());
+                }
+            }
+
             Ok(resp)
         };
 
@@ -268,3 +330,22 @@ impl ItsiHttpService {
         }
     }
 }
+
+/// Answers ACME http-01 challenges ahead of the middleware stack,
+/// so validation succeeds however the routes of the bind are configured.
+async fn acme_http01_response(
+    req: &HttpRequest,
+    challenges: &Http01Challenges,
+) -> Option<HttpResponse> {
+    let path = req.uri().path();
+    if !path.starts_with(HTTP_01_CHALLENGE_PATH) {
+        return None;
+    }
+    let key_authorization = challenges.respond(path).await?;
+    let mut resp = Response::new(HttpBody::full(Bytes::from(key_authorization)));
+    resp.headers_mut().insert(
+        CONTENT_TYPE,
+        HeaderValue::from_static("application/octet-stream"),
+    );
+    Some(resp)
+}
@@ -3,4 +3,5 @@ pub mod itsi_http_service;
 pub mod mime_types;
 pub mod password_hasher;
 pub mod rate_limiter;
+pub mod response_cache;
 pub mod static_file_server;
@@ -59,6 +59,13 @@ pub fn create_password_hash(password: String, algo: Value) -> Result<String> {
     }
 }
 
+/// Whether `hash` is in a format [verify_password_hash] understands.
+pub fn is_supported_hash(hash: &str) -> bool {
+    ["$2a$", "$2b$", "$2y$", "$5$", "$6$", "$argon2", "$none$"]
+        .iter()
+        .any(|prefix| hash.starts_with(prefix))
+}
+
 pub fn verify_password_hash(password: &str, hash: &str) -> Result<bool> {
     if hash.starts_with("$2a$") || hash.starts_with("$2b$") || hash.starts_with("$2y$") {
         Ok(bcrypt::verify(password, hash).map_err(ItsiError::new)?)
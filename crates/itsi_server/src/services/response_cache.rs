@@ -0,0 +1,532 @@
+use base64::{engine::general_purpose, Engine};
+use bytes::Bytes;
+use http::{header, HeaderMap, HeaderName, HeaderValue, Response, StatusCode};
+use parking_lot::Mutex;
+use quick_cache::sync::Cache;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    sync::{Arc, LazyLock, Weak},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use tokio::sync::watch;
+use tracing::{debug, warn};
+use url::Url;
+
+use crate::server::http_message_types::{HttpBody, HttpResponse};
+
+/// Every live response cache store, so that purges can reach all of them.
+static RESPONSE_CACHE_STORES: LazyLock<Mutex<Vec<Weak<ResponseCacheStore>>>> =
+    LazyLock::new(|| Mutex::new(Vec::new()));
+
+/// The number of distinct `Vary` variants kept per cache key.
+const MAX_VARIANTS: usize = 8;
+
+static X_CACHE: HeaderName = HeaderName::from_static("x-cache");
+pub static CACHE_HIT: HeaderValue = HeaderValue::from_static("HIT");
+pub static CACHE_MISS: HeaderValue = HeaderValue::from_static("MISS");
+pub static CACHE_STALE: HeaderValue = HeaderValue::from_static("STALE");
+
+/// Freshness lifetimes extracted from a response's `Cache-Control` header.
+#[derive(Debug, Clone, Copy)]
+pub struct CachePolicy {
+    pub ttl: Duration,
+    pub stale_while_revalidate: Duration,
+    pub stale_if_error: Duration,
+}
+
+impl CachePolicy {
+    /// Determines whether a response may be stored by a shared cache, and for how long.
+    /// Returns None for responses that must not be stored.
+    pub fn from_response_headers(headers: &HeaderMap, default_ttl: Option<u64>) -> Option<Self> {
+        if headers.contains_key(header::SET_COOKIE) {
+            return None;
+        }
+
+        if headers
+            .get_all(header::VARY)
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .any(|v| v.split(',').any(|name| name.trim() == "*"))
+        {
+            return None;
+        }
+
+        let mut max_age = None;
+        let mut s_max_age = None;
+        let mut stale_while_revalidate = 0;
+        let mut stale_if_error = 0;
+
+        for value in headers
+            .get_all(header::CACHE_CONTROL)
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+        {
+            for directive in value.split(',').map(|d| d.trim()) {
+                let (name, argument) = match directive.split_once('=') {
+                    Some((name, argument)) => {
+                        (name.trim(), Some(argument.trim().trim_matches('"')))
+                    }
+                    None => (directive, None),
+                };
+                let seconds = argument.and_then(|a| a.parse::<u64>().ok());
+                match name.to_ascii_lowercase().as_str() {
+                    "no-store" | "no-cache" | "private" => return None,
+                    "max-age" => max_age = seconds,
+                    "s-maxage" => s_max_age = seconds,
+                    "stale-while-revalidate" => stale_while_revalidate = seconds.unwrap_or(0),
+                    "stale-if-error" => stale_if_error = seconds.unwrap_or(0),
+                    _ => {}
+                }
+            }
+        }
+
+        let ttl = s_max_age.or(max_age).or(default_ttl)?;
+        if ttl == 0 {
+            return None;
+        }
+
+        Some(Self {
+            ttl: Duration::from_secs(ttl),
+            stale_while_revalidate: Duration::from_secs(stale_while_revalidate),
+            stale_if_error: Duration::from_secs(stale_if_error),
+        })
+    }
+}
+
+#[derive(Debug)]
+pub enum Freshness {
+    Fresh,
+    /// Stale, but may be served while a single request revalidates it.
+    Revalidate,
+    Stale,
+}
+
+#[derive(Debug, Clone)]
+pub struct CachedResponse {
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    pub body: Bytes,
+    /// The request header values the response varies on, captured when it was stored.
+    pub vary: Vec<(HeaderName, Option<HeaderValue>)>,
+    pub stored_at: SystemTime,
+    pub policy: CachePolicy,
+}
+
+impl CachedResponse {
+    pub fn new(
+        status: StatusCode,
+        headers: HeaderMap,
+        body: Bytes,
+        request_headers: &HeaderMap,
+        policy: CachePolicy,
+    ) -> Self {
+        let vary = headers
+            .get_all(header::VARY)
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .flat_map(|v| v.split(','))
+            .filter_map(|name| name.trim().parse::<HeaderName>().ok())
+            .map(|name| {
+                let value = request_headers.get(&name).cloned();
+                (name, value)
+            })
+            .collect();
+
+        Self {
+            status,
+            headers,
+            body,
+            vary,
+            stored_at: SystemTime::now(),
+            policy,
+        }
+    }
+
+    pub fn age(&self) -> Duration {
+        SystemTime::now()
+            .duration_since(self.stored_at)
+            .unwrap_or_default()
+    }
+
+    pub fn freshness(&self) -> Freshness {
+        let age = self.age();
+        if age < self.policy.ttl {
+            Freshness::Fresh
+        } else if age < self.policy.ttl + self.policy.stale_while_revalidate {
+            Freshness::Revalidate
+        } else {
+            Freshness::Stale
+        }
+    }
+
+    pub fn usable_on_error(&self) -> bool {
+        self.age() < self.policy.ttl + self.policy.stale_if_error
+    }
+
+    /// True once the entry can no longer be served under any circumstance.
+    fn is_expired(&self) -> bool {
+        let age = self.age();
+        age >= self.policy.ttl + self.policy.stale_while_revalidate
+            && age >= self.policy.ttl + self.policy.stale_if_error
+    }
+
+    pub fn matches(&self, request_headers: &HeaderMap) -> bool {
+        self.vary
+            .iter()
+            .all(|(name, value)| request_headers.get(name) == value.as_ref())
+    }
+
+    pub fn to_http_response(&self, cache_status: &HeaderValue) -> HttpResponse {
+        let mut response = Response::new(HttpBody::full(self.body.clone()));
+        *response.status_mut() = self.status;
+        *response.headers_mut() = self.headers.clone();
+        if let Ok(age) = HeaderValue::from_str(&self.age().as_secs().to_string()) {
+            response.headers_mut().insert(header::AGE, age);
+        }
+        mark_cache_status(&mut response, cache_status);
+        response
+    }
+}
+
+pub fn mark_cache_status(response: &mut HttpResponse, cache_status: &HeaderValue) {
+    response
+        .headers_mut()
+        .insert(X_CACHE.clone(), cache_status.clone());
+}
+
+/// Serialized form of a cached response, as written to the disk cache.
+#[derive(Debug, Serialize, Deserialize)]
+struct DiskEntry {
+    status: u16,
+    headers: Vec<(String, DiskHeaderValue)>,
+    body: String,
+    vary: Vec<(String, Option<DiskHeaderValue>)>,
+    stored_at: u64,
+    ttl: u64,
+    stale_while_revalidate: u64,
+    stale_if_error: u64,
+}
+
+/// Header values are stored as text where possible, and as raw bytes otherwise,
+/// so values that aren't valid UTF-8 survive a round trip through the disk cache.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+enum DiskHeaderValue {
+    Text(String),
+    Bytes(Vec<u8>),
+}
+
+impl From<&HeaderValue> for DiskHeaderValue {
+    fn from(value: &HeaderValue) -> Self {
+        match value.to_str() {
+            Ok(text) => Self::Text(text.to_string()),
+            Err(_) => Self::Bytes(value.as_bytes().to_vec()),
+        }
+    }
+}
+
+impl TryFrom<DiskHeaderValue> for HeaderValue {
+    type Error = String;
+
+    fn try_from(value: DiskHeaderValue) -> Result<Self, Self::Error> {
+        match value {
+            DiskHeaderValue::Text(text) => HeaderValue::from_str(&text),
+            DiskHeaderValue::Bytes(bytes) => HeaderValue::from_bytes(&bytes),
+        }
+        .map_err(|e| e.to_string())
+    }
+}
+
+impl From<&CachedResponse> for DiskEntry {
+    fn from(entry: &CachedResponse) -> Self {
+        Self {
+            status: entry.status.as_u16(),
+            headers: entry
+                .headers
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.into()))
+                .collect(),
+            body: general_purpose::STANDARD.encode(&entry.body),
+            vary: entry
+                .vary
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.as_ref().map(Into::into)))
+                .collect(),
+            stored_at: entry
+                .stored_at
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+            ttl: entry.policy.ttl.as_secs(),
+            stale_while_revalidate: entry.policy.stale_while_revalidate.as_secs(),
+            stale_if_error: entry.policy.stale_if_error.as_secs(),
+        }
+    }
+}
+
+impl TryFrom<DiskEntry> for CachedResponse {
+    type Error = String;
+
+    fn try_from(entry: DiskEntry) -> Result<Self, Self::Error> {
+        let mut headers = HeaderMap::new();
+        for (name, value) in entry.headers {
+            let name = name.parse::<HeaderName>().map_err(|e| e.to_string())?;
+            headers.append(name, HeaderValue::try_from(value)?);
+        }
+        let vary = entry
+            .vary
+            .into_iter()
+            .map(|(name, value)| {
+                let name = name.parse::<HeaderName>().map_err(|e| e.to_string())?;
+                let value = value.map(HeaderValue::try_from).transpose()?;
+                Ok((name, value))
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+
+        Ok(Self {
+            status: StatusCode::from_u16(entry.status).map_err(|e| e.to_string())?,
+            headers,
+            body: general_purpose::STANDARD
+                .decode(entry.body)
+                .map_err(|e| e.to_string())?
+                .into(),
+            vary,
+            stored_at: UNIX_EPOCH + Duration::from_secs(entry.stored_at),
+            policy: CachePolicy {
+                ttl: Duration::from_secs(entry.ttl),
+                stale_while_revalidate: Duration::from_secs(entry.stale_while_revalidate),
+                stale_if_error: Duration::from_secs(entry.stale_if_error),
+            },
+        })
+    }
+}
+
+type Variants = Arc<Vec<Arc<CachedResponse>>>;
+
+/// Marks a request as the single upstream fetch for a cache key.
+/// Requests coalesced behind it are woken when the lease is dropped.
+pub struct CacheLease {
+    key: String,
+    store: Arc<ResponseCacheStore>,
+    _sender: watch::Sender<()>,
+}
+
+impl Drop for CacheLease {
+    fn drop(&mut self) {
+        self.store.in_flight.lock().remove(&self.key);
+    }
+}
+
+/// Per-request response cache state, carried from the before to the after hook.
+pub struct ResponseCacheState {
+    pub key: String,
+    pub request_headers: HeaderMap,
+    pub lease: Mutex<Option<CacheLease>>,
+    pub stale: Option<Arc<CachedResponse>>,
+}
+
+#[derive(Debug)]
+pub struct ResponseCacheStore {
+    memory: Cache<String, Variants>,
+    disk_path: Option<PathBuf>,
+    in_flight: Mutex<HashMap<String, watch::Receiver<()>>>,
+}
+
+impl ResponseCacheStore {
+    pub fn new(max_entries: usize, disk_path: Option<PathBuf>) -> std::io::Result<Arc<Self>> {
+        if let Some(disk_path) = disk_path.as_ref() {
+            std::fs::create_dir_all(disk_path)?;
+        }
+        let store = Arc::new(Self {
+            memory: Cache::new(max_entries),
+            disk_path,
+            in_flight: Mutex::new(HashMap::new()),
+        });
+        let mut stores = RESPONSE_CACHE_STORES.lock();
+        stores.retain(|store| store.strong_count() > 0);
+        stores.push(Arc::downgrade(&store));
+        Ok(store)
+    }
+
+    pub fn key_for(method: &str, host: &str, path_and_query: &str) -> String {
+        format!("{} {}{}", method, host, path_and_query)
+    }
+
+    fn disk_file(&self, key: &str) -> Option<PathBuf> {
+        self.disk_path.as_ref().map(|dir| {
+            let digest = Sha256::digest(key.as_bytes());
+            let name: String = digest.iter().map(|b| format!("{:02x}", b)).collect();
+            dir.join(format!("{}.json", name))
+        })
+    }
+
+    async fn load_variants(&self, key: &str) -> Option<Variants> {
+        if let Some(variants) = self.memory.get(key) {
+            return Some(variants);
+        }
+        let path = self.disk_file(key)?;
+        let contents = tokio::fs::read(&path).await.ok()?;
+        let entries: Vec<DiskEntry> = match serde_json::from_slice(&contents) {
+            Ok(entries) => entries,
+            Err(e) => {
+                warn!(
+                    "Discarding unreadable response cache file {:?}: {}",
+                    path, e
+                );
+                tokio::fs::remove_file(&path).await.ok();
+                return None;
+            }
+        };
+        let variants: Variants = Arc::new(
+            entries
+                .into_iter()
+                .filter_map(|entry| CachedResponse::try_from(entry).ok())
+                .filter(|entry| !entry.is_expired())
+                .map(Arc::new)
+                .collect(),
+        );
+        if variants.is_empty() {
+            return None;
+        }
+        self.memory.insert(key.to_string(), variants.clone());
+        Some(variants)
+    }
+
+    pub async fn lookup(
+        &self,
+        key: &str,
+        request_headers: &HeaderMap,
+    ) -> Option<Arc<CachedResponse>> {
+        let variants = self.load_variants(key).await?;
+        variants
+            .iter()
+            .find(|entry| entry.matches(request_headers) && !entry.is_expired())
+            .cloned()
+    }
+
+    pub async fn store(&self, key: &str, entry: CachedResponse) {
+        let entry = Arc::new(entry);
+        let mut variants: Vec<Arc<CachedResponse>> = self
+            .load_variants(key)
+            .await
+            .map(|variants| {
+                variants
+                    .iter()
+                    .filter(|existing| existing.vary != entry.vary && !existing.is_expired())
+                    .cloned()
+                    .collect()
+            })
+            .unwrap_or_default();
+        variants.insert(0, entry);
+        variants.truncate(MAX_VARIANTS);
+
+        if let Some(path) = self.disk_file(key) {
+            let disk_entries: Vec<DiskEntry> = variants.iter().map(|v| v.as_ref().into()).collect();
+            match serde_json::to_vec(&disk_entries) {
+                Ok(contents) => {
+                    // Write to a temporary file first, so other workers never read a partial entry.
+                    let tmp_path = path.with_extension(format!("tmp{}", std::process::id()));
+                    let result = async {
+                        tokio::fs::write(&tmp_path, contents).await?;
+                        tokio::fs::rename(&tmp_path, &path).await
+                    }
+                    .await;
+                    if let Err(e) = result {
+                        warn!("Failed to write response cache file {:?}: {}", path, e);
+                    }
+                }
+                Err(e) => warn!("Failed to serialize response cache entry: {}", e),
+            }
+        }
+
+        debug!(target: "middleware::response_cache", "Stored {} variant(s) for {}", variants.len(), key);
+        self.memory.insert(key.to_string(), Arc::new(variants));
+    }
+
+    /// Attempts to become the single in-flight fetch for `key`.
+    /// If another request already holds the lease, returns a receiver that resolves once it is released.
+    pub fn try_lease(self: &Arc<Self>, key: &str) -> Result<CacheLease, watch::Receiver<()>> {
+        let mut in_flight = self.in_flight.lock();
+        if let Some(receiver) = in_flight.get(key) {
+            return Err(receiver.clone());
+        }
+        let (sender, receiver) = watch::channel(());
+        in_flight.insert(key.to_string(), receiver);
+        Ok(CacheLease {
+            key: key.to_string(),
+            store: self.clone(),
+            _sender: sender,
+        })
+    }
+
+    /// Removes the given keys from memory and disk, returning how many were present.
+    pub fn purge(&self, keys: &[String]) -> usize {
+        keys.iter()
+            .filter(|key| {
+                let in_memory = self.memory.remove(key.as_str()).is_some();
+                let on_disk = self
+                    .disk_file(key)
+                    .is_some_and(|path| std::fs::remove_file(path).is_ok());
+                in_memory || on_disk
+            })
+            .count()
+    }
+
+    pub fn purge_all(&self) -> usize {
+        let mut purged = self.memory.len();
+        self.memory.clear();
+        if let Some(dir) = self.disk_path.as_ref() {
+            if let Ok(entries) = std::fs::read_dir(dir) {
+                for entry in entries.flatten() {
+                    let path = entry.path();
+                    if path.extension().is_some_and(|ext| ext == "json")
+                        && std::fs::remove_file(&path).is_ok()
+                    {
+                        purged += 1;
+                    }
+                }
+            }
+        }
+        purged
+    }
+}
+
+/// Purges cached responses for the given URL (all cacheable methods) from every
+/// response cache in this process, and from any disk caches they share with other workers.
+/// Purges everything when no URL is given.
+pub fn purge_response_cache(url: Option<String>) -> magnus::error::Result<usize> {
+    let stores: Vec<Arc<ResponseCacheStore>> = RESPONSE_CACHE_STORES
+        .lock()
+        .iter()
+        .filter_map(Weak::upgrade)
+        .collect();
+
+    let Some(url) = url else {
+        return Ok(stores.iter().map(|store| store.purge_all()).sum());
+    };
+
+    let parsed = Url::parse(&url).map_err(|e| {
+        magnus::Error::new(
+            magnus::exception::arg_error(),
+            format!("Invalid URL {}: {}", url, e),
+        )
+    })?;
+    let host = match (parsed.host_str(), parsed.port()) {
+        (Some(host), Some(port)) => format!("{}:{}", host, port),
+        (Some(host), None) => host.to_string(),
+        (None, _) => String::new(),
+    };
+    let path_and_query = match parsed.query() {
+        Some(query) => format!("{}?{}", parsed.path(), query),
+        None => parsed.path().to_string(),
+    };
+    let keys = ["GET", "HEAD"]
+        .iter()
+        .map(|method| ResponseCacheStore::key_for(method, &host, &path_and_query))
+        .collect::<Vec<_>>();
+
+    Ok(stores.iter().map(|store| store.purge(&keys)).sum())
+}
@@ -64,6 +64,12 @@ module Itsi
       env["rack.url_scheme"] = scheme
       env["rack.input"] = build_input_io
       env["rack.hijack"] = method(:hijack)
+      if (client_cert_subject = self.client_cert_subject)
+        env["SSL_CLIENT_VERIFY"] = "SUCCESS"
+        env["SSL_CLIENT_S_DN"] = client_cert_subject
+        env["SSL_CLIENT_SAN"] = client_cert_sans.join(",")
+        env["SSL_CLIENT_FINGERPRINT"] = client_cert_fingerprint
+      end
       each_header do |k, v|
         env[case k
             when "content-type" then "CONTENT_TYPE"
@@ -15,7 +15,7 @@ module Itsi
         if File.exist?(filename)
           File.foreach(filename) do |line|
             line.chomp!
-            next if line.empty?
+            next if line.empty? || line.start_with?("#")
 
             user, pass = line.split(":", 2)
             creds[user] = pass
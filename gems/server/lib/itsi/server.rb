@@ -129,6 +129,13 @@ module Itsi
         Process.kill(:USR1, pid)
       end
 
+      # Purges responses stored by the response_cache middleware for the given URL,
+      # or all stored responses if no URL is given.
+      # In-memory entries are per worker process, disk entries are shared.
+      def purge_response_cache(url = nil)
+        purge_response_cache_for(url)
+      end
+
       def passfile(options, subcmd)
         filename = options[:passfile]
         unless filename || subcmd == "echo"
@@ -154,6 +161,24 @@ module Itsi
         end
       end
 
+      def dev_ca(_options, subcmd)
+        case subcmd
+        when "path"
+          puts dev_ca_path
+        when "install"
+          install_dev_ca.each { |outcome| puts outcome }
+        when "list"
+          certificates = dev_ca_certificates
+          puts "No certificates issued yet." if certificates.empty?
+          certificates.each do |hosts, path, expires_at|
+            puts "#{hosts.join(", ")}\n    #{path} (expires #{Time.at(expires_at).utc.strftime("%Y-%m-%d")})"
+          end
+        else
+          puts "Valid subcommands are: path | install | list"
+          exit(0)
+        end
+      end
+
       def unique_path(dir, filename)
         base = File.basename(filename, ".*")
         ext  = File.extname(filename)
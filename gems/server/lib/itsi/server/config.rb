@@ -150,6 +150,10 @@ module Itsi
           max_local_error_reset_streams: itsifile_config.fetch(:max_local_error_reset_streams, nil),
           max_header_list_size: itsifile_config.fetch(:max_header_list_size, 2 * 1024 * 1024),
           max_send_buf_size: itsifile_config.fetch(:max_send_buf_size, 64 * 1024),
+          max_connections: itsifile_config.fetch(:max_connections, nil),
+          max_connections_per_ip: itsifile_config.fetch(:max_connections_per_ip, nil),
+          keep_alive_timeout: itsifile_config.fetch(:keep_alive_timeout, nil),
+          max_requests_per_connection: itsifile_config.fetch(:max_requests_per_connection, nil),
           binds: args.fetch(:binds) { itsifile_config.fetch(:binds, ["http://0.0.0.0:3000"]) },
           middleware_loader: middleware_loader,
           listeners: args.fetch(:listeners, nil),
@@ -158,7 +162,13 @@ module Itsi
           listen_backlog: itsifile_config.fetch(:listen_backlog, 1024),
           nodelay: itsifile_config.fetch(:nodelay, true),
           recv_buffer_size: itsifile_config.fetch(:recv_buffer_size, 262_144),
-          send_buffer_size: itsifile_config.fetch(:send_buffer_size, 262_144)
+          send_buffer_size: itsifile_config.fetch(:send_buffer_size, 262_144),
+          tcp_keepalive: itsifile_config[:tcp_keepalive]&.transform_keys(&:to_s),
+          tcp_fastopen: itsifile_config.fetch(:tcp_fastopen, nil),
+          tcp_defer_accept: itsifile_config.fetch(:tcp_defer_accept, nil),
+          socket_mark: itsifile_config.fetch(:socket_mark, nil),
+          ip_freebind: itsifile_config.fetch(:ip_freebind, false),
+          ip_transparent: itsifile_config.fetch(:ip_transparent, false)
         }.transform_keys(&:to_s)
 
         [srv_config, errors_to_error_lines(errors)]
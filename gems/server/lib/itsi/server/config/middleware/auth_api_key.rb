@@ -33,15 +33,16 @@ module Itsi
             @params[:key_id_source] = nil
           end
 
-          if File.exist?(".itsi-credentials") && !@params[:credentials_file]
+          if File.exist?(".itsi-credentials") && !@params[:credentials_file] && !@params[:valid_keys]&.any?
             @params[:credentials_file] = ".itsi-credentials"
           end
 
-          if @params[:credentials_file] && File.exist?(@params[:credentials_file])
-            @params[:valid_keys] = Passfile.load(@params[:credentials_file])
+          # Credentials files are read (and re-read when they change) by the server.
+          if @params[:credentials_file] && !File.exist?(@params[:credentials_file])
+            raise "Credentials file #{@params[:credentials_file]} not found"
           end
 
-          unless @params[:valid_keys]&.any?
+          unless @params[:valid_keys]&.any? || @params[:credentials_file]
             raise "No credentials provided"
           end
         end
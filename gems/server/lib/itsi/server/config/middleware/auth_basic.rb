@@ -22,20 +22,18 @@ module Itsi
         def initialize(location, params={})
           super
 
-          unless @params[:credential_pairs]&.any?
-            if File.exist?(".itsi-credentials") && !@params[:credentials_file]
-              @params[:credentials_file] = ".itsi-credentials"
-            end
-
-            if @params[:credentials_file] && File.exist?(@params[:credentials_file])
-              @params[:credential_pairs] = Passfile.load(@params[:credentials_file])
-            end
+          if File.exist?(".itsi-credentials") && !@params[:credentials_file] && !@params[:credential_pairs]&.any?
+            @params[:credentials_file] = ".itsi-credentials"
           end
 
-          raise "No credentials provided" unless @params[:credential_pairs]
-          @params[:credential_pairs].compact!
+          # Credentials files are read (and re-read when they change) by the server.
+          if @params[:credentials_file] && !File.exist?(@params[:credentials_file])
+            raise "Credentials file #{@params[:credentials_file]} not found"
+          end
+
+          @params[:credential_pairs] = (@params[:credential_pairs] || {}).compact
 
-          unless @params[:credential_pairs]&.any?
+          unless @params[:credential_pairs].any? || @params[:credentials_file]
             raise "No credentials provided"
           end
         end
@@ -19,17 +19,33 @@ module Itsi
         schema do
           {
             token_source: (Type(TokenSource) & Required()).default({header: {name: 'Authorization', prefix: 'Bearer '}}),
-            verifiers: (Hash(Type(String), Array(Type(String)) & Length(1..1024))) & Required() & Length(1..32),
+            verifiers: Hash(Type(String), Array(Type(String)) & Length(1..1024)),
+            jwks_url: Type(String),
+            jwks_refresh_interval: (Type(Integer) & Range(1..Float::INFINITY)).default(300),
             audiences: Array(Type(String)),
             subjects: Array(Type(String)),
             issuers: Array(Type(String)),
-            leeway: Type(Integer)
+            leeway: Type(Integer),
+            claims_to_headers: Hash(Type(String), Type(String)),
+            required_claims: Hash(Type(String), Type(Hash))
           }
         end
 
         def initialize(location, params)
           super
-          @params[:verifiers].transform_keys!{|k| k.to_s.downcase }
+          @params[:verifiers] = (@params[:verifiers] || {}).transform_keys { |k| k.to_s.downcase }
+          if @params[:verifiers].empty? && !@params[:jwks_url]
+            raise "auth_jwt requires either verifiers or a jwks_url"
+          end
+          @params[:claims_to_headers] = (@params[:claims_to_headers] || {}).transform_keys(&:to_s)
+          @params[:required_claims] = (@params[:required_claims] || {}).to_h do |claim, requirement|
+            check, value = requirement.first
+            unless requirement.size == 1 && %w[equals contains].include?(check.to_s)
+              raise "Invalid requirement for claim #{claim}: expected {equals: value} or {contains: value}"
+            end
+
+            [claim.to_s, { check.to_s => value }]
+          end
         end
 
       end
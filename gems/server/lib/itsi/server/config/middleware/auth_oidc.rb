@@ -0,0 +1,51 @@
+module Itsi
+  class Server
+    module Config
+      class AuthOidc < Middleware
+        require_relative "error_response"
+
+        insert_text <<~SNIPPET
+        auth_oidc \\
+          issuer: ${1:"https://accounts.example.com"},
+          client_id: ${2:ENV['OIDC_CLIENT_ID']},
+          client_secret: ${3:ENV['OIDC_CLIENT_SECRET']},
+          cookie_secret: ${4:ENV['OIDC_COOKIE_SECRET']},
+          claims_to_headers: ${5:{ "email" => "X-User-Email" }}
+        SNIPPET
+
+        detail "Require an OpenID Connect login"
+
+        schema do
+          {
+            issuer: Type(String) & Required(),
+            client_id: Type(String) & Required(),
+            client_secret: Type(String),
+            scopes: Array(Type(String)).default(%w[openid profile email]),
+            callback_path: Type(String).default("/auth/callback"),
+            redirect_uri: Type(String),
+            logout_path: Type(String).default("/auth/logout"),
+            logout_redirect: Type(String).default("/"),
+            cookie_name: Type(String).default("itsi_session"),
+            cookie_secret: Type(String) & Required(),
+            cookie_secure: Bool().default(true),
+            leeway: Type(Integer),
+            claims_to_headers: Hash(Type(String), Type(String)),
+            error_response: Type(ErrorResponseDef).default("unauthorized")
+          }
+        end
+
+        def initialize(location, params)
+          super
+          raise "auth_oidc cookie_secret must be at least 32 characters" if @params[:cookie_secret].length < 32
+          raise "Invalid auth_oidc cookie_name: #{@params[:cookie_name]}" unless @params[:cookie_name] =~ /\A[\w.-]+\z/
+          raise "auth_oidc scopes must include openid" unless @params[:scopes].include?("openid")
+
+          # The identity provider redirects back to redirect_uri, so that's where we handle the callback.
+          @params[:callback_path] = URI.parse(@params[:redirect_uri]).path if @params[:redirect_uri]
+          @params[:claims_to_headers] = (@params[:claims_to_headers] || {}).transform_keys(&:to_s)
+        end
+
+      end
+    end
+  end
+end
@@ -0,0 +1,36 @@
+module Itsi
+  class Server
+    module Config
+      class AuthRequest < Middleware
+        require_relative "error_response"
+
+        insert_text <<~SNIPPET
+        auth_request \\
+          endpoint: ${1:"http://auth.internal/verify"},
+          request_headers: ${2:["Authorization", "Cookie"]},
+          auth_response_headers: ${3:["X-User"]}
+        SNIPPET
+
+        detail "Authorize each request by asking an external service or app route"
+
+        schema do
+          {
+            endpoint: Type(String) & Required(),
+            request_headers: Array(Type(String)).default(%w[Authorization Cookie]),
+            auth_response_headers: Array(Type(String)).default([]),
+            timeout: (Type(Integer) & Range(1..Float::INFINITY)).default(5),
+            error_response: Type(ErrorResponseDef).default("bad_gateway")
+          }
+        end
+
+        def initialize(location, params)
+          super
+          return if @params[:endpoint].start_with?("/") || @params[:endpoint] =~ %r{\Ahttps?://}
+
+          raise "auth_request endpoint must be a path (e.g. /auth/check) or an http(s) URL"
+        end
+
+      end
+    end
+  end
+end
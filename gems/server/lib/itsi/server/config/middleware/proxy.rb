@@ -2,11 +2,13 @@ module Itsi
   class Server
     module Config
       class Proxy < Middleware
+        require_relative "token_source"
+
         insert_text <<~SNIPPET
           proxy \\
             to: "${1:http://backend.example.com{path_and_query}",
             backends: [${2:"127.0.0.1:3001", "127.0.0.1:3002"}],
-            backend_priority: ${3|"round_robin","ordered","random"|},
+            backend_priority: ${3|"round_robin","ordered","random","least_connections",{consistent_hash: {key: "address"}}|},
             headers: { ${4| "X-Forwarded-For" =>  "{addr}"|} },
             verify_ssl: ${5|true,false|},
             timeout: ${6|30,60|},
@@ -14,18 +16,67 @@ module Itsi
             error_response: ${8|"bad_gateway", "service_unavailable", { code: 503\\, default_format: "html"\\, html: { inline: "<h1>Service Unavailable</h1>" } }|}
         SNIPPET
 
+        ProxyConsistentHash = TypedStruct.new do
+          {
+            consistent_hash: Type(TypedStruct.new do
+              {
+                key: (Required() & Or(Enum(["address"]), Type(TokenSource))).default("address")
+              }
+            end) & Required()
+          }
+        end
+
+        ProxyRetry = TypedStruct.new do
+          {
+            max_attempts: Range(1..100).default(3),
+            per_try_timeout: (Type(Integer) & Range(1..Float::INFINITY)),
+            retry_on_connect_error: Bool().default(true),
+            retry_on_timeout: Bool().default(false),
+            retry_on_statuses: Array(Type(Integer)).default([502, 503, 504]),
+            backoff: Type(Float).default(0.1),
+            buffer_limit: (Type(Integer) & Range(1..Float::INFINITY))
+          }
+        end
+
+        ProxyHealthCheck = TypedStruct.new do
+          {
+            path: Type(String).default("/"),
+            interval: (Type(Integer) & Range(1..Float::INFINITY)).default(10),
+            timeout: (Type(Integer) & Range(1..Float::INFINITY)).default(2),
+            expected_statuses: Array(Type(Integer)).default([200]),
+            healthy_threshold: (Type(Integer) & Range(1..Float::INFINITY)).default(1),
+            unhealthy_threshold: (Type(Integer) & Range(1..Float::INFINITY)).default(2)
+          }
+        end
+
+        ProxyPassiveHealthCheck = TypedStruct.new do
+          {
+            max_failures: (Type(Integer) & Range(1..Float::INFINITY)).default(3),
+            cooldown: (Type(Integer) & Range(1..Float::INFINITY)).default(30),
+            failure_statuses: Array(Type(Integer)).default([502, 503, 504])
+          }
+        end
+
         detail "Forwards incoming requests to a backend server using dynamic URL rewriting. Supports various backend selection strategies and header overriding."
 
         schema do
           {
             to: Type(String) & Required(),
             backends: Array(Type(String)),
-            backend_priority: Enum(%w[round_robin ordered random]).default("round_robin"),
+            backend_priority: Or(
+              Enum(%w[round_robin ordered random least_connections]),
+              Type(ProxyConsistentHash)
+            ).default("round_robin"),
             headers: Hash(Type(String), Type(String)).default({}),
             verify_ssl: Bool().default(true),
             tls_sni: Bool().default(true),
             timeout: Type(Integer).default(30),
-            error_response: Type(ErrorResponseDef).default("bad_gateway")
+            connect_timeout: Type(Integer),
+            read_timeout: Type(Integer),
+            retry: Type(ProxyRetry),
+            error_response: Type(ErrorResponseDef).default("bad_gateway"),
+            health_check: Type(ProxyHealthCheck),
+            passive_health_check: Type(ProxyPassiveHealthCheck)
           }
         end
 
@@ -0,0 +1,36 @@
+module Itsi
+  class Server
+    module Config
+      class ResponseCache < Middleware
+
+        insert_text <<~SNIPPET
+        response_cache \\
+          max_entries: ${1|10000,100000|},
+          max_body_size: ${2|1048576,10485760|},
+          disk_path: ${3|nil,"./tmp/itsi_cache"|},
+          default_ttl: ${4|nil,60|},
+          coalesce_timeout: ${5|10,30|},
+          allow_purge: ${6|false,true|},
+          purge_allowed_from: ${7|["127.0.0.1", "::1"]|},
+          cache_authenticated: ${8|false,true|}
+        SNIPPET
+
+        detail "Stores cacheable responses in memory (and optionally on disk) and serves them without hitting the app or proxy backend."
+
+        schema do
+          {
+            max_entries: (Type(Integer) & Range(1..2**32)).default(10_000),
+            max_body_size: (Type(Integer) & Range(0..2**32)).default(1024 * 1024),
+            disk_path: Type(String),
+            default_ttl: Type(Integer),
+            cacheable_statuses: Array(Type(Integer)).default([200, 203, 204, 300, 301, 308, 404, 410]),
+            coalesce_timeout: (Type(Integer) & Range(0..3600)).default(10),
+            allow_purge: Bool().default(false),
+            purge_allowed_from: Array(Type(String)).default(["127.0.0.1", "::1"]),
+            cache_authenticated: Bool().default(false)
+          }
+        end
+      end
+    end
+  end
+end
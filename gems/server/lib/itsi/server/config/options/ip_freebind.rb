@@ -0,0 +1,19 @@
+module Itsi
+  class Server
+    module Config
+      class IpFreebind < Option
+
+        insert_text <<~SNIPPET
+        ip_freebind ${1|true,false|}
+        SNIPPET
+
+        detail "Allows binding to IP addresses that aren't (yet) assigned to this host. Linux only."
+
+        schema do
+          (Bool() & Required()).default(false)
+        end
+
+      end
+    end
+  end
+end
@@ -0,0 +1,19 @@
+module Itsi
+  class Server
+    module Config
+      class IpTransparent < Option
+
+        insert_text <<~SNIPPET
+        ip_transparent ${1|true,false|}
+        SNIPPET
+
+        detail "Sets IP_TRANSPARENT on listening sockets, to accept connections to non-local addresses (e.g. TPROXY). Linux only."
+
+        schema do
+          (Bool() & Required()).default(false)
+        end
+
+      end
+    end
+  end
+end
@@ -0,0 +1,19 @@
+module Itsi
+  class Server
+    module Config
+      class KeepAliveTimeout < Option
+
+        insert_text <<~SNIPPET
+        keep_alive_timeout ${1|5.0,15.0,60.0|} # Keep-alive idle timeout in seconds
+        SNIPPET
+
+        detail "Close connections that have been idle in keep-alive for longer than this many seconds."
+
+        schema do
+          Type(Float) & Range(0.001..Float::INFINITY) & Required()
+        end
+
+      end
+    end
+  end
+end
@@ -0,0 +1,19 @@
+module Itsi
+  class Server
+    module Config
+      class MaxConnections < Option
+
+        insert_text <<~SNIPPET
+        max_connections ${1|1024,4096,10000|}
+        SNIPPET
+
+        detail "The maximum number of connections each worker keeps open. Further connections wait in the listen backlog."
+
+        schema do
+          Type(Integer) & Range(1..Float::INFINITY) & Required()
+        end
+
+      end
+    end
+  end
+end
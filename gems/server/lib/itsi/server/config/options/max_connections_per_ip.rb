@@ -0,0 +1,19 @@
+module Itsi
+  class Server
+    module Config
+      class MaxConnectionsPerIp < Option
+
+        insert_text <<~SNIPPET
+        max_connections_per_ip ${1|16,64,256|}
+        SNIPPET
+
+        detail "The maximum number of connections each worker keeps open from a single client IP. Further connections from that IP are closed."
+
+        schema do
+          Type(Integer) & Range(1..Float::INFINITY) & Required()
+        end
+
+      end
+    end
+  end
+end
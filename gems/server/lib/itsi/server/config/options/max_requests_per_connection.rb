@@ -0,0 +1,19 @@
+module Itsi
+  class Server
+    module Config
+      class MaxRequestsPerConnection < Option
+
+        insert_text <<~SNIPPET
+        max_requests_per_connection ${1|100,1000,10000|}
+        SNIPPET
+
+        detail "Close each connection after it has served this many requests."
+
+        schema do
+          Type(Integer) & Range(1..Float::INFINITY) & Required()
+        end
+
+      end
+    end
+  end
+end
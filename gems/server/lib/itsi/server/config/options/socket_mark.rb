@@ -0,0 +1,19 @@
+module Itsi
+  class Server
+    module Config
+      class SocketMark < Option
+
+        insert_text <<~SNIPPET
+        socket_mark ${1:0x1}
+        SNIPPET
+
+        detail "Sets SO_MARK on listening sockets, for policy routing and firewall rules. Linux only."
+
+        schema do
+          Type(Integer) & Range(0..2**32 - 1) & Required()
+        end
+
+      end
+    end
+  end
+end
@@ -0,0 +1,19 @@
+module Itsi
+  class Server
+    module Config
+      class TcpDeferAccept < Option
+
+        insert_text <<~SNIPPET
+        tcp_defer_accept ${1|5,30|} # Seconds
+        SNIPPET
+
+        detail "Only wake the server for a new connection once it has sent data, waiting up to this many seconds. Linux only."
+
+        schema do
+          Type(Integer) & Range(1..2**31 - 1) & Required()
+        end
+
+      end
+    end
+  end
+end
@@ -0,0 +1,19 @@
+module Itsi
+  class Server
+    module Config
+      class TcpFastopen < Option
+
+        insert_text <<~SNIPPET
+        tcp_fastopen ${1|256,1024|}
+        SNIPPET
+
+        detail "Enables TCP Fast Open on TCP listeners, with the given queue length for pending Fast Open requests. Linux only."
+
+        schema do
+          Type(Integer) & Range(1..2**31 - 1) & Required()
+        end
+
+      end
+    end
+  end
+end
@@ -0,0 +1,23 @@
+module Itsi
+  class Server
+    module Config
+      class TcpKeepalive < Option
+
+        insert_text <<~SNIPPET
+        tcp_keepalive idle: ${1|60,300|}, interval: ${2|10,30|}, count: ${3|5,9|}
+        SNIPPET
+
+        detail "Enables TCP keepalive probes on accepted connections, so dead peers are detected."
+
+        schema do
+          {
+            idle: Type(Float) & Range(1..Float::INFINITY),
+            interval: Type(Float) & Range(1..Float::INFINITY),
+            count: Type(Integer) & Range(1..127)
+          }
+        end
+
+      end
+    end
+  end
+end
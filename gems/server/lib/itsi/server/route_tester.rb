@@ -28,16 +28,22 @@ module Itsi
           "\e[33mcache_control\e[0m(max_age: #{mw_args["max_age"]}, #{mw_args.select do |_, v|
             v == true
           end.keys.join(", ")})"
+        when "response_cache"
+          "\e[33mresponse_cache\e[0m(max_entries: #{mw_args["max_entries"]}#{mw_args["disk_path"] ? ", disk_path: #{mw_args["disk_path"]}" : ""})"
         when "redirect"
           "\e[33mredirect\e[0m(to: #{mw_args["to"]}, type: #{mw_args["type"]})"
         when "static_assets"
           "\e[33mstatic_assets\e[0m(path: #{mw_args["root_dir"]})"
         when "auth_api_key"
-          "\e[33mauth_api_key\e[0m(keys: #{mw_args["valid_keys"].keys}#{mw_args["credentials_file"] ? ", credentials_file: #{mw_args["credentials_file"]}" : ""})"
+          "\e[33mauth_api_key\e[0m(keys: #{(mw_args["valid_keys"] || {}).keys}#{mw_args["credentials_file"] ? ", credentials_file: #{mw_args["credentials_file"]}" : ""})"
         when "auth_basic"
           "\e[33mbasic_auth\e[0m(keys: #{mw_args["realm"]}#{mw_args["credentials_file"] ? ", credentials_file: #{mw_args["credentials_file"]}" : ""})"
         when "auth_jwt"
           "\e[33mjwt_auth\e[0m(#{mw_args["verifiers"].keys.join(",")})"
+        when "auth_oidc"
+          "\e[33mauth_oidc\e[0m(#{mw_args["issuer"]})"
+        when "auth_request"
+          "\e[33mauth_request\e[0m(#{mw_args["endpoint"]})"
         when "rate_limit"
           key = mw_args["key"].is_a?(Hash) ? mw_args["key"]["parameter"] : mw_args["key"]
           "\e[33mrate_limit\e[0m(rps: #{mw_args["requests"]}/#{mw_args["seconds"]}, key: #{key})"
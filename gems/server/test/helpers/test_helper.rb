@@ -25,6 +25,15 @@ def free_bind(protocol = "http", unix_socket: false)
   end
 end
 
+# Sends a GET request over an open `socket`, leaving the connection open,
+# and reads the raw response up to its "hi" body.
+def keep_alive_request(socket)
+  socket.write("GET / HTTP/1.1\r\nHost: localhost\r\n\r\n")
+  response = +""
+  response << socket.readpartial(1024) until response.end_with?("hi")
+  response
+end
+
 def server(
   app: nil, app_with_lint: nil, protocol: "http", bind: free_bind(protocol), itsi_rb: nil, cleanup: true,
            &blk)
@@ -138,4 +138,25 @@ class TestAuthApiKey < Minitest::Test
       assert_equal "200", r3.code
     end
   end
+
+  # Identified keys from a credentials file, reloaded when it changes
+  def test_credentials_file_reload
+    Dir.mktmpdir do |dir|
+      path = File.join(dir, "api-keys")
+      File.write(path, "key-1:#{Itsi.create_password_hash("first", "sha256")}\n")
+      server(
+        itsi_rb: lambda do
+          auth_api_key credentials_file: path
+          get("/foo") {|r| r.ok "ok" }
+        end
+      ) do
+        assert_equal "200", get_resp("/foo", { "X-Api-Key-Id" => "key-1", "Authorization" => "Bearer first" }).code
+
+        File.write(path, "key-1:#{Itsi.create_password_hash("second", "sha256")}\n")
+        sleep 1.5
+        assert_equal "401", get_resp("/foo", { "X-Api-Key-Id" => "key-1", "Authorization" => "Bearer first" }).code
+        assert_equal "200", get_resp("/foo", { "X-Api-Key-Id" => "key-1", "Authorization" => "Bearer second" }).code
+      end
+    end
+  end
 end
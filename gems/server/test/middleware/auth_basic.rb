@@ -88,4 +88,28 @@ class TestAuthBasic < Minitest::Test
       assert_equal "200", res2.code
     end
   end
+
+  # 6. htpasswd files are reloaded when they change
+  def test_credentials_file_reload
+    Dir.mktmpdir do |dir|
+      path = File.join(dir, "htpasswd")
+      File.write(path, "# Admins\nalice:#{Itsi.create_password_hash("wonderland", "bcrypt")}\n")
+      server(
+        itsi_rb: lambda do
+          auth_basic realm: "Admin", credentials_file: path
+          get("/a") {|r| r.ok "ok" }
+        end
+      ) do
+        alice = { "Authorization" => "Basic #{["alice:wonderland"].pack("m0")}" }
+        bob = { "Authorization" => "Basic #{["bob:builder"].pack("m0")}" }
+        assert_equal "200", get_resp("/a", alice).code
+        assert_equal "401", get_resp("/a", bob).code
+
+        File.write(path, "bob:#{Itsi.create_password_hash("builder", "sha256")}\n")
+        sleep 1.5
+        assert_equal "401", get_resp("/a", alice).code
+        assert_equal "200", get_resp("/a", bob).code
+      end
+    end
+  end
 end
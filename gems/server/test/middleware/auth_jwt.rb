@@ -3,6 +3,7 @@ require "jwt"
 require "openssl"
 require "securerandom"
 require "base64"
+require "json"
 
 class TestAuthJwt < Minitest::Test
   ALGORITHMS = {
@@ -211,4 +212,173 @@ class TestAuthJwt < Minitest::Test
       assert_equal "x", get_resp("/api/x", { "Authorization" => "Bearer #{token}" }).body
     end
   end
+
+  # 10. Claims forwarded as request headers
+  def test_claims_to_headers
+    verifier, signer = ALGORITHMS["HS256"].call
+    payload = { "sub" => "user-1", "roles" => %w[admin billing], "org" => { "id" => 42 }, exp: Time.now.to_i + 10 }
+    token = JWT.encode(payload, signer, "HS256")
+    server(
+      itsi_rb: lambda do
+        auth_jwt verifiers: { "HS256" => [verifier] },
+                 claims_to_headers: { "sub" => "X-User-Id", "roles" => "X-User-Roles", "org.id" => "X-Org-Id",
+                                      "email" => "X-User-Email" }
+        get("/") do |r|
+          r.ok [r.header("X-User-Id"), r.header("X-User-Roles"), r.header("X-Org-Id"), r.header("X-User-Email")]
+            .map { |values| values.join(";") }.join("|")
+        end
+      end
+    ) do
+      res = get_resp("/", { "Authorization" => "Bearer #{token}", "X-User-Id" => "spoofed",
+                            "X-User-Email" => "spoofed@example.com" })
+      assert_equal "200", res.code
+      assert_equal "user-1|admin,billing|42|", res.body
+    end
+  end
+
+  # Non-ASCII claims are percent-encoded rather than dropped, and `%` is encoded so values decode unambiguously
+  def test_claims_to_headers_percent_encodes_non_ascii
+    verifier, signer = ALGORITHMS["HS256"].call
+    token = JWT.encode({ "name" => "José\n%61dmin", exp: Time.now.to_i + 10 }, signer, "HS256")
+    server(
+      itsi_rb: lambda do
+        auth_jwt verifiers: { "HS256" => [verifier] }, claims_to_headers: { "name" => "X-User-Name" }
+        get("/") { |r| r.ok r.header("X-User-Name").join(";") }
+      end
+    ) do
+      res = get_resp("/", { "Authorization" => "Bearer #{token}" })
+      assert_equal "200", res.code
+      assert_equal "Jos%C3%A9%0A%2561dmin", res.body
+    end
+  end
+
+  # 11. Required claims
+  def test_required_claims
+    verifier, signer = ALGORITHMS["HS256"].call
+    server(
+      itsi_rb: lambda do
+        auth_jwt verifiers: { "HS256" => [verifier] },
+                 required_claims: { "roles" => { contains: "admin" }, "scope" => { contains: "orders:write" },
+                                    "org.tier" => { equals: "enterprise" } }
+        get("/") { |r| r.ok "ok" }
+      end
+    ) do
+      valid = { "roles" => %w[admin], "scope" => "orders:read orders:write", "org" => { "tier" => "enterprise" },
+                exp: Time.now.to_i + 10 }
+      [
+        [valid, "200"],
+        [valid.merge("roles" => %w[viewer]), "401"],
+        [valid.merge("scope" => "orders:read orders:writer"), "401"],
+        [valid.merge("org" => { "tier" => "free" }), "401"],
+        [valid.reject { |k, _| k == "org" }, "401"]
+      ].each do |payload, code|
+        token = JWT.encode(payload, signer, "HS256")
+        assert_equal code, get_resp("/", { "Authorization" => "Bearer #{token}" }).code, payload.inspect
+      end
+    end
+  end
+
+  # 12. Claims available to string rewrites
+  def test_claim_placeholders
+    verifier, signer = ALGORITHMS["HS256"].call
+    token = JWT.encode({ "sub" => "user-1", exp: Time.now.to_i + 10 }, signer, "HS256")
+    server(
+      itsi_rb: lambda do
+        auth_jwt verifiers: { "HS256" => [verifier] }
+        response_headers additions: { "X-Subject" => ["{jwt.sub}"] }
+        get("/") { |r| r.ok "ok" }
+      end
+    ) do
+      res = get_resp("/", { "Authorization" => "Bearer #{token}" })
+      assert_equal "user-1", res["X-Subject"]
+    end
+  end
+
+  # Serves `jwks` (which tests may update) as a JSON Web Key Set over HTTP.
+  def with_jwks_server(jwks)
+    tcp = TCPServer.new("127.0.0.1", 0)
+    thread = Thread.new do
+      loop do
+        client = tcp.accept
+        while (line = client.gets) && line != "\r\n"; end
+        body = JSON.generate(jwks)
+        client.write("HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: #{body.bytesize}\r\nConnection: close\r\n\r\n#{body}")
+        client.close
+      end
+    end
+    yield "http://127.0.0.1:#{tcp.addr[1]}/.well-known/jwks.json"
+  ensure
+    thread&.kill
+    tcp&.close
+  end
+
+  def base64url(data)
+    Base64.urlsafe_encode64(data, padding: false)
+  end
+
+  # 10. Keys from a JWKS are selected by kid
+  def test_jwks_selects_key_by_kid
+    rsa = OpenSSL::PKey::RSA.new(2048)
+    ec = OpenSSL::PKey::EC.generate("prime256v1")
+    jwks = { keys: [JWT::JWK.new(rsa, kid: "rsa").export, JWT::JWK.new(ec, kid: "ec").export] }
+    payload = { exp: Time.now.to_i + 10 }
+
+    with_jwks_server(jwks) do |jwks_url|
+      server(
+        itsi_rb: lambda do
+          auth_jwt jwks_url: jwks_url
+          get("/") { |r| r.ok "ok" }
+        end
+      ) do
+        rs256 = JWT.encode(payload, rsa, "RS256", { kid: "rsa" })
+        es256 = JWT.encode(payload, ec, "ES256", { kid: "ec" })
+        forged = JWT.encode(payload, OpenSSL::PKey::RSA.new(2048), "RS256", { kid: "rsa" })
+        assert_equal "200", get_resp("/", { "Authorization" => "Bearer #{rs256}" }).code
+        assert_equal "200", get_resp("/", { "Authorization" => "Bearer #{es256}" }).code
+        assert_equal "401", get_resp("/", { "Authorization" => "Bearer #{forged}" }).code
+      end
+    end
+  end
+
+  # 11. An unknown kid triggers a refetch, so rotated keys are picked up
+  def test_jwks_key_rotation
+    old_key = OpenSSL::PKey::RSA.new(2048)
+    new_key = OpenSSL::PKey::RSA.new(2048)
+    jwks = { keys: [JWT::JWK.new(old_key, kid: "old").export] }
+    payload = { exp: Time.now.to_i + 10 }
+
+    with_jwks_server(jwks) do |jwks_url|
+      server(
+        itsi_rb: lambda do
+          auth_jwt jwks_url: jwks_url
+          get("/") { |r| r.ok "ok" }
+        end
+      ) do
+        token = JWT.encode(payload, new_key, "RS256", { kid: "new" })
+        jwks[:keys] << JWT::JWK.new(new_key, kid: "new").export
+        assert_equal "200", get_resp("/", { "Authorization" => "Bearer #{token}" }).code
+      end
+    end
+  end
+
+  # 12. EdDSA keys from a JWKS
+  def test_jwks_eddsa
+    key = OpenSSL::PKey.generate_key("ED25519")
+    jwks = { keys: [{ kty: "OKP", crv: "Ed25519", x: base64url(key.raw_public_key), kid: "ed", use: "sig" }] }
+
+    with_jwks_server(jwks) do |jwks_url|
+      server(
+        itsi_rb: lambda do
+          auth_jwt jwks_url: jwks_url
+          get("/") { |r| r.ok "ok" }
+        end
+      ) do
+        header = base64url(JSON.generate({ alg: "EdDSA", typ: "JWT", kid: "ed" }))
+        claims = base64url(JSON.generate({ exp: Time.now.to_i + 10 }))
+        signing_input = "#{header}.#{claims}"
+        token = "#{signing_input}.#{base64url(key.sign(nil, signing_input))}"
+        assert_equal "200", get_resp("/", { "Authorization" => "Bearer #{token}" }).code
+      end
+    end
+  end
 end
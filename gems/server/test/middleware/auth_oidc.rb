@@ -0,0 +1,239 @@
+require_relative "../helpers/test_helper"
+require "jwt"
+require "openssl"
+require "securerandom"
+require "base64"
+require "json"
+require "digest"
+
+# A minimal OpenID provider: discovery, JWKS and token endpoints.
+# Tests play the part of the user's browser at the authorization endpoint, using #authorize.
+class MockIdp
+  attr_reader :issuer, :token_requests
+  # With `rotate_refresh_tokens`, each refresh token can only be used once.
+  attr_accessor :expires_in, :refresh_subject, :rotate_refresh_tokens, :token_delay
+
+  def initialize
+    @rsa = OpenSSL::PKey::RSA.new(2048)
+    @tcp = TCPServer.new("127.0.0.1", 0)
+    @issuer = "http://127.0.0.1:#{@tcp.addr[1]}"
+    @authorizations = {}
+    @token_requests = []
+    @refresh_tokens = ["refresh-1"]
+    @expires_in = 300
+    @refresh_subject = "user-1"
+    @token_delay = 0
+    @thread = Thread.new do
+      loop { handle(@tcp.accept) }
+    end
+  end
+
+  # Approves an authorization request (the Location Itsi redirected to), returning the code.
+  def authorize(location)
+    code = SecureRandom.hex(8)
+    @authorizations[code] = URI.decode_www_form(URI(location).query).to_h
+    code
+  end
+
+  def close
+    @thread.kill
+    @tcp.close
+  end
+
+  private
+
+  def handle(client)
+    method, path = client.gets.split
+    headers = {}
+    while (line = client.gets) && line != "\r\n"
+      name, value = line.split(":", 2)
+      headers[name.downcase] = value.strip
+    end
+    form = URI.decode_www_form(client.read(headers["content-length"].to_i)).to_h
+    status, response = route(method, path, headers, form)
+    body = JSON.generate(response)
+    client.write("HTTP/1.1 #{status}\r\nContent-Type: application/json\r\nContent-Length: #{body.bytesize}\r\nConnection: close\r\n\r\n#{body}")
+  ensure
+    client.close
+  end
+
+  def route(method, path, headers, form)
+    case "#{method} #{path}"
+    when "GET /.well-known/openid-configuration"
+      ["200 OK", { issuer: issuer, authorization_endpoint: "#{issuer}/authorize",
+                   token_endpoint: "#{issuer}/token", jwks_uri: "#{issuer}/jwks" }]
+    when "GET /jwks"
+      ["200 OK", { keys: [{ kty: "RSA", kid: "k1", alg: "RS256", use: "sig",
+                            n: base64url(@rsa.n.to_s(2)), e: base64url(@rsa.e.to_s(2)) }] }]
+    when "POST /token"
+      @token_requests << form
+      sleep token_delay
+      unless headers["authorization"] == "Basic #{Base64.strict_encode64("itsi:secret")}"
+        return ["401 Unauthorized", { error: "invalid_client" }]
+      end
+
+      token_response(form)
+    else
+      ["404 Not Found", {}]
+    end
+  end
+
+  def token_response(form)
+    case form["grant_type"]
+    when "authorization_code"
+      authorization = @authorizations.delete(form["code"])
+      valid = authorization &&
+              authorization["redirect_uri"] == form["redirect_uri"] &&
+              authorization["code_challenge"] == base64url(Digest::SHA256.digest(form["code_verifier"].to_s))
+      valid ? ["200 OK", tokens(authorization["nonce"])] : ["400 Bad Request", { error: "invalid_grant" }]
+    when "refresh_token"
+      return ["400 Bad Request", { error: "invalid_grant" }] unless @refresh_tokens.include?(form["refresh_token"])
+
+      @refresh_tokens.delete(form["refresh_token"]) if rotate_refresh_tokens
+      ["200 OK", tokens(nil, sub: refresh_subject)]
+    end
+  end
+
+  def tokens(nonce, sub: "user-1")
+    claims = { iss: issuer, aud: "itsi", sub: sub, email: "user@example.com",
+               iat: Time.now.to_i, exp: Time.now.to_i + 300 }
+    claims[:nonce] = nonce if nonce
+    refresh_token = rotate_refresh_tokens ? "refresh-#{SecureRandom.hex(4)}" : "refresh-1"
+    @refresh_tokens << refresh_token unless @refresh_tokens.include?(refresh_token)
+    { access_token: "access", token_type: "Bearer", expires_in: expires_in, refresh_token: refresh_token,
+      id_token: JWT.encode(claims, @rsa, "RS256", { kid: "k1" }) }
+  end
+
+  def base64url(data)
+    Base64.urlsafe_encode64(data, padding: false)
+  end
+end
+
+class TestAuthOidc < Minitest::Test
+  BROWSER = { "Accept" => "text/html,application/xhtml+xml" }.freeze
+
+  def setup
+    @idp = MockIdp.new
+  end
+
+  def teardown
+    @idp.close
+  end
+
+  def oidc_app(idp)
+    lambda do
+      auth_oidc issuer: idp.issuer, client_id: "itsi", client_secret: "secret",
+                cookie_secret: "a" * 32, cookie_secure: false,
+                claims_to_headers: { "email" => "X-User-Email" }
+      get("/private") { |r| r.ok r.header("X-User-Email").join }
+    end
+  end
+
+  def cookie(response, name)
+    response.get_fields("set-cookie")&.map { |c| c.split(";").first }&.find { |c| c.start_with?("#{name}=") }
+  end
+
+  # Logs in through the mock identity provider, returning the session cookie.
+  def login(ctx, path = "/private")
+    redirect = ctx.get_resp(path, BROWSER)
+    assert_equal "302", redirect.code
+    assert redirect["location"].start_with?("#{@idp.issuer}/authorize?")
+
+    state = URI.decode_www_form(URI(redirect["location"]).query).to_h["state"]
+    code = @idp.authorize(redirect["location"])
+    callback = ctx.get_resp("/auth/callback?code=#{code}&state=#{state}",
+                            BROWSER.merge("Cookie" => cookie(redirect, "itsi_session_flow")))
+    assert_equal "302", callback.code
+    assert_equal path, callback["location"]
+    cookie(callback, "itsi_session")
+  end
+
+  def test_login
+    server(itsi_rb: oidc_app(@idp)) do
+      session = login(self, "/private?page=2")
+
+      res = get_resp("/private", { "Cookie" => session, "X-User-Email" => "spoofed@example.com" })
+      assert_equal "200", res.code
+      assert_equal "user@example.com", res.body
+    end
+  end
+
+  def test_requests_without_a_session
+    server(itsi_rb: oidc_app(@idp)) do
+      # Only browser navigations are redirected to log in.
+      assert_equal "401", get_resp("/private").code
+      assert_equal "302", get_resp("/private", BROWSER).code
+
+      name, value = login(self).split("=", 2)
+      tampered = "#{name}=#{value[0] == "A" ? "B" : "A"}#{value[1..]}"
+      assert_equal "302", get_resp("/private", BROWSER.merge("Cookie" => tampered)).code
+    end
+  end
+
+  def test_callback_rejects_mismatched_state
+    idp = @idp
+    server(itsi_rb: oidc_app(idp)) do
+      redirect = get_resp("/private", BROWSER)
+      code = idp.authorize(redirect["location"])
+      res = get_resp("/auth/callback?code=#{code}&state=forged",
+                     BROWSER.merge("Cookie" => cookie(redirect, "itsi_session_flow")))
+      assert_equal "401", res.code
+      assert_empty idp.token_requests
+    end
+  end
+
+  def test_refreshes_expired_sessions
+    idp = @idp
+    idp.expires_in = 1
+    server(itsi_rb: oidc_app(idp)) do
+      session = login(self)
+      sleep 2
+
+      res = get_resp("/private", { "Cookie" => session })
+      assert_equal "200", res.code
+      assert_equal "user@example.com", res.body
+      assert_equal "refresh_token", idp.token_requests.last["grant_type"]
+      refute_nil cookie(res, "itsi_session")
+    end
+  end
+
+  def test_refresh_rejects_id_tokens_for_another_subject
+    idp = @idp
+    idp.expires_in = 1
+    server(itsi_rb: oidc_app(idp)) do
+      session = login(self)
+      idp.refresh_subject = "user-2"
+      sleep 2
+
+      assert_equal "401", get_resp("/private", { "Cookie" => session }).code
+      assert_equal "refresh_token", idp.token_requests.last["grant_type"]
+    end
+  end
+
+  def test_concurrent_requests_share_a_refresh
+    idp = @idp
+    idp.expires_in = 1
+    idp.rotate_refresh_tokens = true
+    server(itsi_rb: oidc_app(idp)) do
+      session = login(self)
+      idp.token_delay = 0.3
+      sleep 2
+
+      responses = 4.times.map { Thread.new { get_resp("/private", { "Cookie" => session }) } }.map(&:value)
+      assert_equal ["200"], responses.map(&:code).uniq
+      # Requests still carrying the old cookie don't present the rotated refresh token again.
+      assert_equal "200", get_resp("/private", { "Cookie" => session }).code
+      assert_equal 1, idp.token_requests.count { |form| form["grant_type"] == "refresh_token" }
+    end
+  end
+
+  def test_logout
+    server(itsi_rb: oidc_app(@idp)) do
+      session = login(self)
+      res = get_resp("/auth/logout", { "Cookie" => session })
+      assert_equal "302", res.code
+      assert_equal "/", res["location"]
+      assert_match(/itsi_session=;.*Max-Age=0/, res["set-cookie"])
+    end
+  end
+end
@@ -0,0 +1,77 @@
+require_relative "../helpers/test_helper"
+
+class TestAuthRequest < Minitest::Test
+  def test_local_endpoint
+    server(
+      itsi_rb: lambda do
+        location "/private*" do
+          auth_request endpoint: "/auth/check", auth_response_headers: ["X-User"]
+        end
+        get("/auth/check") do |r|
+          if r.header("Authorization").first == "Bearer good"
+            r.respond("", 200, { "X-User" => "alice" })
+          else
+            r.respond("", 401, { "WWW-Authenticate" => "Bearer" })
+          end
+        end
+        get("/private") { |r| r.ok r.header("X-User").join(",") }
+      end
+    ) do
+      res = get_resp("/private", { "Authorization" => "Bearer good", "X-User" => "mallory" })
+      assert_equal "200", res.code
+      assert_equal "alice", res.body
+
+      res = get_resp("/private", { "Authorization" => "Bearer bad" })
+      assert_equal "401", res.code
+      assert_equal "Bearer", res["www-authenticate"]
+    end
+  end
+
+  def test_remote_endpoint
+    auth_bind = free_bind
+    server(
+      itsi_rb: lambda do
+        get("/verify") do |r|
+          case r.header("Authorization").first
+          when "Bearer good"
+            r.respond("", 200, { "X-User" => "alice", "X-Original" => r.header("X-Forwarded-Uri").first })
+          when nil
+            r.respond("", 302, { "Location" => "https://login.example.com/" })
+          else
+            r.respond("", 403)
+          end
+        end
+      end,
+      bind: auth_bind
+    ) do
+      server(
+        itsi_rb: lambda do
+          auth_request endpoint: "#{auth_bind}/verify", auth_response_headers: %w[X-User X-Original]
+          get("/private") { |r| r.ok "#{r.header("X-User").join(",")} #{r.header("X-Original").first}" }
+        end
+      ) do
+        res = get_resp("/private?page=2", { "Authorization" => "Bearer good", "X-User" => "mallory" })
+        assert_equal "200", res.code
+        assert_equal "alice /private?page=2", res.body
+
+        res = get_resp("/private")
+        assert_equal "302", res.code
+        assert_equal "https://login.example.com/", res["location"]
+
+        assert_equal "403", get_resp("/private", { "Authorization" => "Bearer bad" }).code
+      end
+    end
+  end
+
+  def test_unreachable_endpoint
+    auth_bind = free_bind
+    server(
+      itsi_rb: lambda do
+        auth_request endpoint: "#{auth_bind}/verify", timeout: 1
+        get("/private") { |r| r.ok "should not get here" }
+      end
+    ) do
+      assert_equal "502", get_resp("/private", { "Authorization" => "Bearer good" }).code
+    end
+  end
+end
@@ -406,4 +406,317 @@ class TestProxy < Minitest::Test
 
     Itsi::Server.stop_background_threads
   end
+
+  def test_active_health_check_skips_unhealthy_backend
+    backend1_bind = free_bind
+    backend2_bind = free_bind
+
+    Itsi::Server.start_in_background_thread(binds: [backend1_bind]) do
+      workers 1
+      get("/health") { |r| r.respond("down", 503) }
+      get("/checked") { |r| r.ok "backend1" }
+    end
+
+    Itsi::Server.start_in_background_thread(binds: [backend2_bind]) do
+      workers 1
+      get("/health") { |r| r.ok "up" }
+      get("/checked") { |r| r.ok "backend2" }
+    end
+
+    sleep 0.2
+
+    server(
+      cleanup: false,
+      itsi_rb: lambda do
+        proxy \
+          to: "http://proxied_host.com{path}{query}",
+          backends: [backend1_bind[%r{//(.*)}, 1], backend2_bind[%r{//(.*)}, 1]],
+          backend_priority: "ordered",
+          health_check: { path: "/health", interval: 1, unhealthy_threshold: 1 }
+        get("/checked") { |r| r.ok "should not get here" }
+      end
+    ) do
+      sleep 0.5
+      res = get_resp("/checked")
+      assert_equal "200", res.code
+      assert_equal "backend2", res.body, "Expected unhealthy backend1 to be skipped"
+    end
+
+    Itsi::Server.stop_background_threads
+  end
+
+  def test_passive_health_check_ejects_failing_backend
+    backend1_bind = free_bind
+    backend2_bind = free_bind
+
+    Itsi::Server.start_in_background_thread(binds: [backend1_bind]) do
+      workers 1
+      get("/ejected") { |r| r.respond("unavailable", 503) }
+    end
+
+    Itsi::Server.start_in_background_thread(binds: [backend2_bind]) do
+      workers 1
+      get("/ejected") { |r| r.ok "backend2" }
+    end
+
+    sleep 0.2
+
+    server(
+      cleanup: false,
+      itsi_rb: lambda do
+        proxy \
+          to: "http://proxied_host.com{path}{query}",
+          backends: [backend1_bind[%r{//(.*)}, 1], backend2_bind[%r{//(.*)}, 1]],
+          backend_priority: "ordered",
+          passive_health_check: { max_failures: 2, cooldown: 30 }
+        get("/ejected") { |r| r.ok "should not get here" }
+      end
+    ) do
+      2.times { assert_equal "503", get_resp("/ejected").code }
+      res = get_resp("/ejected")
+      assert_equal "200", res.code, "Expected backend1 to be ejected after repeated failures"
+      assert_equal "backend2", res.body
+    end
+
+    Itsi::Server.stop_background_threads
+  end
+
+  def test_consistent_hash_routes_same_key_to_same_backend
+    backend_binds = [free_bind, free_bind, free_bind]
+    backend_binds.each_with_index do |bind, i|
+      Itsi::Server.start_in_background_thread(binds: [bind]) do
+        workers 1
+        get("/sticky") { |r| r.ok "backend#{i}" }
+      end
+    end
+
+    sleep 0.2
+
+    server(
+      cleanup: false,
+      itsi_rb: lambda do
+        proxy \
+          to: "http://proxied_host.com{path}{query}",
+          backends: backend_binds.map { |bind| bind[%r{//(.*)}, 1] },
+          backend_priority: { consistent_hash: { key: { header: { name: "X-User-Id" } } } }
+        get("/sticky") { |r| r.ok "should not get here" }
+      end
+    ) do
+      seen = %w[alice bob carol dave erin].map do |user|
+        responses = 3.times.map { get("/sticky", { "X-User-Id" => user }) }
+        assert_equal 1, responses.uniq.size, "Expected #{user} to stick to a single backend"
+        responses.first
+      end
+      assert seen.all? { |body| body.start_with?("backend") }
+    end
+
+    Itsi::Server.stop_background_threads
+  end
+
+  # Starts a backend per bind, each answering /sticky with its own name.
+  def start_sticky_backends(backend_binds)
+    backend_binds.each_with_index do |bind, i|
+      Itsi::Server.start_in_background_thread(binds: [bind]) do
+        workers 1
+        get("/sticky") { |r| r.ok "backend#{i}" }
+      end
+    end
+    sleep 0.2
+  end
+
+  # Routes each user through a consistent_hash proxy to `backend_binds`, returning the backend each reached.
+  def consistent_hash_routes(backend_binds, users)
+    routes = nil
+    server(
+      cleanup: false,
+      itsi_rb: lambda do
+        proxy \
+          to: "http://proxied_host.com{path}{query}",
+          backends: backend_binds.map { |bind| bind[%r{//(.*)}, 1] },
+          backend_priority: { consistent_hash: { key: { header: { name: "X-User-Id" } } } }
+        get("/sticky") { |r| r.ok "should not get here" }
+      end
+    ) do
+      routes = users.to_h { |user| [user, get("/sticky", { "X-User-Id" => user })] }
+    end
+    routes
+  end
+
+  def test_consistent_hash_spreads_keys_across_backends
+    backend_binds = [free_bind, free_bind, free_bind]
+    start_sticky_backends(backend_binds)
+
+    users = 60.times.map { |i| "user-#{i}" }
+    counts = consistent_hash_routes(backend_binds, users).values.tally
+    assert_equal %w[backend0 backend1 backend2], counts.keys.sort
+    counts.each do |backend, count|
+      assert_operator count, :>=, 10, "Expected #{backend} to own a fair share of keys, got #{counts}"
+    end
+  ensure
+    Itsi::Server.stop_background_threads
+  end
+
+  def test_consistent_hash_only_remaps_keys_of_a_removed_backend
+    backend_binds = [free_bind, free_bind, free_bind]
+    start_sticky_backends(backend_binds)
+
+    users = 60.times.map { |i| "user-#{i}" }
+    before = consistent_hash_routes(backend_binds, users)
+    after = consistent_hash_routes(backend_binds.values_at(0, 2), users)
+
+    users.each do |user|
+      if before[user] == "backend1"
+        refute_equal "backend1", after[user]
+      else
+        assert_equal before[user], after[user], "Expected #{user} to stay on #{before[user]}"
+      end
+    end
+  ensure
+    Itsi::Server.stop_background_threads
+  end
+
+  def test_least_connections_avoids_busy_backend
+    backend1_bind = free_bind
+    backend2_bind = free_bind
+
+    Itsi::Server.start_in_background_thread(binds: [backend1_bind]) do
+      workers 1
+      threads 4
+      get("/busy") { |r| sleep 1; r.ok "backend1" }
+    end
+
+    Itsi::Server.start_in_background_thread(binds: [backend2_bind]) do
+      workers 1
+      threads 4
+      get("/busy") { |r| sleep 1; r.ok "backend2" }
+    end
+
+    sleep 0.2
+
+    server(
+      cleanup: false,
+      itsi_rb: lambda do
+        proxy \
+          to: "http://proxied_host.com{path}{query}",
+          backends: [backend1_bind[%r{//(.*)}, 1], backend2_bind[%r{//(.*)}, 1]],
+          backend_priority: "least_connections"
+        get("/busy") { |r| r.ok "should not get here" }
+      end
+    ) do |uri|
+      responses = 4.times.map do
+        Thread.new { Net::HTTP.get(URI("#{uri}/busy")) }.tap { sleep 0.05 }
+      end.map(&:value)
+      assert_equal 2, responses.count("backend1")
+      assert_equal 2, responses.count("backend2")
+    end
+
+    Itsi::Server.stop_background_threads
+  end
+
+  def test_upgrade_passthrough
+    backend = TCPServer.new("127.0.0.1", 0)
+    backend_port = backend.addr[1]
+    backend_thread = Thread.new do
+      socket = backend.accept
+      request = +""
+      request << socket.readpartial(1024) until request.include?("\r\n\r\n")
+      socket.write("HTTP/1.1 101 Switching Protocols\r\nConnection: Upgrade\r\nUpgrade: echo\r\n\r\n")
+      while (line = socket.gets)
+        socket.write("echo: #{line}")
+      end
+      socket.close
+    end
+
+    server(
+      itsi_rb: lambda do
+        proxy \
+          to: "http://proxied_host.com{path}{query}",
+          backends: ["127.0.0.1:#{backend_port}"]
+        get("/socket") { |r| r.ok "should not get here" }
+      end
+    ) do |uri|
+      socket = TCPSocket.new(uri.host, uri.port)
+      socket.write("GET /socket HTTP/1.1\r\nHost: #{uri.host}\r\nConnection: Upgrade\r\nUpgrade: echo\r\n\r\n")
+      status_line = socket.gets
+      assert_match(/101/, status_line)
+      # Skip the remaining response headers.
+      nil until socket.gets == "\r\n"
+      socket.write("ping\n")
+      assert_equal "echo: ping\n", socket.gets
+      socket.write("pong\n")
+      assert_equal "echo: pong\n", socket.gets
+      socket.close
+    end
+  ensure
+    backend_thread&.kill
+    backend&.close
+  end
+
+  def test_retry_on_status
+    backend_bind = free_bind
+    attempts = 0
+
+    Itsi::Server.start_in_background_thread(binds: [backend_bind]) do
+      workers 1
+      get("/flaky") do |r|
+        attempts += 1
+        attempts < 3 ? r.respond("unavailable", 503) : r.ok("recovered")
+      end
+    end
+
+    sleep 0.2
+
+    server(
+      cleanup: false,
+      itsi_rb: lambda do
+        proxy \
+          to: "http://proxied_host.com{path}{query}",
+          backends: [backend_bind[%r{//(.*)}, 1]],
+          retry: { max_attempts: 3, retry_on_statuses: [503], backoff: 0.0 }
+        get("/flaky") { |r| r.ok "should not get here" }
+      end
+    ) do
+      res = get_resp("/flaky")
+      assert_equal "200", res.code
+      assert_equal "recovered", res.body
+      assert_equal 3, attempts
+    end
+
+    Itsi::Server.stop_background_threads
+  end
+
+  def test_retry_only_buffers_non_idempotent_requests_within_limit
+    backend_bind = free_bind
+    attempts = 0
+
+    Itsi::Server.start_in_background_thread(binds: [backend_bind]) do
+      workers 1
+      post("/flaky") do |r|
+        attempts += 1
+        attempts.odd? ? r.respond("unavailable", 503) : r.ok(r.body.read)
+      end
+    end
+
+    sleep 0.2
+
+    server(
+      cleanup: false,
+      itsi_rb: lambda do
+        proxy \
+          to: "http://proxied_host.com{path}{query}",
+          backends: [backend_bind[%r{//(.*)}, 1]],
+          retry: { max_attempts: 2, retry_on_statuses: [503], backoff: 0.0, buffer_limit: 16 }
+        post("/flaky") { |r| r.ok "should not get here" }
+      end
+    ) do
+      res = post("/flaky", "small")
+      assert_equal "200", res.code
+      assert_equal "small", res.body
+
+      res = post("/flaky", "a body larger than the limit")
+      assert_equal "503", res.code, "Expected oversized non-idempotent request not to be retried"
+    end
+
+    Itsi::Server.stop_background_threads
+  end
 end
@@ -0,0 +1,322 @@
+require_relative "../helpers/test_helper"
+require "tmpdir"
+
+class TestResponseCache < Minitest::Test
+  def test_caches_response_with_max_age
+    calls = 0
+    server(
+      itsi_rb: lambda do
+        response_cache
+        get("/foo") do |r|
+          calls += 1
+          r.respond("cached-#{calls}", 200, { "Cache-Control" => "public, max-age=60" })
+        end
+      end
+    ) do
+      first = get_resp("/foo")
+      assert_equal "MISS", first["X-Cache"]
+      assert_equal "cached-1", first.body
+
+      second = get_resp("/foo")
+      assert_equal "HIT", second["X-Cache"]
+      assert_equal "cached-1", second.body
+      assert second.key?("Age")
+      assert_equal 1, calls
+    end
+  end
+
+  def test_does_not_cache_without_freshness
+    calls = 0
+    server(
+      itsi_rb: lambda do
+        response_cache
+        get("/foo") do |r|
+          calls += 1
+          r.ok "uncached-#{calls}"
+        end
+      end
+    ) do
+      assert_equal "uncached-1", get("/foo")
+      assert_equal "uncached-2", get("/foo")
+    end
+  end
+
+  def test_default_ttl_applies_when_no_cache_control
+    calls = 0
+    server(
+      itsi_rb: lambda do
+        response_cache default_ttl: 60
+        get("/foo") do |r|
+          calls += 1
+          r.ok "body-#{calls}"
+        end
+      end
+    ) do
+      assert_equal "body-1", get("/foo")
+      assert_equal "body-1", get("/foo")
+    end
+  end
+
+  def test_does_not_cache_private_or_no_store
+    calls = 0
+    server(
+      itsi_rb: lambda do
+        response_cache default_ttl: 60
+        get("/private") do |r|
+          calls += 1
+          r.respond("private-#{calls}", 200, { "Cache-Control" => "private, max-age=60" })
+        end
+        get("/no-store") do |r|
+          calls += 1
+          r.respond("no-store-#{calls}", 200, { "Cache-Control" => "no-store" })
+        end
+      end
+    ) do
+      refute_equal get("/private"), get("/private")
+      refute_equal get("/no-store"), get("/no-store")
+    end
+  end
+
+  def test_does_not_cache_authorized_requests
+    calls = 0
+    server(
+      itsi_rb: lambda do
+        response_cache default_ttl: 60
+        get("/foo") do |r|
+          calls += 1
+          r.ok "body-#{calls}"
+        end
+      end
+    ) do
+      assert_equal "body-1", get("/foo", { "Authorization" => "Bearer abc" })
+      assert_equal "body-2", get("/foo", { "Authorization" => "Bearer abc" })
+    end
+  end
+
+  def test_does_not_cache_requests_authenticated_by_middleware
+    calls = 0
+    server(
+      itsi_rb: lambda do
+        auth_api_key valid_keys: [Itsi.create_password_hash("alice-key", "sha256"),
+                                  Itsi.create_password_hash("bob-key", "sha256")],
+                     token_source: { header: { name: "X-Api-Key" } }
+        response_cache default_ttl: 60
+        get("/me") do |r|
+          calls += 1
+          r.ok "#{r.header("X-Api-Key").first.delete_suffix("-key")}-#{calls}"
+        end
+      end
+    ) do
+      assert_equal "alice-1", get("/me", { "X-Api-Key" => "alice-key" })
+      assert_equal "bob-2", get("/me", { "X-Api-Key" => "bob-key" })
+      assert_equal "alice-3", get("/me", { "X-Api-Key" => "alice-key" })
+    end
+  end
+
+  def test_does_not_cache_requests_with_cookies
+    calls = 0
+    server(
+      itsi_rb: lambda do
+        response_cache default_ttl: 60
+        get("/foo") do |r|
+          calls += 1
+          r.ok "body-#{calls}"
+        end
+      end
+    ) do
+      assert_equal "body-1", get("/foo", { "Cookie" => "session=alice" })
+      assert_equal "body-2", get("/foo", { "Cookie" => "session=bob" })
+    end
+  end
+
+  def test_cache_authenticated_opts_in
+    calls = 0
+    server(
+      itsi_rb: lambda do
+        auth_api_key valid_keys: [Itsi.create_password_hash("alice-key", "sha256"),
+                                  Itsi.create_password_hash("bob-key", "sha256")],
+                     token_source: { header: { name: "X-Api-Key" } }
+        response_cache default_ttl: 60, cache_authenticated: true
+        get("/catalog") do |r|
+          calls += 1
+          r.ok "catalog-#{calls}"
+        end
+      end
+    ) do
+      assert_equal "catalog-1", get("/catalog", { "X-Api-Key" => "alice-key" })
+      assert_equal "catalog-1", get("/catalog", { "X-Api-Key" => "bob-key" })
+      # Authentication still runs before the cache.
+      assert_equal "401", get_resp("/catalog", { "X-Api-Key" => "mallory-key" }).code
+    end
+  end
+
+  def test_vary_headers_select_variant
+    server(
+      itsi_rb: lambda do
+        response_cache
+        get("/foo") do |r|
+          r.respond("lang-#{r["Accept-Language"]}-#{rand}", 200,
+                    { "Cache-Control" => "max-age=60", "Vary" => "Accept-Language" })
+        end
+      end
+    ) do
+      en = get("/foo", { "Accept-Language" => "en" })
+      fr = get("/foo", { "Accept-Language" => "fr" })
+      refute_equal en, fr
+      assert_equal en, get("/foo", { "Accept-Language" => "en" })
+      assert_equal fr, get("/foo", { "Accept-Language" => "fr" })
+    end
+  end
+
+  def test_purge_request_removes_entry
+    calls = 0
+    server(
+      itsi_rb: lambda do
+        response_cache default_ttl: 60, allow_purge: true
+        get("/foo") do |r|
+          calls += 1
+          r.ok "body-#{calls}"
+        end
+      end
+    ) do |uri|
+      assert_equal "body-1", get("/foo")
+      assert_equal "body-1", get("/foo")
+
+      purge = Net::HTTP.start(uri.host, uri.port) do |http|
+        http.request(Net::HTTPGenericRequest.new("PURGE", false, true, "/foo"))
+      end
+      assert_equal "200", purge.code
+
+      assert_equal "body-2", get("/foo")
+    end
+  end
+
+  def test_purge_request_rejected_from_untrusted_addresses
+    calls = 0
+    server(
+      itsi_rb: lambda do
+        response_cache default_ttl: 60, allow_purge: true, purge_allowed_from: ["10.0.0.0/8"]
+        get("/foo") do |r|
+          calls += 1
+          r.ok "body-#{calls}"
+        end
+      end
+    ) do |uri|
+      assert_equal "body-1", get("/foo")
+
+      purge = Net::HTTP.start(uri.host, uri.port) do |http|
+        http.request(Net::HTTPGenericRequest.new("PURGE", false, true, "/foo"))
+      end
+      assert_equal "403", purge.code
+
+      assert_equal "body-1", get("/foo")
+    end
+  end
+
+  def test_coalesces_concurrent_misses
+    calls = 0
+    server(
+      itsi_rb: lambda do
+        response_cache
+        get("/foo") do |r|
+          calls += 1
+          sleep 0.5
+          r.respond("body-#{calls}", 200, { "Cache-Control" => "public, max-age=60" })
+        end
+      end
+    ) do
+      responses = 3.times.map { Thread.new { get_resp("/foo") } }.map(&:value)
+      assert_equal ["body-1"], responses.map(&:body).uniq
+      assert_equal 1, calls
+    end
+  end
+
+  def test_serves_stale_while_revalidating
+    calls = 0
+    server(
+      itsi_rb: lambda do
+        response_cache
+        get("/foo") do |r|
+          calls += 1
+          sleep 0.5 if calls > 1
+          r.respond("body-#{calls}", 200, { "Cache-Control" => "public, max-age=1, stale-while-revalidate=30" })
+        end
+      end
+    ) do
+      assert_equal "body-1", get("/foo")
+      sleep 1.5
+
+      # One request goes upstream to revalidate, others are served the stale entry meanwhile.
+      revalidation = Thread.new { get_resp("/foo") }
+      sleep 0.2
+      stale = get_resp("/foo")
+      assert_equal "STALE", stale["X-Cache"]
+      assert_equal "body-1", stale.body
+      assert_equal "body-2", revalidation.value.body
+
+      fresh = get_resp("/foo")
+      assert_equal "HIT", fresh["X-Cache"]
+      assert_equal "body-2", fresh.body
+    end
+  end
+
+  def test_serves_stale_on_upstream_error
+    calls = 0
+    server(
+      itsi_rb: lambda do
+        response_cache
+        get("/foo") do |r|
+          calls += 1
+          if calls == 1
+            r.respond("body-1", 200, { "Cache-Control" => "public, max-age=1, stale-if-error=30" })
+          else
+            r.respond("failed", 500)
+          end
+        end
+      end
+    ) do
+      assert_equal "body-1", get("/foo")
+      sleep 1.5
+
+      stale = get_resp("/foo")
+      assert_equal "200", stale.code
+      assert_equal "STALE", stale["X-Cache"]
+      assert_equal "body-1", stale.body
+      assert_equal 2, calls
+    end
+  end
+
+  def test_disk_cache_keeps_non_utf8_header_values
+    Dir.mktmpdir do |dir|
+      body = "disk-body"
+      2.times do
+        server(
+          itsi_rb: lambda do
+            response_cache disk_path: dir, default_ttl: 60
+            get("/foo") { |r| r.respond(body, 200, { "X-Name" => "caf\xE9".b }) }
+          end
+        ) do
+          # The second server has an empty memory cache, and serves the entry from disk.
+          res = get_resp("/foo")
+          assert_equal "disk-body", res.body
+          assert_equal "caf\xE9".b, res["X-Name"].b
+        end
+        body = "new-body"
+      end
+    end
+  end
+
+  def test_disk_cache_persists_entries
+    Dir.mktmpdir do |dir|
+      server(
+        itsi_rb: lambda do
+          response_cache disk_path: dir, default_ttl: 60
+          get("/foo") { |r| r.ok "disk-body" }
+        end
+      ) do
+        assert_equal "disk-body", get("/foo")
+      end
+      refute_empty Dir[File.join(dir, "*.json")]
+    end
+  end
+end
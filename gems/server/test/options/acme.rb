@@ -0,0 +1,365 @@
+require_relative "../helpers/test_helper"
+require "base64"
+require "json"
+require "redis"
+require "securerandom"
+require "time"
+require "tmpdir"
+
+# A minimal stand-in for an ACME directory (in the spirit of Pebble).
+# It skips JWS verification, validates http-01 challenges against a local HTTP bind,
+# and dns-01 challenges against the records file a hook script writes to.
+class AcmeStandIn
+  attr_reader :ca_cert, :url, :orders, :eab_kids, :renewal_info_requests
+  attr_accessor :http_port, :reject_orders, :dns_records
+
+  def initialize
+    @ca_key = OpenSSL::PKey::RSA.new(2048)
+    @ca_cert = certificate("/CN=ACME Stand-in CA", @ca_key.public_key, ca: true)
+    @tcp_server = TCPServer.new("127.0.0.1", 0)
+    @url = "https://127.0.0.1:#{@tcp_server.addr[1]}"
+    @orders = 0
+    @eab_kids = []
+    @renewal_info_requests = []
+  end
+
+  def certificate(subject, public_key, ca: false, extensions: [])
+    cert = OpenSSL::X509::Certificate.new
+    cert.version = 2
+    cert.serial = rand(1 << 64)
+    cert.subject = OpenSSL::X509::Name.parse(subject)
+    cert.issuer = ca ? cert.subject : @ca_cert.subject
+    cert.public_key = public_key
+    cert.not_before = Time.now - 60
+    cert.not_after = Time.now + (90 * 24 * 3600)
+    factory = OpenSSL::X509::ExtensionFactory.new(cert, ca ? cert : @ca_cert)
+    if ca
+      cert.add_extension(factory.create_extension("basicConstraints", "CA:TRUE", true))
+      cert.add_extension(factory.create_extension("subjectKeyIdentifier", "hash"))
+    end
+    extensions.each { |name, value| cert.add_extension(factory.create_extension(name, value)) }
+    cert.sign(@ca_key, OpenSSL::Digest.new("SHA256"))
+    cert
+  end
+
+  def start
+    key = OpenSSL::PKey::RSA.new(2048)
+    context = OpenSSL::SSL::SSLContext.new
+    context.cert = certificate("/CN=127.0.0.1", key.public_key, extensions: [["subjectAltName", "IP:127.0.0.1"]])
+    context.key = key
+    ssl_server = OpenSSL::SSL::SSLServer.new(@tcp_server, context)
+    @thread = Thread.new do
+      loop do
+        socket = ssl_server.accept
+        Thread.new(socket) { |s| handle(s) }
+      rescue OpenSSL::SSL::SSLError
+        next
+      end
+    end
+    self
+  end
+
+  def stop
+    @thread&.kill
+    @tcp_server.close
+  end
+
+  def handle(socket)
+    method, path = socket.gets.split(" ")
+    headers = {}
+    while (line = socket.gets) && line != "\r\n"
+      name, value = line.split(":", 2)
+      headers[name.downcase] = value.strip
+    end
+    body = socket.read(headers["content-length"].to_i)
+    payload = body.empty? ? "" : Base64.urlsafe_decode64(JSON.parse(body)["payload"])
+    @jwk ||= body.empty? ? nil : JSON.parse(Base64.urlsafe_decode64(JSON.parse(body)["protected"]))["jwk"]
+    status, response_headers, response_body = route(method, path, payload)
+    socket.write("HTTP/1.1 #{status} OK\r\nReplay-Nonce: #{SecureRandom.hex(8)}\r\n")
+    response_headers.each { |name, value| socket.write("#{name}: #{value}\r\n") }
+    socket.write("Content-Length: #{response_body.bytesize}\r\nConnection: close\r\n\r\n")
+    socket.write(response_body) unless method == "HEAD"
+  ensure
+    socket.close
+  end
+
+  def order
+    case @state
+    when :pending then { status: "pending", authorizations: ["#{url}/authz/1"], finalize: "#{url}/finalize/1" }
+    when :ready then { status: "ready", authorizations: ["#{url}/authz/1"], finalize: "#{url}/finalize/1" }
+    else { status: "valid", authorizations: ["#{url}/authz/1"], finalize: "#{url}/finalize/1",
+           certificate: "#{url}/cert/1" }
+    end
+  end
+
+  # The TXT record value for the current token, per RFC 8555 section 8.4.
+  def dns_01_value
+    members = @jwk["kty"] == "RSA" ? %w[e kty n] : %w[crv kty x y]
+    thumbprint = Base64.urlsafe_encode64(OpenSSL::Digest::SHA256.digest(@jwk.slice(*members).sort.to_h.to_json),
+                                         padding: false)
+    Base64.urlsafe_encode64(OpenSSL::Digest::SHA256.digest("#{@token}.#{thumbprint}"), padding: false)
+  end
+
+  def route(method, path, payload)
+    case [method, path]
+    in ["GET", "/directory"]
+      [200, {}, { newNonce: "#{url}/nonce", newAccount: "#{url}/account", newOrder: "#{url}/order",
+                  renewalInfo: "#{url}/renewal-info" }.to_json]
+    in ["GET", %r{\A/renewal-info/}]
+      @renewal_info_requests << path.delete_prefix("/renewal-info/")
+      window = { start: (Time.now + (60 * 24 * 3600)).utc.iso8601, end: (Time.now + (61 * 24 * 3600)).utc.iso8601 }
+      [200, { "Retry-After" => "21600" }, { suggestedWindow: window }.to_json]
+    in ["HEAD", "/nonce"]
+      [200, {}, ""]
+    in ["POST", "/account"]
+      if (eab = JSON.parse(payload)["externalAccountBinding"])
+        @eab_kids << JSON.parse(Base64.urlsafe_decode64(eab["protected"]))["kid"]
+      end
+      [201, { "Location" => "#{url}/account/1" }, { status: "valid" }.to_json]
+    in ["POST", "/order"] if reject_orders
+      @orders += 1
+      [403, { "Content-Type" => "application/problem+json" },
+       { type: "urn:ietf:params:acme:error:rejectedIdentifier", detail: "rejected" }.to_json]
+    in ["POST", "/order"]
+      @orders += 1
+      @domain = JSON.parse(payload)["identifiers"].first["value"]
+      @token = SecureRandom.urlsafe_base64(32, false)
+      @state = :pending
+      [201, { "Location" => "#{url}/order/1" }, order.to_json]
+    in ["POST", "/order/1"]
+      [200, {}, order.to_json]
+    in ["POST", "/authz/1"]
+      [200, {}, {
+        status: @state == :pending ? "pending" : "valid",
+        identifier: { type: "dns", value: @domain },
+        challenges: [{ type: "http-01", url: "#{url}/challenge/1", token: @token },
+                     { type: "dns-01", url: "#{url}/challenge/2", token: @token }]
+      }.to_json]
+    in ["POST", "/challenge/1"]
+      response = Net::HTTP.get(URI("http://127.0.0.1:#{@http_port}/.well-known/acme-challenge/#{@token}"))
+      @state = :ready if response.start_with?("#{@token}.")
+      [200, {}, "{}"]
+    in ["POST", "/challenge/2"]
+      records = File.exist?(dns_records) ? File.readlines(dns_records, chomp: true) : []
+      @state = :ready if records.include?("_acme-challenge.#{@domain} #{dns_01_value}")
+      [200, {}, "{}"]
+    in ["POST", "/finalize/1"]
+      csr = OpenSSL::X509::Request.new(Base64.urlsafe_decode64(JSON.parse(payload)["csr"]))
+      @issued = certificate("/CN=#{@domain}", csr.public_key, extensions: [["subjectAltName", "DNS:#{@domain}"],
+                                                                                    ["authorityKeyIdentifier", "keyid:always"]])
+      @state = :valid
+      [200, {}, order.to_json]
+    in ["POST", "/cert/1"]
+      [200, { "Content-Type" => "application/pem-certificate-chain" }, @issued.to_pem + @ca_cert.to_pem]
+    else
+      [404, {}, ""]
+    end
+  end
+end
+
+class TestAcme < Minitest::Test
+  ACME_ENV = %w[ITSI_ACME_DIRECTORY_URL ITSI_ACME_CA_PEM_PATH ITSI_ACME_CACHE_DIR].freeze
+
+  def setup
+    @stand_in = AcmeStandIn.new.start
+    @dir = Dir.mktmpdir
+    File.write(File.join(@dir, "ca.pem"), @stand_in.ca_cert.to_pem)
+    @previous_env = ENV.to_h.slice(*ACME_ENV)
+    ENV["ITSI_ACME_DIRECTORY_URL"] = "#{@stand_in.url}/directory"
+    ENV["ITSI_ACME_CA_PEM_PATH"] = File.join(@dir, "ca.pem")
+    ENV["ITSI_ACME_CACHE_DIR"] = File.join(@dir, "cache")
+  end
+
+  def teardown
+    ACME_ENV.each { |name| ENV[name] = @previous_env[name] }
+    @stand_in.stop
+    FileUtils.rm_rf(@dir)
+  end
+
+  def served_certificate(port, server_name)
+    tcp = TCPSocket.new("127.0.0.1", port)
+    ssl = OpenSSL::SSL::SSLSocket.new(tcp, OpenSSL::SSL::SSLContext.new.tap { |c| c.verify_mode = OpenSSL::SSL::VERIFY_NONE })
+    ssl.hostname = server_name
+    ssl.connect
+    ssl.peer_cert
+  rescue StandardError
+    nil
+  ensure
+    ssl&.close
+    tcp&.close
+  end
+
+  def start_acme_server(https_bind_options, http_bind: free_bind, https_bind: free_bind("https"))
+    Itsi::Server.start_in_background_thread(binds: [http_bind, "#{https_bind}?#{https_bind_options}"]) do
+      workers 1
+      get("/") { |r| r.ok "ok" }
+    end
+  end
+
+  def wait_for_certificate(https_bind, domain)
+    20.times do
+      certificate = served_certificate(URI(https_bind).port, domain)
+      return certificate if certificate
+
+      sleep 0.5
+    end
+    nil
+  end
+
+  def await_acme_certificate(https_bind_options, domain: "acme.test")
+    http_bind = free_bind
+    https_bind = free_bind("https")
+    @stand_in.http_port = URI(http_bind).port
+    start_acme_server(https_bind_options, http_bind: http_bind, https_bind: https_bind)
+    certificate = wait_for_certificate(https_bind, domain)
+    yield certificate if block_given? && certificate
+    certificate
+  ensure
+    Itsi::Server.stop_background_threads
+  end
+
+  def redis_url
+    ENV.fetch("REDIS_URL", "redis://localhost:6379/13")
+  end
+
+  def redis_or_skip
+    redis = Redis.new(url: redis_url)
+    begin
+      redis.ping
+    rescue StandardError
+      skip "Redis not running"
+    end
+    redis.flushdb
+    redis
+  end
+
+  def test_http_01_challenge
+    certificate = await_acme_certificate("cert=acme&domains=acme.test&acme_email=admin@acme.test&acme_challenge=http-01")
+    assert_equal "/CN=ACME Stand-in CA", certificate.issuer.to_s
+    assert_empty Dir.glob(File.join(@dir, "cache", "http-01", "*"))
+  end
+
+  def test_http_01_challenges_are_only_answered_by_servers_using_them
+    FileUtils.mkdir_p(File.join(@dir, "cache", "http-01"))
+    File.write(File.join(@dir, "cache", "http-01", "token"), "token.thumbprint")
+    http_bind = free_bind
+    Itsi::Server.start_in_background_thread(binds: [http_bind]) do
+      workers 1
+      get("/") { |r| r.ok "ok" }
+    end
+    sleep 0.2
+    response = Net::HTTP.get_response(URI("#{http_bind}/.well-known/acme-challenge/token"))
+    assert_equal "404", response.code
+  ensure
+    Itsi::Server.stop_background_threads
+  end
+
+  def test_dns_01_challenge
+    @stand_in.dns_records = File.join(@dir, "records")
+    hook = File.join(@dir, "dns_hook.sh")
+    File.write(hook, <<~SH)
+      #!/bin/sh
+      echo "$1 $2" >> #{@dir}/hook.log
+      case "$1" in
+        present) echo "$2 $3" >> #{@dir}/records ;;
+        cleanup) grep -v -x "$2 $3" #{@dir}/records > #{@dir}/records.tmp; mv #{@dir}/records.tmp #{@dir}/records ;;
+      esac
+    SH
+    File.chmod(0o755, hook)
+
+    certificate = await_acme_certificate(
+      "cert=acme&domains=acme.test&acme_email=admin@acme.test&acme_challenge=dns-01&acme_dns_hook=#{hook}"
+    )
+    assert_equal "/CN=ACME Stand-in CA", certificate.issuer.to_s
+    assert_equal ["present _acme-challenge.acme.test", "cleanup _acme-challenge.acme.test"],
+                 File.readlines(File.join(@dir, "hook.log"), chomp: true)
+    assert_empty File.read(File.join(@dir, "records"))
+  end
+
+  def test_eab_key_type_and_renewal_info
+    hmac_key = Base64.urlsafe_encode64(SecureRandom.bytes(32), padding: false)
+    options = "cert=acme&domains=eab.acme.test&acme_email=eab@acme.test&acme_challenge=http-01" \
+              "&acme_directory=#{@stand_in.url}/directory&acme_eab_kid=kid-123&acme_eab_hmac_key=#{hmac_key}" \
+              "&acme_key_type=p384&acme_renew_days=20"
+
+    cert_id = nil
+    certificate = await_acme_certificate(options, domain: "eab.acme.test") do |served|
+      serial = served.serial.to_s(2)
+      serial = "\x00".b + serial if serial.getbyte(0) >= 0x80
+      cert_id = [served.authority_key_identifier, serial].map { |part| Base64.urlsafe_encode64(part, padding: false) }.join(".")
+      # Renewal information is fetched once the certificate is deployed.
+      10.times do
+        break if @stand_in.renewal_info_requests.include?(cert_id)
+
+        sleep 0.2
+      end
+    end
+    assert_equal "/CN=eab.acme.test", certificate.subject.to_s
+    assert_equal "secp384r1", certificate.public_key.group.curve_name
+    assert_includes @stand_in.eab_kids, "kid-123"
+    assert_includes @stand_in.renewal_info_requests, cert_id
+  end
+
+  def test_redis_cache
+    redis = redis_or_skip
+    options = "cert=acme&domains=acme.test&acme_email=admin@acme.test&acme_challenge=http-01&acme_cache=#{redis_url}"
+
+    assert_equal "/CN=ACME Stand-in CA", await_acme_certificate(options).issuer.to_s
+    assert_equal 1, @stand_in.orders
+    assert_equal 1, redis.keys("itsi:acme:cert:*").size
+    assert_empty redis.keys("itsi:acme:lock:*")
+
+    # Another host sharing the cache deploys the stored certificate, without ordering its own.
+    assert_equal "/CN=ACME Stand-in CA", await_acme_certificate(options).issuer.to_s
+    assert_equal 1, @stand_in.orders
+  ensure
+    redis&.flushdb
+  end
+
+  def test_redis_cache_lock_contention
+    redis = redis_or_skip
+    options = "cert=acme&domains=acme.test&acme_email=admin@acme.test&acme_challenge=http-01&acme_cache=#{redis_url}"
+    http_binds = [free_bind, free_bind]
+    https_binds = [free_bind("https"), free_bind("https")]
+    # Both servers share this process's http-01 challenge store, so either can answer the challenge.
+    @stand_in.http_port = URI(http_binds.first).port
+
+    # Two hosts start at once: one takes the lock and orders, the other waits and deploys the stored certificate.
+    http_binds.zip(https_binds).each do |http_bind, https_bind|
+      start_acme_server(options, http_bind: http_bind, https_bind: https_bind)
+    end
+    certificates = https_binds.map { |https_bind| wait_for_certificate(https_bind, "acme.test") }
+
+    assert certificates.all?
+    assert_equal 1, certificates.map(&:serial).uniq.size
+    assert_equal 1, @stand_in.orders
+    assert_empty redis.keys("itsi:acme:lock:*")
+  ensure
+    Itsi::Server.stop_background_threads
+    redis&.flushdb
+  end
+
+  def test_redis_cache_releases_lock_when_order_fails
+    redis = redis_or_skip
+    @stand_in.reject_orders = true
+    start_acme_server(
+      "cert=acme&domains=acme.test&acme_email=admin@acme.test&acme_challenge=http-01&acme_cache=#{redis_url}"
+    )
+    20.times do
+      break if @stand_in.orders.positive?
+
+      sleep 0.1
+    end
+    assert @stand_in.orders.positive?
+
+    # The lock is released as soon as the order fails, rather than held until it expires.
+    released = 40.times.any? do
+      sleep 0.05
+      redis.keys("itsi:acme:lock:*").empty?
+    end
+    assert released
+  ensure
+    Itsi::Server.stop_background_threads
+    redis&.flushdb
+  end
+end
@@ -1,4 +1,8 @@
 require_relative "../helpers/test_helper"
+require "open3"
+require "securerandom"
+require "tempfile"
+require "tmpdir"
 
 class TestBind < Minitest::Test
   def test_http
@@ -24,6 +28,556 @@ class TestBind < Minitest::Test
     end
   end
 
+  def test_https_advertises_http3
+    https_bind = free_bind("https")
+    port = URI(https_bind).port
+    Itsi::Server.start_in_background_thread(binds: [https_bind, "h3://0.0.0.0:#{port}"]) do
+      workers 1
+      get("/") { |r| r.ok "Hello, HTTPS!" }
+    end
+    sleep 0.2
+
+    response = Net::HTTP.start("0.0.0.0", port, use_ssl: true, verify_mode: OpenSSL::SSL::VERIFY_NONE) do |http|
+      http.request(Net::HTTP::Get.new("/"))
+    end
+    assert_equal "200", response.code
+    assert_equal %(h3=":#{port}"; ma=86400), response["alt-svc"]
+  ensure
+    Itsi::Server.stop_background_threads
+  end
+
+  def test_http3
+    curl_features = begin
+      Open3.capture2e("curl", "--version").first
+    rescue Errno::ENOENT
+      ""
+    end
+    skip "curl with HTTP/3 support not installed" unless curl_features.include?("HTTP3")
+
+    port = URI(free_bind).port
+    Itsi::Server.start_in_background_thread(binds: ["h3://0.0.0.0:#{port}"]) do
+      workers 1
+      post("/") { |r| r.ok "Hello, #{r.body.read} over #{r.version}!" }
+    end
+    sleep 0.2
+
+    output, status = Open3.capture2e(
+      "curl", "--silent", "--show-error", "--http3-only", "--insecure", "--max-time", "5",
+      "--data", "QUIC", "https://127.0.0.1:#{port}/"
+    )
+    assert status.success?, output
+    assert_equal "Hello, QUIC over HTTP/3.0!", output
+  ensure
+    Itsi::Server.stop_background_threads
+  end
+
+  def raw_get(uri, preamble)
+    socket = TCPSocket.new(uri.host, uri.port)
+    socket.write(preamble)
+    socket.write("GET / HTTP/1.1\r\nHost: #{uri.host}\r\nConnection: close\r\n\r\n")
+    socket.read.split("\r\n\r\n", 2).last
+  ensure
+    socket&.close
+  end
+
+  def test_proxy_protocol_v1
+    server(
+      bind: "#{free_bind}?proxy_protocol=v1&proxy_protocol_trusted=127.0.0.1",
+      itsi_rb: lambda do
+        get("/") { |r| r.ok r.remote_addr }
+      end
+    ) do |uri|
+      assert_equal "203.0.113.7", raw_get(uri, "PROXY TCP4 203.0.113.7 127.0.0.1 56324 #{uri.port}\r\n")
+    end
+  end
+
+  def test_proxy_protocol_v2
+    server(
+      bind: "#{free_bind}?proxy_protocol=v2&proxy_protocol_trusted=127.0.0.1",
+      itsi_rb: lambda do
+        get("/") { |r| r.ok r.remote_addr }
+      end
+    ) do |uri|
+      addresses = [203, 0, 113, 7, 127, 0, 0, 1].pack("C*") + [56_324, uri.port].pack("n2")
+      header = "\r\n\r\n\0\r\nQUIT\n".b + [0x21, 0x11, addresses.bytesize].pack("CCn") + addresses
+      assert_equal "203.0.113.7", raw_get(uri, header)
+    end
+  end
+
+  def test_proxy_protocol_silent_client_does_not_block_accepts
+    server(
+      bind: "#{free_bind}?proxy_protocol=v1&proxy_protocol_trusted=127.0.0.1",
+      itsi_rb: lambda do
+        get("/") { |r| r.ok r.remote_addr }
+      end
+    ) do |uri|
+      silent = TCPSocket.new(uri.host, uri.port)
+      Timeout.timeout(2) do
+        assert_equal "203.0.113.7", raw_get(uri, "PROXY TCP4 203.0.113.7 127.0.0.1 56324 #{uri.port}\r\n")
+      end
+    ensure
+      silent&.close
+    end
+  end
+
+  def test_proxy_protocol_untrusted_source
+    server(
+      bind: "#{free_bind}?proxy_protocol=any&proxy_protocol_trusted=10.0.0.0/8",
+      itsi_rb: lambda do
+        get("/") { |r| r.ok r.remote_addr }
+      end
+    ) do |uri|
+      assert_equal "127.0.0.1", raw_get(uri, "")
+    end
+  end
+
+  def issue_certificate(subject, issuer: nil, issuer_key: nil, extensions: [])
+    key = OpenSSL::PKey::RSA.new(2048)
+    cert = OpenSSL::X509::Certificate.new
+    cert.version = 2
+    cert.serial = rand(1..2**32)
+    cert.subject = OpenSSL::X509::Name.parse(subject)
+    cert.issuer = issuer ? issuer.subject : cert.subject
+    cert.public_key = key.public_key
+    cert.not_before = Time.now - 60
+    cert.not_after = Time.now + 3600
+    factory = OpenSSL::X509::ExtensionFactory.new(issuer || cert, cert)
+    extensions.each { |name, value, critical| cert.add_extension(factory.create_extension(name, value, critical)) }
+    cert.sign(issuer_key || key, OpenSSL::Digest.new("SHA256"))
+    [cert, key]
+  end
+
+  def test_mutual_tls
+    ca, ca_key = issue_certificate(
+      "/CN=Test Client CA",
+      extensions: [["basicConstraints", "CA:TRUE", true], ["keyUsage", "keyCertSign,cRLSign", true]]
+    )
+    client_cert, client_key = issue_certificate(
+      "/CN=service-a",
+      issuer: ca, issuer_key: ca_key,
+      extensions: [["subjectAltName", "DNS:service-a.internal"], ["extendedKeyUsage", "clientAuth"]]
+    )
+    ca_file = Tempfile.new(["client_ca", ".pem"])
+    ca_file.write(ca.to_pem)
+    ca_file.flush
+
+    server(
+      bind: "#{free_bind("https")}?client_ca=#{ca_file.path}",
+      itsi_rb: lambda do
+        get("/") { |r| r.ok "#{r.client_cert_subject}|#{r.client_cert_sans.join(",")}" }
+      end
+    ) do |uri|
+      response = Net::HTTP.start(uri.hostname, uri.port, use_ssl: true, verify_mode: OpenSSL::SSL::VERIFY_NONE,
+                                                         cert: client_cert, key: client_key) do |http|
+        http.request(Net::HTTP::Get.new("/"))
+      end
+      assert_equal "CN=service-a|service-a.internal", response.body
+
+      assert_raises(OpenSSL::SSL::SSLError, EOFError, Errno::ECONNRESET) do
+        Net::HTTP.start(uri.hostname, uri.port, use_ssl: true, verify_mode: OpenSSL::SSL::VERIFY_NONE) do |http|
+          http.request(Net::HTTP::Get.new("/"))
+        end
+      end
+    end
+  ensure
+    ca_file&.close!
+  end
+
+  # Answers OCSP requests with a good status, valid for `validity` seconds.
+  # With `answer_for`, responses name that certificate instead of the one requested.
+  # Responses are signed by the CA, unless another `signer` certificate and key are given.
+  def start_ocsp_responder(ca, ca_key, validity: 3600, answer_for: nil, signer: [ca, ca_key])
+    responder = TCPServer.new("127.0.0.1", 0)
+    requests = Queue.new
+    Thread.new do
+      loop do
+        socket = responder.accept
+        headers = {}
+        socket.gets
+        while (line = socket.gets) && line != "\r\n"
+          name, value = line.split(":", 2)
+          headers[name.downcase] = value.strip
+        end
+        request = OpenSSL::OCSP::Request.new(socket.read(headers["content-length"].to_i))
+        requests << request
+        certid = answer_for ? OpenSSL::OCSP::CertificateId.new(answer_for, ca) : request.certid.first
+        basic = OpenSSL::OCSP::BasicResponse.new
+        basic.add_status(certid, OpenSSL::OCSP::V_CERTSTATUS_GOOD, 0, nil, 0, validity, [])
+        basic.sign(*signer, [], 0)
+        body = OpenSSL::OCSP::Response.create(OpenSSL::OCSP::RESPONSE_STATUS_SUCCESSFUL, basic).to_der
+        socket.write("HTTP/1.1 200 OK\r\nContent-Type: application/ocsp-response\r\n")
+        socket.write("Content-Length: #{body.bytesize}\r\nConnection: close\r\n\r\n#{body}")
+        socket.close
+      end
+    end
+    [responder, requests]
+  end
+
+  def ocsp_ca
+    issue_certificate(
+      "/CN=Test OCSP CA",
+      extensions: [["basicConstraints", "CA:TRUE", true], ["keyUsage", "keyCertSign,cRLSign", true]]
+    )
+  end
+
+  def ocsp_certificate(ca, ca_key, responder)
+    issue_certificate(
+      "/CN=ocsp.test",
+      issuer: ca, issuer_key: ca_key,
+      extensions: [["subjectAltName", "DNS:ocsp.test"],
+                   ["authorityInfoAccess", "OCSP;URI:http://127.0.0.1:#{responder.addr[1]}/"]]
+    )
+  end
+
+  def ocsp_status(uri)
+    output, = Open3.capture2e("openssl", "s_client", "-connect", "127.0.0.1:#{uri.port}",
+                              "-servername", "ocsp.test", "-status", stdin_data: "")
+    output
+  end
+
+  # Serves `cert` with stapling enabled, caching OCSP responses in a temporary directory.
+  def with_stapling_server(cert, key, ca, ocsp_stapling: "true")
+    Dir.mktmpdir do |dir|
+      File.write(File.join(dir, "chain.pem"), cert.to_pem + ca.to_pem)
+      File.write(File.join(dir, "key.pem"), key.to_pem)
+      previous_cache_dir = ENV["ITSI_ACME_CACHE_DIR"]
+      ENV["ITSI_ACME_CACHE_DIR"] = File.join(dir, "cache")
+      begin
+        server(
+          bind: "#{free_bind("https")}?cert=#{dir}/chain.pem&key=#{dir}/key.pem&ocsp_stapling=#{ocsp_stapling}",
+          itsi_rb: lambda do
+            get("/") { |r| r.ok "ok" }
+          end
+        ) do |uri|
+          yield uri, File.join(dir, "cache", "ocsp")
+        end
+      ensure
+        ENV["ITSI_ACME_CACHE_DIR"] = previous_cache_dir
+      end
+    end
+  end
+
+  def test_ocsp_stapling
+    ca, ca_key = ocsp_ca
+    responder, = start_ocsp_responder(ca, ca_key)
+    cert, key = ocsp_certificate(ca, ca_key, responder)
+    with_stapling_server(cert, key, ca) do |uri, cache_dir|
+      # The first handshake queues the certificate for stapling.
+      output = ""
+      10.times do
+        output = ocsp_status(uri)
+        break if output.include?("OCSP Response Status: successful")
+
+        sleep 0.2
+      end
+      assert_includes output, "OCSP Response Status: successful"
+      assert_includes output, "Cert Status: good"
+      assert_equal 1, Dir.glob(File.join(cache_dir, "*.der")).size
+    end
+  ensure
+    responder&.close
+  end
+
+  def test_ocsp_stapling_is_opt_in
+    ca, ca_key = ocsp_ca
+    responder, requests = start_ocsp_responder(ca, ca_key)
+    cert, key = ocsp_certificate(ca, ca_key, responder)
+    with_stapling_server(cert, key, ca, ocsp_stapling: "false") do |uri|
+      ocsp_status(uri)
+      sleep 0.5
+      assert_includes ocsp_status(uri), "OCSP response: no response sent"
+      assert_equal 0, requests.size
+    end
+  ensure
+    responder&.close
+  end
+
+  def test_ocsp_stapling_refreshes_responses
+    ca, ca_key = ocsp_ca
+    # Responses are refreshed half way through their validity, so every couple of seconds.
+    responder, requests = start_ocsp_responder(ca, ca_key, validity: 4)
+    cert, key = ocsp_certificate(ca, ca_key, responder)
+    with_stapling_server(cert, key, ca) do |uri|
+      ocsp_status(uri)
+      deadline = Time.now + 10
+      sleep 0.2 while requests.size < 3 && Time.now < deadline
+      assert_operator requests.size, :>=, 3
+      assert_includes ocsp_status(uri), "Cert Status: good"
+    end
+  ensure
+    responder&.close
+  end
+
+  def test_ocsp_stapling_with_a_delegated_responder
+    ca, ca_key = ocsp_ca
+    signer = issue_certificate("/CN=Test OCSP Responder", issuer: ca, issuer_key: ca_key,
+                                                          extensions: [["extendedKeyUsage", "OCSPSigning"]])
+    responder, = start_ocsp_responder(ca, ca_key, signer: signer)
+    cert, key = ocsp_certificate(ca, ca_key, responder)
+    with_stapling_server(cert, key, ca) do |uri|
+      output = ""
+      10.times do
+        output = ocsp_status(uri)
+        break if output.include?("Cert Status: good")
+
+        sleep 0.2
+      end
+      assert_includes output, "Cert Status: good"
+    end
+  ensure
+    responder&.close
+  end
+
+  def test_ocsp_stapling_rejects_responses_signed_with_another_key
+    ca, ca_key = ocsp_ca
+    # A forger can copy the CA's name, but not its key.
+    forged_ca = issue_certificate("/CN=Test OCSP CA",
+                                  extensions: [["basicConstraints", "CA:TRUE", true]])
+    responder, requests = start_ocsp_responder(ca, ca_key, signer: forged_ca)
+    cert, key = ocsp_certificate(ca, ca_key, responder)
+    with_stapling_server(cert, key, ca) do |uri, cache_dir|
+      ocsp_status(uri)
+      deadline = Time.now + 5
+      sleep 0.1 while requests.empty? && Time.now < deadline
+      sleep 0.5
+      assert_operator requests.size, :>=, 1
+      assert_includes ocsp_status(uri), "OCSP response: no response sent"
+      assert_empty Dir.glob(File.join(cache_dir, "*.der"))
+    end
+  ensure
+    responder&.close
+  end
+
+  def test_ocsp_stapling_rejects_responses_for_other_certificates
+    ca, ca_key = ocsp_ca
+    other_cert, = issue_certificate("/CN=other.test", issuer: ca, issuer_key: ca_key)
+    responder, requests = start_ocsp_responder(ca, ca_key, answer_for: other_cert)
+    cert, key = ocsp_certificate(ca, ca_key, responder)
+    with_stapling_server(cert, key, ca) do |uri|
+      ocsp_status(uri)
+      deadline = Time.now + 5
+      sleep 0.1 while requests.empty? && Time.now < deadline
+      sleep 0.5
+      assert_operator requests.size, :>=, 1
+      assert_includes ocsp_status(uri), "OCSP response: no response sent"
+    end
+  ensure
+    responder&.close
+  end
+
+  def served_certificate(uri, server_name)
+    tcp = TCPSocket.new(uri.host, uri.port)
+    ssl = OpenSSL::SSL::SSLSocket.new(tcp, OpenSSL::SSL::SSLContext.new.tap { |c| c.verify_mode = OpenSSL::SSL::VERIFY_NONE })
+    ssl.hostname = server_name
+    ssl.connect
+    ssl.peer_cert.subject.to_s
+  ensure
+    ssl&.close
+    tcp&.close
+  end
+
+  def test_dev_ca_caches_certificates
+    ca_dir = Dir.mktmpdir
+    previous_ca_dir = ENV["ITSI_LOCAL_CA_DIR"]
+    ENV["ITSI_LOCAL_CA_DIR"] = ca_dir
+    domain = "dev-ca-#{SecureRandom.hex(4)}.itsi.localhost"
+    served = 2.times.map do
+      bind = "#{free_bind("https")}?domains=#{domain}"
+      server(bind: bind, itsi_rb: lambda do
+        get("/") { |r| r.ok "ok" }
+      end) do |uri|
+        tls_connect(uri) { |ssl| ssl.peer_cert.to_der }
+      end
+    end
+    assert_equal served.first, served.last
+
+    ca = OpenSSL::X509::Certificate.new(File.read(Itsi::Server.dev_ca_path))
+    assert OpenSSL::X509::Certificate.new(served.first).verify(ca.public_key)
+
+    hosts, path, expires_at = Itsi::Server.dev_ca_certificates.find { |sans, _, _| sans.include?(domain) }
+    assert_equal [domain], hosts
+    assert path.start_with?(ca_dir)
+    assert_operator expires_at, :>, Time.now.to_i + 30 * 24 * 3600
+    # Private keys are only readable by their owner.
+    keys = Dir.glob(File.join(ca_dir, "**", "*.key"))
+    refute_empty keys
+    keys.each { |key| assert_equal 0o600, File.stat(key).mode & 0o777, key }
+  ensure
+    ENV["ITSI_LOCAL_CA_DIR"] = previous_ca_dir
+    FileUtils.rm_rf(ca_dir) if ca_dir
+  end
+
+  def test_sni_certificates
+    Dir.mktmpdir do |dir|
+      %w[a.test b.test].each do |name|
+        cert, key = issue_certificate("/CN=#{name}", extensions: [["subjectAltName", "DNS:#{name}"]])
+        File.write(File.join(dir, "#{name}.crt"), cert.to_pem)
+        File.write(File.join(dir, "#{name}.key"), key.to_pem)
+      end
+      default_cert, default_key = issue_certificate("/CN=default.test")
+      File.write(File.join(dir, "default.crt"), default_cert.to_pem)
+      File.write(File.join(dir, "default.key"), default_key.to_pem)
+
+      server(
+        bind: "#{free_bind("https")}?certs_dir=#{dir}&cert=#{dir}/default.crt&key=#{dir}/default.key",
+        itsi_rb: lambda do
+          get("/") { |r| r.ok "ok" }
+        end
+      ) do |uri|
+        assert_equal "/CN=a.test", served_certificate(uri, "a.test")
+        assert_equal "/CN=b.test", served_certificate(uri, "b.test")
+        assert_equal "/CN=default.test", served_certificate(uri, "unknown.test")
+      end
+    end
+  end
+
+  def test_certificate_hot_reload
+    Dir.mktmpdir do |dir|
+      cert, key = issue_certificate("/CN=before.test")
+      File.write(File.join(dir, "cert.pem"), cert.to_pem)
+      File.write(File.join(dir, "key.pem"), key.to_pem)
+
+      server(
+        bind: "#{free_bind("https")}?cert=#{dir}/cert.pem&key=#{dir}/key.pem",
+        itsi_rb: lambda do
+          get("/") { |r| r.ok "ok" }
+        end
+      ) do |uri|
+        assert_equal "/CN=before.test", served_certificate(uri, "before.test")
+
+        # A certificate without its matching key is rejected, and the previous one kept.
+        mismatched, = issue_certificate("/CN=mismatched.test")
+        File.write(File.join(dir, "cert.pem"), mismatched.to_pem)
+        sleep 1.5
+        assert_equal "/CN=before.test", served_certificate(uri, "before.test")
+
+        cert, key = issue_certificate("/CN=after.test")
+        File.write(File.join(dir, "key.pem"), key.to_pem)
+        File.write(File.join(dir, "cert.pem"), cert.to_pem)
+        subject = nil
+        20.times do
+          subject = served_certificate(uri, "after.test")
+          break if subject == "/CN=after.test"
+
+          sleep 0.2
+        end
+        assert_equal "/CN=after.test", subject
+      end
+    end
+  end
+
+  def test_der_certificate_files
+    Dir.mktmpdir do |dir|
+      cert, key = issue_certificate("/CN=der.test")
+      File.binwrite(File.join(dir, "cert.der"), cert.to_der)
+      File.binwrite(File.join(dir, "key.der"), key.private_to_der)
+
+      server(
+        bind: "#{free_bind("https")}?cert=#{dir}/cert.der&key=#{dir}/key.der",
+        itsi_rb: lambda do
+          get("/") { |r| r.ok "ok" }
+        end
+      ) do |uri|
+        assert_equal "/CN=der.test", served_certificate(uri, "der.test")
+      end
+    end
+  end
+
+  def tls_connect(uri, session: nil, **context_options)
+    context = OpenSSL::SSL::SSLContext.new
+    context.verify_mode = OpenSSL::SSL::VERIFY_NONE
+    context_options.each { |name, value| context.public_send("#{name}=", value) }
+    tcp = TCPSocket.new(uri.host, uri.port)
+    ssl = OpenSSL::SSL::SSLSocket.new(tcp, context)
+    ssl.session = session if session
+    ssl.connect
+    yield ssl
+  ensure
+    ssl&.close
+    tcp&.close
+  end
+
+  def test_tls_protocol_options
+    server(
+      bind: "#{free_bind("https")}?tls_min_version=1.3&alpn=http/1.1&tls_ciphers=TLS13_AES_256_GCM_SHA384",
+      itsi_rb: lambda do
+        get("/") { |r| r.ok "ok" }
+      end
+    ) do |uri|
+      tls_connect(uri, alpn_protocols: %w[h2 http/1.1]) do |ssl|
+        assert_equal "TLSv1.3", ssl.ssl_version
+        assert_equal "TLS_AES_256_GCM_SHA384", ssl.cipher.first
+        assert_equal "http/1.1", ssl.alpn_protocol
+      end
+      assert_raises(OpenSSL::SSL::SSLError) do
+        tls_connect(uri, max_version: OpenSSL::SSL::TLS1_2_VERSION) { nil }
+      end
+    end
+  end
+
+  def test_shared_session_tickets
+    Dir.mktmpdir do |dir|
+      File.binwrite(File.join(dir, "ticket.key"), SecureRandom.bytes(80))
+      options = "tls_min_version=1.2&tls_ticket_keys=#{dir}/ticket.key"
+      itsi_rb = lambda do
+        get("/") { |r| r.ok "ok" }
+      end
+
+      session = nil
+      server(bind: "#{free_bind("https")}?#{options}", itsi_rb: itsi_rb) do |uri|
+        tls_connect(uri, max_version: OpenSSL::SSL::TLS1_2_VERSION) { |ssl| session = ssl.session }
+      end
+
+      # A different server process with the same ticket keys resumes the session.
+      server(bind: "#{free_bind("https")}?#{options}", itsi_rb: itsi_rb) do |uri|
+        tls_connect(uri, session: session, max_version: OpenSSL::SSL::TLS1_2_VERSION) do |ssl|
+          assert ssl.session_reused?
+        end
+      end
+    end
+  end
+
+  ITSI_EXE = File.expand_path("../../exe/itsi", __dir__)
+  ITSI_LIB = File.expand_path("../../lib", __dir__)
+
+  def test_systemd_socket_activation
+    socket = TCPServer.new("127.0.0.1", 0)
+    port = socket.addr[1]
+    Dir.mktmpdir do |dir|
+      File.write(File.join(dir, "Itsi.rb"), %(get("/") { |r| r.ok "activated" }\n))
+      env = { "LISTEN_FDS" => "1", "LISTEN_FDNAMES" => "web" }
+      pid = spawn(env, RbConfig.ruby, "-I", ITSI_LIB, ITSI_EXE, "-b", "http://127.0.0.1:#{port}?fd_name=web",
+                  3 => socket, chdir: dir, %i[out err] => File::NULL)
+      body = nil
+      50.times do
+        body = Net::HTTP.get(URI("http://127.0.0.1:#{port}/"))
+        break
+      rescue SystemCallError, IOError
+        sleep 0.1
+      end
+      assert_equal "activated", body
+    ensure
+      if pid
+        Process.kill(:TERM, pid)
+        Process.wait(pid)
+      end
+    end
+  ensure
+    socket&.close
+  end
+
+  def test_socket_activation_without_matching_socket
+    socket = TCPServer.new("127.0.0.1", 0)
+    Dir.mktmpdir do |dir|
+      File.write(File.join(dir, "Itsi.rb"), %(get("/") { |r| r.ok "activated" }\n))
+      output, = Open3.capture2e({ "LISTEN_FDS" => "1" }, RbConfig.ruby, "-I", ITSI_LIB, ITSI_EXE,
+                                "-b", free_bind("http"), 3 => socket, chdir: dir)
+      assert_match(/no matching inherited socket/, output)
+    end
+  ensure
+    socket&.close
+  end
+
   def test_unix_socket_http
     server(
       bind: free_bind("http", unix_socket: true),
@@ -34,6 +588,23 @@ class TestBind < Minitest::Test
     end
   end
 
+  def test_abstract_unix_socket
+    skip "Abstract Unix sockets are only supported on Linux" unless RUBY_PLATFORM.include?("linux")
+
+    name = "itsi_test_#{Process.pid}_#{rand(1000)}"
+    server(
+      bind: "unix://@#{name}",
+      app: lambda do |env|
+      [200, { "Content-Type" => "text/plain" }, ["Hello, Abstract!"]]
+    end) do
+      socket = UNIXSocket.new("\0#{name}")
+      socket.write("GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+      assert_match(/Hello, Abstract!\z/, socket.read)
+    ensure
+      socket&.close
+    end
+  end
+
   def test_unix_socket_https
     server(
       bind: free_bind("http", unix_socket: true),
@@ -0,0 +1,67 @@
+require_relative "../helpers/test_helper"
+
+class TestKeepAlive < Minitest::Test
+  def test_keep_alive_timeout
+    server(
+      itsi_rb: lambda do
+        keep_alive_timeout 0.2
+        get("/") { |r| r.ok "hi" }
+      end
+    ) do
+      socket = TCPSocket.new("127.0.0.1", @uri.port)
+      assert_match(/200 OK/, keep_alive_request(socket))
+      assert_match(/200 OK/, keep_alive_request(socket))
+
+      sleep 0.5
+      assert_raises(EOFError, Errno::ECONNRESET) { socket.readpartial(1024) }
+    ensure
+      socket&.close
+    end
+  end
+
+  def test_keep_alive_timeout_ignores_requests_in_flight
+    server(
+      itsi_rb: lambda do
+        keep_alive_timeout 0.1
+        get("/") { |r| sleep 0.3; r.ok "hi" }
+      end
+    ) do
+      socket = TCPSocket.new("127.0.0.1", @uri.port)
+      assert_match(/200 OK/, keep_alive_request(socket))
+    ensure
+      socket&.close
+    end
+  end
+
+  def test_max_requests_per_connection
+    server(
+      itsi_rb: lambda do
+        max_requests_per_connection 2
+        get("/") { |r| r.ok "hi" }
+      end
+    ) do
+      socket = TCPSocket.new("127.0.0.1", @uri.port)
+      refute_match(/connection: close/i, keep_alive_request(socket))
+      assert_match(/connection: close/i, keep_alive_request(socket))
+      assert_raises(EOFError, Errno::ECONNRESET) { socket.readpartial(1024) }
+    ensure
+      socket&.close
+    end
+  end
+
+  def test_bind_overrides_max_requests_per_connection
+    server(
+      bind: "#{free_bind("http")}?max_requests_per_connection=1",
+      itsi_rb: lambda do
+        max_requests_per_connection 100
+        get("/") { |r| r.ok "hi" }
+      end
+    ) do
+      socket = TCPSocket.new("127.0.0.1", @uri.port)
+      assert_match(/connection: close/i, keep_alive_request(socket))
+      assert_raises(EOFError, Errno::ECONNRESET) { socket.readpartial(1024) }
+    ensure
+      socket&.close
+    end
+  end
+end
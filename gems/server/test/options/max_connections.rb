@@ -0,0 +1,108 @@
+require_relative "../helpers/test_helper"
+
+class TestMaxConnections < Minitest::Test
+  def test_max_connections_per_ip
+    server(
+      itsi_rb: lambda do
+        max_connections_per_ip 1
+        get("/") { |r| r.ok "hi" }
+      end
+    ) do
+      held = TCPSocket.new("127.0.0.1", @uri.port)
+      assert_match(/200 OK/, keep_alive_request(held))
+
+      rejected = TCPSocket.new("127.0.0.1", @uri.port)
+      rejected.write("GET / HTTP/1.1\r\nHost: localhost\r\n\r\n")
+      assert_raises(EOFError, Errno::ECONNRESET) { rejected.readpartial(1024) }
+
+      held.close
+      sleep 0.1
+      assert_equal "hi", get("/")
+    ensure
+      held&.close
+      rejected&.close
+    end
+  end
+
+  def https_get(uri)
+    Net::HTTP.start(uri.hostname, uri.port, use_ssl: true, verify_mode: OpenSSL::SSL::VERIFY_NONE,
+                                            open_timeout: 1, read_timeout: 1) do |http|
+      http.request(Net::HTTP::Get.new("/")).body
+    end
+  end
+
+  def test_max_connections_per_ip_counts_tls_handshakes
+    server(
+      protocol: "https",
+      itsi_rb: lambda do
+        max_connections_per_ip 1
+        get("/") { |r| r.ok "hi" }
+      end
+    ) do |uri|
+      # Connects, but never starts the TLS handshake.
+      stalled = TCPSocket.new("127.0.0.1", uri.port)
+      sleep 0.1
+      assert_raises(OpenSSL::SSL::SSLError, EOFError, Errno::ECONNRESET) { https_get(uri) }
+
+      stalled.close
+      sleep 0.1
+      assert_equal "hi", https_get(uri)
+    ensure
+      stalled&.close
+    end
+  end
+
+  def test_max_connections_closes_stalled_tls_handshakes
+    server(
+      protocol: "https",
+      itsi_rb: lambda do
+        max_connections 1
+        get("/") { |r| r.ok "hi" }
+      end
+    ) do |uri|
+      stalled = TCPSocket.new("127.0.0.1", uri.port)
+      sleep 0.1
+
+      # The stalled connection is closed to make room for a new one.
+      assert_equal "hi", https_get(uri)
+      assert_raises(EOFError, Errno::ECONNRESET) { stalled.readpartial(1024) }
+    ensure
+      stalled&.close
+    end
+  end
+
+  def test_max_connections_reaps_idle_connections
+    server(
+      itsi_rb: lambda do
+        max_connections 1
+        get("/") { |r| r.ok "hi" }
+      end
+    ) do
+      idle = TCPSocket.new("127.0.0.1", @uri.port)
+      assert_match(/200 OK/, keep_alive_request(idle))
+
+      # The idle keep-alive connection is closed to make room for a new one.
+      assert_equal "hi", get("/")
+      assert_raises(EOFError, Errno::ECONNRESET) { idle.readpartial(1024) }
+    ensure
+      idle&.close
+    end
+  end
+
+  def test_bind_max_connections
+    server(
+      bind: "#{free_bind("http")}?max_connections=1",
+      itsi_rb: lambda do
+        get("/") { |r| r.ok "hi" }
+      end
+    ) do
+      idle = TCPSocket.new("127.0.0.1", @uri.port)
+      assert_match(/200 OK/, keep_alive_request(idle))
+
+      assert_equal "hi", get("/")
+      assert_raises(EOFError, Errno::ECONNRESET) { idle.readpartial(1024) }
+    ensure
+      idle&.close
+    end
+  end
+end
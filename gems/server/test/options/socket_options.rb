@@ -0,0 +1,88 @@
+require_relative "../helpers/test_helper"
+
+class TestSocketOptions < Minitest::Test
+  # Socket option numbers, for Rubies built without the constants.
+  TCP_FASTOPEN = Socket.const_defined?(:TCP_FASTOPEN) ? Socket::TCP_FASTOPEN : 23
+  IP_FREEBIND = Socket.const_defined?(:IP_FREEBIND) ? Socket::IP_FREEBIND : 15
+
+  # Finds the listening socket this process holds on `port`, via /proc (Linux only).
+  def listening_socket(port)
+    Dir.children("/proc/self/fd").each do |fd|
+      next unless (File.readlink("/proc/self/fd/#{fd}") rescue "").start_with?("socket:")
+
+      socket = Socket.for_fd(fd.to_i)
+      socket.autoclose = false
+      next unless socket.getsockopt(Socket::SOL_SOCKET, Socket::SO_ACCEPTCONN).bool
+      return socket if socket.local_address.ip? && socket.local_address.ip_port == port
+    end
+    nil
+  end
+
+  def linux_or_skip(option)
+    skip "#{option} is only supported on Linux" unless RUBY_PLATFORM.include?("linux")
+  end
+
+  def test_tcp_keepalive
+    server(
+      itsi_rb: lambda do
+        tcp_keepalive idle: 60, interval: 10, count: 5
+        get("/") { |r| r.ok "hi" }
+      end
+    ) do |uri|
+      assert_equal "hi", get("/")
+      next unless RUBY_PLATFORM.include?("linux")
+
+      socket = listening_socket(uri.port)
+      assert socket.getsockopt(Socket::SOL_SOCKET, Socket::SO_KEEPALIVE).bool
+      assert_equal 60, socket.getsockopt(Socket::IPPROTO_TCP, Socket::TCP_KEEPIDLE).int
+      assert_equal 10, socket.getsockopt(Socket::IPPROTO_TCP, Socket::TCP_KEEPINTVL).int
+      assert_equal 5, socket.getsockopt(Socket::IPPROTO_TCP, Socket::TCP_KEEPCNT).int
+    end
+  end
+
+  def test_tcp_fastopen
+    linux_or_skip "TCP_FASTOPEN"
+
+    server(
+      itsi_rb: lambda do
+        tcp_fastopen 256
+        get("/") { |r| r.ok "hi" }
+      end
+    ) do |uri|
+      assert_equal "hi", get("/")
+      assert_equal 256, listening_socket(uri.port).getsockopt(Socket::IPPROTO_TCP, TCP_FASTOPEN).int
+    end
+  end
+
+  def test_tcp_defer_accept
+    linux_or_skip "TCP_DEFER_ACCEPT"
+
+    server(
+      itsi_rb: lambda do
+        tcp_defer_accept 5
+        get("/") { |r| r.ok "hi" }
+      end
+    ) do
+      assert_equal "hi", get("/")
+    end
+  end
+
+  def test_ip_freebind
+    linux_or_skip "IP_FREEBIND"
+
+    # 192.0.2.0/24 is reserved for documentation, so it's never assigned to a local interface.
+    # Binding to it only succeeds with IP_FREEBIND.
+    port = URI(free_bind).port
+    server(
+      bind: "http://192.0.2.1:#{port}",
+      itsi_rb: lambda do
+        ip_freebind true
+        get("/") { |r| r.ok "hi" }
+      end
+    ) do
+      socket = listening_socket(port)
+      assert_equal "192.0.2.1", socket.local_address.ip_address
+      assert socket.getsockopt(Socket::IPPROTO_IP, IP_FREEBIND).bool
+    end
+  end
+end